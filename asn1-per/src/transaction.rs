@@ -11,7 +11,7 @@ use thiserror::Error;
 #[async_trait]
 pub trait Procedure {
     const CODE: u8;
-    type TopPdu: SerDes + Send + Sync + 'static;
+    type TopPdu: SerDes + TransactionKeyed + Send + Sync + 'static;
     type Request: Send + Sync + 'static + Debug;
     type Success;
     type Failure;
@@ -27,7 +27,7 @@ pub trait Procedure {
 #[async_trait]
 pub trait Indication {
     const CODE: u8;
-    type TopPdu: SerDes + Send + Sync + 'static;
+    type TopPdu: SerDes + TransactionKeyed + Send + Sync + 'static;
     type Request: Send + Sync + 'static + Debug;
     fn encode_request(r: Self::Request) -> Result<Vec<u8>, PerCodecError>;
     async fn call_provider<T: IndicationHandler<Self>>(
@@ -37,12 +37,32 @@ pub trait Indication {
     );
 }
 
+/// Trait representing the ability to identify the transaction that a PDU belongs to, so that a response
+/// can be matched with the request that caused it.
+pub trait TransactionKeyed {
+    /// Returns the UE-associated AP ID allocated by the node that initiated the procedure, or the
+    /// transaction ID of a non UE-associated procedure.  None if the PDU carries neither.
+    fn transaction_key(&self) -> Option<TransactionKey>;
+}
+
+/// The key of a transaction.  As well as matching responses with requests, this tells the transport
+/// whether a PDU is UE-associated signalling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionKey {
+    /// UE-associated AP ID.
+    Ue(u32),
+    /// Transaction ID.
+    Transaction(u32),
+}
+
 pub type ResponseAction<T> = (T, Option<Pin<Box<dyn Future<Output = ()> + Send>>>);
 
 #[derive(Error, Debug)]
 pub enum RequestError<U> {
     #[error("Unsuccessful outcome")]
     UnsuccessfulOutcome(U),
+    #[error("Timed out waiting for response")]
+    Timeout,
     #[error("{0}")]
     Other(String),
 }
@@ -105,11 +125,19 @@ pub trait RequestMessageHandler: Send + Sync {
         message: &[u8],
         logger: &Logger,
     ) -> Option<ResponseAction<Vec<u8>>>;
+
+    /// Returns the transaction key of a request in wire format, so that the transport can tell which UE,
+    /// if any, it belongs to.
+    fn transaction_key(&self, _message: &[u8]) -> Option<TransactionKey> {
+        None
+    }
 }
 
 // An interface provider is a request message handler.
 #[async_trait]
-impl<T: SerDes + Send + Sync, I: InterfaceProvider<TopPdu = T>> RequestMessageHandler for I {
+impl<T: SerDes + TransactionKeyed + Send + Sync, I: InterfaceProvider<TopPdu = T>>
+    RequestMessageHandler for I
+{
     async fn handle_request(
         &self,
         message: &[u8],
@@ -135,4 +163,8 @@ impl<T: SerDes + Send + Sync, I: InterfaceProvider<TopPdu = T>> RequestMessageHa
             }
         }
     }
+
+    fn transaction_key(&self, message: &[u8]) -> Option<TransactionKey> {
+        T::from_bytes(message).ok()?.transaction_key()
+    }
 }
@@ -0,0 +1,19 @@
+//! config_file - loading of a binary's configuration from a TOML file
+
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::path::Path;
+
+/// Reads a configuration from a TOML file.  Settings missing from the file take their default values,
+/// and a setting that is not recognized, or fails validation, is an error that names it.
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    parse(&text).map_err(|e| anyhow!("Invalid config file {} - {}", path.display(), e))
+}
+
+/// Parses a configuration in TOML format.
+pub fn parse<T: DeserializeOwned>(text: &str) -> Result<T> {
+    Ok(toml::from_str(text)?)
+}
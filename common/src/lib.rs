@@ -1,3 +1,4 @@
+pub mod config_file;
 pub mod logging;
 pub mod shutdown_handle;
 pub mod signal;
@@ -244,6 +244,11 @@ pub struct RefreshWorker {
     #[serde(rename = "connectionState")]
     pub connection_state: models::ConnectionState,
 
+    /// The worker is shutting down, and is finishing the procedures it has under way.
+    #[serde(rename = "draining")]
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub draining: Option<bool>,
+
 }
 
 impl RefreshWorker {
@@ -253,6 +258,7 @@ impl RefreshWorker {
             worker_id,
             worker_info,
             connection_state,
+            draining: None,
         }
     }
 }
@@ -269,6 +275,14 @@ impl std::string::ToString for RefreshWorker {
 
             // Skipping connectionState in query parameter serialization
 
+
+            self.draining.as_ref().map(|draining| {
+                vec![
+                    "draining".to_string(),
+                    draining.to_string(),
+                ].join(",")
+            }),
+
         ];
 
         params.into_iter().flatten().collect::<Vec<_>>().join(",")
@@ -289,6 +303,7 @@ impl std::str::FromStr for RefreshWorker {
             pub worker_id: Vec<uuid::Uuid>,
             pub worker_info: Vec<models::WorkerInfo>,
             pub connection_state: Vec<models::ConnectionState>,
+            pub draining: Vec<bool>,
         }
 
         let mut intermediate_rep = IntermediateRep::default();
@@ -312,6 +327,8 @@ impl std::str::FromStr for RefreshWorker {
                     "workerInfo" => intermediate_rep.worker_info.push(<models::WorkerInfo as std::str::FromStr>::from_str(val).map_err(|x| x.to_string())?),
                     #[allow(clippy::redundant_clone)]
                     "connectionState" => intermediate_rep.connection_state.push(<models::ConnectionState as std::str::FromStr>::from_str(val).map_err(|x| x.to_string())?),
+                    #[allow(clippy::redundant_clone)]
+                    "draining" => intermediate_rep.draining.push(<bool as std::str::FromStr>::from_str(val).map_err(|x| x.to_string())?),
                     _ => return std::result::Result::Err("Unexpected key while parsing RefreshWorker".to_string())
                 }
             }
@@ -325,6 +342,7 @@ impl std::str::FromStr for RefreshWorker {
             worker_id: intermediate_rep.worker_id.into_iter().next().ok_or_else(|| "workerId missing in RefreshWorker".to_string())?,
             worker_info: intermediate_rep.worker_info.into_iter().next().ok_or_else(|| "workerInfo missing in RefreshWorker".to_string())?,
             connection_state: intermediate_rep.connection_state.into_iter().next().ok_or_else(|| "connectionState missing in RefreshWorker".to_string())?,
+            draining: intermediate_rep.draining.into_iter().next(),
         })
     }
 }
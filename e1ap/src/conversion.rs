@@ -1,4 +1,9 @@
 use crate::ies::Snssai;
+use crate::{
+    PlmnIdentity, SliceSupportItem, SliceSupportList, SupportedPlmnsItem, SupportedPlmnsList,
+};
+use anyhow::{anyhow, Result};
+use asn1_per::NonEmpty;
 
 impl From<xxap::Snssai> for Snssai {
     fn from(x: xxap::Snssai) -> Self {
@@ -14,3 +19,33 @@ impl From<Snssai> for xxap::Snssai {
         xxap::Snssai(x.sst[0], x.sd.map(|x| [x[0], x[1], x[2]]))
     }
 }
+
+// The PLMNs that a CU-UP supports are those that its gNB broadcasts, each with the slices that it has in any
+// of the gNB's tracking areas.
+impl TryFrom<&xxap::RanConfig> for SupportedPlmnsList {
+    type Error = anyhow::Error;
+    fn try_from(config: &xxap::RanConfig) -> Result<Self> {
+        let plmns = config
+            .plmn_slices()
+            .into_iter()
+            .map(|(plmn, slices)| SupportedPlmnsItem {
+                plmn_identity: PlmnIdentity(plmn),
+                slice_support_list: NonEmpty::from_vec(
+                    slices
+                        .into_iter()
+                        .map(|x| SliceSupportItem { snssai: x.into() })
+                        .collect(),
+                )
+                .map(SliceSupportList),
+                nr_cgi_support_list: None,
+                qos_parameters_support_list: None,
+                npn_support_info: None,
+                extended_slice_support_list: None,
+                extended_nr_cgi_support_list: None,
+            })
+            .collect();
+        Ok(SupportedPlmnsList(
+            NonEmpty::from_vec(plmns).ok_or_else(|| anyhow!("No PLMNs configured"))?,
+        ))
+    }
+}
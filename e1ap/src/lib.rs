@@ -11,3 +11,4 @@ pub use e1ap_cp::*;
 mod e1ap_up;
 pub use e1ap_up::*;
 mod conversion;
+mod transaction_key;
@@ -0,0 +1,142 @@
+//! transaction_key - identifies the transaction that an E1AP PDU belongs to, so that a response can be
+//! matched with its request.
+//!
+//! For a UE-associated procedure this is the GnbCuCpUeE1apId.  For a non UE-associated procedure it is
+//! the TransactionId.
+
+use crate::{E1apPdu, InitiatingMessage, SuccessfulOutcome, UnsuccessfulOutcome};
+use asn1_per::{TransactionKey, TransactionKey::*, TransactionKeyed};
+
+impl TransactionKeyed for E1apPdu {
+    fn transaction_key(&self) -> Option<TransactionKey> {
+        match self {
+            E1apPdu::InitiatingMessage(m) => m.transaction_key(),
+            E1apPdu::SuccessfulOutcome(m) => m.transaction_key(),
+            E1apPdu::UnsuccessfulOutcome(m) => m.transaction_key(),
+        }
+    }
+}
+
+impl TransactionKeyed for InitiatingMessage {
+    fn transaction_key(&self) -> Option<TransactionKey> {
+        match self {
+            InitiatingMessage::Reset(x) => Some(Transaction(x.transaction_id.0 as u32)),
+            InitiatingMessage::ErrorIndication(x) => x.gnb_cu_cp_ue_e1ap_id.map(|id| Ue(id.0)),
+            InitiatingMessage::GnbCuUpE1SetupRequest(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::GnbCuCpE1SetupRequest(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::GnbCuUpConfigurationUpdate(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::GnbCuCpConfigurationUpdate(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::E1ReleaseRequest(x) => Some(Transaction(x.transaction_id.0 as u32)),
+            InitiatingMessage::BearerContextSetupRequest(x) => Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0)),
+            InitiatingMessage::BearerContextModificationRequest(x) => {
+                Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0))
+            }
+            InitiatingMessage::BearerContextModificationRequired(x) => {
+                Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0))
+            }
+            InitiatingMessage::BearerContextReleaseCommand(x) => Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0)),
+            InitiatingMessage::BearerContextReleaseRequest(x) => Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0)),
+            InitiatingMessage::BearerContextInactivityNotification(x) => {
+                Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0))
+            }
+            InitiatingMessage::DlDataNotification(x) => Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0)),
+            InitiatingMessage::UlDataNotification(x) => Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0)),
+            InitiatingMessage::DataUsageReport(x) => Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0)),
+            InitiatingMessage::GnbCuUpCounterCheckRequest(x) => Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0)),
+            InitiatingMessage::GnbCuUpStatusIndication(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::GnbCuCpMeasurementResultsInformation(x) => {
+                Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0))
+            }
+            InitiatingMessage::MrdcDataUsageReport(x) => Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0)),
+            InitiatingMessage::DeactivateTrace(x) => Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0)),
+            InitiatingMessage::TraceStart(x) => Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0)),
+            InitiatingMessage::ResourceStatusRequest(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::ResourceStatusUpdate(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::IabUpTnlAddressUpdate(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::CellTrafficTrace(x) => Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0)),
+            InitiatingMessage::EarlyForwardingSnTransfer(x) => Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0)),
+        }
+    }
+}
+
+impl TransactionKeyed for SuccessfulOutcome {
+    fn transaction_key(&self) -> Option<TransactionKey> {
+        match self {
+            SuccessfulOutcome::ResetAcknowledge(x) => Some(Transaction(x.transaction_id.0 as u32)),
+            SuccessfulOutcome::GnbCuUpE1SetupResponse(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::GnbCuCpE1SetupResponse(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::GnbCuUpConfigurationUpdateAcknowledge(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::GnbCuCpConfigurationUpdateAcknowledge(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::E1ReleaseResponse(x) => Some(Transaction(x.transaction_id.0 as u32)),
+            SuccessfulOutcome::BearerContextSetupResponse(x) => Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0)),
+            SuccessfulOutcome::BearerContextModificationResponse(x) => {
+                Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0))
+            }
+            SuccessfulOutcome::BearerContextModificationConfirm(x) => {
+                Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0))
+            }
+            SuccessfulOutcome::BearerContextReleaseComplete(x) => {
+                Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0))
+            }
+            SuccessfulOutcome::ResourceStatusResponse(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::IabUpTnlAddressUpdateAcknowledge(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+        }
+    }
+}
+
+impl TransactionKeyed for UnsuccessfulOutcome {
+    fn transaction_key(&self) -> Option<TransactionKey> {
+        match self {
+            UnsuccessfulOutcome::GnbCuUpE1SetupFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            UnsuccessfulOutcome::GnbCuCpE1SetupFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            UnsuccessfulOutcome::GnbCuUpConfigurationUpdateFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            UnsuccessfulOutcome::GnbCuCpConfigurationUpdateFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            UnsuccessfulOutcome::BearerContextSetupFailure(x) => Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0)),
+            UnsuccessfulOutcome::BearerContextModificationFailure(x) => {
+                Some(Ue(x.gnb_cu_cp_ue_e1ap_id.0))
+            }
+            UnsuccessfulOutcome::ResourceStatusFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            UnsuccessfulOutcome::IabUpTnlAddressUpdateFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+        }
+    }
+}
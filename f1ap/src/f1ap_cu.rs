@@ -30,11 +30,14 @@ where
 
 impl<T> Application for F1apCu<T> where
     T: RequestProvider<F1SetupProcedure>
+        + RequestProvider<F1RemovalProcedure>
         + RequestProvider<GnbDuConfigurationUpdateProcedure>
         + EventHandler
         + Clone
         + IndicationHandler<InitialUlRrcMessageTransferProcedure>
         + IndicationHandler<UlRrcMessageTransferProcedure>
+        + IndicationHandler<UeContextReleaseRequestProcedure>
+        + IndicationHandler<UeInactivityNotificationProcedure>
 {
 }
 
@@ -45,9 +48,12 @@ where
         + Sync
         + EventHandler
         + RequestProvider<F1SetupProcedure>
+        + RequestProvider<F1RemovalProcedure>
         + RequestProvider<GnbDuConfigurationUpdateProcedure>
         + IndicationHandler<InitialUlRrcMessageTransferProcedure>
-        + IndicationHandler<UlRrcMessageTransferProcedure>,
+        + IndicationHandler<UlRrcMessageTransferProcedure>
+        + IndicationHandler<UeContextReleaseRequestProcedure>
+        + IndicationHandler<UeInactivityNotificationProcedure>,
     // Todo - add all other procedures
 {
     type TopPdu = F1apPdu;
@@ -63,6 +69,9 @@ where
             InitiatingMessage::F1SetupRequest(req) => {
                 F1SetupProcedure::call_provider(&self.0, req, logger).await
             }
+            InitiatingMessage::F1RemovalRequest(req) => {
+                F1RemovalProcedure::call_provider(&self.0, req, logger).await
+            }
             InitiatingMessage::InitialUlRrcMessageTransfer(req) => {
                 InitialUlRrcMessageTransferProcedure::call_provider(&self.0, req, logger).await;
                 None
@@ -74,6 +83,14 @@ where
             InitiatingMessage::GnbDuConfigurationUpdate(req) => {
                 GnbDuConfigurationUpdateProcedure::call_provider(&self.0, req, logger).await
             }
+            InitiatingMessage::UeContextReleaseRequest(req) => {
+                UeContextReleaseRequestProcedure::call_provider(&self.0, req, logger).await;
+                None
+            }
+            InitiatingMessage::UeInactivityNotification(req) => {
+                UeInactivityNotificationProcedure::call_provider(&self.0, req, logger).await;
+                None
+            }
             _ => todo!(),
         }
     }
@@ -9,5 +9,6 @@ pub use top_pdu::*;
 mod f1ap_cu;
 pub use f1ap_cu::*;
 mod conversion;
+mod transaction_key;
 #[cfg(test)]
 mod test;
@@ -1,6 +1,6 @@
 use crate::{
-    BapAddress, F1SetupResponse, F1apPdu, NrModeInfo, RrcVersion, ServedCellInformation,
-    ServedPlmnsList,
+    BapAddress, F1SetupResponse, F1apPdu, GnbCuUeF1apId, GnbDuUeF1apId, NrModeInfo, RrcVersion,
+    ServedCellInformation, ServedPlmnsList, SuccessfulOutcome, UeContextReleaseComplete,
 };
 use asn1_per::*;
 
@@ -59,3 +59,37 @@ fn test_f1_response_encode_decode() -> Result<(), PerCodecError> {
     let _f1_setup_response = F1SetupResponse::from_bytes(&bytes)?;
     Ok(())
 }
+
+#[test]
+fn test_transaction_key() {
+    let complete = |gnb_cu_ue_f1ap_id| {
+        F1apPdu::SuccessfulOutcome(SuccessfulOutcome::UeContextReleaseComplete(
+            UeContextReleaseComplete {
+                gnb_cu_ue_f1ap_id: GnbCuUeF1apId(gnb_cu_ue_f1ap_id),
+                gnb_du_ue_f1ap_id: GnbDuUeF1apId(1),
+                criticality_diagnostics: None,
+            },
+        ))
+    };
+    assert_eq!(complete(7).transaction_key(), Some(TransactionKey::Ue(7)));
+    assert_ne!(complete(7).transaction_key(), complete(8).transaction_key());
+
+    let f1_setup_response =
+        F1apPdu::SuccessfulOutcome(SuccessfulOutcome::F1SetupResponse(F1SetupResponse {
+            transaction_id: crate::TransactionId(3),
+            gnb_cu_name: None,
+            cells_to_be_activated_list: None,
+            gnb_cu_rrc_version: RrcVersion {
+                latest_rrc_version: bitvec![u8, Msb0;0, 0, 0],
+                latest_rrc_version_enhanced: None,
+            },
+            transport_layer_address_info: None,
+            ul_bh_non_up_traffic_mapping: None,
+            bap_address: None,
+            extended_gnb_du_name: None,
+        }));
+    assert_eq!(
+        f1_setup_response.transaction_key(),
+        Some(TransactionKey::Transaction(3))
+    );
+}
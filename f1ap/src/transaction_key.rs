@@ -0,0 +1,247 @@
+//! transaction_key - identifies the transaction that an F1AP PDU belongs to, so that a response can be
+//! matched with its request.
+//!
+//! For a UE-associated procedure this is the GnbCuUeF1apId.  For a non UE-associated procedure it is
+//! the TransactionId.
+
+use crate::{F1apPdu, InitiatingMessage, SuccessfulOutcome, UnsuccessfulOutcome};
+use asn1_per::{TransactionKey, TransactionKey::*, TransactionKeyed};
+
+impl TransactionKeyed for F1apPdu {
+    fn transaction_key(&self) -> Option<TransactionKey> {
+        match self {
+            F1apPdu::InitiatingMessage(m) => m.transaction_key(),
+            F1apPdu::SuccessfulOutcome(m) => m.transaction_key(),
+            F1apPdu::UnsuccessfulOutcome(m) => m.transaction_key(),
+        }
+    }
+}
+
+impl TransactionKeyed for InitiatingMessage {
+    fn transaction_key(&self) -> Option<TransactionKey> {
+        match self {
+            InitiatingMessage::Reset(x) => Some(Transaction(x.transaction_id.0 as u32)),
+            InitiatingMessage::F1SetupRequest(x) => Some(Transaction(x.transaction_id.0 as u32)),
+            InitiatingMessage::GnbDuConfigurationUpdate(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::GnbCuConfigurationUpdate(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::UeContextSetupRequest(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::UeContextReleaseCommand(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::UeContextModificationRequest(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::UeContextModificationRequired(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::WriteReplaceWarningRequest(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::PwsCancelRequest(x) => Some(Transaction(x.transaction_id.0 as u32)),
+            InitiatingMessage::ErrorIndication(x) => x.gnb_cu_ue_f1ap_id.map(|id| Ue(id.0)),
+            InitiatingMessage::UeContextReleaseRequest(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            // Sent before the gNB-CU has allocated its UE F1AP ID.  The gNB-DU UE F1AP ID is not a gNB-CU UE key, so
+            // this is not UE associated - the gNB-CU binds the UE to this TNLA when it creates the UE.
+            InitiatingMessage::InitialUlRrcMessageTransfer(_) => None,
+            InitiatingMessage::DlRrcMessageTransfer(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::UlRrcMessageTransfer(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::UeInactivityNotification(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::GnbDuResourceCoordinationRequest(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::SystemInformationDeliveryCommand(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::Notify(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::NetworkAccessRateReduction(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::PwsRestartIndication(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::PwsFailureIndication(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::GnbDuStatusIndication(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::RrcDeliveryReport(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::F1RemovalRequest(x) => Some(Transaction(x.transaction_id.0 as u32)),
+            InitiatingMessage::TraceStart(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::DeactivateTrace(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::DuCuRadioInformationTransfer(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::CuDuRadioInformationTransfer(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::BapMappingConfiguration(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::GnbDuResourceConfiguration(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::IabtnlAddressRequest(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::IabupConfigurationUpdateRequest(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::ResourceStatusRequest(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::ResourceStatusUpdate(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::AccessAndMobilityIndication(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::ReferenceTimeInformationReportingControl(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::ReferenceTimeInformationReport(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::AccessSuccess(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::CellTrafficTrace(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::PositioningAssistanceInformationControl(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::PositioningAssistanceInformationFeedback(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::PositioningMeasurementRequest(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::PositioningMeasurementReport(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::PositioningMeasurementAbort(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::PositioningMeasurementFailureIndication(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::PositioningMeasurementUpdate(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::TrpInformationRequest(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            InitiatingMessage::PositioningInformationRequest(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::PositioningActivationRequest(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::PositioningDeactivation(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::ECidMeasurementInitiationRequest(x) => {
+                Some(Ue(x.gnb_cu_ue_f1ap_id.0))
+            }
+            InitiatingMessage::ECidMeasurementFailureIndication(x) => {
+                Some(Ue(x.gnb_cu_ue_f1ap_id.0))
+            }
+            InitiatingMessage::ECidMeasurementReport(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            InitiatingMessage::ECidMeasurementTerminationCommand(x) => {
+                Some(Ue(x.gnb_cu_ue_f1ap_id.0))
+            }
+            InitiatingMessage::PositioningInformationUpdate(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            _ => None,
+        }
+    }
+}
+
+impl TransactionKeyed for SuccessfulOutcome {
+    fn transaction_key(&self) -> Option<TransactionKey> {
+        match self {
+            SuccessfulOutcome::ResetAcknowledge(x) => Some(Transaction(x.transaction_id.0 as u32)),
+            SuccessfulOutcome::F1SetupResponse(x) => Some(Transaction(x.transaction_id.0 as u32)),
+            SuccessfulOutcome::GnbDuConfigurationUpdateAcknowledge(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::GnbCuConfigurationUpdateAcknowledge(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::UeContextSetupResponse(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            SuccessfulOutcome::UeContextReleaseComplete(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            SuccessfulOutcome::UeContextModificationResponse(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            SuccessfulOutcome::UeContextModificationConfirm(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            SuccessfulOutcome::WriteReplaceWarningResponse(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::PwsCancelResponse(x) => Some(Transaction(x.transaction_id.0 as u32)),
+            SuccessfulOutcome::GnbDuResourceCoordinationResponse(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::F1RemovalResponse(x) => Some(Transaction(x.transaction_id.0 as u32)),
+            SuccessfulOutcome::BapMappingConfigurationAcknowledge(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::GnbDuResourceConfigurationAcknowledge(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::IabtnlAddressResponse(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::IabupConfigurationUpdateResponse(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::ResourceStatusResponse(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::PositioningMeasurementResponse(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::TrpInformationResponse(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            SuccessfulOutcome::PositioningInformationResponse(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            SuccessfulOutcome::PositioningActivationResponse(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            SuccessfulOutcome::ECidMeasurementInitiationResponse(x) => {
+                Some(Ue(x.gnb_cu_ue_f1ap_id.0))
+            }
+        }
+    }
+}
+
+impl TransactionKeyed for UnsuccessfulOutcome {
+    fn transaction_key(&self) -> Option<TransactionKey> {
+        match self {
+            UnsuccessfulOutcome::F1SetupFailure(x) => Some(Transaction(x.transaction_id.0 as u32)),
+            UnsuccessfulOutcome::GnbDuConfigurationUpdateFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            UnsuccessfulOutcome::GnbCuConfigurationUpdateFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            UnsuccessfulOutcome::UeContextSetupFailure(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            UnsuccessfulOutcome::UeContextModificationFailure(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            UnsuccessfulOutcome::UeContextModificationRefuse(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            UnsuccessfulOutcome::F1RemovalFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            UnsuccessfulOutcome::BapMappingConfigurationFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            UnsuccessfulOutcome::GnbDuResourceConfigurationFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            UnsuccessfulOutcome::IabtnlAddressFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            UnsuccessfulOutcome::IabupConfigurationUpdateFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            UnsuccessfulOutcome::ResourceStatusFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            UnsuccessfulOutcome::PositioningMeasurementFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            UnsuccessfulOutcome::TrpInformationFailure(x) => {
+                Some(Transaction(x.transaction_id.0 as u32))
+            }
+            UnsuccessfulOutcome::PositioningInformationFailure(x) => {
+                Some(Ue(x.gnb_cu_ue_f1ap_id.0))
+            }
+            UnsuccessfulOutcome::PositioningActivationFailure(x) => Some(Ue(x.gnb_cu_ue_f1ap_id.0)),
+            UnsuccessfulOutcome::ECidMeasurementInitiationFailure(x) => {
+                Some(Ue(x.gnb_cu_ue_f1ap_id.0))
+            }
+        }
+    }
+}
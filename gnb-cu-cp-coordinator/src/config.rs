@@ -1,6 +1,9 @@
 //! config - the config of the GNB-CU Coordinator
 
-#[derive(Debug, Clone)]
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     // The port on which to serve the coordination API.
     pub bind_port: u16,
@@ -9,9 +12,10 @@ pub struct Config {
     pub connection_control_config: ConnectionControlConfig,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ConnectionControlConfig {
-    // AMF address
+    // AMF address.  A comma separated list of IP addresses if the AMF is multihomed.
     pub amf_address: String,
 
     // Worker refresh interval
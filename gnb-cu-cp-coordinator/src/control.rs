@@ -104,6 +104,8 @@ struct WorkerState {
     e1: ConnectionState,
     f1: ConnectionState,
     ng: ConnectionState,
+    // The worker is shutting down.  It is not brought into service, nor asked to help other workers.
+    draining: bool,
 }
 
 impl WorkerState {
@@ -113,6 +115,7 @@ impl WorkerState {
             e1: ConnectionState::new(refresh.connection_state.e1_up),
             f1: ConnectionState::new(refresh.connection_state.f1_up),
             ng: ConnectionState::new(refresh.connection_state.ng_up),
+            draining: refresh.draining.unwrap_or_default(),
         }
     }
 }
@@ -196,11 +199,20 @@ impl<T: Api<ClientContext>, P: ConnectionApiProvider<T>> Controller<T, P> {
             x.e1.up = refresh.connection_state.e1_up;
             x.f1.up = refresh.connection_state.f1_up;
             x.ng.up = refresh.connection_state.ng_up;
+            x.draining = refresh.draining.unwrap_or_default();
             x
         } else {
             WorkerState::new(refresh)
         };
 
+        // Is this worker shutting down?
+        if this_worker.draining {
+            // Yes - leave it to finish what it is doing.
+            debug!(logger, "{:x} is draining", worker_id);
+            let _ = workers.insert(worker_id, this_worker);
+            return;
+        }
+
         // Does this worker have the NGAP interface up?
         if !this_worker.ng.up {
             // No - set up or join NGAP as appropriate
@@ -213,7 +225,7 @@ impl<T: Api<ClientContext>, P: ConnectionApiProvider<T>> Controller<T, P> {
         // Does this worker have the E1AP interface up?
         if !this_worker.e1.up {
             // Find a worker to help it get connected.
-            if let Some(connected_worker) = workers.values().find(|x| x.e1.up) {
+            if let Some(connected_worker) = workers.values().find(|x| x.e1.up && !x.draining) {
                 let _ = self
                     .add_e1ap(
                         connected_worker,
@@ -231,7 +243,7 @@ impl<T: Api<ClientContext>, P: ConnectionApiProvider<T>> Controller<T, P> {
 
             // Help other workers get connected.
             for (other_worker_id, other_worker_state) in workers.iter_mut() {
-                if !other_worker_state.e1.up {
+                if !other_worker_state.e1.up && !other_worker_state.draining {
                     let _ = self
                         .add_e1ap(
                             &this_worker,
@@ -248,7 +260,7 @@ impl<T: Api<ClientContext>, P: ConnectionApiProvider<T>> Controller<T, P> {
         // Same routine for the F1.
         if !this_worker.f1.up {
             // Find a worker that is connected.
-            if let Some(connected_worker) = workers.values().find(|x| x.f1.up) {
+            if let Some(connected_worker) = workers.values().find(|x| x.f1.up && !x.draining) {
                 // Tell it to add this worker.
                 let _ = self
                     .add_f1ap(
@@ -267,7 +279,7 @@ impl<T: Api<ClientContext>, P: ConnectionApiProvider<T>> Controller<T, P> {
 
             // Find all workers that are not connected and attempt to add them.
             for (other_worker_id, other_worker_state) in workers.iter_mut() {
-                if !other_worker_state.f1.up {
+                if !other_worker_state.f1.up && !other_worker_state.draining {
                     let _ = self
                         .add_f1ap(
                             &this_worker,
@@ -1,14 +1,48 @@
 use anyhow::Result;
-use common::{logging, signal};
+use clap::Parser;
+use common::{config_file, logging, signal};
 use gnb_cu_cp_coordinator::Config;
 use slog::info;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// TOML file giving the coordinator's configuration.  The other options override the settings in it.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Port on which to serve the coordination API.  Default 43521.
+    #[arg(short, long)]
+    bind_port: Option<u16>,
+
+    /// AMF's NGAP IP address, or a comma separated list of them if the AMF is multihomed.  Default 127.0.0.1.
+    #[arg(short, long)]
+    amf_address: Option<String>,
+}
 
 #[async_std::main]
 async fn main() -> Result<()> {
+    let args = Args::parse();
+    let config = config(args)?;
     let root_logger = logging::init();
-    let shutdown_handle = gnb_cu_cp_coordinator::spawn(Config::default(), root_logger.clone())?;
+    let shutdown_handle = gnb_cu_cp_coordinator::spawn(config, root_logger.clone())?;
     let s = signal::wait_for_signal().await?;
     info!(root_logger, "Caught signal {} - terminate", s);
     shutdown_handle.graceful_shutdown().await;
     Ok(())
 }
+
+fn config(args: Args) -> Result<Config> {
+    let mut config: Config = match &args.config {
+        Some(path) => config_file::load(path)?,
+        None => Config::default(),
+    };
+    if let Some(bind_port) = args.bind_port {
+        config.bind_port = bind_port;
+    }
+    if let Some(amf_address) = args.amf_address {
+        config.connection_control_config.amf_address = amf_address;
+    }
+    Ok(config)
+}
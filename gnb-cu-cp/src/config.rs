@@ -1,14 +1,24 @@
 //! config - the config of a GNB-CU
 
+use asn1_per::Procedure;
 pub use coordinator::ConnectionControlConfig;
+use net::{SctpConfig, StackConfig};
+use ngap::{NgSetupProcedure, RanConfigurationUpdateProcedure};
+use serde::Deserialize;
 use std::net::{IpAddr, Ipv4Addr};
+use xxap::RanConfig;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     // The IP address that the worker binds all of it listen ports to. To test multiple workers running on a
     // single system, each can be given a different 127.0.0.0/8 IP address.
     pub ip_addr: IpAddr,
 
+    // Further local IP addresses for SCTP multihoming.  The NGAP, F1AP and E1AP associations are bound to
+    // these as well as to ip_addr, so that they survive the loss of a path.
+    pub secondary_ip_addrs: Vec<IpAddr>,
+
     // Set this to Autonomous to have a single worker that connects immediately to AMF on the given
     // IP address.
     //
@@ -21,30 +31,76 @@ pub struct Config {
     // TTL to set on the UE state once UE is configured
     pub ue_ttl_secs: usize,
 
+    // How long a worker goes on using its cached copy of a DU's served cells before reading them again.  This
+    // bounds the time it takes for a change in a DU's cells, handled by one worker, to reach the others.
+    pub du_cache_ttl_secs: u64,
+
+    // On shutdown, how long to give the procedures under way to finish before closing the associations.
+    pub drain_timeout_secs: u64,
+
+    // How often the worker logs the depth and wait counts of the send queue of each NGAP, F1AP and E1AP TNLA.
+    pub send_queue_stats_interval_secs: u64,
+
+    // The port on which the worker serves the Management API, through which an operator can activate, deactivate
+    // and bar cells and change their system information.  Not served if None.
+    pub management_api_bind_port: Option<u16>,
+
     // Human readable name signaled in NG Setup Request, E1 GnbCuUpE1SetupResponse and F1SetupResponse
     pub name: Option<String>,
 
-    // PLMN
-    pub plmn: [u8; 3],
+    // The gNB ID, tracking areas, PLMNs and slices signalled to the AMF in NG Setup.
+    pub ran: RanConfig,
+
+    // Response timers and pending request limits for requests that this worker sends on each interface.
+    pub ngap_stack_config: StackConfig,
+    pub f1ap_stack_config: StackConfig,
+    pub e1ap_stack_config: StackConfig,
+
+    // SCTP settings, such as the maximum size of a received message, for each interface.
+    pub ngap_sctp_config: SctpConfig,
+    pub f1ap_sctp_config: SctpConfig,
+    pub e1ap_sctp_config: SctpConfig,
+
+    // The port of the Redis server on localhost that holds the UE and DU state.  Used by the gnb-cu-cp
+    // binary, and ignored by callers of spawn(), which pass in their own stores.
+    pub redis_port: u16,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             ip_addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            secondary_ip_addrs: vec![],
             connection_style: ConnectionStyle::Autonomous(ConnectionControlConfig {
                 fast_start: true,
                 ..ConnectionControlConfig::default()
             }),
             initial_ue_ttl_secs: 5,
             ue_ttl_secs: 86_400, // a day
+            du_cache_ttl_secs: 10,
+            drain_timeout_secs: 15,
+            send_queue_stats_interval_secs: 60,
+            management_api_bind_port: None,
             name: Some("Alsoran".to_string()),
-            plmn: [0x2, 0xf8, 0x39],
+            ran: RanConfig::default(),
+            // NG Setup and RAN Configuration Update carry our whole configuration, so can safely be sent again
+            // on another of the AMF's TNLAs.
+            ngap_stack_config: StackConfig::default()
+                .with_retry_on_tnla_failure(NgSetupProcedure::CODE)
+                .with_retry_on_tnla_failure(RanConfigurationUpdateProcedure::CODE),
+            f1ap_stack_config: StackConfig::default(),
+            e1ap_stack_config: StackConfig::default(),
+            ngap_sctp_config: SctpConfig::default(),
+            f1ap_sctp_config: SctpConfig::default(),
+            e1ap_sctp_config: SctpConfig::default(),
+            redis_port: 6379,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+// In a config file, this is a table named after the variant - for example [connection_style.coordinated].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ConnectionStyle {
     // Singleton worker that connects directly to the AMF.
     Autonomous(ConnectionControlConfig),
@@ -54,7 +110,18 @@ pub enum ConnectionStyle {
     Coordinated(WorkerConnectionManagementConfig),
 }
 
-#[derive(Debug, Clone)]
+impl ConnectionStyle {
+    // How often the worker sends its state to the coordinator.
+    pub fn refresh_interval_secs(&self) -> u16 {
+        match self {
+            ConnectionStyle::Autonomous(x) => x.worker_refresh_interval_secs,
+            ConnectionStyle::Coordinated(x) => x.refresh_interval_secs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct WorkerConnectionManagementConfig {
     // The port the worker will bind the Connection API to.
     pub connection_api_bind_port: u16,
@@ -67,4 +134,13 @@ pub struct WorkerConnectionManagementConfig {
     // The base path of URL that the worker will use to contact the coordinator.
     // Example: "http://example.com:12345"
     pub coordinator_base_path: String,
+
+    // How often the worker sends its state to the coordinator.  This must be the coordinator's
+    // worker_refresh_interval_secs, which is how long the coordinator expects to wait between refreshes.
+    #[serde(default = "default_refresh_interval_secs")]
+    pub refresh_interval_secs: u16,
+}
+
+fn default_refresh_interval_secs() -> u16 {
+    ConnectionControlConfig::default().worker_refresh_interval_secs
 }
@@ -0,0 +1,149 @@
+//! cu_up_state - serializable model of GNB-CU-CP's per CU-UP state
+
+use super::SerDes;
+use anyhow::Result;
+use async_trait::async_trait;
+use e1ap::{GnbCuUpE1SetupRequest, GnbCuUpId, SupportedPlmnsItem};
+use speedy::{Readable, Writable};
+use xxap::Snssai;
+
+/// Storage of CU-UP state.  Like DU state, this is written rarely - on E1 Setup - but needs to be readable
+/// by every worker.
+#[async_trait]
+pub trait CuUpStateStore: Clone + Send + Sync + 'static {
+    async fn store_cu_up(&self, s: CuUpState) -> Result<()>;
+    async fn retrieve_all_cu_ups(&self) -> Result<Vec<CuUpState>>;
+}
+
+/// What a CU-UP told the CU-CP about itself in its E1 Setup.
+#[derive(Clone, Debug)]
+pub struct CuUpState {
+    pub gnb_cu_up_id: GnbCuUpId,
+    pub name: Option<String>,
+
+    // The remote IP address of the CU-UP's E1AP TNLAs.  This is how a worker finds its own TNLA to the CU-UP.
+    pub address: String,
+
+    pub supported_plmns: Vec<CuUpSupportedPlmn>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CuUpSupportedPlmn {
+    pub plmn: [u8; 3],
+
+    // The slices that the CU-UP supports in the PLMN.  If the CU-UP did not list them, this is empty, and it
+    // supports any slice.
+    pub slices: Vec<Snssai>,
+
+    // The non-dynamic 5QIs that the CU-UP supports in the PLMN, or None if it did not list them, in which
+    // case it supports any.
+    pub five_qis: Option<Vec<u8>>,
+}
+
+#[derive(Readable, Writable)]
+pub struct CuUpStateSerializable {
+    pub gnb_cu_up_id: u64,
+    pub name: Option<String>,
+    pub address: String,
+    pub supported_plmns: Vec<CuUpSupportedPlmnSerializable>,
+}
+
+#[derive(Readable, Writable)]
+pub struct CuUpSupportedPlmnSerializable {
+    pub plmn: [u8; 3],
+    pub slices: Vec<(u8, Option<[u8; 3]>)>,
+    pub five_qis: Option<Vec<u8>>,
+}
+
+impl CuUpState {
+    pub fn new(r: &GnbCuUpE1SetupRequest, address: String) -> Self {
+        CuUpState {
+            gnb_cu_up_id: r.gnb_cu_up_id,
+            name: r.gnb_cu_up_name.as_ref().map(|x| x.0.clone()),
+            address,
+            supported_plmns: r.supported_plmns.0.iter().map(|x| x.into()).collect(),
+        }
+    }
+
+    /// Whether the CU-UP can carry PDU sessions with the given slices and non-dynamic 5QIs in a PLMN.
+    pub fn supports(&self, plmn: &[u8; 3], slices: &[Snssai], five_qis: &[u8]) -> bool {
+        self.supported_plmns.iter().any(|x| {
+            x.plmn == *plmn
+                && (x.slices.is_empty() || slices.iter().all(|slice| x.slices.contains(slice)))
+                && x.five_qis.as_ref().map_or(true, |supported| {
+                    five_qis.iter().all(|y| supported.contains(y))
+                })
+        })
+    }
+}
+
+impl From<&SupportedPlmnsItem> for CuUpSupportedPlmn {
+    fn from(x: &SupportedPlmnsItem) -> Self {
+        CuUpSupportedPlmn {
+            plmn: x.plmn_identity.0,
+            slices: x
+                .slice_support_list
+                .iter()
+                .flat_map(|x| x.0.iter())
+                .map(|x| x.snssai.clone().into())
+                .collect(),
+            five_qis: x
+                .qos_parameters_support_list
+                .as_ref()
+                .and_then(|x| x.ng_ran_qos_support_list.as_ref())
+                .map(|x| {
+                    x.0.iter()
+                        .map(|x| x.non_dynamic_5qi_descriptor.five_qi)
+                        .collect()
+                }),
+        }
+    }
+}
+
+impl SerDes for CuUpState {
+    fn into_bytes(self) -> Result<Vec<u8>> {
+        Ok(CuUpStateSerializable::from(self).write_to_vec()?)
+    }
+    fn from_bytes(v: &[u8]) -> Result<Self> {
+        let s = CuUpStateSerializable::read_from_buffer(v)?;
+        Ok(CuUpState::from(s))
+    }
+}
+
+impl From<CuUpState> for CuUpStateSerializable {
+    fn from(x: CuUpState) -> Self {
+        CuUpStateSerializable {
+            gnb_cu_up_id: x.gnb_cu_up_id.0,
+            name: x.name,
+            address: x.address,
+            supported_plmns: x
+                .supported_plmns
+                .into_iter()
+                .map(|x| CuUpSupportedPlmnSerializable {
+                    plmn: x.plmn,
+                    slices: x.slices.into_iter().map(|x| (x.0, x.1)).collect(),
+                    five_qis: x.five_qis,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<CuUpStateSerializable> for CuUpState {
+    fn from(x: CuUpStateSerializable) -> Self {
+        CuUpState {
+            gnb_cu_up_id: GnbCuUpId(x.gnb_cu_up_id),
+            name: x.name,
+            address: x.address,
+            supported_plmns: x
+                .supported_plmns
+                .into_iter()
+                .map(|x| CuUpSupportedPlmn {
+                    plmn: x.plmn,
+                    slices: x.slices.into_iter().map(|x| Snssai(x.0, x.1)).collect(),
+                    five_qis: x.five_qis,
+                })
+                .collect(),
+        }
+    }
+}
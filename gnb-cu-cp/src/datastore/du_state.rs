@@ -0,0 +1,247 @@
+//! du_state - serializable model of GNB-CU's per DU state
+
+use super::SerDes;
+use anyhow::{ensure, Result};
+use asn1_per::SerDes as Asn1Serdes;
+use async_trait::async_trait;
+use f1ap::{FiveGsTac, GnbDuId, NrCgi, NrPci, PlmnIdentity, ServedCellInformation};
+use speedy::{Readable, Writable};
+use uuid::Uuid;
+
+/// Storage of DU state.  DU state is written rarely - on F1 Setup and DU configuration update - but needs
+/// to be readable by every worker.  It is deleted when the DU goes away.
+///
+/// The store also records which workers have F1AP TNLAs to each DU address, so that a DU is only treated
+/// as gone once no worker is connected to it.
+#[async_trait]
+pub trait DuStateStore: Clone + Send + Sync + 'static {
+    async fn store_du(&self, s: DuState) -> Result<()>;
+    async fn retrieve_du(&self, gnb_du_id: &GnbDuId) -> Result<DuState>;
+    async fn retrieve_all_dus(&self) -> Result<Vec<DuState>>;
+    async fn delete_du(&self, gnb_du_id: &GnbDuId) -> Result<()>;
+
+    /// Records that the worker has F1AP TNLAs to the DU address.
+    async fn add_du_connection(&self, address: &str, worker_id: Uuid) -> Result<()>;
+
+    /// Records that the worker has no F1AP TNLAs left to the DU address, returning the number of workers
+    /// that still have.
+    async fn remove_du_connection(&self, address: &str, worker_id: Uuid) -> Result<usize>;
+}
+
+#[derive(Clone, Debug)]
+pub struct DuState {
+    pub gnb_du_id: GnbDuId,
+
+    // The remote IP address of the DU's F1AP TNLAs.  This is how a worker finds its own TNLA to the DU.
+    pub address: String,
+
+    pub served_cells: Vec<ServedCell>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ServedCell {
+    pub nr_cgi: NrCgi,
+    pub nr_pci: NrPci,
+    pub five_gs_tac: Option<FiveGsTac>,
+    pub served_plmns: Vec<PlmnIdentity>,
+
+    // The state of the cell as set by the operator.  A cell starts out active and not barred.
+    pub active: bool,
+    pub barred: bool,
+
+    // The SIB2 that the CU gives the DU to broadcast in the cell, and its value tag, which changes each time
+    // the SIB2 does so that UEs know to read it again.
+    pub sib2: Sib2Parameters,
+    pub sib2_value_tag: u8,
+}
+
+/// The cell reselection parameters of a SIB2.  See TS 38.331, 6.3.1.
+#[derive(Clone, Debug, PartialEq, Eq, Readable, Writable)]
+pub struct Sib2Parameters {
+    // In dB - from 0 to 6, or an even number from 8 to 24.
+    pub q_hyst: u8,
+    pub cell_reselection_priority: u8,
+    pub thresh_serving_low_p: u8,
+    pub q_rx_lev_min: i8,
+    pub s_intra_search_p: u8,
+    pub t_reselection_nr: u8,
+}
+
+#[derive(Readable, Writable)]
+pub struct DuStateSerializable {
+    pub gnb_du_id: u64,
+    pub address: String,
+    pub served_cells: Vec<ServedCellSerializable>,
+}
+
+#[derive(Readable, Writable)]
+pub struct ServedCellSerializable {
+    pub nr_cgi: Vec<u8>,
+    pub nr_pci: u16,
+    pub five_gs_tac: Option<[u8; 3]>,
+    pub served_plmns: Vec<[u8; 3]>,
+    pub active: bool,
+    pub barred: bool,
+    pub sib2: Sib2Parameters,
+    pub sib2_value_tag: u8,
+}
+
+impl DuState {
+    pub fn new(gnb_du_id: GnbDuId, address: String) -> Self {
+        DuState {
+            gnb_du_id,
+            address,
+            served_cells: vec![],
+        }
+    }
+
+    pub fn served_cell(&self, nr_cgi: &NrCgi) -> Option<&ServedCell> {
+        self.served_cells.iter().find(|x| x.has_nr_cgi(nr_cgi))
+    }
+}
+
+impl ServedCell {
+    pub fn has_nr_cgi(&self, nr_cgi: &NrCgi) -> bool {
+        self.nr_cgi.plmn_identity.0 == nr_cgi.plmn_identity.0
+            && self.nr_cgi.nr_cell_identity.0 == nr_cgi.nr_cell_identity.0
+    }
+}
+
+impl Sib2Parameters {
+    /// The values of q-Hyst, in dB.  A q-Hyst is signalled as its index in this list.
+    pub const Q_HYST_VALUES: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 8, 10, 12, 14, 16, 18, 20, 22, 24];
+
+    /// Checks that each parameter is in the range that SIB2 allows.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            Self::Q_HYST_VALUES.contains(&self.q_hyst),
+            "q-Hyst must be from 0 to 6 dB, or an even number of dB from 8 to 24"
+        );
+        ensure!(
+            self.cell_reselection_priority <= 7,
+            "Cell reselection priority must be from 0 to 7"
+        );
+        ensure!(
+            self.thresh_serving_low_p <= 31,
+            "threshServingLowP must be from 0 to 31"
+        );
+        ensure!(
+            (-70..=-22).contains(&self.q_rx_lev_min),
+            "q-RxLevMin must be from -70 to -22"
+        );
+        ensure!(
+            self.s_intra_search_p <= 31,
+            "s-IntraSearchP must be from 0 to 31"
+        );
+        ensure!(
+            self.t_reselection_nr <= 7,
+            "t-ReselectionNR must be from 0 to 7"
+        );
+        Ok(())
+    }
+}
+
+impl Default for Sib2Parameters {
+    fn default() -> Self {
+        Sib2Parameters {
+            q_hyst: 1,
+            cell_reselection_priority: 2,
+            thresh_serving_low_p: 2,
+            q_rx_lev_min: -50,
+            s_intra_search_p: 2,
+            t_reselection_nr: 2,
+        }
+    }
+}
+
+impl From<&ServedCellInformation> for ServedCell {
+    fn from(x: &ServedCellInformation) -> Self {
+        ServedCell {
+            nr_cgi: x.nr_cgi.clone(),
+            nr_pci: x.nr_pci,
+            five_gs_tac: x.five_gs_tac.clone(),
+            served_plmns: x
+                .served_plmns
+                .0
+                .iter()
+                .map(|x| x.plmn_identity.clone())
+                .collect(),
+            active: true,
+            barred: false,
+            sib2: Sib2Parameters::default(),
+            sib2_value_tag: 0,
+        }
+    }
+}
+
+impl SerDes for DuState {
+    fn into_bytes(self) -> Result<Vec<u8>> {
+        Ok(DuStateSerializable::try_from(self)?.write_to_vec()?)
+    }
+    fn from_bytes(v: &[u8]) -> Result<Self> {
+        let s = DuStateSerializable::read_from_buffer(v)?;
+        DuState::try_from(s)
+    }
+}
+
+impl TryFrom<DuState> for DuStateSerializable {
+    type Error = anyhow::Error;
+    fn try_from(x: DuState) -> Result<Self> {
+        Ok(DuStateSerializable {
+            gnb_du_id: x.gnb_du_id.0,
+            address: x.address,
+            served_cells: x
+                .served_cells
+                .into_iter()
+                .map(ServedCellSerializable::try_from)
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl TryFrom<DuStateSerializable> for DuState {
+    type Error = anyhow::Error;
+    fn try_from(x: DuStateSerializable) -> Result<Self> {
+        Ok(DuState {
+            gnb_du_id: GnbDuId(x.gnb_du_id),
+            address: x.address,
+            served_cells: x
+                .served_cells
+                .into_iter()
+                .map(ServedCell::try_from)
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl TryFrom<ServedCell> for ServedCellSerializable {
+    type Error = anyhow::Error;
+    fn try_from(x: ServedCell) -> Result<Self> {
+        Ok(ServedCellSerializable {
+            nr_cgi: Asn1Serdes::into_bytes(x.nr_cgi)?,
+            nr_pci: x.nr_pci.0,
+            five_gs_tac: x.five_gs_tac.map(|x| x.0),
+            served_plmns: x.served_plmns.into_iter().map(|x| x.0).collect(),
+            active: x.active,
+            barred: x.barred,
+            sib2: x.sib2,
+            sib2_value_tag: x.sib2_value_tag,
+        })
+    }
+}
+
+impl TryFrom<ServedCellSerializable> for ServedCell {
+    type Error = anyhow::Error;
+    fn try_from(x: ServedCellSerializable) -> Result<Self> {
+        Ok(ServedCell {
+            nr_cgi: Asn1Serdes::from_bytes(&x.nr_cgi)?,
+            nr_pci: NrPci(x.nr_pci),
+            five_gs_tac: x.five_gs_tac.map(FiveGsTac),
+            served_plmns: x.served_plmns.into_iter().map(PlmnIdentity).collect(),
+            active: x.active,
+            barred: x.barred,
+            sib2: x.sib2,
+            sib2_value_tag: x.sib2_value_tag,
+        })
+    }
+}
@@ -0,0 +1,74 @@
+//! mock_cu_up_store - in-memory storage of CU-UP state, for testing and for single worker deployments
+
+use super::{CuUpState, CuUpStateStore};
+use anyhow::Result;
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use dashmap::DashMap;
+
+#[derive(Clone, Debug)]
+pub struct MockCuUpStore {
+    kvs: Arc<DashMap<u64, CuUpState>>,
+}
+
+impl MockCuUpStore {
+    pub fn new() -> Self {
+        MockCuUpStore {
+            kvs: Arc::new(DashMap::new()),
+        }
+    }
+}
+
+impl Default for MockCuUpStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl CuUpStateStore for MockCuUpStore {
+    async fn store_cu_up(&self, s: CuUpState) -> Result<()> {
+        self.kvs.insert(s.gnb_cu_up_id.0, s);
+        Ok(())
+    }
+    async fn retrieve_all_cu_ups(&self) -> Result<Vec<CuUpState>> {
+        Ok(self.kvs.iter().map(|x| x.value().clone()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datastore::{CuUpSupportedPlmn, SerDes};
+    use e1ap::GnbCuUpId;
+    use xxap::Snssai;
+
+    #[async_std::test]
+    async fn test_mock_cu_up_store() -> Result<()> {
+        let m = MockCuUpStore::new();
+        let cu_up_state = CuUpState {
+            gnb_cu_up_id: GnbCuUpId(7),
+            name: Some("UP".to_string()),
+            address: "127.0.0.2".to_string(),
+            supported_plmns: vec![CuUpSupportedPlmn {
+                plmn: [2, 3, 2],
+                slices: vec![Snssai(1, None), Snssai(2, Some([0, 0, 1]))],
+                five_qis: Some(vec![9]),
+            }],
+        };
+
+        // Check that the CU-UP state survives serialization, as it would if stored in Redis.
+        let cu_up_state = CuUpState::from_bytes(&cu_up_state.into_bytes()?)?;
+
+        m.store_cu_up(cu_up_state).await?;
+        let cu_ups = m.retrieve_all_cu_ups().await?;
+        assert_eq!(cu_ups.len(), 1);
+        let cu_up = &cu_ups[0];
+        assert_eq!(cu_up.address, "127.0.0.2");
+        assert!(cu_up.supports(&[2, 3, 2], &[Snssai(2, Some([0, 0, 1]))], &[9]));
+        assert!(!cu_up.supports(&[2, 3, 3], &[Snssai(1, None)], &[9]));
+        assert!(!cu_up.supports(&[2, 3, 2], &[Snssai(3, None)], &[9]));
+        assert!(!cu_up.supports(&[2, 3, 2], &[Snssai(1, None)], &[5]));
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+//! mock_du_store - in-memory storage of DU state, for testing and for single worker deployments
+
+use super::{DuState, DuStateStore};
+use anyhow::{anyhow, Result};
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use f1ap::GnbDuId;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+#[derive(Clone, Debug)]
+pub struct MockDuStore {
+    kvs: Arc<DashMap<u64, DuState>>,
+    // The workers connected to each DU address.
+    connections: Arc<DashMap<String, HashSet<Uuid>>>,
+}
+
+impl MockDuStore {
+    pub fn new() -> Self {
+        MockDuStore {
+            kvs: Arc::new(DashMap::new()),
+            connections: Arc::new(DashMap::new()),
+        }
+    }
+}
+
+impl Default for MockDuStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl DuStateStore for MockDuStore {
+    async fn store_du(&self, s: DuState) -> Result<()> {
+        self.kvs.insert(s.gnb_du_id.0, s);
+        Ok(())
+    }
+    async fn retrieve_du(&self, gnb_du_id: &GnbDuId) -> Result<DuState> {
+        self.kvs
+            .get(&gnb_du_id.0)
+            .ok_or_else(|| anyhow!("No such DU"))
+            .map(|x| x.clone())
+    }
+    async fn retrieve_all_dus(&self) -> Result<Vec<DuState>> {
+        Ok(self.kvs.iter().map(|x| x.value().clone()).collect())
+    }
+    async fn delete_du(&self, gnb_du_id: &GnbDuId) -> Result<()> {
+        self.kvs.remove(&gnb_du_id.0);
+        Ok(())
+    }
+    async fn add_du_connection(&self, address: &str, worker_id: Uuid) -> Result<()> {
+        self.connections
+            .entry(address.to_string())
+            .or_default()
+            .insert(worker_id);
+        Ok(())
+    }
+    async fn remove_du_connection(&self, address: &str, worker_id: Uuid) -> Result<usize> {
+        let mut workers = self.connections.entry(address.to_string()).or_default();
+        workers.remove(&worker_id);
+        Ok(workers.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datastore::{SerDes, ServedCell, Sib2Parameters};
+    use bitvec::prelude::*;
+    use f1ap::{FiveGsTac, NrCellIdentity, NrCgi, NrPci, PlmnIdentity};
+
+    #[async_std::test]
+    async fn test_mock_du_store() -> Result<()> {
+        let m = MockDuStore::new();
+        let mut du_state = DuState::new(GnbDuId(1), "127.0.0.3".to_string());
+        du_state.served_cells.push(ServedCell {
+            nr_cgi: NrCgi {
+                plmn_identity: PlmnIdentity([2, 3, 2]),
+                nr_cell_identity: NrCellIdentity(bitvec![u8,Msb0;0;36]),
+            },
+            nr_pci: NrPci(1),
+            five_gs_tac: Some(FiveGsTac([0, 0, 1])),
+            served_plmns: vec![PlmnIdentity([2, 3, 2])],
+            active: false,
+            barred: true,
+            sib2: Sib2Parameters {
+                q_hyst: 4,
+                ..Sib2Parameters::default()
+            },
+            sib2_value_tag: 1,
+        });
+
+        // Check that the DU state survives serialization, as it would if stored in Redis.
+        let du_state = DuState::from_bytes(&du_state.into_bytes()?)?;
+
+        m.store_du(du_state).await?;
+        let du_state = m.retrieve_du(&GnbDuId(1)).await?;
+        assert_eq!(du_state.address, "127.0.0.3");
+        assert_eq!(du_state.served_cells.len(), 1);
+        assert_eq!(du_state.served_cells[0].nr_pci.0, 1);
+        assert!(!du_state.served_cells[0].active);
+        assert!(du_state.served_cells[0].barred);
+        assert_eq!(du_state.served_cells[0].sib2.q_hyst, 4);
+        assert!(m.retrieve_du(&GnbDuId(2)).await.is_err());
+        assert_eq!(m.retrieve_all_dus().await?.len(), 1);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_delete_du() -> Result<()> {
+        let m = MockDuStore::new();
+        for gnb_du_id in [1, 2] {
+            m.store_du(DuState::new(GnbDuId(gnb_du_id), "127.0.0.3".to_string()))
+                .await?;
+        }
+
+        // Deleting a DU leaves the other one.
+        m.delete_du(&GnbDuId(1)).await?;
+        assert!(m.retrieve_du(&GnbDuId(1)).await.is_err());
+        let dus = m.retrieve_all_dus().await?;
+        assert_eq!(dus.len(), 1);
+        assert_eq!(dus[0].gnb_du_id.0, 2);
+
+        // Deleting a DU that is not there is not an error.
+        m.delete_du(&GnbDuId(1)).await?;
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_du_connections() -> Result<()> {
+        let m = MockDuStore::new();
+        let (worker_1, worker_2) = (Uuid::new_v4(), Uuid::new_v4());
+        m.add_du_connection("127.0.0.3", worker_1).await?;
+        m.add_du_connection("127.0.0.3", worker_2).await?;
+        m.add_du_connection("127.0.0.4", worker_2).await?;
+
+        // The DU is still connected until the last worker goes.
+        assert_eq!(m.remove_du_connection("127.0.0.3", worker_1).await?, 1);
+        assert_eq!(m.remove_du_connection("127.0.0.3", worker_1).await?, 1);
+        assert_eq!(m.remove_du_connection("127.0.0.3", worker_2).await?, 0);
+        assert_eq!(m.remove_du_connection("127.0.0.5", worker_2).await?, 0);
+        Ok(())
+    }
+}
@@ -5,16 +5,22 @@ use anyhow::{anyhow, Result};
 use async_std::sync::Arc;
 use async_trait::async_trait;
 use dashmap::DashMap;
+use f1ap::NrCgi;
+use ngap::AmfUeNgapId;
 
 #[derive(Clone, Debug)]
 pub struct MockUeStore {
     kvs: Arc<DashMap<u32, UeState>>,
+    amf_ue_ngap_ids: Arc<DashMap<u64, u32>>,
+    short_i_rntis: Arc<DashMap<u32, u32>>,
 }
 
 impl MockUeStore {
     pub fn new() -> Self {
         MockUeStore {
             kvs: Arc::new(DashMap::new()),
+            amf_ue_ngap_ids: Arc::new(DashMap::new()),
+            short_i_rntis: Arc::new(DashMap::new()),
         }
     }
 }
@@ -28,6 +34,12 @@ impl Default for MockUeStore {
 #[async_trait]
 impl StateStore<UeState> for MockUeStore {
     async fn store(&self, k: u32, s: UeState, _ttl_secs: usize) -> Result<()> {
+        if let Some(amf_ue_ngap_id) = &s.amf_ue_ngap_id {
+            self.amf_ue_ngap_ids.insert(amf_ue_ngap_id.0, k);
+        }
+        if let Some(short_i_rnti) = s.short_i_rnti() {
+            self.short_i_rntis.insert(short_i_rnti, k);
+        }
         self.kvs.insert(k, s);
         Ok(())
     }
@@ -38,11 +50,37 @@ impl StateStore<UeState> for MockUeStore {
             .map(|x| x.clone())
     }
     async fn delete(&self, k: &u32) -> Result<()> {
-        self.kvs.remove(k);
+        if let Some((_, s)) = self.kvs.remove(k) {
+            if let Some(amf_ue_ngap_id) = s.amf_ue_ngap_id {
+                self.amf_ue_ngap_ids.remove(&amf_ue_ngap_id.0);
+            }
+            if let Some(short_i_rnti) = s.short_i_rnti() {
+                self.short_i_rntis.remove(&short_i_rnti);
+            }
+        }
         Ok(())
     }
 }
-impl UeStateStore for MockUeStore {}
+
+#[async_trait]
+impl UeStateStore for MockUeStore {
+    async fn ues_in_cell(&self, nr_cgi: &NrCgi) -> Result<Vec<u32>> {
+        Ok(self
+            .kvs
+            .iter()
+            .filter(|x| x.value().is_in_cell(nr_cgi))
+            .map(|x| *x.key())
+            .collect())
+    }
+
+    async fn ue_with_amf_ue_ngap_id(&self, amf_ue_ngap_id: &AmfUeNgapId) -> Result<Option<u32>> {
+        Ok(self.amf_ue_ngap_ids.get(&amf_ue_ngap_id.0).map(|x| *x))
+    }
+
+    async fn ue_with_short_i_rnti(&self, short_i_rnti: u32) -> Result<Option<u32>> {
+        Ok(self.short_i_rntis.get(&short_i_rnti).map(|x| *x))
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -62,12 +100,63 @@ mod tests {
             },
         );
         let key = ue_state.key;
+        let nr_cgi = ue_state.nr_cgi.clone();
         m.store(key, ue_state, 0).await?;
         let _ue_state = m.retrieve(&key).await.unwrap();
+        assert_eq!(m.ues_in_cell(&nr_cgi).await?, vec![key]);
         assert!(m.retrieve(&0).await.is_err());
         m.delete(&key).await.unwrap();
         assert!(m.retrieve(&key).await.is_err());
         m.delete(&0).await.unwrap();
         Ok(())
     }
+
+    #[async_std::test]
+    async fn test_amf_ue_ngap_id_index() -> Result<()> {
+        let m = MockUeStore::new();
+        let mut ue_state = UeState::new(
+            GnbDuUeF1apId(3),
+            f1ap::NrCgi {
+                plmn_identity: f1ap::PlmnIdentity([2, 3, 2]),
+                nr_cell_identity: f1ap::NrCellIdentity(bitvec![u8,Msb0;0;36]),
+            },
+        );
+        let key = ue_state.key;
+
+        // The UE is not indexed until the AMF has given it an ID.
+        m.store(key, ue_state.clone(), 0).await?;
+        assert_eq!(m.ue_with_amf_ue_ngap_id(&AmfUeNgapId(7)).await?, None);
+
+        ue_state.amf_ue_ngap_id = Some(AmfUeNgapId(7));
+        m.store(key, ue_state, 0).await?;
+        assert_eq!(m.ue_with_amf_ue_ngap_id(&AmfUeNgapId(7)).await?, Some(key));
+
+        // Deleting the UE removes it from the index.
+        m.delete(&key).await?;
+        assert_eq!(m.ue_with_amf_ue_ngap_id(&AmfUeNgapId(7)).await?, None);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_short_i_rnti_index() -> Result<()> {
+        let m = MockUeStore::new();
+        let mut ue_state = UeState::new(
+            GnbDuUeF1apId(3),
+            f1ap::NrCgi {
+                plmn_identity: f1ap::PlmnIdentity([2, 3, 2]),
+                nr_cell_identity: f1ap::NrCellIdentity(bitvec![u8,Msb0;0;36]),
+            },
+        );
+        let key = ue_state.key;
+
+        // The UE is indexed by the rightmost 24 bits of its resume identity.
+        ue_state.resume_identity = Some(0xab_0012_3456);
+        m.store(key, ue_state, 0).await?;
+        assert_eq!(m.ue_with_short_i_rnti(0x12_3456).await?, Some(key));
+        assert_eq!(m.ue_with_short_i_rnti(0x12_3457).await?, None);
+
+        m.delete(&key).await?;
+        assert_eq!(m.ue_with_short_i_rnti(0x12_3456).await?, None);
+        Ok(())
+    }
 }
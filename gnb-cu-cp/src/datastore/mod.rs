@@ -1,8 +1,20 @@
+mod cu_up_state;
+mod du_state;
+pub mod mock_cu_up_store;
+pub mod mock_du_store;
 pub mod mock_ue_store;
+pub mod redis_cu_up_store;
+pub mod redis_du_store;
 pub mod redis_ue_store;
 mod state_store;
 mod ue_state;
+pub use cu_up_state::{CuUpState, CuUpStateStore, CuUpSupportedPlmn};
+pub use du_state::{DuState, DuStateStore, ServedCell, Sib2Parameters};
+pub use mock_cu_up_store::MockCuUpStore;
+pub use mock_du_store::MockDuStore;
 pub use mock_ue_store::MockUeStore;
+pub use redis_cu_up_store::RedisCuUpStore;
+pub use redis_du_store::RedisDuStore;
 pub use redis_ue_store::RedisUeStore;
 pub use state_store::{SerDes, StateStore};
-pub use ue_state::{UeState, UeStateStore};
+pub use ue_state::{DrbState, PduSessionState, QosFlowState, UeState, UeStateStore};
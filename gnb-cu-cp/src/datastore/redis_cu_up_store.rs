@@ -0,0 +1,50 @@
+//! redis_cu_up_store - storage of CU-UP state in Redis
+
+use super::{CuUpState, CuUpStateStore, SerDes};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use redis::{AsyncCommands, Client};
+
+// The set of IDs of CU-UPs that have state stored.
+const CU_UP_IDS_KEY: &str = "cu_up_ids";
+
+#[derive(Clone)]
+pub struct RedisCuUpStore {
+    client: Client,
+}
+
+impl RedisCuUpStore {
+    pub fn new(port: u16) -> Result<Self> {
+        let client = Client::open(format!("redis://127.0.0.1:{}/", port))?;
+        Ok(RedisCuUpStore { client })
+    }
+}
+
+// Like DU state, CU-UP state is stored without a TTL.
+fn cu_up_key(gnb_cu_up_id: u64) -> String {
+    format!("cu_up:{}", gnb_cu_up_id)
+}
+
+#[async_trait]
+impl CuUpStateStore for RedisCuUpStore {
+    async fn store_cu_up(&self, s: CuUpState) -> Result<()> {
+        let mut conn = self.client.get_async_connection().await?;
+        let gnb_cu_up_id = s.gnb_cu_up_id.0;
+        let _: () = conn.set(cu_up_key(gnb_cu_up_id), s.into_bytes()?).await?;
+        let _: () = conn.sadd(CU_UP_IDS_KEY, gnb_cu_up_id).await?;
+        Ok(())
+    }
+    async fn retrieve_all_cu_ups(&self) -> Result<Vec<CuUpState>> {
+        let mut conn = self.client.get_async_connection().await?;
+        let gnb_cu_up_ids: Vec<u64> = conn.smembers(CU_UP_IDS_KEY).await?;
+        let mut cu_ups = Vec::with_capacity(gnb_cu_up_ids.len());
+        for gnb_cu_up_id in gnb_cu_up_ids {
+            let v: Vec<u8> = conn
+                .get(cu_up_key(gnb_cu_up_id))
+                .await
+                .with_context(|| format!("Failed Redis get on CU-UP {}?", gnb_cu_up_id))?;
+            cu_ups.push(CuUpState::from_bytes(&v)?);
+        }
+        Ok(cu_ups)
+    }
+}
@@ -0,0 +1,88 @@
+//! redis_du_store - storage of DU state in Redis
+
+use super::{DuState, DuStateStore, SerDes};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use f1ap::GnbDuId;
+use redis::{AsyncCommands, Client};
+use uuid::Uuid;
+
+// The set of IDs of DUs that have state stored.
+const DU_IDS_KEY: &str = "du_ids";
+
+#[derive(Clone)]
+pub struct RedisDuStore {
+    client: Client,
+}
+
+impl RedisDuStore {
+    pub fn new(port: u16) -> Result<Self> {
+        let client = Client::open(format!("redis://127.0.0.1:{}/", port))?;
+        Ok(RedisDuStore { client })
+    }
+}
+
+// DU state is stored without a TTL, under a different key format to UE state.  It is deleted when the DU
+// goes away.
+fn du_key(gnb_du_id: u64) -> String {
+    format!("du:{}", gnb_du_id)
+}
+
+// The set of IDs of the workers connected to a DU address.
+fn du_connections_key(address: &str) -> String {
+    format!("du_connections:{}", address)
+}
+
+#[async_trait]
+impl DuStateStore for RedisDuStore {
+    async fn store_du(&self, s: DuState) -> Result<()> {
+        let mut conn = self.client.get_async_connection().await?;
+        let gnb_du_id = s.gnb_du_id.0;
+        let _: () = conn.set(du_key(gnb_du_id), s.into_bytes()?).await?;
+        let _: () = conn.sadd(DU_IDS_KEY, gnb_du_id).await?;
+        Ok(())
+    }
+    async fn retrieve_du(&self, gnb_du_id: &GnbDuId) -> Result<DuState> {
+        let mut conn = self.client.get_async_connection().await?;
+        let v: Vec<u8> = conn
+            .get(du_key(gnb_du_id.0))
+            .await
+            .with_context(|| format!("Failed Redis get on DU {}?", gnb_du_id.0))?;
+        DuState::from_bytes(&v)
+    }
+    async fn retrieve_all_dus(&self) -> Result<Vec<DuState>> {
+        let mut conn = self.client.get_async_connection().await?;
+        let gnb_du_ids: Vec<u64> = conn.smembers(DU_IDS_KEY).await?;
+        let mut dus = Vec::with_capacity(gnb_du_ids.len());
+        for gnb_du_id in gnb_du_ids {
+            // The DU may have been deleted since we read its ID.
+            let v: Option<Vec<u8>> = conn
+                .get(du_key(gnb_du_id))
+                .await
+                .with_context(|| format!("Failed Redis get on DU {}?", gnb_du_id))?;
+            if let Some(v) = v {
+                dus.push(DuState::from_bytes(&v)?);
+            }
+        }
+        Ok(dus)
+    }
+    async fn delete_du(&self, gnb_du_id: &GnbDuId) -> Result<()> {
+        let mut conn = self.client.get_async_connection().await?;
+        let _: () = conn.del(du_key(gnb_du_id.0)).await?;
+        let _: () = conn.srem(DU_IDS_KEY, gnb_du_id.0).await?;
+        Ok(())
+    }
+    async fn add_du_connection(&self, address: &str, worker_id: Uuid) -> Result<()> {
+        let mut conn = self.client.get_async_connection().await?;
+        let _: () = conn
+            .sadd(du_connections_key(address), worker_id.to_string())
+            .await?;
+        Ok(())
+    }
+    async fn remove_du_connection(&self, address: &str, worker_id: Uuid) -> Result<usize> {
+        let mut conn = self.client.get_async_connection().await?;
+        let key = du_connections_key(address);
+        let _: () = conn.srem(&key, worker_id.to_string()).await?;
+        Ok(conn.scard(&key).await?)
+    }
+}
@@ -3,6 +3,8 @@
 use super::SerDes;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use f1ap::NrCgi;
+use ngap::AmfUeNgapId;
 use redis::{AsyncCommands, Client};
 
 use super::{StateStore, UeState, UeStateStore};
@@ -19,10 +21,30 @@ impl RedisUeStore {
     }
 }
 
+// The index from AMF UE NGAP ID to UE key.  Its entries have the same TTL as the UE state.
+fn amf_ue_ngap_id_key(amf_ue_ngap_id: u64) -> String {
+    format!("amf_ue:{}", amf_ue_ngap_id)
+}
+
+// Likewise, the index from the short I-RNTI of a suspended UE to its key.
+fn short_i_rnti_key(short_i_rnti: u32) -> String {
+    format!("short_i_rnti:{}", short_i_rnti)
+}
+
 #[async_trait]
 impl StateStore<UeState> for RedisUeStore {
     async fn store(&self, k: u32, v: UeState, ttl_secs: usize) -> Result<()> {
         let mut conn = self.client.get_async_connection().await?;
+        if let Some(amf_ue_ngap_id) = &v.amf_ue_ngap_id {
+            let _: () = conn
+                .set_ex(amf_ue_ngap_id_key(amf_ue_ngap_id.0), k, ttl_secs)
+                .await?;
+        }
+        if let Some(short_i_rnti) = v.short_i_rnti() {
+            let _: () = conn
+                .set_ex(short_i_rnti_key(short_i_rnti), k, ttl_secs)
+                .await?;
+        }
         conn.set_ex(k, v.into_bytes()?, ttl_secs).await?;
         Ok(())
     }
@@ -36,8 +58,54 @@ impl StateStore<UeState> for RedisUeStore {
     }
     async fn delete(&self, k: &u32) -> Result<()> {
         let mut conn = self.client.get_async_connection().await?;
+        let v: Option<Vec<u8>> = conn.get(k).await?;
+        if let Some(s) = v.and_then(|v| UeState::from_bytes(&v).ok()) {
+            if let Some(amf_ue_ngap_id) = s.amf_ue_ngap_id {
+                let _: () = conn.del(amf_ue_ngap_id_key(amf_ue_ngap_id.0)).await?;
+            }
+            if let Some(short_i_rnti) = s.short_i_rnti() {
+                let _: () = conn.del(short_i_rnti_key(short_i_rnti)).await?;
+            }
+        }
         conn.del(k).await?;
         Ok(())
     }
 }
-impl UeStateStore for RedisUeStore {}
+
+#[async_trait]
+impl UeStateStore for RedisUeStore {
+    // UE state is keyed by the bare UE key, so scan for numeric keys and skip the rest, such as DU state.
+    async fn ues_in_cell(&self, nr_cgi: &NrCgi) -> Result<Vec<u32>> {
+        let mut conn = self.client.get_async_connection().await?;
+        let mut keys = vec![];
+        let mut iter = conn.scan_match::<_, String>("[0-9]*").await?;
+        while let Some(key) = iter.next_item().await {
+            if let Ok(key) = key.parse::<u32>() {
+                keys.push(key);
+            }
+        }
+        drop(iter);
+
+        let mut ues = vec![];
+        for key in keys {
+            // The UE may have been deleted or expired since the scan.
+            let v: Option<Vec<u8>> = conn.get(key).await?;
+            if let Some(v) = v {
+                if UeState::from_bytes(&v)?.is_in_cell(nr_cgi) {
+                    ues.push(key);
+                }
+            }
+        }
+        Ok(ues)
+    }
+
+    async fn ue_with_amf_ue_ngap_id(&self, amf_ue_ngap_id: &AmfUeNgapId) -> Result<Option<u32>> {
+        let mut conn = self.client.get_async_connection().await?;
+        Ok(conn.get(amf_ue_ngap_id_key(amf_ue_ngap_id.0)).await?)
+    }
+
+    async fn ue_with_short_i_rnti(&self, short_i_rnti: u32) -> Result<Option<u32>> {
+        let mut conn = self.client.get_async_connection().await?;
+        Ok(conn.get(short_i_rnti_key(short_i_rnti)).await?)
+    }
+}
@@ -4,13 +4,28 @@ use super::SerDes;
 use super::StateStore;
 use anyhow::Result;
 use asn1_per::SerDes as Asn1Serdes;
+use async_trait::async_trait;
 use e1ap::GnbCuUpUeE1apId;
 use f1ap::{GnbDuUeF1apId, NrCgi};
-use ngap::AmfUeNgapId;
+use ngap::{AmfUeNgapId, QosCharacteristics, QosFlowLevelQosParameters};
 use rand::Rng;
 use speedy::{Readable, Writable};
 
-pub trait UeStateStore: StateStore<UeState> {}
+#[async_trait]
+pub trait UeStateStore: StateStore<UeState> {
+    /// The keys of the UEs in the given cell.  This looks at every UE, so is only for rare events such as
+    /// the cell being deleted.
+    async fn ues_in_cell(&self, nr_cgi: &NrCgi) -> Result<Vec<u32>>;
+
+    /// The key of the UE that the AMF knows by the given AMF UE NGAP ID, if any.  A UE is indexed by its
+    /// AMF UE NGAP ID when it is stored, for as long as its state lives.
+    async fn ue_with_amf_ue_ngap_id(&self, amf_ue_ngap_id: &AmfUeNgapId) -> Result<Option<u32>>;
+
+    /// The key of the suspended UE that was given the short I-RNTI, if any.  A UE is indexed by the short
+    /// I-RNTI of its resume identity when it is stored.  Short I-RNTIs are not unique, so the caller must
+    /// check the UE's resume identity.
+    async fn ue_with_short_i_rnti(&self, short_i_rnti: u32) -> Result<Option<u32>>;
+}
 
 #[derive(Clone, Debug)]
 pub struct UeState {
@@ -19,6 +34,40 @@ pub struct UeState {
     pub nr_cgi: NrCgi,
     pub gnb_cu_up_ue_e1ap_id: Option<GnbCuUpUeE1apId>,
     pub amf_ue_ngap_id: Option<AmfUeNgapId>,
+    pub resume_identity: Option<u64>,
+    pub pdu_sessions: Vec<PduSessionState>,
+    // The remote IP address of the NGAP TNLA that the UE is bound to, so that any worker can keep the UE
+    // on it.  Written by the worker when the UE is stored.
+    pub ngap_tnla_address: Option<String>,
+    // Likewise, the remote IP address of the F1AP TNLA to the UE's DU...
+    pub f1ap_tnla_address: Option<String>,
+    // ...and of the E1AP TNLA to the CU-UP that has the UE's bearer context.
+    pub e1ap_tnla_address: Option<String>,
+}
+
+/// A PDU session.  Its QoS flows are mapped to one or more DRBs.  The first DRB is the session's default DRB.
+#[derive(Clone, Debug, Readable, Writable)]
+pub struct PduSessionState {
+    pub pdu_session_id: u8,
+    pub sst: u8,
+    pub sd: Option<[u8; 3]>,
+    pub qos_flows: Vec<QosFlowState>,
+    pub drbs: Vec<DrbState>,
+}
+
+/// A DRB of a PDU session, and the QFIs of the QoS flows mapped to it.  DRB IDs are unique within the UE.
+#[derive(Clone, Debug, Readable, Writable)]
+pub struct DrbState {
+    pub drb_id: u8,
+    pub qfis: Vec<u8>,
+}
+
+/// The parameters of a QoS flow that we pass on to the CU-UP and DU.
+#[derive(Clone, Debug, Readable, Writable)]
+pub struct QosFlowState {
+    pub qfi: u8,
+    pub five_qi: u8,
+    pub priority_level_arp: u8,
 }
 
 #[derive(Readable, Writable)]
@@ -28,6 +77,11 @@ pub struct UeStateSerializable {
     pub nr_cgi: Vec<u8>,
     pub amf_ue_ngap_id: Option<u64>,
     pub gnb_cu_up_ue_e1ap_id: Option<u32>,
+    pub resume_identity: Option<u64>,
+    pub pdu_sessions: Vec<PduSessionState>,
+    pub ngap_tnla_address: Option<String>,
+    pub f1ap_tnla_address: Option<String>,
+    pub e1ap_tnla_address: Option<String>,
 }
 
 impl UeState {
@@ -38,6 +92,70 @@ impl UeState {
             nr_cgi,
             gnb_cu_up_ue_e1ap_id: None,
             amf_ue_ngap_id: None,
+            resume_identity: None,
+            pdu_sessions: vec![],
+            ngap_tnla_address: None,
+            f1ap_tnla_address: None,
+            e1ap_tnla_address: None,
+        }
+    }
+
+    pub fn is_in_cell(&self, nr_cgi: &NrCgi) -> bool {
+        self.nr_cgi.plmn_identity.0 == nr_cgi.plmn_identity.0
+            && self.nr_cgi.nr_cell_identity.0 == nr_cgi.nr_cell_identity.0
+    }
+
+    // The short I-RNTI is the rightmost 24 bits of the full I-RNTI.
+    pub fn short_i_rnti(&self) -> Option<u32> {
+        self.resume_identity.map(|x| (x & 0xff_ffff) as u32)
+    }
+
+    pub fn pdu_session_mut(&mut self, pdu_session_id: u8) -> Option<&mut PduSessionState> {
+        self.pdu_sessions
+            .iter_mut()
+            .find(|x| x.pdu_session_id == pdu_session_id)
+    }
+
+    pub fn drb_ids(&self) -> impl Iterator<Item = u8> + '_ {
+        self.pdu_sessions
+            .iter()
+            .flat_map(|x| x.drbs.iter().map(|x| x.drb_id))
+    }
+}
+
+impl PduSessionState {
+    pub fn snssai(&self) -> xxap::Snssai {
+        xxap::Snssai(self.sst, self.sd)
+    }
+
+    pub fn drb(&self, drb_id: u8) -> Option<&DrbState> {
+        self.drbs.iter().find(|x| x.drb_id == drb_id)
+    }
+
+    pub fn is_default_drb(&self, drb_id: u8) -> bool {
+        self.drbs.first().map(|x| x.drb_id) == Some(drb_id)
+    }
+
+    /// The QoS flows mapped to the DRB.
+    pub fn drb_qos_flows<'a>(
+        &'a self,
+        drb: &'a DrbState,
+    ) -> impl Iterator<Item = &'a QosFlowState> + 'a {
+        self.qos_flows.iter().filter(|x| drb.qfis.contains(&x.qfi))
+    }
+}
+
+impl QosFlowState {
+    pub fn new(qfi: u8, x: &QosFlowLevelQosParameters) -> Self {
+        let five_qi = match &x.qos_characteristics {
+            QosCharacteristics::NonDynamic5qi(x) => x.five_qi.0,
+            // A dynamic 5QI need not have a standardized 5QI.  If not, treat it as best effort.
+            QosCharacteristics::Dynamic5qi(x) => x.five_qi.map(|x| x.0).unwrap_or(9),
+        };
+        QosFlowState {
+            qfi,
+            five_qi,
+            priority_level_arp: x.allocation_and_retention_priority.priority_level_arp.0,
         }
     }
 }
@@ -61,6 +179,11 @@ impl TryFrom<UeState> for UeStateSerializable {
             gnb_du_ue_f1ap_id: x.gnb_du_ue_f1ap_id.0,
             amf_ue_ngap_id: x.amf_ue_ngap_id.map(|x| x.0),
             gnb_cu_up_ue_e1ap_id: x.gnb_cu_up_ue_e1ap_id.map(|x| x.0),
+            resume_identity: x.resume_identity,
+            pdu_sessions: x.pdu_sessions,
+            ngap_tnla_address: x.ngap_tnla_address,
+            f1ap_tnla_address: x.f1ap_tnla_address,
+            e1ap_tnla_address: x.e1ap_tnla_address,
         })
     }
 }
@@ -74,6 +197,11 @@ impl TryFrom<UeStateSerializable> for UeState {
             gnb_du_ue_f1ap_id: GnbDuUeF1apId(x.gnb_du_ue_f1ap_id),
             amf_ue_ngap_id: x.amf_ue_ngap_id.map(AmfUeNgapId),
             gnb_cu_up_ue_e1ap_id: x.gnb_cu_up_ue_e1ap_id.map(GnbCuUpUeE1apId),
+            resume_identity: x.resume_identity,
+            pdu_sessions: x.pdu_sessions,
+            ngap_tnla_address: x.ngap_tnla_address,
+            f1ap_tnla_address: x.f1ap_tnla_address,
+            e1ap_tnla_address: x.e1ap_tnla_address,
         })
     }
 }
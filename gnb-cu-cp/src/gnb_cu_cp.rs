@@ -1,26 +1,39 @@
 //! gnb_cu_cp - the collection of services used by the GNB-CU-CP workflow business logic.
 
-use std::{future::Future, pin::Pin};
+use std::{future::Future, net::IpAddr, pin::Pin};
 
 use super::Config;
 use crate::{
-    datastore::{UeState, UeStateStore},
+    datastore::{CuUpStateStore, DuStateStore, ServedCell, UeState, UeStateStore},
     rrc_transaction::RrcTransaction,
 };
 use anyhow::Result;
 use async_channel::Sender;
 use async_trait::async_trait;
+use f1ap::NrCgi;
 use net::{Indication, Procedure, RequestError};
 use rrc::UlDcchMessage;
 use slog::Logger;
+use uuid::Uuid;
 
 /// Trait representing the collection of services needed by gNB-CU-CP workflows.
 #[async_trait]
-pub trait GnbCuCp: Send + Sync + Clone + 'static + UeStateStore {
+pub trait GnbCuCp:
+    Send + Sync + Clone + 'static + UeStateStore + DuStateStore + CuUpStateStore
+{
     fn config(&self) -> &Config;
 
+    fn worker_id(&self) -> Uuid;
+
+    /// Whether the worker is shutting down.  A draining worker finishes the procedures it has under way,
+    /// but takes on no new UEs.
+    fn is_draining(&self) -> bool;
+
     async fn ngap_connect(&self, amf_address: &str) -> Result<()>;
 
+    /// Set the weight factor of the NGAP TNLAs to the given AMF IP address, for the choice of TNLA for new UEs.
+    async fn set_ngap_tnla_weight(&self, amf_ip_address: IpAddr, weight: u8);
+
     async fn ngap_request<P: Procedure>(
         &self,
         r: P::Request,
@@ -35,6 +48,33 @@ pub trait GnbCuCp: Send + Sync + Clone + 'static + UeStateStore {
     ) -> Result<P::Success, RequestError<P::Failure>>;
     async fn f1ap_indication<P: Indication>(&self, r: P::Request, logger: &Logger);
 
+    /// Send a non UE-associated F1AP request to the DU with the given address.  This fails if the DU is not
+    /// connected to this worker.
+    async fn f1ap_request_to_du<P: Procedure>(
+        &self,
+        r: P::Request,
+        du_address: &str,
+        logger: &Logger,
+    ) -> Result<P::Success, RequestError<P::Failure>>;
+
+    /// Send a non UE-associated F1AP indication to the DU with the given address.  This fails if the DU is not
+    /// connected to this worker.
+    async fn f1ap_indication_to_du<P: Indication>(
+        &self,
+        r: P::Request,
+        du_address: &str,
+        logger: &Logger,
+    ) -> Result<()>;
+
+    /// The IP address of the DU that sent the F1AP message being handled.
+    async fn f1ap_request_remote_ip(&self) -> Option<IpAddr>;
+
+    /// Whether this worker has an F1AP TNLA to the DU with the given address.
+    async fn is_du_connected(&self, du_address: &str) -> bool;
+
+    /// Send the UE's F1AP signalling to the DU that sent the F1AP message being handled.
+    async fn bind_ue_to_du(&self, ue_key: u32) -> Result<()>;
+
     async fn e1ap_request<P: Procedure>(
         &self,
         r: P::Request,
@@ -43,6 +83,16 @@ pub trait GnbCuCp: Send + Sync + Clone + 'static + UeStateStore {
 
     async fn e1ap_indication<P: Indication>(&self, r: P::Request, logger: &Logger);
 
+    /// The IP address of the CU-UP that sent the E1AP request being handled.
+    async fn e1ap_request_remote_ip(&self) -> Option<IpAddr>;
+
+    /// Send the UE's E1AP signalling to the CU-UP with the given address.
+    async fn bind_ue_to_cu_up(&self, ue_key: u32, cu_up_address: &str) -> Result<()>;
+
+    /// Look up a cell served by one of the DUs.  This is answered from the worker's cache of DU state
+    /// where possible.
+    async fn served_cell(&self, nr_cgi: &NrCgi) -> Result<ServedCell>;
+
     // TODO - make RRC request and indication similar to the above?
 
     /// Start a new RRC transaction.
@@ -6,7 +6,7 @@ use super::GnbCuCp;
 use async_trait::async_trait;
 use e1ap::*;
 use net::{EventHandler, RequestError, RequestProvider, ResponseAction, TnlaEvent};
-use slog::{info, Logger};
+use slog::{info, warn, Logger};
 
 #[derive(Clone)]
 pub struct E1apHandler<G: GnbCuCp> {
@@ -41,6 +41,18 @@ impl<G: GnbCuCp> EventHandler for E1apHandler<G> {
                 )
             }
             TnlaEvent::Terminated => info!(logger, "E1AP TNLA {} closed", tnla_id),
+            TnlaEvent::PathUp(addr) => info!(logger, "E1AP TNLA {} path to {} up", tnla_id, addr),
+            TnlaEvent::PathDown(addr) => {
+                warn!(logger, "E1AP TNLA {} path to {} down", tnla_id, addr)
+            }
+            TnlaEvent::Restarted => warn!(logger, "E1AP TNLA {} peer restarted", tnla_id),
+            TnlaEvent::PeerShutdown => info!(logger, "E1AP TNLA {} shutting down", tnla_id),
+            TnlaEvent::SendFailed(stream_id) => {
+                warn!(
+                    logger,
+                    "E1AP TNLA {} send failed on stream {}", tnla_id, stream_id
+                )
+            }
         };
     }
 }
@@ -3,18 +3,25 @@
 use super::GnbCuCp;
 use super::RrcHandler;
 use crate::workflows::Workflow;
+use async_std::sync::Arc;
 use async_trait::async_trait;
+use dashmap::DashMap;
 use f1ap::*;
 use net::{
     EventHandler, IndicationHandler, RequestError, RequestProvider, ResponseAction, TnlaEvent,
 };
 use pdcp::PdcpPdu;
 use slog::{debug, info, warn, Logger};
+use std::net::IpAddr;
 
 #[derive(Clone)]
 pub struct F1apHandler<G: GnbCuCp> {
     gnb_cu_cp: G,
     rrc_handler: RrcHandler<G>,
+
+    // The remote IP address of each F1AP TNLA that is up, so that we can tell which DU a TNLA that has gone
+    // down was to.
+    du_tnlas: Arc<DashMap<u32, IpAddr>>,
 }
 
 impl<G: GnbCuCp> F1apHandler<G> {
@@ -25,8 +32,42 @@ impl<G: GnbCuCp> F1apHandler<G> {
         F1apCu::new(F1apHandler {
             gnb_cu_cp,
             rrc_handler,
+            du_tnlas: Arc::new(DashMap::new()),
         })
     }
+
+    async fn du_tnla_established(&self, tnla_id: u32, address: IpAddr, logger: &Logger) {
+        let first = !self.du_tnlas.iter().any(|x| *x.value() == address);
+        self.du_tnlas.insert(tnla_id, address);
+        if first {
+            if let Err(e) = Workflow::new(&self.gnb_cu_cp, logger)
+                .du_connected(&address.to_string())
+                .await
+            {
+                warn!(
+                    logger,
+                    "Failed to record connection to DU {} - {:?}", address, e
+                );
+            }
+        }
+    }
+
+    // The DU may still be connected to other workers, so only the record of this worker's connection is removed
+    // when its last F1AP TNLA to the DU goes.
+    async fn du_tnla_terminated(&self, address: IpAddr, logger: &Logger) {
+        if self.du_tnlas.iter().any(|x| *x.value() == address) {
+            return;
+        }
+        if let Err(e) = Workflow::new(&self.gnb_cu_cp, logger)
+            .du_disconnected(&address.to_string())
+            .await
+        {
+            warn!(
+                logger,
+                "Failed to record disconnection from DU {} - {:?}", address, e
+            );
+        }
+    }
 }
 
 #[async_trait]
@@ -40,6 +81,17 @@ impl<G: GnbCuCp> RequestProvider<F1SetupProcedure> for F1apHandler<G> {
     }
 }
 
+#[async_trait]
+impl<G: GnbCuCp> RequestProvider<F1RemovalProcedure> for F1apHandler<G> {
+    async fn request(
+        &self,
+        r: F1RemovalRequest,
+        logger: &Logger,
+    ) -> Result<ResponseAction<F1RemovalResponse>, RequestError<F1RemovalFailure>> {
+        Workflow::new(&self.gnb_cu_cp, logger).f1_removal(r).await
+    }
+}
+
 #[async_trait]
 impl<G: GnbCuCp> RequestProvider<GnbDuConfigurationUpdateProcedure> for F1apHandler<G> {
     async fn request(
@@ -88,14 +140,62 @@ impl<G: GnbCuCp> IndicationHandler<UlRrcMessageTransferProcedure> for F1apHandle
     }
 }
 
+#[async_trait]
+impl<G: GnbCuCp> IndicationHandler<UeContextReleaseRequestProcedure> for F1apHandler<G> {
+    async fn handle(&self, r: UeContextReleaseRequest, logger: &Logger) {
+        if let Err(e) = Workflow::new(&self.gnb_cu_cp, logger)
+            .ue_context_release_request(r)
+            .await
+        {
+            debug!(
+                logger,
+                "Ue context release request procedure failed - {:?}", e
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl<G: GnbCuCp> IndicationHandler<UeInactivityNotificationProcedure> for F1apHandler<G> {
+    async fn handle(&self, r: UeInactivityNotification, logger: &Logger) {
+        if let Err(e) = Workflow::new(&self.gnb_cu_cp, logger)
+            .ue_inactivity_notification(r)
+            .await
+        {
+            debug!(
+                logger,
+                "Ue inactivity notification procedure failed - {:?}", e
+            );
+        }
+    }
+}
+
 #[async_trait]
 impl<G: GnbCuCp> EventHandler for F1apHandler<G> {
     async fn handle_event(&self, event: TnlaEvent, tnla_id: u32, logger: &Logger) {
         match event {
             TnlaEvent::Established(addr) => {
-                info!(logger, "F1AP TNLA {} established with DU {}", tnla_id, addr)
+                info!(logger, "F1AP TNLA {} established with DU {}", tnla_id, addr);
+                self.du_tnla_established(tnla_id, addr.ip(), logger).await;
+            }
+            TnlaEvent::Terminated => {
+                info!(logger, "F1AP TNLA {} closed", tnla_id);
+                if let Some((_, address)) = self.du_tnlas.remove(&tnla_id) {
+                    self.du_tnla_terminated(address, logger).await;
+                }
+            }
+            TnlaEvent::PathUp(addr) => info!(logger, "F1AP TNLA {} path to {} up", tnla_id, addr),
+            TnlaEvent::PathDown(addr) => {
+                warn!(logger, "F1AP TNLA {} path to {} down", tnla_id, addr)
+            }
+            TnlaEvent::Restarted => warn!(logger, "F1AP TNLA {} peer restarted", tnla_id),
+            TnlaEvent::PeerShutdown => info!(logger, "F1AP TNLA {} shutting down", tnla_id),
+            TnlaEvent::SendFailed(stream_id) => {
+                warn!(
+                    logger,
+                    "F1AP TNLA {} send failed on stream {}", tnla_id, stream_id
+                )
             }
-            TnlaEvent::Terminated => info!(logger, "F1AP TNLA {} closed", tnla_id),
         };
     }
 }
@@ -0,0 +1,134 @@
+//! management_api - lets an operator control the cells served by the DUs
+
+use anyhow::{anyhow, ensure, Result};
+use async_trait::async_trait;
+use bitvec::prelude::*;
+use common::ShutdownHandle;
+use f1ap::{NrCellIdentity, NrCgi, PlmnIdentity};
+use management_api::models;
+use management_api::server::MakeService;
+use management_api::Api;
+use management_api::ConfigureCellResponse;
+use slog::{debug, error, warn, Logger};
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use stop_token::StopSource;
+use swagger::auth::MakeAllowAllAuthenticator;
+use swagger::ApiError;
+use swagger::EmptyContext;
+use swagger::{Has, XSpanIdString};
+
+use crate::datastore::Sib2Parameters;
+use crate::gnb_cu_cp::GnbCuCp;
+use crate::workflows::{CellConfiguration, Workflow};
+
+#[derive(Clone)]
+pub struct ManagementApiHandler<C, G: GnbCuCp> {
+    gnb_cu_cp: G,
+    logger: Logger,
+    marker: PhantomData<C>,
+}
+
+impl<C, G: GnbCuCp> ManagementApiHandler<C, G> {
+    pub fn new(gnb_cu_cp: G, logger: Logger) -> Self {
+        ManagementApiHandler {
+            gnb_cu_cp,
+            logger,
+            marker: PhantomData,
+        }
+    }
+}
+
+pub async fn serve<G: GnbCuCp>(
+    addr: SocketAddr,
+    gnb_cu_cp: G,
+    logger: Logger,
+) -> Result<ShutdownHandle> {
+    let stop_source = StopSource::new();
+    let stop_token = stop_source.token();
+    // As in connection_api::serve(), the type parameter C is inferred at the call to ManagementApiHandler::new().
+    let server = ManagementApiHandler::new(gnb_cu_cp, logger.clone());
+    let service = MakeService::new(server);
+    let service = MakeAllowAllAuthenticator::new(service, "cosmo");
+    let service = management_api::server::context::MakeAddContext::<_, EmptyContext>::new(service);
+    let server_task = async_std::task::spawn(async move {
+        let server = hyper::server::Server::bind(&addr)
+            .serve(service)
+            .with_graceful_shutdown(stop_token);
+        if let Err(e) = server.await {
+            error!(logger, "Server error: {}", e);
+        } else {
+            debug!(logger, "Management API server graceful shutdown");
+        }
+    });
+
+    Ok(ShutdownHandle::new(server_task, stop_source))
+}
+
+#[async_trait]
+impl<C, G: GnbCuCp> Api<C> for ManagementApiHandler<C, G>
+where
+    C: Clone + Has<XSpanIdString> + Send + Sync,
+{
+    /// Activates, deactivates or bars a cell served by a DU, or changes its system information
+    async fn configure_cell(
+        &self,
+        cell_configuration: models::CellConfiguration,
+        _context: &C,
+    ) -> Result<ConfigureCellResponse, ApiError> {
+        let result = match convert_cell_configuration(cell_configuration) {
+            Ok(c) => {
+                Workflow::new(&self.gnb_cu_cp, &self.logger)
+                    .configure_cell(c)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => Ok(ConfigureCellResponse::Success),
+            Err(e) => {
+                warn!(self.logger, "Failed to configure cell - {}", e);
+                Ok(ConfigureCellResponse::Failure(e.to_string()))
+            }
+        }
+    }
+}
+
+fn convert_cell_configuration(x: models::CellConfiguration) -> Result<CellConfiguration> {
+    ensure!(
+        (0..(1 << 36)).contains(&x.nr_cell_identity),
+        "NR Cell Identity must be 36 bits"
+    );
+    let sib2 = match x.sib2 {
+        Some(sib2) => Some(Sib2Parameters {
+            q_hyst: sib2_parameter("q_hyst", sib2.q_hyst)?,
+            cell_reselection_priority: sib2_parameter(
+                "cell_reselection_priority",
+                sib2.cell_reselection_priority,
+            )?,
+            thresh_serving_low_p: sib2_parameter(
+                "thresh_serving_low_p",
+                sib2.thresh_serving_low_p,
+            )?,
+            q_rx_lev_min: sib2_parameter("q_rx_lev_min", sib2.q_rx_lev_min)?,
+            s_intra_search_p: sib2_parameter("s_intra_search_p", sib2.s_intra_search_p)?,
+            t_reselection_nr: sib2_parameter("t_reselection_nr", sib2.t_reselection_nr)?,
+        }),
+        None => None,
+    };
+    Ok(CellConfiguration {
+        nr_cgi: NrCgi {
+            plmn_identity: PlmnIdentity(xxap::plmn_from_mcc_mnc(&x.mcc, &x.mnc)?),
+            nr_cell_identity: NrCellIdentity(
+                x.nr_cell_identity.to_be_bytes().view_bits::<Msb0>()[28..].to_bitvec(),
+            ),
+        },
+        active: x.active,
+        barred: x.barred,
+        sib2,
+    })
+}
+
+fn sib2_parameter<T: TryFrom<i32>>(name: &str, value: i32) -> Result<T> {
+    T::try_from(value).map_err(|_| anyhow!("SIB2 {name} {value} out of range"))
+}
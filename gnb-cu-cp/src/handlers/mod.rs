@@ -8,3 +8,4 @@ pub use self::ngap::NgapHandler;
 pub use self::rrc::RrcHandler;
 use super::GnbCuCp;
 pub mod connection_api;
+pub mod management_api;
@@ -8,7 +8,7 @@ use net::{
     EventHandler, IndicationHandler, RequestError, RequestProvider, ResponseAction, TnlaEvent,
 };
 use ngap::*;
-use slog::{debug, info, Logger};
+use slog::{debug, info, warn, Logger};
 
 impl<G: GnbCuCp> RequestProvider<NgSetupProcedure> for NgapHandler<G> {}
 
@@ -33,6 +33,18 @@ impl<G: GnbCuCp> EventHandler for NgapHandler<G> {
                 );
             }
             TnlaEvent::Terminated => info!(logger, "NGAP TNLA {} closed", tnla_id),
+            TnlaEvent::PathUp(addr) => info!(logger, "NGAP TNLA {} path to {} up", tnla_id, addr),
+            TnlaEvent::PathDown(addr) => {
+                warn!(logger, "NGAP TNLA {} path to {} down", tnla_id, addr)
+            }
+            TnlaEvent::Restarted => warn!(logger, "NGAP TNLA {} peer restarted", tnla_id),
+            TnlaEvent::PeerShutdown => info!(logger, "NGAP TNLA {} shutting down", tnla_id),
+            TnlaEvent::SendFailed(stream_id) => {
+                warn!(
+                    logger,
+                    "NGAP TNLA {} send failed on stream {}", tnla_id, stream_id
+                )
+            }
         };
         // TODO
     }
@@ -70,6 +82,22 @@ impl<G: GnbCuCp> RequestProvider<InitialContextSetupProcedure> for NgapHandler<G
     }
 }
 
+#[async_trait]
+impl<G: GnbCuCp> RequestProvider<AmfConfigurationUpdateProcedure> for NgapHandler<G> {
+    async fn request(
+        &self,
+        r: AmfConfigurationUpdate,
+        logger: &Logger,
+    ) -> Result<
+        ResponseAction<AmfConfigurationUpdateAcknowledge>,
+        RequestError<AmfConfigurationUpdateFailure>,
+    > {
+        Workflow::new(&self.gnb_cu_cp, logger)
+            .amf_configuration_update(r)
+            .await
+    }
+}
+
 #[async_trait]
 impl<G: GnbCuCp> IndicationHandler<AmfStatusIndicationProcedure> for NgapHandler<G> {
     async fn handle(&self, i: AmfStatusIndication, logger: &Logger) {
@@ -95,6 +123,22 @@ impl<G: GnbCuCp> RequestProvider<PduSessionResourceSetupProcedure> for NgapHandl
     }
 }
 
+#[async_trait]
+impl<G: GnbCuCp> RequestProvider<PduSessionResourceModifyProcedure> for NgapHandler<G> {
+    async fn request(
+        &self,
+        r: PduSessionResourceModifyRequest,
+        logger: &Logger,
+    ) -> Result<ResponseAction<PduSessionResourceModifyResponse>, RequestError<()>> {
+        Ok((
+            Workflow::new(&self.gnb_cu_cp, logger)
+                .pdu_session_resource_modify(r)
+                .await,
+            None,
+        ))
+    }
+}
+
 #[async_trait]
 impl<G: GnbCuCp> RequestProvider<PduSessionResourceReleaseProcedure> for NgapHandler<G> {
     async fn request(
@@ -110,3 +154,32 @@ impl<G: GnbCuCp> RequestProvider<PduSessionResourceReleaseProcedure> for NgapHan
         ))
     }
 }
+
+#[async_trait]
+impl<G: GnbCuCp> RequestProvider<UeContextReleaseProcedure> for NgapHandler<G> {
+    async fn request(
+        &self,
+        r: UeContextReleaseCommand,
+        logger: &Logger,
+    ) -> Result<ResponseAction<UeContextReleaseComplete>, RequestError<()>> {
+        match Workflow::new(&self.gnb_cu_cp, logger)
+            .ue_context_release(r)
+            .await
+        {
+            Ok(x) => Ok((x, None)),
+            Err(e) => {
+                debug!(logger, "Ue context release procedure failed - {:?}", e);
+                Err(RequestError::Other(e.to_string()))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<G: GnbCuCp> IndicationHandler<PagingProcedure> for NgapHandler<G> {
+    async fn handle(&self, i: Paging, logger: &Logger) {
+        if let Err(e) = Workflow::new(&self.gnb_cu_cp, logger).paging(i).await {
+            debug!(logger, "Paging procedure failed - {:?}", e);
+        };
+    }
+}
@@ -9,6 +9,10 @@ mod workflows;
 pub use config::{Config, ConnectionStyle, WorkerConnectionManagementConfig};
 pub use coordinator::ConnectionControlConfig;
 use datastore::UeState;
-pub use datastore::{MockUeStore, RedisUeStore};
+pub use datastore::{
+    MockCuUpStore, MockDuStore, MockUeStore, RedisCuUpStore, RedisDuStore, RedisUeStore,
+};
 use gnb_cu_cp::GnbCuCp;
-pub use worker::spawn;
+pub use net::StackConfig;
+pub use xxap::{plmn_from_mcc_mnc, BroadcastPlmn, PagingDrx, RanConfig, Snssai, SupportedTa};
+pub use worker::{spawn, spawn_with_transport};
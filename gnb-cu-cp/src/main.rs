@@ -1,22 +1,36 @@
 use anyhow::Result;
 use clap::Parser;
-use common::{logging, panic, signal};
+use common::{config_file, logging, panic, signal};
 use coordinator::ConnectionControlConfig;
-use gnb_cu_cp::{Config, ConnectionStyle, RedisUeStore};
+use gnb_cu_cp::{Config, ConnectionStyle, RedisCuUpStore, RedisDuStore, RedisUeStore};
 use slog::info;
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
+use std::path::PathBuf;
 use uuid::Uuid;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Local IP address to bind server ports to (E1AP and F1AP).
-    #[arg(short, long, default_value_t = IpAddr::V4(Ipv4Addr::UNSPECIFIED))]
-    local_ip: IpAddr,
+    /// TOML file giving the worker's configuration.  The other options override the settings in it.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
 
-    /// AMF's NGAP IP address to connect to.
-    #[arg(short, long, default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
-    amf_ip: IpAddr,
+    /// Local IP address to bind server ports to (E1AP and F1AP).  Default 0.0.0.0.
+    #[arg(short, long)]
+    local_ip: Option<IpAddr>,
+
+    /// Further local IP addresses for SCTP multihoming.  May be repeated.
+    #[arg(long)]
+    secondary_local_ip: Vec<IpAddr>,
+
+    /// AMF's NGAP IP address to connect to.  Default 127.0.0.1.  This makes the worker autonomous - that
+    /// is, it connects to the AMF itself rather than waiting for a coordinator.
+    #[arg(short, long)]
+    amf_ip: Option<IpAddr>,
+
+    /// Further IP addresses of a multihomed AMF.  May be repeated.
+    #[arg(long, requires = "amf_ip")]
+    secondary_amf_ip: Vec<IpAddr>,
 }
 
 #[async_std::main]
@@ -24,21 +38,15 @@ async fn main() -> Result<()> {
     panic::exit_on_panic();
 
     let args = Args::parse();
-    let config = Config {
-        ip_addr: args.local_ip,
-        connection_style: ConnectionStyle::Autonomous(ConnectionControlConfig {
-            fast_start: true,
-            amf_address: args.amf_ip.to_string(),
-            ..ConnectionControlConfig::default()
-        }),
-
-        ..Config::default()
-    };
+    let config = config(args)?;
     let root_logger = logging::init();
+    let redis_port = config.redis_port;
     let shutdown_handle = gnb_cu_cp::spawn(
         Uuid::new_v4(),
         config,
-        RedisUeStore::new(6379).unwrap(),
+        RedisUeStore::new(redis_port)?,
+        RedisDuStore::new(redis_port)?,
+        RedisCuUpStore::new(redis_port)?,
         root_logger.clone(),
     )
     .await?;
@@ -47,3 +55,36 @@ async fn main() -> Result<()> {
     shutdown_handle.graceful_shutdown().await;
     Ok(())
 }
+
+fn config(args: Args) -> Result<Config> {
+    let mut config: Config = match &args.config {
+        Some(path) => config_file::load(path)?,
+        None => Config::default(),
+    };
+    if let Some(local_ip) = args.local_ip {
+        config.ip_addr = local_ip;
+    }
+    if !args.secondary_local_ip.is_empty() {
+        config.secondary_ip_addrs = args.secondary_local_ip;
+    }
+    if let Some(amf_ip) = args.amf_ip {
+        let amf_address = std::iter::once(&amf_ip)
+            .chain(args.secondary_amf_ip.iter())
+            .map(|ip| ip.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        config.connection_style = match config.connection_style {
+            ConnectionStyle::Autonomous(x) => {
+                ConnectionStyle::Autonomous(ConnectionControlConfig { amf_address, ..x })
+            }
+            ConnectionStyle::Coordinated(_) => {
+                ConnectionStyle::Autonomous(ConnectionControlConfig {
+                    fast_start: true,
+                    amf_address,
+                    ..ConnectionControlConfig::default()
+                })
+            }
+        };
+    }
+    Ok(config)
+}
@@ -1,7 +1,9 @@
 //! worker - the top level struct for a gNB-CU-CP worker, which implements the GnbCuCp trait
 
 use super::config::ConnectionStyle;
-use super::datastore::{UeState, UeStateStore};
+use super::datastore::{
+    CuUpState, CuUpStateStore, DuState, DuStateStore, ServedCell, UeState, UeStateStore,
+};
 use super::handlers::RrcHandler;
 use super::rrc_transaction::{PendingRrcTransactions, RrcTransaction};
 use super::Config;
@@ -9,7 +11,7 @@ use crate::datastore::StateStore;
 use crate::handlers::connection_api::ConnectionApiHandler;
 use crate::handlers::{E1apHandler, F1apHandler, NgapHandler};
 use crate::{GnbCuCp, WorkerConnectionManagementConfig};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_channel::Sender;
 use async_std::future;
 use async_std::sync::Mutex;
@@ -19,17 +21,23 @@ use coordination_api::{
     Api as CoordinationApi, Client as CoordinationApiClient, RefreshWorkerResponse,
 };
 use coordinator::Coordinator;
-use f1ap::{DlRrcMessageTransfer, DlRrcMessageTransferProcedure, GnbCuUeF1apId, SrbId};
+use dashmap::DashMap;
+use f1ap::{
+    DlRrcMessageTransfer, DlRrcMessageTransferProcedure, GnbCuUeF1apId, GnbDuId, NrCgi, SrbId,
+};
 use net::{
     Indication, IndicationHandler, Procedure, RequestError, RequestProvider, SctpTransportProvider,
-    ShutdownHandle, Stack,
+    ShutdownHandle, Stack, TransportProvider,
 };
+use ngap::AmfUeNgapId;
 use rrc::UlDcchMessage;
 use slog::{debug, info, warn, Logger};
 use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use stop_token::{StopSource, StopToken};
 use swagger::{ApiError, AuthData, ContextBuilder, EmptyContext, Push, XSpanIdString};
 use uuid::Uuid;
@@ -41,17 +49,28 @@ pub type ClientContext = swagger::make_context_ty!(
     XSpanIdString
 );
 #[derive(Clone)]
-pub struct Worker<A: CoordinationApi<ClientContext>, U: UeStateStore> {
+pub struct Worker<
+    A: CoordinationApi<ClientContext>,
+    U: UeStateStore,
+    D: DuStateStore,
+    C: CuUpStateStore,
+    T: TransportProvider,
+> {
     worker_id: Uuid,
     config: Config,
-    ngap: Stack,
-    f1ap: Stack,
-    e1ap: Stack,
+    ngap: Stack<T>,
+    f1ap: Stack<T>,
+    e1ap: Stack<T>,
     ue_store: U,
+    du_store: D,
+    cu_up_store: C,
+    // Each DU's state, and when it was read from or written to the datastore.
+    du_cache: Arc<DashMap<u64, (Instant, DuState)>>,
     coordinator: A,
     logger: Logger,
     rrc_transactions: PendingRrcTransactions,
     shutdown_handles: Arc<Mutex<Vec<ShutdownHandle>>>,
+    draining: Arc<AtomicBool>,
 }
 
 // TS38.412, 7
@@ -70,26 +89,56 @@ const F1AP_BIND_PORT: u16 = 38472;
 const E1AP_SCTP_PPID: u32 = 64;
 const E1AP_BIND_PORT: u16 = 38462;
 
-pub async fn spawn<U: UeStateStore>(
+pub async fn spawn<U: UeStateStore, D: DuStateStore, C: CuUpStateStore>(
+    worker_id: Uuid,
+    config: Config,
+    ue_store: U,
+    du_store: D,
+    cu_up_store: C,
+    logger: Logger,
+) -> Result<ShutdownHandle> {
+    spawn_with_transport::<SctpTransportProvider, U, D, C>(
+        worker_id,
+        config,
+        ue_store,
+        du_store,
+        cu_up_store,
+        logger,
+    )
+    .await
+}
+
+/// Spawn a worker whose NGAP, F1AP and E1AP run over the given transport rather than SCTP.
+pub async fn spawn_with_transport<
+    T: TransportProvider,
+    U: UeStateStore,
+    D: DuStateStore,
+    C: CuUpStateStore,
+>(
     worker_id: Uuid,
     config: Config,
     ue_store: U,
+    du_store: D,
+    cu_up_store: C,
     logger: Logger,
 ) -> Result<ShutdownHandle> {
     let stop_source = StopSource::new();
     let stop_token = stop_source.token();
 
     info!(&logger, "Starting gNB-CU-CP worker {}", worker_id);
-    info!(&logger, "PLMN is {:02x?}", config.plmn);
+    info!(&logger, "PLMN is {:02x?}", config.ran.plmn);
     debug!(&logger, "Config: {:?}", config);
+    config.ran.validate()?;
 
     let handle = match config.connection_style {
         // Run a combined worker and coordinator.
         ConnectionStyle::Autonomous(ref connection_control_config) => {
             let (coordinator, receiver) = Coordinator::new(logger.clone());
-            let worker = Worker::new(
+            let worker = Worker::<_, _, _, _, T>::new(
                 config.clone(),
                 ue_store,
+                du_store,
+                cu_up_store,
                 worker_id,
                 logger.clone(),
                 coordinator.clone(),
@@ -115,7 +164,15 @@ pub async fn spawn<U: UeStateStore>(
                 &worker_connection_management_config.coordinator_base_path,
             )
             .unwrap();
-            let worker = Worker::new(config, ue_store, worker_id, logger, coordinator);
+            let worker = Worker::<_, _, _, _, T>::new(
+                config,
+                ue_store,
+                du_store,
+                cu_up_store,
+                worker_id,
+                logger,
+                coordinator,
+            );
             worker.start_servers().await?;
             async_std::task::spawn(async move {
                 worker.run(stop_token).await;
@@ -125,27 +182,50 @@ pub async fn spawn<U: UeStateStore>(
     Ok(ShutdownHandle::new(handle, stop_source))
 }
 
-impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeStateStore>
-    Worker<A, U>
+impl<
+        A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>,
+        U: UeStateStore,
+        D: DuStateStore,
+        C: CuUpStateStore,
+        T: TransportProvider,
+    > Worker<A, U, D, C, T>
 {
     fn new(
         config: Config,
         ue_store: U,
+        du_store: D,
+        cu_up_store: C,
         worker_id: Uuid,
         logger: Logger,
         coordinator: A,
-    ) -> Worker<A, U> {
+    ) -> Worker<A, U, D, C, T> {
         Worker {
             worker_id,
+            // The NGAP TNLAs all lead to the AMF, so UEs can be spread over them.  Each F1AP or E1AP TNLA may
+            // lead to a different DU or CU-UP, so a UE's signalling there only goes where the UE is bound.
+            ngap: Stack::new_with_config(
+                T::new_with_config(config.ngap_sctp_config.clone()),
+                config.ngap_stack_config.clone(),
+            )
+            .with_ue_load_balancing(),
+            f1ap: Stack::new_with_config(
+                T::new_with_config(config.f1ap_sctp_config.clone()),
+                config.f1ap_stack_config.clone(),
+            ),
+            e1ap: Stack::new_with_config(
+                T::new_with_config(config.e1ap_sctp_config.clone()),
+                config.e1ap_stack_config.clone(),
+            ),
             config,
-            ngap: Stack::new(SctpTransportProvider::new()),
-            f1ap: Stack::new(SctpTransportProvider::new()),
-            e1ap: Stack::new(SctpTransportProvider::new()),
             ue_store,
+            du_store,
+            cu_up_store,
+            du_cache: Arc::new(DashMap::new()),
             coordinator,
             logger,
             rrc_transactions: PendingRrcTransactions::new(),
             shutdown_handles: Arc::new(Mutex::new(Vec::new())),
+            draining: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -164,6 +244,11 @@ impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeSta
             let connection_api_handle = self.serve_connection_api(connection_api_bind_port).await?;
             self.add_shutdown_handle(connection_api_handle).await;
         };
+
+        if let Some(port) = self.config.management_api_bind_port {
+            let management_api_handle = self.serve_management_api(port).await?;
+            self.add_shutdown_handle(management_api_handle).await;
+        }
         Ok(())
     }
 
@@ -172,8 +257,10 @@ impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeSta
         // connection API.
         self.send_periodic_refreshes_to_coordinator(stop_token.clone())
             .await;
+        self.log_periodic_send_queue_stats(stop_token.clone());
 
         stop_token.await;
+        self.drain().await;
 
         while let Some(item) = self.shutdown_handles.lock().await.pop() {
             item.graceful_shutdown().await;
@@ -182,6 +269,35 @@ impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeSta
         self.ngap.graceful_shutdown().await;
     }
 
+    // Stop taking on new UEs, tell the coordinator, and give the procedures under way the chance to finish
+    // before the associations are closed.  The three interfaces are drained together, since a workflow
+    // started on one may be waiting for a response on another.
+    async fn drain(&self) {
+        info!(&self.logger, "Draining");
+        self.draining.store(true, Ordering::Relaxed);
+        if let Err(e) = self.send_refresh_worker().await {
+            warn!(
+                self.logger,
+                "Failed to tell coordinator we are draining - {}", e
+            );
+        }
+
+        let timeout = Duration::from_secs(self.config.drain_timeout_secs);
+        let (ngap_drained, (f1ap_drained, e1ap_drained)) = futures_lite::future::zip(
+            self.ngap.drain(timeout),
+            futures_lite::future::zip(self.f1ap.drain(timeout), self.e1ap.drain(timeout)),
+        )
+        .await;
+        if ngap_drained && f1ap_drained && e1ap_drained {
+            info!(&self.logger, "Drained");
+        } else {
+            warn!(
+                &self.logger,
+                "Procedures still under way after {:?} - shutting down anyway", timeout
+            );
+        }
+    }
+
     async fn serve(self, stop_token: StopToken) -> Result<()> {
         self.start_servers().await?;
         self.run(stop_token).await;
@@ -191,7 +307,7 @@ impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeSta
     async fn send_periodic_refreshes_to_coordinator(&self, stop_token: StopToken) {
         let clone = self.clone();
         async_std::task::spawn(async move {
-            let interval_secs = 10; // TODO - make configurable
+            let interval_secs = clone.config.connection_style.refresh_interval_secs() as u64;
 
             loop {
                 let stop_token_clone = stop_token.clone();
@@ -208,6 +324,18 @@ impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeSta
         });
     }
 
+    fn log_periodic_send_queue_stats(&self, stop_token: StopToken) {
+        let clone = self.clone();
+        async_std::task::spawn(async move {
+            let interval = Duration::from_secs(clone.config.send_queue_stats_interval_secs);
+            while future::timeout(interval, stop_token.clone()).await.is_err() {
+                clone.ngap.log_send_queue_stats("NGAP", &clone.logger).await;
+                clone.f1ap.log_send_queue_stats("F1AP", &clone.logger).await;
+                clone.e1ap.log_send_queue_stats("E1AP", &clone.logger).await;
+            }
+        });
+    }
+
     async fn send_refresh_worker(&self) -> Result<RefreshWorkerResponse, ApiError> {
         let context: ClientContext = swagger::make_context!(
             ContextBuilder,
@@ -244,6 +372,7 @@ impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeSta
                         f1_up,
                         e1_up,
                     },
+                    draining: Some(self.is_draining()),
                 },
                 &context,
             )
@@ -251,7 +380,7 @@ impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeSta
     }
 
     async fn serve_f1ap(&self) -> Result<ShutdownHandle> {
-        let f1_listen_address = self.worker_listen_address(F1AP_BIND_PORT);
+        let f1_listen_address = self.sctp_listen_addresses(F1AP_BIND_PORT);
         info!(
             &self.logger,
             "Listen for connection from DU on {}", f1_listen_address
@@ -269,7 +398,7 @@ impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeSta
     }
 
     async fn serve_e1ap(&self) -> Result<ShutdownHandle> {
-        let e1_listen_address = self.worker_listen_address(E1AP_BIND_PORT);
+        let e1_listen_address = self.sctp_listen_addresses(E1AP_BIND_PORT);
         info!(
             &self.logger,
             "Listen for connection from CU-UP on {}", e1_listen_address
@@ -295,48 +424,238 @@ impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeSta
         crate::handlers::connection_api::serve(addr, self.clone(), self.logger.clone()).await
     }
 
+    async fn serve_management_api(&self, port: u16) -> Result<ShutdownHandle> {
+        let management_api_listen_address = self.worker_listen_address(port);
+        info!(
+            &self.logger,
+            "Serve management API on {management_api_listen_address}",
+        );
+        let addr = management_api_listen_address.parse()?;
+        crate::handlers::management_api::serve(addr, self.clone(), self.logger.clone()).await
+    }
+
     fn worker_listen_address(&self, port: u16) -> String {
-        format!("{}:{}", self.config.ip_addr, port)
+        SocketAddr::new(self.config.ip_addr, port).to_string()
+    }
+
+    // The local addresses for SCTP, as a comma separated list.  There is more than one if the worker
+    // is multihomed.
+    fn sctp_local_addresses(&self) -> String {
+        self.sctp_local_ips()
+            .map(|ip| ip.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    fn sctp_listen_addresses(&self, port: u16) -> String {
+        self.sctp_local_ips()
+            .map(|ip| SocketAddr::new(*ip, port).to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    fn sctp_local_ips(&self) -> impl Iterator<Item = &IpAddr> {
+        std::iter::once(&self.config.ip_addr).chain(self.config.secondary_ip_addrs.iter())
     }
 
     async fn add_shutdown_handle(&self, shutdown_handle: ShutdownHandle) {
         self.shutdown_handles.lock().await.push(shutdown_handle);
     }
+
+    // A DU configuration update is handled by whichever worker it arrives on, and reaches the other workers
+    // through the datastore.  Cached DU state is only used for du_cache_ttl_secs, so that each worker picks
+    // up changes to the DU's cells within that time.
+    fn cached_served_cell(&self, nr_cgi: &NrCgi) -> Option<ServedCell> {
+        let ttl = Duration::from_secs(self.config.du_cache_ttl_secs);
+        self.du_cache
+            .iter()
+            .filter(|x| x.0.elapsed() < ttl)
+            .find_map(|x| x.1.served_cell(nr_cgi).cloned())
+    }
+
+    fn cache_du(&self, du: DuState) {
+        self.du_cache.insert(du.gnb_du_id.0, (Instant::now(), du));
+    }
 }
 
 #[async_trait]
-impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeStateStore>
-    StateStore<UeState> for Worker<A, U>
+impl<
+        A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>,
+        U: UeStateStore,
+        D: DuStateStore,
+        C: CuUpStateStore,
+        T: TransportProvider,
+    > StateStore<UeState> for Worker<A, U, D, C, T>
 {
-    async fn store(&self, k: u32, s: UeState, ttl_secs: usize) -> Result<()> {
+    // The UE's TNLA bindings are kept in step with the UE state.  Storing the UE pins it to its current
+    // TNLAs, and retrieving it on another worker restores the bindings there.
+    async fn store(&self, k: u32, mut s: UeState, ttl_secs: usize) -> Result<()> {
+        match self.ngap.bind_ue(k).await {
+            Ok(remote_ip) => s.ngap_tnla_address = Some(remote_ip),
+            Err(e) => debug!(
+                self.logger,
+                "UE {:#010x} not bound to an NGAP TNLA - {}", k, e
+            ),
+        }
+        match self.f1ap.bind_ue(k).await {
+            Ok(remote_ip) => s.f1ap_tnla_address = Some(remote_ip),
+            Err(e) => debug!(
+                self.logger,
+                "UE {:#010x} not bound to an F1AP TNLA - {}", k, e
+            ),
+        }
+        // A UE only has a CU-UP while it has a bearer context.
+        s.e1ap_tnla_address = None;
+        if s.gnb_cu_up_ue_e1ap_id.is_some() {
+            match self.e1ap.bind_ue(k).await {
+                Ok(remote_ip) => s.e1ap_tnla_address = Some(remote_ip),
+                Err(e) => debug!(
+                    self.logger,
+                    "UE {:#010x} not bound to an E1AP TNLA - {}", k, e
+                ),
+            }
+        }
         self.ue_store.store(k, s, ttl_secs).await
     }
     async fn retrieve(&self, k: &u32) -> Result<UeState> {
-        self.ue_store.retrieve(k).await
+        let s = self.ue_store.retrieve(k).await?;
+        if let Some(remote_ip) = &s.ngap_tnla_address {
+            if let Err(e) = self.ngap.restore_ue_binding(*k, remote_ip).await {
+                debug!(self.logger, "UE {:#010x} stays unbound - {}", k, e);
+            }
+        }
+        if let Some(remote_ip) = &s.f1ap_tnla_address {
+            if let Err(e) = self.f1ap.restore_ue_binding(*k, remote_ip).await {
+                debug!(self.logger, "UE {:#010x} stays unbound from DU - {}", k, e);
+            }
+        }
+        if let Some(remote_ip) = &s.e1ap_tnla_address {
+            if let Err(e) = self.e1ap.restore_ue_binding(*k, remote_ip).await {
+                debug!(
+                    self.logger,
+                    "UE {:#010x} stays unbound from CU-UP - {}", k, e
+                );
+            }
+        }
+        Ok(s)
     }
     async fn delete(&self, k: &u32) -> Result<()> {
+        for stack in [&self.ngap, &self.f1ap, &self.e1ap] {
+            stack.unbind_ue(*k).await;
+        }
         self.ue_store.delete(k).await
     }
 }
-impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeStateStore>
-    UeStateStore for Worker<A, U>
+
+#[async_trait]
+impl<
+        A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>,
+        U: UeStateStore,
+        D: DuStateStore,
+        C: CuUpStateStore,
+        T: TransportProvider,
+    > UeStateStore for Worker<A, U, D, C, T>
 {
+    async fn ues_in_cell(&self, nr_cgi: &NrCgi) -> Result<Vec<u32>> {
+        self.ue_store.ues_in_cell(nr_cgi).await
+    }
+    async fn ue_with_amf_ue_ngap_id(&self, amf_ue_ngap_id: &AmfUeNgapId) -> Result<Option<u32>> {
+        self.ue_store.ue_with_amf_ue_ngap_id(amf_ue_ngap_id).await
+    }
+    async fn ue_with_short_i_rnti(&self, short_i_rnti: u32) -> Result<Option<u32>> {
+        self.ue_store.ue_with_short_i_rnti(short_i_rnti).await
+    }
+}
+
+#[async_trait]
+impl<
+        A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>,
+        U: UeStateStore,
+        D: DuStateStore,
+        C: CuUpStateStore,
+        T: TransportProvider,
+    > DuStateStore for Worker<A, U, D, C, T>
+{
+    async fn store_du(&self, s: DuState) -> Result<()> {
+        self.cache_du(s.clone());
+        self.du_store.store_du(s).await
+    }
+
+    // Reads go to the datastore, since another worker may have updated the DU.  They also refresh
+    // the cache.
+    async fn retrieve_du(&self, gnb_du_id: &GnbDuId) -> Result<DuState> {
+        let du = self.du_store.retrieve_du(gnb_du_id).await?;
+        self.cache_du(du.clone());
+        Ok(du)
+    }
+    async fn retrieve_all_dus(&self) -> Result<Vec<DuState>> {
+        let dus = self.du_store.retrieve_all_dus().await?;
+        self.du_cache
+            .retain(|gnb_du_id, _| dus.iter().any(|x| x.gnb_du_id.0 == *gnb_du_id));
+        for du in dus.iter() {
+            self.cache_du(du.clone());
+        }
+        Ok(dus)
+    }
+    async fn delete_du(&self, gnb_du_id: &GnbDuId) -> Result<()> {
+        self.du_cache.remove(&gnb_du_id.0);
+        self.du_store.delete_du(gnb_du_id).await
+    }
+    async fn add_du_connection(&self, address: &str, worker_id: Uuid) -> Result<()> {
+        self.du_store.add_du_connection(address, worker_id).await
+    }
+    async fn remove_du_connection(&self, address: &str, worker_id: Uuid) -> Result<usize> {
+        self.du_store.remove_du_connection(address, worker_id).await
+    }
+}
+
+#[async_trait]
+impl<
+        A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>,
+        U: UeStateStore,
+        D: DuStateStore,
+        C: CuUpStateStore,
+        T: TransportProvider,
+    > CuUpStateStore for Worker<A, U, D, C, T>
+{
+    async fn store_cu_up(&self, s: CuUpState) -> Result<()> {
+        self.cu_up_store.store_cu_up(s).await
+    }
+    async fn retrieve_all_cu_ups(&self) -> Result<Vec<CuUpState>> {
+        self.cu_up_store.retrieve_all_cu_ups().await
+    }
 }
 
 #[async_trait]
-impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeStateStore> GnbCuCp
-    for Worker<A, U>
+impl<
+        A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>,
+        U: UeStateStore,
+        D: DuStateStore,
+        C: CuUpStateStore,
+        T: TransportProvider,
+    > GnbCuCp for Worker<A, U, D, C, T>
 {
     fn config(&self) -> &Config {
         &self.config
     }
+    fn worker_id(&self) -> Uuid {
+        self.worker_id
+    }
+    fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+    // The AMF IP address may be a comma separated list, if the AMF is multihomed.
     async fn ngap_connect(&self, amf_ip_address: &str) -> Result<()> {
-        let amf_address = format!("{}:{}", amf_ip_address, NGAP_BIND_PORT);
+        let amf_address = amf_ip_address
+            .split(',')
+            .map(|ip| host_and_port(ip.trim(), NGAP_BIND_PORT))
+            .collect::<Vec<_>>()
+            .join(",");
         debug!(&self.logger, "Connect to AMF {}", amf_address);
         self.ngap
             .connect(
                 &amf_address,
-                &self.config.ip_addr.to_string(),
+                &self.sctp_local_addresses(),
                 NGAP_SCTP_PPID,
                 NgapHandler::new_ngap_application(self.clone()),
                 self.logger.clone(),
@@ -345,17 +664,25 @@ impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeSta
         Ok(())
     }
 
+    async fn set_ngap_tnla_weight(&self, amf_ip_address: IpAddr, weight: u8) {
+        debug!(
+            &self.logger,
+            "NGAP TNLA weight factor of AMF {} is {}", amf_ip_address, weight
+        );
+        self.ngap.set_tnla_weight(amf_ip_address, weight).await
+    }
+
     async fn ngap_request<P: Procedure>(
         &self,
         r: P::Request,
         logger: &Logger,
     ) -> Result<P::Success, RequestError<P::Failure>> {
-        <Stack as RequestProvider<P>>::request(&self.ngap, r, logger)
+        <Stack<T> as RequestProvider<P>>::request(&self.ngap, r, logger)
             .await
             .map(|(x, _)| x)
     }
     async fn ngap_indication<P: Indication>(&self, r: P::Request, logger: &Logger) {
-        <Stack as IndicationHandler<P>>::handle(&self.ngap, r, logger).await
+        <Stack<T> as IndicationHandler<P>>::handle(&self.ngap, r, logger).await
     }
 
     async fn f1ap_request<P: Procedure>(
@@ -363,12 +690,12 @@ impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeSta
         r: P::Request,
         logger: &Logger,
     ) -> Result<P::Success, RequestError<P::Failure>> {
-        <Stack as RequestProvider<P>>::request(&self.f1ap, r, logger)
+        <Stack<T> as RequestProvider<P>>::request(&self.f1ap, r, logger)
             .await
             .map(|(x, _)| x)
     }
     async fn f1ap_indication<P: Indication>(&self, r: P::Request, logger: &Logger) {
-        <Stack as IndicationHandler<P>>::handle(&self.f1ap, r, logger).await
+        <Stack<T> as IndicationHandler<P>>::handle(&self.f1ap, r, logger).await
     }
 
     async fn e1ap_request<P: Procedure>(
@@ -376,12 +703,61 @@ impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeSta
         r: P::Request,
         logger: &Logger,
     ) -> Result<P::Success, RequestError<P::Failure>> {
-        <Stack as RequestProvider<P>>::request(&self.e1ap, r, logger)
+        <Stack<T> as RequestProvider<P>>::request(&self.e1ap, r, logger)
             .await
             .map(|(x, _)| x)
     }
     async fn e1ap_indication<P: Indication>(&self, r: P::Request, logger: &Logger) {
-        <Stack as IndicationHandler<P>>::handle(&self.e1ap, r, logger).await
+        <Stack<T> as IndicationHandler<P>>::handle(&self.e1ap, r, logger).await
+    }
+
+    async fn f1ap_request_to_du<P: Procedure>(
+        &self,
+        r: P::Request,
+        du_address: &str,
+        logger: &Logger,
+    ) -> Result<P::Success, RequestError<P::Failure>> {
+        self.f1ap.request_to_peer::<P>(r, du_address, logger).await
+    }
+
+    async fn f1ap_indication_to_du<P: Indication>(
+        &self,
+        r: P::Request,
+        du_address: &str,
+        logger: &Logger,
+    ) -> Result<()> {
+        self.f1ap.indicate_to_peer::<P>(r, du_address, logger).await
+    }
+
+    async fn f1ap_request_remote_ip(&self) -> Option<IpAddr> {
+        self.f1ap.request_remote_address().await.map(|x| x.ip())
+    }
+
+    async fn is_du_connected(&self, du_address: &str) -> bool {
+        self.f1ap.has_peer(du_address).await
+    }
+
+    async fn bind_ue_to_du(&self, ue_key: u32) -> Result<()> {
+        self.f1ap.bind_ue_to_request_tnla(ue_key).await
+    }
+
+    async fn e1ap_request_remote_ip(&self) -> Option<IpAddr> {
+        self.e1ap.request_remote_address().await.map(|x| x.ip())
+    }
+
+    async fn bind_ue_to_cu_up(&self, ue_key: u32, cu_up_address: &str) -> Result<()> {
+        self.e1ap.bind_ue_to_peer(ue_key, cu_up_address).await
+    }
+
+    async fn served_cell(&self, nr_cgi: &NrCgi) -> Result<ServedCell> {
+        if let Some(cell) = self.cached_served_cell(nr_cgi) {
+            return Ok(cell);
+        }
+
+        // The DU may have set up or updated its cells via another worker, so refresh the cache and look again.
+        self.retrieve_all_dus().await?;
+        self.cached_served_cell(nr_cgi)
+            .ok_or_else(|| anyhow!("Cell {:?} is not served by any DU", nr_cgi))
     }
 
     /// Start a new RRC transaction.
@@ -440,3 +816,11 @@ impl<A: Clone + Send + Sync + 'static + CoordinationApi<ClientContext>, U: UeSta
         Box::pin(future)
     }
 }
+
+// Append a port to an IP address or host name, bracketing an IPv6 address.
+fn host_and_port(host: &str, port: u16) -> String {
+    match host.parse::<IpAddr>() {
+        Ok(ip) => SocketAddr::new(ip, port).to_string(),
+        Err(_) => format!("{}:{}", host, port),
+    }
+}
@@ -0,0 +1,122 @@
+//! amf_configuration_update - AMF changes its TNL associations and their weight factors
+
+use super::{GnbCuCp, Workflow};
+use anyhow::{bail, Result};
+use asn1_per::NonEmpty;
+use net::{RequestError, ResponseAction};
+use ngap::*;
+use std::net::IpAddr;
+
+impl<'a, G: GnbCuCp> Workflow<'a, G> {
+    // AMF Configuration Update procedure
+    // 1.    Ngap AmfConfigurationUpdate >>
+    // 2.    Connect to each added AMF TNL association
+    // 3.    Ngap AmfConfigurationUpdateAcknowledge <<
+    //
+    // The TNL address weight factors feed into the choice of NGAP TNLA for new UEs.  See TS 38.413, 8.7.3.
+    pub async fn amf_configuration_update(
+        &self,
+        r: AmfConfigurationUpdate,
+    ) -> Result<
+        ResponseAction<AmfConfigurationUpdateAcknowledge>,
+        RequestError<AmfConfigurationUpdateFailure>,
+    > {
+        self.log_message(">> AmfConfigurationUpdate");
+
+        let mut setup = vec![];
+        let mut failed = vec![];
+        if let Some(AmfTnlAssociationToAddList(items)) = r.amf_tnl_association_to_add_list {
+            for item in items {
+                let weight = tnla_weight(
+                    item.tnl_association_usage.as_ref(),
+                    Some(&item.tnl_address_weight_factor),
+                );
+                match self
+                    .add_amf_tnla(&item.amf_tnl_association_address, weight)
+                    .await
+                {
+                    Ok(()) => setup.push(AmfTnlAssociationSetupItem {
+                        amf_tnl_association_address: item.amf_tnl_association_address,
+                    }),
+                    Err(e) => {
+                        self.log_message_error(&format!("Failed to set up AMF TNLA - {e}"));
+                        failed.push(TnlAssociationItem {
+                            tnl_association_address: item.amf_tnl_association_address,
+                            cause: Cause::Transport(CauseTransport::Unspecified),
+                        })
+                    }
+                }
+            }
+        }
+
+        if let Some(AmfTnlAssociationToUpdateList(items)) = r.amf_tnl_association_to_update_list {
+            for item in items {
+                if let (Ok(ip_addr), Some(weight)) = (
+                    self.amf_tnla_ip_addr(&item.amf_tnl_association_address),
+                    tnla_weight(
+                        item.tnl_association_usage.as_ref(),
+                        item.tnl_address_weight_factor.as_ref(),
+                    ),
+                ) {
+                    self.set_ngap_tnla_weight(ip_addr, weight).await;
+                }
+            }
+        }
+
+        // Closing a TNLA is left to the AMF.  In the meantime, no new UEs are bound to it.
+        if let Some(AmfTnlAssociationToRemoveList(items)) = r.amf_tnl_association_to_remove_list {
+            for item in items {
+                if let Ok(ip_addr) = self.amf_tnla_ip_addr(&item.amf_tnl_association_address) {
+                    self.set_ngap_tnla_weight(ip_addr, 0).await;
+                }
+            }
+        }
+
+        self.log_message("<< AmfConfigurationUpdateAcknowledge");
+        Ok((
+            AmfConfigurationUpdateAcknowledge {
+                amf_tnl_association_setup_list: NonEmpty::from_vec(setup)
+                    .map(AmfTnlAssociationSetupList),
+                amf_tnl_association_failed_to_setup_list: NonEmpty::from_vec(failed)
+                    .map(TnlAssociationList),
+                criticality_diagnostics: None,
+            },
+            None,
+        ))
+    }
+
+    async fn add_amf_tnla(
+        &self,
+        address: &CpTransportLayerInformation,
+        weight: Option<u8>,
+    ) -> Result<()> {
+        let ip_addr = self.amf_tnla_ip_addr(address)?;
+        if let Some(weight) = weight {
+            self.set_ngap_tnla_weight(ip_addr, weight).await;
+        }
+        self.ngap_connect(&ip_addr.to_string()).await
+    }
+
+    fn amf_tnla_ip_addr(&self, address: &CpTransportLayerInformation) -> Result<IpAddr> {
+        match address {
+            CpTransportLayerInformation::EndpointIpAddress(x) => {
+                x.ip_addr_for(&self.config().ip_addr)
+            }
+            CpTransportLayerInformation::EndpointIpAddressAndPort(_) => {
+                bail!("AMF TNLA with port number not supported")
+            }
+        }
+    }
+}
+
+// A TNLA that the AMF reserves for non UE-associated signalling is given weight 0, so that UEs are not bound
+// to it.
+fn tnla_weight(
+    usage: Option<&TnlAssociationUsage>,
+    weight_factor: Option<&TnlAddressWeightFactor>,
+) -> Option<u8> {
+    match usage {
+        Some(TnlAssociationUsage::NonUe) => Some(0),
+        _ => weight_factor.map(|x| x.0),
+    }
+}
@@ -1,5 +1,5 @@
-use crate::datastore::UeState;
-use anyhow::Result;
+use crate::datastore::{DrbState, PduSessionState, QosFlowState, UeState};
+use anyhow::{anyhow, Result};
 use asn1_per::*;
 use e1ap::*;
 use ngap::{
@@ -10,11 +10,10 @@ use slog::{debug, Logger};
 use xxap::{GtpTunnel, PduSessionId};
 
 pub fn build_e1_setup_item(
-    _ue: &UeState,
     r: &PduSessionResourceSetupItemSuReq,
+    session: &PduSessionState,
     logger: &Logger,
 ) -> Result<PduSessionResourceToSetupItem> {
-    let snssai: xxap::Snssai = r.snssai.clone().into();
     let session_params = PduSessionResourceSetupRequestTransfer::from_bytes(
         &r.pdu_session_resource_setup_request_transfer,
     )?;
@@ -29,10 +28,16 @@ pub fn build_e1_setup_item(
         gtp_tunnel.gtp_teid.0
     );
 
+    let drbs = session
+        .drbs
+        .iter()
+        .map(|drb| build_e1_drb_to_setup_item(session, drb))
+        .collect::<Result<Vec<_>>>()?;
+
     Ok(PduSessionResourceToSetupItem {
-        pdu_session_id: PduSessionId(r.pdu_session_id.0),
+        pdu_session_id: PduSessionId(session.pdu_session_id),
         pdu_session_type: PduSessionType::Ipv4,
-        snssai: snssai.into(),
+        snssai: session.snssai().into(),
         security_indication: SecurityIndication {
             integrity_protection_indication: IntegrityProtectionIndication::Preferred,
             confidentiality_protection_indication: ConfidentialityProtectionIndication::Preferred,
@@ -44,78 +49,10 @@ pub fn build_e1_setup_item(
         pdu_session_inactivity_timer: None,
         existing_allocated_ng_dl_up_tnl_info: None,
         network_instance: None,
-        drb_to_setup_list_ng_ran: DrbToSetupListNgRan(nonempty![DrbToSetupItemNgRan {
-            drb_id: DrbId(1),
-            sdap_configuration: SdapConfiguration {
-                default_drb: DefaultDrb::True, // test
-                sdap_header_ul: SdapHeaderUl::Present,
-                sdap_header_dl: SdapHeaderDl::Present,
-            },
-            pdcp_configuration: PdcpConfiguration {
-                pdcp_sn_size_ul: PdcpSnSize::S12,
-                pdcp_sn_size_dl: PdcpSnSize::S12,
-                rlc_mode: RlcMode::RlcTm,
-                rohc_parameters: None,
-                t_reordering_timer: None,
-                discard_timer: None,
-                ul_data_split_threshold: None,
-                pdcp_duplication: None,
-                pdcp_reestablishment: None,
-                pdcp_data_recovery: None,
-                duplication_activation: None,
-                out_of_order_delivery: None,
-                pdcp_status_report_indication: None,
-                additional_pdc_pduplication_information: None,
-                ehc_parameters: None,
-            },
-            cell_group_information: CellGroupInformation(nonempty![CellGroupInformationItem {
-                cell_group_id: CellGroupId(1),
-                ul_configuration: None,
-                dl_tx_stop: None,
-                rat_type: None,
-                number_of_tunnels: None,
-            }]),
-            qos_flow_information_to_be_setup: QosFlowQosParameterList(nonempty![
-                QosFlowQosParameterItem {
-                    qos_flow_identifier: QosFlowIdentifier(1),
-                    qos_flow_level_qos_parameters: QosFlowLevelQosParameters {
-                        qos_characteristics: QosCharacteristics::NonDynamic5qi(
-                            NonDynamic5qiDescriptor {
-                                five_qi: 1,
-                                qos_priority_level: None,
-                                averaging_window: None,
-                                max_data_burst_volume: None,
-                                cn_packet_delay_budget_downlink: None,
-                                cn_packet_delay_budget_uplink: None,
-                            },
-                        ),
-                        ngran_allocation_retention_priority: NgranAllocationAndRetentionPriority {
-                            priority_level: PriorityLevel(1),
-                            pre_emption_capability: PreEmptionCapability::MayTriggerPreEmption,
-                            pre_emption_vulnerability: PreEmptionVulnerability::NotPreEmptable,
-                        },
-                        gbr_qos_flow_information: None,
-                        reflective_qos_attribute: None,
-                        additional_qos_information: None,
-                        paging_policy_indicator: None,
-                        reflective_qos_indicator: None,
-                        qos_monitoring_request: None,
-                        mcg_offered_gbr_qos_flow_info: None,
-                        qos_monitoring_reporting_frequency: None,
-                        qos_monitoring_disabled: None,
-                    },
-                    qos_flow_mapping_indication: None,
-                    redundant_qos_flow_indicator: None,
-                    tsc_traffic_characteristics: None,
-                },
-            ]),
-            drb_data_forwarding_information_request: None,
-            drb_inactivity_timer: None,
-            pdcp_sn_status_information: None,
-            drb_qos: None,
-            daps_request_info: None,
-            ignore_mapping_rule_indication: None,
-        }]),
+        drb_to_setup_list_ng_ran: DrbToSetupListNgRan(
+            NonEmpty::from_vec(drbs)
+                .ok_or_else(|| anyhow!("Session {} has no DRBs", session.pdu_session_id))?,
+        ),
         common_network_instance: None,
         redundant_n_g_ul_up_tnl_information: None,
         redundant_common_network_instance: None,
@@ -123,6 +60,63 @@ pub fn build_e1_setup_item(
     })
 }
 
+fn build_e1_drb_to_setup_item(
+    session: &PduSessionState,
+    drb: &DrbState,
+) -> Result<DrbToSetupItemNgRan> {
+    let qos_flows = NonEmpty::from_vec(
+        session
+            .drb_qos_flows(drb)
+            .map(build_e1_qos_flow_item)
+            .collect(),
+    )
+    .ok_or_else(|| anyhow!("DRB {} has no QoS flows", drb.drb_id))?;
+
+    Ok(DrbToSetupItemNgRan {
+        drb_id: DrbId(drb.drb_id),
+        sdap_configuration: SdapConfiguration {
+            default_drb: if session.is_default_drb(drb.drb_id) {
+                DefaultDrb::True
+            } else {
+                DefaultDrb::False
+            },
+            sdap_header_ul: SdapHeaderUl::Present,
+            sdap_header_dl: SdapHeaderDl::Present,
+        },
+        pdcp_configuration: PdcpConfiguration {
+            pdcp_sn_size_ul: PdcpSnSize::S12,
+            pdcp_sn_size_dl: PdcpSnSize::S12,
+            rlc_mode: RlcMode::RlcTm,
+            rohc_parameters: None,
+            t_reordering_timer: None,
+            discard_timer: None,
+            ul_data_split_threshold: None,
+            pdcp_duplication: None,
+            pdcp_reestablishment: None,
+            pdcp_data_recovery: None,
+            duplication_activation: None,
+            out_of_order_delivery: None,
+            pdcp_status_report_indication: None,
+            additional_pdc_pduplication_information: None,
+            ehc_parameters: None,
+        },
+        cell_group_information: CellGroupInformation(nonempty![CellGroupInformationItem {
+            cell_group_id: CellGroupId(1),
+            ul_configuration: None,
+            dl_tx_stop: None,
+            rat_type: None,
+            number_of_tunnels: None,
+        }]),
+        qos_flow_information_to_be_setup: QosFlowQosParameterList(qos_flows),
+        drb_data_forwarding_information_request: None,
+        drb_inactivity_timer: None,
+        pdcp_sn_status_information: None,
+        drb_qos: None,
+        daps_request_info: None,
+        ignore_mapping_rule_indication: None,
+    })
+}
+
 pub fn build_bearer_context_setup(
     ue: &UeState,
     serving_plmn: PlmnIdentity,
@@ -166,10 +160,12 @@ pub fn build_bearer_context_setup(
     }
 }
 
+// The bearer context can be suspended or resumed, and its sessions modified, in the same request.
 pub fn build_bearer_context_modification(
     ue: &UeState,
     gnb_cu_up_ue_e1ap_id: GnbCuUpUeE1apId,
-    items: NonEmpty<PduSessionResourceToModifyItem>,
+    bearer_context_status_change: Option<BearerContextStatusChange>,
+    items: Option<NonEmpty<PduSessionResourceToModifyItem>>,
 ) -> BearerContextModificationRequest {
     // TODO incomplete - for example need to supply a system_bearer_context_modification_request
     // with DrbToModifyListNgRan containing the UpTransportLayerInformation received in the
@@ -181,11 +177,11 @@ pub fn build_bearer_context_modification(
         security_information: None,
         ue_dl_aggregate_maximum_bit_rate: None,
         ue_dl_maximum_integrity_protected_data_rate: None,
-        bearer_context_status_change: None,
+        bearer_context_status_change,
         new_ul_tnl_information_required: None,
         ue_inactivity_timer: None,
         data_discard_required: None,
-        system_bearer_context_modification_request: Some(
+        system_bearer_context_modification_request: items.map(|items| {
             SystemBearerContextModificationRequest::NgRanBearerContextModificationRequest(
                 NgRanBearerContextModificationRequest {
                     pdu_session_resource_to_setup_mod_list: None,
@@ -194,40 +190,47 @@ pub fn build_bearer_context_modification(
                     )),
                     pdu_session_resource_to_remove_list: None,
                 },
-            ),
-        ),
+            )
+        }),
         ran_ue_id: None,
         gnb_du_id: None,
         activity_notification_level: None,
     }
 }
 
+// Supply the DU's downlink tunnel for each of the session's DRBs.
 pub fn build_e1_modify_item(
     pdu_session_id: PduSessionId,
-    gtp_tunnel: GtpTunnel,
+    drbs: Vec<(DrbId, GtpTunnel)>,
 ) -> Result<PduSessionResourceToModifyItem> {
-    let drb_to_modify_list_ng_ran = Some(DrbToModifyListNgRan(nonempty![DrbToModifyItemNgRan {
-        drb_id: DrbId(pdu_session_id.0),
-        sdap_configuration: None,
-        pdcp_configuration: None,
-        drb_data_forwarding_information: None,
-        pdcp_sn_status_request: None,
-        pdcp_sn_status_information: None,
-        dl_up_parameters: Some(UpParameters(nonempty![UpParametersItem {
-            up_tnl_information: UpTnlInformation::GtpTunnel(gtp_tunnel),
-            cell_group_id: CellGroupId(1),
-            qos_mapping_information: None,
-        }])),
-        cell_group_to_add: None,
-        cell_group_to_modify: None,
-        cell_group_to_remove: None,
-        flow_mapping_information: None,
-        drb_inactivity_timer: None,
-        old_qos_flow_map_ul_endmarkerexpected: None,
-        drb_qos: None,
-        early_forwarding_count_req: None,
-        early_forwarding_count_info: None,
-    }]));
+    let drbs = drbs
+        .into_iter()
+        .map(|(drb_id, gtp_tunnel)| DrbToModifyItemNgRan {
+            drb_id,
+            sdap_configuration: None,
+            pdcp_configuration: None,
+            drb_data_forwarding_information: None,
+            pdcp_sn_status_request: None,
+            pdcp_sn_status_information: None,
+            dl_up_parameters: Some(UpParameters(nonempty![UpParametersItem {
+                up_tnl_information: UpTnlInformation::GtpTunnel(gtp_tunnel),
+                cell_group_id: CellGroupId(1),
+                qos_mapping_information: None,
+            }])),
+            cell_group_to_add: None,
+            cell_group_to_modify: None,
+            cell_group_to_remove: None,
+            flow_mapping_information: None,
+            drb_inactivity_timer: None,
+            old_qos_flow_map_ul_endmarkerexpected: None,
+            drb_qos: None,
+            early_forwarding_count_req: None,
+            early_forwarding_count_info: None,
+        })
+        .collect();
+    let drb_to_modify_list_ng_ran = Some(DrbToModifyListNgRan(
+        NonEmpty::from_vec(drbs).ok_or_else(|| anyhow!("Session has no DRBs"))?,
+    ));
 
     //: Some(UpTnlInformation::GtpTunnel(gtp_tunnel.clone()))
     Ok(PduSessionResourceToModifyItem {
@@ -249,3 +252,98 @@ pub fn build_e1_modify_item(
         data_forwardingto_eutran_information_list: None,
     })
 }
+
+// Modify the QoS flows mapped to each of a session's DRBs, and optionally its aggregate maximum bit rate.
+// The CU-UP replies with each DRB's uplink tunnel, which we need to give to the DU.
+pub fn build_e1_qos_flow_modify_item(
+    session: &PduSessionState,
+    pdu_session_resource_dl_ambr: Option<BitRate>,
+) -> Result<PduSessionResourceToModifyItem> {
+    let drbs = session
+        .drbs
+        .iter()
+        .map(|drb| {
+            let qos_flows = NonEmpty::from_vec(
+                session
+                    .drb_qos_flows(drb)
+                    .map(build_e1_qos_flow_item)
+                    .collect(),
+            )
+            .ok_or_else(|| anyhow!("DRB {} has no QoS flows", drb.drb_id))?;
+            Ok(DrbToModifyItemNgRan {
+                drb_id: DrbId(drb.drb_id),
+                sdap_configuration: None,
+                pdcp_configuration: None,
+                drb_data_forwarding_information: None,
+                pdcp_sn_status_request: None,
+                pdcp_sn_status_information: None,
+                dl_up_parameters: None,
+                cell_group_to_add: None,
+                cell_group_to_modify: None,
+                cell_group_to_remove: None,
+                flow_mapping_information: Some(QosFlowQosParameterList(qos_flows)),
+                drb_inactivity_timer: None,
+                old_qos_flow_map_ul_endmarkerexpected: None,
+                drb_qos: None,
+                early_forwarding_count_req: None,
+                early_forwarding_count_info: None,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let drb_to_modify_list_ng_ran = Some(DrbToModifyListNgRan(
+        NonEmpty::from_vec(drbs)
+            .ok_or_else(|| anyhow!("Session {} has no DRBs", session.pdu_session_id))?,
+    ));
+
+    Ok(PduSessionResourceToModifyItem {
+        pdu_session_id: PduSessionId(session.pdu_session_id),
+        security_indication: None,
+        pdu_session_resource_dl_ambr,
+        ng_ul_up_tnl_information: None,
+        pdu_session_data_forwarding_information_request: None,
+        pdu_session_data_forwarding_information: None,
+        pdu_session_inactivity_timer: None,
+        network_instance: None,
+        drb_to_setup_list_ng_ran: None,
+        drb_to_modify_list_ng_ran,
+        drb_to_remove_list_ng_ran: None,
+        snssai: None,
+        common_network_instance: None,
+        redundant_n_g_ul_up_tnl_information: None,
+        redundant_common_network_instance: None,
+        data_forwardingto_eutran_information_list: None,
+    })
+}
+
+pub fn build_e1_qos_flow_item(flow: &QosFlowState) -> QosFlowQosParameterItem {
+    QosFlowQosParameterItem {
+        qos_flow_identifier: QosFlowIdentifier(flow.qfi),
+        qos_flow_level_qos_parameters: QosFlowLevelQosParameters {
+            qos_characteristics: QosCharacteristics::NonDynamic5qi(NonDynamic5qiDescriptor {
+                five_qi: flow.five_qi,
+                qos_priority_level: None,
+                averaging_window: None,
+                max_data_burst_volume: None,
+                cn_packet_delay_budget_downlink: None,
+                cn_packet_delay_budget_uplink: None,
+            }),
+            ngran_allocation_retention_priority: NgranAllocationAndRetentionPriority {
+                priority_level: PriorityLevel(flow.priority_level_arp),
+                pre_emption_capability: PreEmptionCapability::MayTriggerPreEmption,
+                pre_emption_vulnerability: PreEmptionVulnerability::NotPreEmptable,
+            },
+            gbr_qos_flow_information: None,
+            reflective_qos_attribute: None,
+            additional_qos_information: None,
+            paging_policy_indicator: None,
+            reflective_qos_indicator: None,
+            qos_monitoring_request: None,
+            mcg_offered_gbr_qos_flow_info: None,
+            qos_monitoring_reporting_frequency: None,
+            qos_monitoring_disabled: None,
+        },
+        qos_flow_mapping_indication: None,
+        redundant_qos_flow_indicator: None,
+        tsc_traffic_characteristics: None,
+    }
+}
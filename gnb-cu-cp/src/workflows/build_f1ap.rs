@@ -1,77 +1,59 @@
 //! build_f1ap - construction of F1AP messages
 
 use super::GnbCuCp;
-use crate::datastore::UeState;
-use anyhow::Result;
+use crate::datastore::{
+    DrbState, PduSessionState, QosFlowState, ServedCell, Sib2Parameters, UeState,
+};
+use anyhow::{anyhow, Result};
 use asn1_per::*;
 use f1ap::*;
 use rrc::{
-    AccessStratumRelease, BandNr, FeatureSetDownlinkPerCc, FeatureSetUplinkPerCc, FeatureSets,
-    FreqBandIndicatorNr, ModulationOrder, PdcpParameters, PhyParameters, RfParameters,
-    SupportedBandwidth, SupportedRohcProfiles, UeCapabilityRatContainer, UeNrCapability,
+    AccessStratumRelease, BandNr, CellReselectionInfoCommon, CellReselectionPriority,
+    CellReselectionServingFreqInfo, FeatureSetDownlinkPerCc, FeatureSetUplinkPerCc, FeatureSets,
+    FreqBandIndicatorNr, IntraFreqCellReselectionInfo, ModulationOrder, PdcpParameters,
+    PhyParameters, QHyst, QRxLevMin, RfParameters, SupportedBandwidth, SupportedRohcProfiles,
+    UeCapabilityRatContainer, UeNrCapability,
 };
-use xxap::{GtpTunnel, PduSessionId, Snssai};
+use xxap::{GtpTunnel, PduSessionId};
 
+// Modify a session's DRB to carry its current set of QoS flows.
+pub fn build_drb_to_be_modified_item(
+    session: &PduSessionState,
+    drb: &DrbState,
+    gtp_tunnel: GtpTunnel,
+) -> Result<DrbsToBeModifiedItem> {
+    Ok(DrbsToBeModifiedItem {
+        drb_id: DrbId(drb.drb_id),
+        qos_information: Some(build_drb_qos_information(session, drb)?),
+        ul_up_tnl_information_to_be_setup_list: UlUpTnlInformationToBeSetupList(nonempty![
+            UlUpTnlInformationToBeSetupItem {
+                ul_up_tnl_information: UpTransportLayerInformation::GtpTunnel(gtp_tunnel),
+                bh_info: None,
+            },
+        ]),
+        ul_configuration: None,
+        dlpdcpsn_length: None,
+        ulpdcpsn_length: None,
+        bearer_type_change: None,
+        rlc_mode: None,
+        duplication_activation: None,
+        dc_based_duplication_configured: None,
+        dc_based_duplication_activation: None,
+        additional_pdcp_duplication_tnl_list: None,
+        rlc_duplication_information: None,
+        transmission_stop_indicator: None,
+    })
+}
+
+// Set up a session's DRB, carrying the QoS flows mapped to it.
 pub fn build_drb_to_be_setup_item(
-    drb_id: DrbId,
-    snssai: Snssai,
+    session: &PduSessionState,
+    drb: &DrbState,
     gtp_tunnel: GtpTunnel,
 ) -> Result<DrbsToBeSetupItem> {
-    // TODO - removing hardcoded values
-
     Ok(DrbsToBeSetupItem {
-        drb_id,
-        qos_information: QosInformation::DrbInformation(DrbInformation {
-            drb_qos: QosFlowLevelQosParameters {
-                qos_characteristics: QosCharacteristics::NonDynamic5qi(NonDynamic5qiDescriptor {
-                    five_qi: 9,
-                    qos_priority_level: None,
-                    averaging_window: None,
-                    max_data_burst_volume: None,
-                    cn_packet_delay_budget_downlink: None,
-                    cn_packet_delay_budget_uplink: None,
-                }),
-                ngran_allocation_retention_priority: NgranAllocationAndRetentionPriority {
-                    priority_level: PriorityLevel(14),
-                    pre_emption_capability: PreEmptionCapability::MayTriggerPreEmption,
-                    pre_emption_vulnerability: PreEmptionVulnerability::NotPreEmptable,
-                },
-                gbr_qos_flow_information: None,
-                reflective_qos_attribute: None,
-                pdu_session_id: Some(PduSessionId(1)),
-                ulpdu_session_aggregate_maximum_bit_rate: None,
-                qos_monitoring_request: None,
-            },
-            snssai: snssai.into(),
-            notification_control: None,
-            flows_mapped_to_drb_list: FlowsMappedToDrbList(nonempty![FlowsMappedToDrbItem {
-                qos_flow_identifier: QosFlowIdentifier(0),
-                qos_flow_level_qos_parameters: QosFlowLevelQosParameters {
-                    qos_characteristics: QosCharacteristics::NonDynamic5qi(
-                        NonDynamic5qiDescriptor {
-                            five_qi: 9,
-                            qos_priority_level: None,
-                            averaging_window: None,
-                            max_data_burst_volume: None,
-                            cn_packet_delay_budget_downlink: None,
-                            cn_packet_delay_budget_uplink: None,
-                        },
-                    ),
-                    ngran_allocation_retention_priority: NgranAllocationAndRetentionPriority {
-                        priority_level: PriorityLevel(14),
-                        pre_emption_capability: PreEmptionCapability::MayTriggerPreEmption,
-                        pre_emption_vulnerability: PreEmptionVulnerability::NotPreEmptable,
-                    },
-                    gbr_qos_flow_information: None,
-                    reflective_qos_attribute: None,
-                    pdu_session_id: None,
-                    ulpdu_session_aggregate_maximum_bit_rate: None,
-                    qos_monitoring_request: None,
-                },
-                qos_flow_mapping_indication: None,
-                tsc_traffic_characteristics: None,
-            }]),
-        }),
+        drb_id: DrbId(drb.drb_id),
+        qos_information: build_drb_qos_information(session, drb)?,
         ul_up_tnl_information_to_be_setup_list: UlUpTnlInformationToBeSetupList(nonempty![
             UlUpTnlInformationToBeSetupItem {
                 ul_up_tnl_information: UpTransportLayerInformation::GtpTunnel(gtp_tunnel),
@@ -90,6 +72,115 @@ pub fn build_drb_to_be_setup_item(
     })
 }
 
+fn build_drb_qos_information(session: &PduSessionState, drb: &DrbState) -> Result<QosInformation> {
+    let flows_mapped_to_drb_list = NonEmpty::from_vec(
+        session
+            .drb_qos_flows(drb)
+            .map(build_flows_mapped_to_drb_item)
+            .collect(),
+    )
+    .ok_or_else(|| anyhow!("DRB {} has no QoS flows", drb.drb_id))?;
+
+    // The DRB takes the QoS parameters of its first QoS flow.
+    let drb_qos = QosFlowLevelQosParameters {
+        pdu_session_id: Some(PduSessionId(session.pdu_session_id)),
+        ..flows_mapped_to_drb_list
+            .head
+            .qos_flow_level_qos_parameters
+            .clone()
+    };
+
+    Ok(QosInformation::DrbInformation(DrbInformation {
+        drb_qos,
+        snssai: session.snssai().into(),
+        notification_control: None,
+        flows_mapped_to_drb_list: FlowsMappedToDrbList(flows_mapped_to_drb_list),
+    }))
+}
+
+fn build_flows_mapped_to_drb_item(flow: &QosFlowState) -> FlowsMappedToDrbItem {
+    FlowsMappedToDrbItem {
+        qos_flow_identifier: QosFlowIdentifier(flow.qfi),
+        qos_flow_level_qos_parameters: QosFlowLevelQosParameters {
+            qos_characteristics: QosCharacteristics::NonDynamic5qi(NonDynamic5qiDescriptor {
+                five_qi: flow.five_qi,
+                qos_priority_level: None,
+                averaging_window: None,
+                max_data_burst_volume: None,
+                cn_packet_delay_budget_downlink: None,
+                cn_packet_delay_budget_uplink: None,
+            }),
+            ngran_allocation_retention_priority: NgranAllocationAndRetentionPriority {
+                priority_level: PriorityLevel(flow.priority_level_arp),
+                pre_emption_capability: PreEmptionCapability::MayTriggerPreEmption,
+                pre_emption_vulnerability: PreEmptionVulnerability::NotPreEmptable,
+            },
+            gbr_qos_flow_information: None,
+            reflective_qos_attribute: None,
+            pdu_session_id: None,
+            ulpdu_session_aggregate_maximum_bit_rate: None,
+            qos_monitoring_request: None,
+        },
+        qos_flow_mapping_indication: None,
+        tsc_traffic_characteristics: None,
+    }
+}
+
+pub fn build_ue_context_modification_request(
+    ue: &UeState,
+    drbs_to_be_modified_list: Option<DrbsToBeModifiedList>,
+) -> UeContextModificationRequest {
+    UeContextModificationRequest {
+        gnb_cu_ue_f1ap_id: GnbCuUeF1apId(ue.key),
+        gnb_du_ue_f1ap_id: ue.gnb_du_ue_f1ap_id,
+        sp_cell_id: None,
+        serv_cell_index: None,
+        sp_cell_ul_configured: None,
+        drx_cycle: None,
+        cu_to_du_rrc_information: None,
+        transmission_action_indicator: None,
+        resource_coordination_transfer_container: None,
+        rrc_reconfiguration_complete_indicator: None,
+        rrc_container: None,
+        s_cell_to_be_setup_mod_list: None,
+        s_cell_to_be_removed_list: None,
+        srbs_to_be_setup_mod_list: None,
+        drbs_to_be_setup_mod_list: None,
+        drbs_to_be_modified_list,
+        srbs_to_be_released_list: None,
+        drbs_to_be_released_list: None,
+        inactivity_monitoring_request: None,
+        rat_frequency_priority_information: None,
+        drx_configuration_indicator: None,
+        rlc_failure_indication: None,
+        uplink_tx_direct_current_list_information: None,
+        gnb_du_configuration_query: None,
+        gnb_du_ue_ambr_ul: None,
+        execute_duplication: None,
+        rrc_delivery_status_request: None,
+        resource_coordination_transfer_information: None,
+        serving_cell_mo: None,
+        needfor_gap: None,
+        full_configuration: None,
+        additional_rrm_priority_index: None,
+        lower_layer_presence_status_change: None,
+        bh_channels_to_be_setup_mod_list: None,
+        bh_channels_to_be_modified_list: None,
+        bh_channels_to_be_released_list: None,
+        nr_v2x_services_authorized: None,
+        ltev2x_services_authorized: None,
+        nr_ue_sidelink_aggregate_maximum_bitrate: None,
+        lte_ue_sidelink_aggregate_maximum_bitrate: None,
+        pc5_link_ambr: None,
+        sl_drbs_to_be_setup_mod_list: None,
+        sl_drbs_to_be_modified_list: None,
+        sl_drbs_to_be_released_list: None,
+        conditional_intra_du_mobility_information: None,
+        f1c_transfer_path: None,
+        scg_indicator: None,
+    }
+}
+
 fn build_scell_to_be_setup_item(nr_cgi: NrCgi) -> SCellToBeSetupItem {
     SCellToBeSetupItem {
         s_cell_id: nr_cgi,
@@ -305,3 +396,72 @@ pub fn build_ue_context_setup_request<G: GnbCuCp>(
         f1c_transfer_path: None,
     })
 }
+
+// Activate the given cells served by the DU, giving each the system information that the CU is responsible for.
+pub fn build_cells_to_be_activated_list<'a>(
+    cells: impl Iterator<Item = &'a ServedCell>,
+) -> Result<Option<CellsToBeActivatedList>> {
+    let items = cells
+        .map(build_cells_to_be_activated_item)
+        .collect::<Result<Vec<_>>>()?;
+    Ok(NonEmpty::from_vec(items).map(CellsToBeActivatedList))
+}
+
+fn build_cells_to_be_activated_item(cell: &ServedCell) -> Result<CellsToBeActivatedListItem> {
+    Ok(CellsToBeActivatedListItem {
+        nr_cgi: cell.nr_cgi.clone(),
+        nr_pci: Some(cell.nr_pci),
+        gnb_cu_system_information: Some(GnbCuSystemInformation {
+            sib_type_to_be_updated_list: nonempty![SibTypeToBeUpdatedListItem {
+                sib_type: 2,
+                sib_message: build_sib2(&cell.sib2)?.into_bytes()?,
+                value_tag: cell.sib2_value_tag,
+                area_scope: None
+            }],
+            system_information_area_id: None,
+        }),
+        available_plmn_list: None,
+        extended_available_plmn_list: None,
+        iab_info_iab_donor_cu: None,
+        available_snpn_id_list: None,
+    })
+}
+
+pub fn build_sib2(sib2: &Sib2Parameters) -> Result<rrc::Sib2> {
+    let q_hyst = Sib2Parameters::Q_HYST_VALUES
+        .iter()
+        .position(|x| *x == sib2.q_hyst)
+        .ok_or_else(|| anyhow!("Bad q-Hyst {}", sib2.q_hyst))?;
+    Ok(rrc::Sib2 {
+        cell_reselection_info_common: CellReselectionInfoCommon {
+            nrof_ss_blocks_to_average: None,
+            abs_thresh_ss_blocks_consolidation: None,
+            range_to_best_cell: None,
+            q_hyst: QHyst::try_from(q_hyst as u8).map_err(|_| anyhow!("Bad q-Hyst"))?,
+            speed_state_reselection_pars: None,
+        },
+        cell_reselection_serving_freq_info: CellReselectionServingFreqInfo {
+            s_non_intra_search_p: None,
+            s_non_intra_search_q: None,
+            thresh_serving_low_p: rrc::ReselectionThreshold(sib2.thresh_serving_low_p),
+            thresh_serving_low_q: None,
+            cell_reselection_priority: CellReselectionPriority(sib2.cell_reselection_priority),
+            cell_reselection_sub_priority: None,
+        },
+        intra_freq_cell_reselection_info: IntraFreqCellReselectionInfo {
+            q_rx_lev_min: QRxLevMin(sib2.q_rx_lev_min),
+            q_rx_lev_min_sul: None,
+            q_qual_min: None,
+            s_intra_search_p: rrc::ReselectionThreshold(sib2.s_intra_search_p),
+            s_intra_search_q: None,
+            t_reselection_nr: rrc::TReselection(sib2.t_reselection_nr),
+            frequency_band_list: None,
+            frequency_band_list_sul: None,
+            p_max: None,
+            smtc: None,
+            ss_rssi_measurement: None,
+            ssb_to_measure: None,
+            derive_ssb_index_from_cell: true,
+        },
+    })
+}
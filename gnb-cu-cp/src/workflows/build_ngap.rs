@@ -4,16 +4,19 @@ use crate::gnb_cu_cp::GnbCuCp;
 use anyhow::Result;
 use asn1_per::*;
 use ngap::{
-    AssociatedQosFlowItem, AssociatedQosFlowList, GlobalGnbId, GlobalRanNodeId, GnbId,
-    PduSessionResourceSetupItemSuRes, PduSessionResourceSetupResponseTransfer, PlmnIdentity,
-    QosFlowPerTnlInformation, UpTransportLayerInformation,
+    AssociatedQosFlowItem, AssociatedQosFlowList, Cause, GlobalGnbId, GlobalRanNodeId, GnbId,
+    PduSessionResourceFailedToModifyItemModRes, PduSessionResourceModifyItemModRes,
+    PduSessionResourceModifyResponseTransfer, PduSessionResourceModifyUnsuccessfulTransfer,
+    PduSessionResourceSetupItemSuRes, PduSessionResourceSetupResponseTransfer,
+    PduSessionResourceSetupUnsuccessfulTransfer, PlmnIdentity, QosFlowAddOrModifyResponseItem,
+    QosFlowAddOrModifyResponseList, QosFlowPerTnlInformation, UpTransportLayerInformation,
 };
 use xxap::{GtpTunnel, PduSessionId};
 
 pub fn build_global_ran_node_id<T: GnbCuCp>(gnb_cu_cp: &T) -> GlobalRanNodeId {
     GlobalRanNodeId::GlobalGnbId(GlobalGnbId {
-        plmn_identity: PlmnIdentity(gnb_cu_cp.config().plmn),
-        gnb_id: GnbId::GnbId(bitvec![u8,Msb0; 1; 22]),
+        plmn_identity: PlmnIdentity(gnb_cu_cp.config().ran.plmn),
+        gnb_id: GnbId::GnbId(gnb_cu_cp.config().ran.gnb_id_bits()),
     })
 }
 
@@ -43,3 +46,61 @@ pub fn build_pdu_session_resource_setup_item_su_res(
         .into_bytes()?,
     })
 }
+
+// The transfer is the same whether the session failed during an Initial Context Setup or a PDU Session
+// Resource Setup.
+pub fn build_pdu_session_resource_setup_unsuccessful_transfer(cause: Cause) -> Result<Vec<u8>> {
+    Ok(PduSessionResourceSetupUnsuccessfulTransfer {
+        cause,
+        criticality_diagnostics: None,
+    }
+    .into_bytes()?)
+}
+
+pub fn build_pdu_session_resource_modify_item_mod_res(
+    pdu_session_id: PduSessionId,
+    qfis_added_or_modified: Vec<u8>,
+) -> Result<PduSessionResourceModifyItemModRes> {
+    let qos_flow_add_or_modify_response_list = NonEmpty::from_vec(
+        qfis_added_or_modified
+            .into_iter()
+            .map(|qfi| QosFlowAddOrModifyResponseItem {
+                qos_flow_identifier: ngap::QosFlowIdentifier(qfi),
+                current_qos_para_set_index: None,
+            })
+            .collect(),
+    )
+    .map(QosFlowAddOrModifyResponseList);
+
+    Ok(PduSessionResourceModifyItemModRes {
+        pdu_session_id,
+        pdu_session_resource_modify_response_transfer: PduSessionResourceModifyResponseTransfer {
+            dl_ngu_up_tnl_information: None,
+            ul_ngu_up_tnl_information: None,
+            qos_flow_add_or_modify_response_list,
+            additional_dl_qos_flow_per_tnl_information: None,
+            qos_flow_failed_to_add_or_modify_list: None,
+            additional_ngu_up_tnl_information: None,
+            redundant_dl_ngu_up_tnl_information: None,
+            redundant_ul_ngu_up_tnl_information: None,
+            additional_redundant_dl_qos_flow_per_tnl_information: None,
+            additional_redundant_ngu_up_tnl_information: None,
+        }
+        .into_bytes()?,
+    })
+}
+
+pub fn build_pdu_session_resource_failed_to_modify_item_mod_res(
+    pdu_session_id: PduSessionId,
+    cause: Cause,
+) -> Result<PduSessionResourceFailedToModifyItemModRes> {
+    Ok(PduSessionResourceFailedToModifyItemModRes {
+        pdu_session_id,
+        pdu_session_resource_modify_unsuccessful_transfer:
+            PduSessionResourceModifyUnsuccessfulTransfer {
+                cause,
+                criticality_diagnostics: None,
+            }
+            .into_bytes()?,
+    })
+}
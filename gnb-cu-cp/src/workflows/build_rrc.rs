@@ -1,5 +1,6 @@
 //! build_rrc - construction of RRC messages
 
+use crate::datastore::{DrbState, PduSessionState};
 use anyhow::Result;
 use asn1_per::{nonempty, NonEmpty};
 use net::*;
@@ -35,6 +36,22 @@ pub fn build_rrc_setup(rrc_transaction_identifier: u8) -> Result<f1ap::RrcContai
     Ok(f1ap::RrcContainer(message_bytes))
 }
 
+// TS 38.331, 5.3.15 - the UE waits for wait_time seconds, from 1 to 16, before trying again.
+pub fn build_rrc_reject(wait_time: u8) -> Result<f1ap::RrcContainer> {
+    let message_bytes = DlCcchMessage {
+        message: DlCcchMessageType::C1(C1_1::RrcReject(RrcReject {
+            critical_extensions: CriticalExtensions17::RrcReject(RrcRejectIEs {
+                wait_time: Some(RejectWaitTime(wait_time)),
+                late_non_critical_extension: None,
+            }),
+        })),
+    }
+    .into_bytes()?;
+
+    // Like Rrc Setup, this is a DL-CCCH-Message, so is not encoded in a PDCP PDU.
+    Ok(f1ap::RrcContainer(message_bytes))
+}
+
 pub fn build_rrc_security_mode_command(
     rrc_transaction_identifier: u8,
 ) -> Result<f1ap::RrcContainer> {
@@ -76,15 +93,78 @@ pub fn build_rrc_dl_information_transfer(
     })
 }
 
+pub fn build_rrc_release(
+    rrc_transaction_identifier: u8,
+    suspend_config: Option<SuspendConfig>,
+) -> Result<f1ap::RrcContainer> {
+    make_pdcp_encapsulated_rrc_container(DlDcchMessage {
+        message: DlDcchMessageType::C1(C1_2::RrcRelease(rrc::RrcRelease {
+            rrc_transaction_identifier: RrcTransactionIdentifier(rrc_transaction_identifier),
+            critical_extensions: CriticalExtensions18::RrcRelease(RrcReleaseIEs {
+                redirected_carrier_info: None,
+                cell_reselection_priorities: None,
+                suspend_config,
+                deprioritisation_req: None,
+                late_non_critical_extension: None,
+                non_critical_extension: None,
+            }),
+        })),
+    })
+}
+
+// The UE restores SRB2 and its DRBs from its stored configuration.  We also give it their configuration,
+// with the QoS flows mapped to each DRB.  See TS 38.331, 5.3.13.4.
+pub fn build_rrc_resume(
+    rrc_transaction_identifier: u8,
+    pdu_sessions: &[PduSessionState],
+) -> Result<f1ap::RrcContainer> {
+    let drbs = NonEmpty::from_vec(
+        pdu_sessions
+            .iter()
+            .flat_map(|session| {
+                session
+                    .drbs
+                    .iter()
+                    .map(|drb| build_drb_qos_flow_mapping(session, drb, drb.qfis.clone(), vec![]))
+            })
+            .collect(),
+    );
+
+    make_pdcp_encapsulated_rrc_container(DlDcchMessage {
+        message: DlDcchMessageType::C1(C1_2::RrcResume(rrc::RrcResume {
+            rrc_transaction_identifier: RrcTransactionIdentifier(rrc_transaction_identifier),
+            critical_extensions: CriticalExtensions19::RrcResume(RrcResumeIEs {
+                radio_bearer_config: Some(RadioBearerConfig {
+                    srb_to_add_mod_list: Some(SrbToAddModList(nonempty![SrbToAddMod {
+                        srb_identity: SrbIdentity(2),
+                        reestablish_pdcp: None,
+                        discard_on_pdcp: None,
+                        pdcp_config: None,
+                    }])),
+                    srb_3_to_release: None,
+                    drb_to_add_mod_list: drbs.map(DrbToAddModList),
+                    drb_to_release_list: None,
+                    security_config: None,
+                }),
+                master_cell_group: None,
+                meas_config: None,
+                full_config: None,
+                late_non_critical_extension: None,
+                non_critical_extension: None,
+            }),
+        })),
+    })
+}
+
+// Add new DRBs, and give the UE the cell group config from the DU.
 pub fn build_rrc_reconfiguration(
     rrc_transaction_identifier: u8,
     nas_messages: Option<NonEmpty<Vec<u8>>>,
+    drbs: NonEmpty<DrbToAddMod>,
     cell_group_config: Vec<u8>,
 ) -> Result<f1ap::RrcContainer> {
     let dedicated_nas_message_list = nas_messages.map(|x| (x.map(DedicatedNasMessage)));
 
-    // TODO - lots of hardcoding here
-
     make_pdcp_encapsulated_rrc_container(DlDcchMessage {
         message: DlDcchMessageType::C1(C1_2::RrcReconfiguration(rrc::RrcReconfiguration {
             rrc_transaction_identifier: RrcTransactionIdentifier(rrc_transaction_identifier),
@@ -92,32 +172,7 @@ pub fn build_rrc_reconfiguration(
                 radio_bearer_config: Some(RadioBearerConfig {
                     srb_to_add_mod_list: None,
                     srb_3_to_release: None,
-                    drb_to_add_mod_list: Some(DrbToAddModList(nonempty![DrbToAddMod {
-                        cn_association: Some(CnAssociation::SdapConfig(SdapConfig {
-                            pdu_session: PduSessionId(1),
-                            sdap_header_dl: SdapHeaderDl::Present,
-                            sdap_header_ul: SdapHeaderUl::Present,
-                            default_drb: true,
-                            mapped_qos_flows_to_add: None,
-                            mapped_qos_flows_to_release: None
-                        })),
-                        drb_identity: DrbIdentity(1),
-                        reestablish_pdcp: None,
-                        recover_pdcp: None,
-                        pdcp_config: Some(PdcpConfig {
-                            drb: Some(Drb {
-                                discard_timer: Some(DiscardTimer::Ms10),
-                                pdcp_sn_size_ul: Some(PdcpSnSizeUl::Len12bits),
-                                pdcp_sn_size_dl: Some(PdcpSnSizeDl::Len12bits),
-                                header_compression: HeaderCompression::NotUsed,
-                                integrity_protection: None,
-                                status_report_required: None,
-                                out_of_order_delivery: None
-                            }),
-                            more_than_one_rlc: None,
-                            t_reordering: None
-                        })
-                    }])),
+                    drb_to_add_mod_list: Some(DrbToAddModList(drbs)),
                     drb_to_release_list: None,
                     security_config: None,
                 }),
@@ -138,3 +193,113 @@ pub fn build_rrc_reconfiguration(
         })),
     })
 }
+
+// Reconfigure existing DRBs, for example to change the QoS flows mapped to them.
+pub fn build_rrc_reconfiguration_drbs(
+    rrc_transaction_identifier: u8,
+    nas_messages: Option<NonEmpty<Vec<u8>>>,
+    drbs: Option<NonEmpty<DrbToAddMod>>,
+) -> Result<f1ap::RrcContainer> {
+    let dedicated_nas_message_list = nas_messages.map(|x| (x.map(DedicatedNasMessage)));
+
+    make_pdcp_encapsulated_rrc_container(DlDcchMessage {
+        message: DlDcchMessageType::C1(C1_2::RrcReconfiguration(rrc::RrcReconfiguration {
+            rrc_transaction_identifier: RrcTransactionIdentifier(rrc_transaction_identifier),
+            critical_extensions: CriticalExtensions15::RrcReconfiguration(RrcReconfigurationIEs {
+                radio_bearer_config: drbs.map(|x| RadioBearerConfig {
+                    srb_to_add_mod_list: None,
+                    srb_3_to_release: None,
+                    drb_to_add_mod_list: Some(DrbToAddModList(x)),
+                    drb_to_release_list: None,
+                    security_config: None,
+                }),
+                secondary_cell_group: None,
+                meas_config: None,
+                late_non_critical_extension: None,
+                non_critical_extension: Some(RrcReconfigurationV1530IEs {
+                    master_cell_group: None,
+                    full_config: None,
+                    dedicated_nas_message_list,
+                    master_key_update: None,
+                    dedicated_sib_1_delivery: None,
+                    dedicated_system_information_delivery: None,
+                    other_config: None,
+                    non_critical_extension: None,
+                }),
+            }),
+        })),
+    })
+}
+
+// Give the UE the cell group config from the DU, for example when the DU has set up the UE afresh.
+pub fn build_rrc_reconfiguration_cell_group(
+    rrc_transaction_identifier: u8,
+    cell_group_config: Vec<u8>,
+) -> Result<f1ap::RrcContainer> {
+    make_pdcp_encapsulated_rrc_container(DlDcchMessage {
+        message: DlDcchMessageType::C1(C1_2::RrcReconfiguration(rrc::RrcReconfiguration {
+            rrc_transaction_identifier: RrcTransactionIdentifier(rrc_transaction_identifier),
+            critical_extensions: CriticalExtensions15::RrcReconfiguration(RrcReconfigurationIEs {
+                radio_bearer_config: None,
+                secondary_cell_group: None,
+                meas_config: None,
+                late_non_critical_extension: None,
+                non_critical_extension: Some(RrcReconfigurationV1530IEs {
+                    master_cell_group: Some(cell_group_config),
+                    full_config: None,
+                    dedicated_nas_message_list: None,
+                    master_key_update: None,
+                    dedicated_sib_1_delivery: None,
+                    dedicated_system_information_delivery: None,
+                    other_config: None,
+                    non_critical_extension: None,
+                }),
+            }),
+        })),
+    })
+}
+
+// Add a new DRB of a session, with the QoS flows mapped to it.
+pub fn build_drb_to_add(session: &PduSessionState, drb: &DrbState) -> DrbToAddMod {
+    DrbToAddMod {
+        pdcp_config: Some(PdcpConfig {
+            drb: Some(Drb {
+                discard_timer: Some(DiscardTimer::Ms10),
+                pdcp_sn_size_ul: Some(PdcpSnSizeUl::Len12bits),
+                pdcp_sn_size_dl: Some(PdcpSnSizeDl::Len12bits),
+                header_compression: HeaderCompression::NotUsed,
+                integrity_protection: None,
+                status_report_required: None,
+                out_of_order_delivery: None,
+            }),
+            more_than_one_rlc: None,
+            t_reordering: None,
+        }),
+        ..build_drb_qos_flow_mapping(session, drb, drb.qfis.clone(), vec![])
+    }
+}
+
+// Change the QoS flows that the UE's SDAP maps to a session's DRB.
+pub fn build_drb_qos_flow_mapping(
+    session: &PduSessionState,
+    drb: &DrbState,
+    qfis_to_add: Vec<u8>,
+    qfis_to_release: Vec<u8>,
+) -> DrbToAddMod {
+    DrbToAddMod {
+        cn_association: Some(CnAssociation::SdapConfig(SdapConfig {
+            pdu_session: PduSessionId(session.pdu_session_id),
+            sdap_header_dl: SdapHeaderDl::Present,
+            sdap_header_ul: SdapHeaderUl::Present,
+            default_drb: session.is_default_drb(drb.drb_id),
+            mapped_qos_flows_to_add: NonEmpty::from_vec(qfis_to_add.into_iter().map(Qfi).collect()),
+            mapped_qos_flows_to_release: NonEmpty::from_vec(
+                qfis_to_release.into_iter().map(Qfi).collect(),
+            ),
+        })),
+        drb_identity: DrbIdentity(drb.drb_id),
+        reestablish_pdcp: None,
+        recover_pdcp: None,
+        pdcp_config: None,
+    }
+}
@@ -1,8 +1,9 @@
 //! e1_setup - the initial handshake that establishes an instance of the E1 reference point between GNB-CU and GNB-DU
 
 use super::Workflow;
+use crate::datastore::{CuUpState, CuUpSupportedPlmn};
 use crate::gnb_cu_cp::GnbCuCp;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use e1ap::*;
 use net::{RequestError, ResponseAction};
 use slog::info;
@@ -10,8 +11,12 @@ use slog::info;
 impl<'a, G: GnbCuCp> Workflow<'a, G> {
     // E1 Setup Procedure
     // 1.    E1ap GnbCuUpE1SetupRequest >>
-    // 2.    E1ap GnbCuUpE1SetupResponse <<
+    // 2.    Check that the CU-UP supports 5GC and at least one of our PLMNs and slices
+    // 3.    Store the CU-UP's capabilities
+    // 4.    E1ap GnbCuUpE1SetupResponse <<
     // Then update coordinator as a follow-on task.
+    //
+    // The stored capabilities are used to choose a CU-UP for each UE's PDU sessions.  See TS 38.463, 8.2.3.
     pub async fn e1_setup(
         &self,
         r: GnbCuUpE1SetupRequest,
@@ -22,6 +27,17 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
             "E1AP interface initialized with {:?}", r.gnb_cu_up_id
         );
 
+        if let Err(cause) = self.check_cu_up_support(&r) {
+            return Err(self.e1_setup_failure(r.transaction_id, cause));
+        }
+
+        if let Err(e) = self.store_cu_up_state(&r).await {
+            self.log_message_error(&format!("Failed to store CU-UP state - {e}"));
+            return Err(
+                self.e1_setup_failure(r.transaction_id, Cause::Misc(CauseMisc::Unspecified))
+            );
+        }
+
         // Associate this TNLA with the E1AP interface instance.
         let coordinator_notify = self.associate_connection();
 
@@ -36,4 +52,64 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
             Some(coordinator_notify),
         ))
     }
+
+    // E1AP has no cause for a core network or PLMN that the CU-CP does not serve, so these get an unspecified
+    // one.  A CU-UP that supports one of our PLMNs, but none of its slices, is told that there are no resources
+    // for the slice.
+    fn check_cu_up_support(&self, r: &GnbCuUpE1SetupRequest) -> Result<(), Cause> {
+        if let CnSupport::CEpc = r.cn_support {
+            self.log_message_error("CU-UP does not support 5GC");
+            return Err(Cause::Misc(CauseMisc::Unspecified));
+        }
+
+        let supported_plmns: Vec<CuUpSupportedPlmn> =
+            r.supported_plmns.0.iter().map(|x| x.into()).collect();
+        let plmn_slices = self.config().ran.plmn_slices();
+        let common_plmns: Vec<_> = plmn_slices
+            .iter()
+            .filter_map(|(plmn, slices)| {
+                supported_plmns
+                    .iter()
+                    .find(|x| x.plmn == *plmn)
+                    .map(|x| (x, slices))
+            })
+            .collect();
+        if common_plmns.is_empty() {
+            self.log_message_error("CU-UP supports none of our PLMNs");
+            return Err(Cause::RadioNetwork(CauseRadioNetwork::Unspecified));
+        }
+        if !common_plmns.iter().any(|(supported, slices)| {
+            supported.slices.is_empty() || slices.iter().any(|x| supported.slices.contains(x))
+        }) {
+            self.log_message_error("CU-UP supports none of our slices");
+            return Err(Cause::RadioNetwork(
+                CauseRadioNetwork::ResourcesNotAvailableForTheSlice,
+            ));
+        }
+        Ok(())
+    }
+
+    // The CU-UP's address is that of the TNLA that the setup request arrived on.  A CU-UP that sets up again
+    // replaces its old state.
+    async fn store_cu_up_state(&self, r: &GnbCuUpE1SetupRequest) -> Result<()> {
+        let Some(address) = self.e1ap_request_remote_ip().await else {
+            bail!("Unknown CU-UP address")
+        };
+        self.store_cu_up(CuUpState::new(r, address.to_string()))
+            .await
+    }
+
+    fn e1_setup_failure(
+        &self,
+        transaction_id: TransactionId,
+        cause: Cause,
+    ) -> RequestError<GnbCuUpE1SetupFailure> {
+        self.log_message("<< GnbCuUpE1SetupFailure");
+        RequestError::UnsuccessfulOutcome(GnbCuUpE1SetupFailure {
+            transaction_id,
+            cause,
+            time_to_wait: None,
+            criticality_diagnostics: None,
+        })
+    }
 }
@@ -0,0 +1,79 @@
+//! f1_removal - the DU removes the F1 interface, or loses its TNLAs to the CU
+
+use super::Workflow;
+use crate::gnb_cu_cp::GnbCuCp;
+use anyhow::{bail, Result};
+use f1ap::*;
+use net::{RequestError, ResponseAction};
+use slog::info;
+
+impl<'a, G: GnbCuCp> Workflow<'a, G> {
+    // F1 Removal procedure
+    // 1.    F1ap F1RemovalRequest >>
+    // 2.    Delete the DU state
+    // 3.    F1ap F1RemovalResponse <<
+    //
+    // The DU then takes down its TNLAs.  The request has no gNB-DU ID, so the DU is the one whose address is that
+    // of the TNLA that the request arrived on.  See TS 38.473, 8.2.8.
+    pub async fn f1_removal(
+        &self,
+        r: F1RemovalRequest,
+    ) -> Result<ResponseAction<F1RemovalResponse>, RequestError<F1RemovalFailure>> {
+        self.log_message(">> F1RemovalRequest");
+
+        if let Err(e) = self.remove_du_at_request_address().await {
+            self.log_message_error(&format!("Failed to remove DU - {e}"));
+            return Err(RequestError::UnsuccessfulOutcome(F1RemovalFailure {
+                transaction_id: r.transaction_id,
+                cause: Cause::Misc(CauseMisc::Unspecified),
+                criticality_diagnostics: None,
+            }));
+        }
+
+        self.log_message("<< F1RemovalResponse");
+        Ok((
+            F1RemovalResponse {
+                transaction_id: r.transaction_id,
+                criticality_diagnostics: None,
+            },
+            None,
+        ))
+    }
+
+    async fn remove_du_at_request_address(&self) -> Result<()> {
+        let Some(address) = self.f1ap_request_remote_ip().await else {
+            bail!("Unknown DU address")
+        };
+        self.remove_du(&address.to_string()).await
+    }
+
+    // Record that this worker has F1AP TNLAs to the DU with the given address.
+    pub async fn du_connected(&self, address: &str) -> Result<()> {
+        self.add_du_connection(address, self.worker_id()).await
+    }
+
+    // This worker has lost its last F1AP TNLA to the DU with the given address.  A DU has TNLAs to every
+    // worker, so it has only gone away once no worker is connected to it.  A worker that is shutting down
+    // never deletes the DU, since that is not the DU's doing.
+    pub async fn du_disconnected(&self, address: &str) -> Result<()> {
+        let remaining = self.remove_du_connection(address, self.worker_id()).await?;
+        if remaining > 0 || self.is_draining() {
+            return Ok(());
+        }
+        self.remove_du(address).await
+    }
+
+    // Delete the state of the DU with the given address, so that no worker admits UEs in its cells.
+    async fn remove_du(&self, address: &str) -> Result<()> {
+        for du in self.retrieve_all_dus().await? {
+            if du.address == address {
+                info!(
+                    self.logger,
+                    "Delete state of DU {} at {}", du.gnb_du_id.0, address
+                );
+                self.delete_du(&du.gnb_du_id).await?;
+            }
+        }
+        Ok(())
+    }
+}
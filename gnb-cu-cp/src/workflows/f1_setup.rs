@@ -1,16 +1,12 @@
 //! f1_setup - the initial handshake that establishes an instance of the F1 reference point between GNB-CU and GNB-DU
 
-use super::Workflow;
+use super::{build_f1ap, Workflow};
+use crate::datastore::{DuState, ServedCell};
 use crate::gnb_cu_cp::GnbCuCp;
 use anyhow::Result;
-use asn1_per::{nonempty, SerDes};
 use bitvec::prelude::*;
 use f1ap::*;
 use net::{RequestError, ResponseAction};
-use rrc::{
-    CellReselectionInfoCommon, CellReselectionPriority, CellReselectionServingFreqInfo,
-    IntraFreqCellReselectionInfo, QHyst, QRxLevMin,
-};
 use slog::info;
 
 impl<'a, G: GnbCuCp> Workflow<'a, G> {
@@ -29,12 +25,44 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
 
         let coordinator_notify = self.associate_connection();
 
-        // Activate all served cells in the setup response.
-        // TODO: store information about served cells for use later.
-        let sib2 = build_sib2().into_bytes()?;
-        let cells_to_be_activated_list = r.gnb_du_served_cells_list.map(|cells| {
-            CellsToBeActivatedList(cells.0.map(|x| served_cell_to_activated(x, sib2.clone())))
-        });
+        let setup_failure = || {
+            RequestError::UnsuccessfulOutcome(F1SetupFailure {
+                transaction_id: r.transaction_id,
+                cause: Cause::Misc(CauseMisc::Unspecified),
+                time_to_wait: None,
+                criticality_diagnostics: None,
+            })
+        };
+
+        // Store the DU's served cells, so that every worker can look them up.  The DU's address is that of the TNLA
+        // that the setup request arrived on, which is how the worker with the DU's TNLA sends to it later.
+        let Some(address) = self.f1ap_request_remote_ip().await else {
+            self.log_message_error("Unknown DU address");
+            return Err(setup_failure());
+        };
+        let mut du_state = DuState::new(r.gnb_du_id, address.to_string());
+        du_state.served_cells = r
+            .gnb_du_served_cells_list
+            .iter()
+            .flat_map(|cells| cells.0.iter())
+            .map(|x| ServedCell::from(&x.served_cell_information))
+            .collect();
+
+        // Activate all served cells in the setup response.  A DU that sets up again starts afresh, with all its
+        // cells active and unbarred, and broadcasting the default system information.
+        let cells_to_be_activated_list =
+            match build_f1ap::build_cells_to_be_activated_list(du_state.served_cells.iter()) {
+                Ok(x) => x,
+                Err(e) => {
+                    self.log_message_error(&format!("Failed to build cells to be activated - {e}"));
+                    None
+                }
+            };
+
+        if let Err(e) = self.store_du(du_state).await {
+            self.log_message_error(&format!("Failed to store DU state - {e}"));
+            return Err(setup_failure());
+        }
 
         self.log_message("<< F1SetupResponse");
         Ok((
@@ -56,64 +84,3 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
         ))
     }
 }
-
-fn build_sib2() -> rrc::Sib2 {
-    rrc::Sib2 {
-        cell_reselection_info_common: CellReselectionInfoCommon {
-            nrof_ss_blocks_to_average: None,
-            abs_thresh_ss_blocks_consolidation: None,
-            range_to_best_cell: None,
-            q_hyst: QHyst::Db1,
-            speed_state_reselection_pars: None,
-        },
-        cell_reselection_serving_freq_info: CellReselectionServingFreqInfo {
-            s_non_intra_search_p: None,
-            s_non_intra_search_q: None,
-            thresh_serving_low_p: rrc::ReselectionThreshold(2),
-            thresh_serving_low_q: None,
-            cell_reselection_priority: CellReselectionPriority(2),
-            cell_reselection_sub_priority: None,
-        },
-        intra_freq_cell_reselection_info: IntraFreqCellReselectionInfo {
-            q_rx_lev_min: QRxLevMin(-50),
-            q_rx_lev_min_sul: None,
-            q_qual_min: None,
-            s_intra_search_p: rrc::ReselectionThreshold(2),
-            s_intra_search_q: None,
-            t_reselection_nr: rrc::TReselection(2),
-            frequency_band_list: None,
-            frequency_band_list_sul: None,
-            p_max: None,
-            smtc: None,
-            ss_rssi_measurement: None,
-            ssb_to_measure: None,
-            derive_ssb_index_from_cell: true,
-        },
-    }
-}
-
-fn served_cell_to_activated(
-    served_cell: GnbDuServedCellsItem,
-    sib_2: Vec<u8>,
-) -> CellsToBeActivatedListItem {
-    let served_cell_information = &served_cell.served_cell_information;
-    let nr_pci = Some(served_cell_information.nr_pci);
-
-    CellsToBeActivatedListItem {
-        nr_cgi: served_cell_information.nr_cgi.clone(),
-        nr_pci,
-        gnb_cu_system_information: Some(GnbCuSystemInformation {
-            sib_type_to_be_updated_list: nonempty![SibTypeToBeUpdatedListItem {
-                sib_type: 2,
-                sib_message: sib_2,
-                value_tag: 0,
-                area_scope: None
-            }],
-            system_information_area_id: None,
-        }),
-        available_plmn_list: None,
-        extended_available_plmn_list: None,
-        iab_info_iab_donor_cu: None,
-        available_snpn_id_list: None,
-    }
-}
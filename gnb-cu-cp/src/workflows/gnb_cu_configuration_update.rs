@@ -1,13 +1,26 @@
-//! add_e1ap_endpoint - Adds an E1AP endpoint
+//! gnb_cu_configuration_update - the CU adds a TNLA to the DU, or changes the state of a DU cell
 
-use super::Workflow;
+use super::{build_f1ap, Workflow};
+use crate::datastore::{DuState, Sib2Parameters};
 use crate::gnb_cu_cp::GnbCuCp;
-use anyhow::Result;
+use anyhow::{anyhow, bail, ensure, Result};
 use asn1_per::*;
 use f1ap::{
-    CpTransportLayerAddress, GnbCuConfigurationUpdate, GnbCuConfigurationUpdateProcedure,
-    GnbCuTnlAssociationToAddItem, GnbCuTnlAssociationToAddList, TnlAssociationUsage, TransactionId,
+    CellBarred, CellsToBeBarredItem, CellsToBeBarredList, CellsToBeDeactivatedList,
+    CellsToBeDeactivatedListItem, CpTransportLayerAddress, GnbCuConfigurationUpdate,
+    GnbCuConfigurationUpdateProcedure, GnbCuTnlAssociationToAddItem, GnbCuTnlAssociationToAddList,
+    NrCgi, TnlAssociationUsage, TransactionId,
 };
+use rand::Rng;
+
+/// An operator's change to one of the cells served by a DU.  A setting that is None is left as it is.
+#[derive(Debug)]
+pub struct CellConfiguration {
+    pub nr_cgi: NrCgi,
+    pub active: Option<bool>,
+    pub barred: Option<bool>,
+    pub sib2: Option<Sib2Parameters>,
+}
 
 impl<'a, G: GnbCuCp> Workflow<'a, G> {
     pub async fn gnb_cu_configuration_update(&self, f1ap_endpoint_ip_addr: &str) -> Result<()> {
@@ -51,4 +64,118 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
 
         Ok(())
     }
+
+    // Cell configuration procedure
+    // 1.    Look up the DU that serves the cell, which must be connected to this worker
+    // 2.    F1ap GnbCuConfigurationUpdate(cells to be activated, deactivated or barred) <<
+    // 3.    F1ap GnbCuConfigurationUpdateAcknowledge >>
+    // 4.    Store the cell's new state
+    //
+    // A cell is activated again to give it a new SIB2.  See TS 38.473, 8.2.5.
+    pub async fn configure_cell(&self, c: CellConfiguration) -> Result<()> {
+        if let Some(sib2) = &c.sib2 {
+            sib2.validate()?;
+        }
+        let mut du = self.du_serving_cell(&c.nr_cgi).await?;
+        let Some(cell) = du.served_cells.iter_mut().find(|x| x.has_nr_cgi(&c.nr_cgi)) else {
+            bail!("Cell {:?} not found", c.nr_cgi)
+        };
+
+        let was_active = cell.active;
+        let mut sib2_changed = false;
+        if let Some(active) = c.active {
+            cell.active = active;
+        }
+        if let Some(sib2) = c.sib2 {
+            if sib2 != cell.sib2 {
+                cell.sib2 = sib2;
+                cell.sib2_value_tag = (cell.sib2_value_tag + 1) % 32;
+                sib2_changed = true;
+            }
+        }
+        let cell_barred = match c.barred {
+            Some(barred) if barred != cell.barred => {
+                cell.barred = barred;
+                Some(if barred {
+                    CellBarred::Barred
+                } else {
+                    CellBarred::NotBarred
+                })
+            }
+            _ => None,
+        };
+
+        let activate = cell.active && (!was_active || sib2_changed);
+        let deactivate = was_active && !cell.active;
+        // There is nothing to tell the DU about an inactive cell's new SIB2 until the cell is activated.
+        if !activate && !deactivate && cell_barred.is_none() {
+            return self.store_du(du).await;
+        }
+
+        let cell = cell.clone();
+        let gnb_cu_configuration_update = GnbCuConfigurationUpdate {
+            transaction_id: TransactionId(rand::thread_rng().gen()),
+            cells_to_be_activated_list: if activate {
+                build_f1ap::build_cells_to_be_activated_list(std::iter::once(&cell))?
+            } else {
+                None
+            },
+            cells_to_be_deactivated_list: deactivate.then(|| {
+                CellsToBeDeactivatedList(nonempty![CellsToBeDeactivatedListItem {
+                    nr_cgi: cell.nr_cgi.clone()
+                }])
+            }),
+            gnb_cu_tnl_association_to_add_list: None,
+            gnb_cu_tnl_association_to_remove_list: None,
+            gnb_cu_tnl_association_to_update_list: None,
+            cells_to_be_barred_list: cell_barred.map(|cell_barred| {
+                CellsToBeBarredList(nonempty![CellsToBeBarredItem {
+                    nr_cgi: cell.nr_cgi.clone(),
+                    cell_barred,
+                    iab_barred: None,
+                }])
+            }),
+            protected_eutra_resources_list: None,
+            neighbour_cell_information_list: None,
+            transport_layer_address_info: None,
+            ul_bh_non_up_traffic_mapping: None,
+            bap_address: None,
+        };
+
+        self.log_message("<< GnbCuConfigurationUpdate");
+        let response = self
+            .f1ap_request_to_du::<GnbCuConfigurationUpdateProcedure>(
+                gnb_cu_configuration_update,
+                &du.address,
+                self.logger,
+            )
+            .await?;
+        self.log_message(">> GnbCuConfigurationUpdateAcknowledge");
+
+        if let Some(failed) = response.cells_failed_to_be_activated_list {
+            if let Some(x) = failed.0.iter().find(|x| cell.has_nr_cgi(&x.nr_cgi)) {
+                bail!("DU failed to activate cell - {:?}", x.cause)
+            }
+        }
+
+        self.store_du(du).await
+    }
+
+    // Each worker's cache of DU state may be out of date, so this reads all DUs from the datastore.  Only the
+    // worker that the DU is connected to can send it a GnbCuConfigurationUpdate.
+    async fn du_serving_cell(&self, nr_cgi: &NrCgi) -> Result<DuState> {
+        let du = self
+            .retrieve_all_dus()
+            .await?
+            .into_iter()
+            .find(|x| x.served_cell(nr_cgi).is_some())
+            .ok_or_else(|| anyhow!("No DU serves cell {:?}", nr_cgi))?;
+        ensure!(
+            self.is_du_connected(&du.address).await,
+            "{:?} serving cell {:?} is not connected to this worker",
+            du.gnb_du_id,
+            nr_cgi
+        );
+        Ok(du)
+    }
 }
@@ -1,15 +1,26 @@
-//! f1_setup - the initial handshake that establishes an instance of the F1 reference point between GNB-CU and GNB-DU
+//! gnb_du_configuration_update - the DU adds, modifies or deletes its served cells
 
-use super::Workflow;
+use super::{build_f1ap, Workflow};
+use crate::datastore::{DuState, ServedCell};
 use crate::gnb_cu_cp::GnbCuCp;
-use anyhow::Result;
+use anyhow::{bail, ensure, Result};
 use f1ap::*;
 use net::{RequestError, ResponseAction};
+use ngap::{Cause as NgapCause, CauseRadioNetwork as NgapCauseRadioNetwork};
+use slog::{debug, warn};
+use std::future::Future;
+use std::pin::Pin;
 
 impl<'a, G: GnbCuCp> Workflow<'a, G> {
-    // F1 Setup Procedure
+    // GNB-DU Configuration Update procedure
     // 1.    F1ap GnbDuConfigurationUpdate >>
-    // 2.    F1ap GnbDuConfigurationUpdateAcknowledge <<
+    // 2.    Store the DU's new list of served cells
+    // 3.    F1ap GnbDuConfigurationUpdateAcknowledge(cells to be activated) <<
+    // 4.    Release the UEs in deleted cells
+    //
+    // Added and modified cells are activated, with the system information that the CU is responsible for.
+    // A deleted cell is no longer served by the DU, so is removed from the DU state, after which no worker
+    // admits UEs in it.  See TS 38.473, 8.2.4.
     pub async fn gnb_du_configuration_update(
         &self,
         r: GnbDuConfigurationUpdate,
@@ -19,31 +30,182 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
     > {
         self.log_message(">> GnbDuConfigurationUpdate");
 
-        if r.served_cells_to_add_list.is_some() {
-            self.log_message_error("Served cells to add present on GnbDuConfigurationUpdate but not implemented and ignored")
-        }
-        if r.served_cells_to_modify_list.is_some() {
-            self.log_message_error("Served cells to modify present on GnbDuConfigurationUpdate but not implemented and ignored")
-        }
-        if r.served_cells_to_delete_list.is_some() {
-            self.log_message_error("Served cells to delete present on GnbDuConfigurationUpdate but not implemented and ignored")
+        let deleted_cells: Vec<NrCgi> = r
+            .served_cells_to_delete_list
+            .iter()
+            .flat_map(|cells| cells.0.iter())
+            .map(|x| x.old_nr_cgi.clone())
+            .collect();
+
+        let mut cells_to_be_activated = None;
+        if r.served_cells_to_add_list.is_some()
+            || r.served_cells_to_modify_list.is_some()
+            || !deleted_cells.is_empty()
+        {
+            let du = match self.update_du_served_cells(&r).await {
+                Ok(du) => du,
+                Err(e) => {
+                    self.log_message_error(&format!("Failed to update served cells - {e}"));
+                    return Err(RequestError::UnsuccessfulOutcome(
+                        GnbDuConfigurationUpdateFailure {
+                            transaction_id: r.transaction_id,
+                            cause: Cause::Misc(CauseMisc::Unspecified),
+                            time_to_wait: None,
+                            criticality_diagnostics: None,
+                        },
+                    ));
+                }
+            };
+            cells_to_be_activated = match build_cells_to_be_activated(&r, &du) {
+                Ok(x) => x,
+                Err(e) => {
+                    self.log_message_error(&format!("Failed to build cells to be activated - {e}"));
+                    None
+                }
+            };
         }
         if r.gnb_du_tnl_association_to_remove_list.is_some() {
             self.log_message_error("Tnl association to delete present on GnbDuConfigurationUpdate but not implemented and ignored")
         }
 
+        // The UEs in deleted cells are released once the DU has the acknowledgement.
+        let release_ues =
+            (!deleted_cells.is_empty()).then(|| self.release_ues_in_cells(deleted_cells));
+
         self.log_message("<< GnbDuConfigurationUpdateAcknowledge");
         Ok((
             GnbDuConfigurationUpdateAcknowledge {
                 transaction_id: r.transaction_id,
-                cells_to_be_activated_list: None,
+                cells_to_be_activated_list: cells_to_be_activated,
                 criticality_diagnostics: None,
                 cells_to_be_deactivated_list: None,
                 transport_layer_address_info: None,
                 ul_bh_non_up_traffic_mapping: None,
                 bap_address: None,
             },
-            None,
+            release_ues,
         ))
     }
+
+    // Apply the served cell changes to the stored DU state.  A modified cell keeps the state that the operator
+    // has given it.
+    async fn update_du_served_cells(&self, r: &GnbDuConfigurationUpdate) -> Result<DuState> {
+        let mut du = self.du_sending_update(r).await?;
+
+        if let Some(cells) = &r.served_cells_to_delete_list {
+            for item in cells.0.iter() {
+                du.served_cells.retain(|x| !x.has_nr_cgi(&item.old_nr_cgi));
+            }
+        }
+        if let Some(cells) = &r.served_cells_to_modify_list {
+            for item in cells.0.iter() {
+                let mut cell = ServedCell::from(&item.served_cell_information);
+                if let Some(old) = du.served_cell(&item.old_nr_cgi) {
+                    cell.active = old.active;
+                    cell.barred = old.barred;
+                    cell.sib2 = old.sib2.clone();
+                    cell.sib2_value_tag = old.sib2_value_tag;
+                }
+                du.served_cells.retain(|x| !x.has_nr_cgi(&item.old_nr_cgi));
+                du.served_cells.push(cell);
+            }
+        }
+        if let Some(cells) = &r.served_cells_to_add_list {
+            for item in cells.0.iter() {
+                let cell = ServedCell::from(&item.served_cell_information);
+                du.served_cells.retain(|x| !x.has_nr_cgi(&cell.nr_cgi));
+                du.served_cells.push(cell);
+            }
+        }
+
+        self.store_du(du.clone()).await?;
+        Ok(du)
+    }
+
+    // The DU is the one whose address is that of the TNLA that the update arrived on.  The gNB-DU ID is optional
+    // in the update, so is only used as a check.
+    async fn du_sending_update(&self, r: &GnbDuConfigurationUpdate) -> Result<DuState> {
+        let Some(address) = self.f1ap_request_remote_ip().await else {
+            bail!("Unknown DU address")
+        };
+        let address = address.to_string();
+        let Some(du) = self
+            .retrieve_all_dus()
+            .await?
+            .into_iter()
+            .find(|x| x.address == address)
+        else {
+            bail!("No DU has set up from {}", address)
+        };
+        if let Some(gnb_du_id) = r.gnb_du_id {
+            ensure!(
+                gnb_du_id.0 == du.gnb_du_id.0,
+                "gNB-DU ID {} does not match DU {} at {}",
+                gnb_du_id.0,
+                du.gnb_du_id.0,
+                address
+            );
+        }
+        Ok(du)
+    }
+
+    fn release_ues_in_cells(&self, cells: Vec<NrCgi>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let gnb_cu_cp = self.gnb_cu_cp.clone();
+        let logger = self.logger.clone();
+        Box::pin(async move { Workflow::new(&gnb_cu_cp, &logger).release_ues(&cells).await })
+    }
+
+    // Release the UEs that were in cells that the DU no longer serves.  If the AMF knows about a UE, it is
+    // asked to release it.
+    async fn release_ues(&self, cells: &[NrCgi]) {
+        for nr_cgi in cells {
+            let ue_keys = match self.ues_in_cell(nr_cgi).await {
+                Ok(x) => x,
+                Err(e) => {
+                    warn!(self.logger, "Failed to find UEs in deleted cell - {}", e);
+                    continue;
+                }
+            };
+            for ue_key in ue_keys {
+                debug!(self.logger, "Release UE {:#010x} in deleted cell", ue_key);
+                let result = match self.retrieve(&ue_key).await {
+                    Ok(ue) => {
+                        self.request_ue_context_release(
+                            &ue,
+                            NgapCause::RadioNetwork(NgapCauseRadioNetwork::CellNotAvailable),
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    warn!(self.logger, "Failed to release UE {:#010x} - {}", ue_key, e);
+                }
+            }
+        }
+    }
+}
+
+// Added cells, and modified cells, which may have changed in ways that affect their system information.  A
+// cell that the operator has deactivated stays inactive.
+fn build_cells_to_be_activated(
+    r: &GnbDuConfigurationUpdate,
+    du: &DuState,
+) -> Result<Option<CellsToBeActivatedList>> {
+    let added = r
+        .served_cells_to_add_list
+        .iter()
+        .flat_map(|cells| cells.0.iter())
+        .map(|x| &x.served_cell_information.nr_cgi);
+    let modified = r
+        .served_cells_to_modify_list
+        .iter()
+        .flat_map(|cells| cells.0.iter())
+        .map(|x| &x.served_cell_information.nr_cgi);
+    build_f1ap::build_cells_to_be_activated_list(
+        added
+            .chain(modified)
+            .filter_map(|x| du.served_cell(x))
+            .filter(|x| x.active),
+    )
 }
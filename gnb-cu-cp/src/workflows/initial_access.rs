@@ -1,8 +1,11 @@
 //! initial_access - procedure in which UE makes first contact with the 5G core
 
-use super::{GnbCuCp, Workflow};
-use crate::datastore::UeState;
-use anyhow::{anyhow, Result};
+use super::{
+    rrc_inactive::{ResumeIdentity, ResumeRequest},
+    GnbCuCp, Workflow,
+};
+use crate::datastore::{ServedCell, UeState};
+use anyhow::{anyhow, bail, Result};
 use bitvec::prelude::*;
 use f1ap::{InitialUlRrcMessageTransfer, SrbId};
 use net::SerDes;
@@ -12,22 +15,64 @@ use slog::debug;
 
 impl<'a, G: GnbCuCp> Workflow<'a, G> {
     // Initial Access Procedure
-    // 1. >> Rrc RrcSetupRequest
+    // 1. >> Rrc RrcSetupRequest (or RrcResumeRequest, if the UE can't be resumed)
     // 2. << Rrc RrcSetup
     // 3. >> Rrc RrcSetupComplete
     // 4.    Ngap InitialUeMessage >>
     pub async fn initial_access(&self, r: InitialUlRrcMessageTransfer) -> Result<()> {
         self.log_message(">> InitialUlRrcMessageTransfer");
 
-        let _rrc_setup_request = expect_rrc_setup_request(&r.rrc_container.0)?;
-        self.log_message(">> Rrc RrcSetupRequest");
+        // A worker that is shutting down leaves new UEs to the other workers.
+        if self.is_draining() {
+            return self.reject_ue(r).await;
+        }
+
+        // The UE must be in a cell that one of our DUs told us about.
+        let served_cell = self.served_cell(&r.nr_cgi).await?;
+
+        let (rrc_establishment_cause, ng_5g_s_tmsi_part_1) = match decode_initial_rrc_message(&r)? {
+            InitialRrcMessage::RrcSetupRequest(x) => {
+                self.log_message(">> Rrc RrcSetupRequest");
+                let x = x.rrc_setup_request;
+                (
+                    rrc_establishment_cause(&x.establishment_cause),
+                    match x.ue_identity {
+                        InitialUeIdentity::Ng5gSTmsiPart1(x) => Some(x),
+                        InitialUeIdentity::RandomValue(_) => None,
+                    },
+                )
+            }
+            InitialRrcMessage::RrcResumeRequest(resume_request) => {
+                self.log_message(">> Rrc RrcResumeRequest");
+                match self.retrieve_suspended_ue(&resume_request).await {
+                    Ok(ue) => {
+                        self.bind_ue_to_du(ue.key).await?;
+                        return self.rrc_resume(&r, ue).await;
+                    }
+                    // TS 38.331, 5.3.13.3 - the network may respond to a resume request with an Rrc Setup.
+                    Err(e) => debug!(self.logger, "Can't resume UE, falling back to setup - {e}"),
+                }
+
+                // A UE that falls back to setup sends its whole 5G-S-TMSI in the Rrc Setup Complete.
+                (rrc_resume_cause(&resume_request.resume_cause), None)
+            }
+        };
 
         let ue = UeState::new(r.gnb_du_ue_f1ap_id, r.nr_cgi);
         debug!(self.logger, "Created UE {:#010x}", ue.key);
 
+        // The UE's F1AP signalling goes to the DU that it is attached to.
+        self.bind_ue_to_du(ue.key).await?;
+
         let rrc_setup_complete = self.perform_rrc_setup_procedure(&ue).await?;
 
-        let initial_ue_message = self.build_initial_ue_message(&ue, rrc_setup_complete);
+        let initial_ue_message = self.build_initial_ue_message(
+            &ue,
+            &served_cell,
+            rrc_establishment_cause,
+            ng_5g_s_tmsi_part_1,
+            rrc_setup_complete,
+        )?;
 
         debug!(self.logger, "Store UE {:#010x}", ue.key);
         self.store(ue.key, ue, self.config().initial_ue_ttl_secs)
@@ -39,6 +84,23 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
         Ok(())
     }
 
+    // Reject a UE with an Rrc Reject, so that it backs off rather than retrying straight away into a worker
+    // that is about to go.  It is told to wait for as long as the worker may take to drain, up to the 16
+    // seconds that Rrc allows.
+    async fn reject_ue(&self, r: InitialUlRrcMessageTransfer) -> Result<()> {
+        let ue = UeState::new(r.gnb_du_ue_f1ap_id, r.nr_cgi);
+        self.bind_ue_to_du(ue.key).await?;
+
+        let wait_time = self.config().drain_timeout_secs.clamp(1, 16) as u8;
+        let rrc_reject = super::build_rrc::build_rrc_reject(wait_time)?;
+        self.log_message("<< RrcReject");
+        self.send_rrc_to_ue(&ue, SrbId(0), rrc_reject, self.logger)
+            .await;
+
+        // The UE was never stored, but deleting it removes its binding to the DU.
+        self.delete(&ue.key).await
+    }
+
     async fn perform_rrc_setup_procedure(&self, ue: &UeState) -> Result<RrcSetupCompleteIEs> {
         let rrc_transaction = self.gnb_cu_cp.new_rrc_transaction(ue).await;
         let rrc_setup = super::build_rrc::build_rrc_setup(0)?;
@@ -59,27 +121,44 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
     fn build_initial_ue_message(
         &self,
         ue: &UeState,
+        served_cell: &ServedCell,
+        rrc_establishment_cause: RrcEstablishmentCause,
+        ng_5g_s_tmsi_part_1: Option<BitVec<u8, Msb0>>,
         rrc_setup_complete: RrcSetupCompleteIEs,
-    ) -> InitialUeMessage {
-        // TODO: get establishment cause from the earlier Rrc Setup Request.
-        let rrc_establishment_cause = RrcEstablishmentCause::MtAccess;
+    ) -> Result<InitialUeMessage> {
+        let five_g_s_tmsi = match rrc_setup_complete.ng_5g_s_tmsi_value {
+            Some(Ng5gSTmsiValue::Ng5gSTmsi(x)) => Some(five_g_s_tmsi(&x.0)?),
+            // TS 38.331, 5.3.3.4 - the UE sends the leftmost 9 bits of its 5G-S-TMSI here, having sent the
+            // rightmost 39 bits in the Rrc Setup Request.
+            Some(Ng5gSTmsiValue::Ng5gSTmsiPart2(mut x)) => {
+                let Some(part_1) = ng_5g_s_tmsi_part_1 else {
+                    return Err(anyhow!("5G-S-TMSI part 2 without part 1"));
+                };
+                x.extend_from_bitslice(&part_1);
+                Some(five_g_s_tmsi(&x)?)
+            }
+            None => None,
+        };
 
-        // TODO: likewise get NrCgi from the F1AP UL Initial Transfer Request.  (Frunk transmogrify ideally.)
+        let Some(five_gs_tac) = &served_cell.five_gs_tac else {
+            return Err(anyhow!("UE's serving cell has no 5GS TAC"));
+        };
+        let plmn_identity = ngap::PlmnIdentity(ue.nr_cgi.plmn_identity.0);
         let nr_cgi = ngap::NrCgi {
-            plmn_identity: ngap::PlmnIdentity(self.gnb_cu_cp.config().plmn),
-            nr_cell_identity: ngap::NrCellIdentity(bitvec![u8,Msb0;0;36]),
+            plmn_identity: plmn_identity.clone(),
+            nr_cell_identity: ngap::NrCellIdentity(ue.nr_cgi.nr_cell_identity.0.clone()),
         };
 
         // Initial UE Message to the AMF containing the enclosed NAS message.
-        InitialUeMessage {
+        Ok(InitialUeMessage {
             ran_ue_ngap_id: RanUeNgapId(ue.key),
             nas_pdu: NasPdu(rrc_setup_complete.dedicated_nas_message.0),
             user_location_information: UserLocationInformation::UserLocationInformationNr(
                 UserLocationInformationNr {
                     nr_cgi,
                     tai: Tai {
-                        plmn_identity: ngap::PlmnIdentity(self.gnb_cu_cp.config().plmn),
-                        tac: Tac([0, 0, 1]),
+                        plmn_identity,
+                        tac: Tac(five_gs_tac.0),
                     },
                     time_stamp: None,
                     ps_cell_information: None,
@@ -87,7 +166,7 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
                 },
             ),
             rrc_establishment_cause,
-            five_g_s_tmsi: None,
+            five_g_s_tmsi,
             amf_set_id: None,
             ue_context_request: Some(UeContextRequest::Requested),
             allowed_nssai: None,
@@ -99,15 +178,123 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
             edt_session: None,
             authenticated_indication: None,
             npn_access_information: None,
-        }
+        })
     }
 }
 
-fn expect_rrc_setup_request(message: &[u8]) -> Result<RrcSetupRequest> {
+fn rrc_establishment_cause(x: &EstablishmentCause) -> RrcEstablishmentCause {
+    match x {
+        EstablishmentCause::Emergency => RrcEstablishmentCause::Emergency,
+        EstablishmentCause::HighPriorityAccess => RrcEstablishmentCause::HighPriorityAccess,
+        EstablishmentCause::MtAccess => RrcEstablishmentCause::MtAccess,
+        EstablishmentCause::MoSignalling => RrcEstablishmentCause::MoSignalling,
+        EstablishmentCause::MoData => RrcEstablishmentCause::MoData,
+        EstablishmentCause::MoVoiceCall => RrcEstablishmentCause::MoVoiceCall,
+        EstablishmentCause::MoVideoCall => RrcEstablishmentCause::MoVideoCall,
+        EstablishmentCause::MoSms => RrcEstablishmentCause::MoSms,
+        EstablishmentCause::MpsPriorityAccess => RrcEstablishmentCause::MpsPriorityAccess,
+        EstablishmentCause::McsPriorityAccess => RrcEstablishmentCause::McsPriorityAccess,
+        // A spare value means a cause from a later release than ours.
+        _ => RrcEstablishmentCause::MoSignalling,
+    }
+}
+
+// TS 38.413, 9.3.1.111 - the RRC Establishment Cause also carries the cause of a UE's resume request.  An RNA
+// update is the UE signalling with the network.
+fn rrc_resume_cause(x: &ResumeCause) -> RrcEstablishmentCause {
+    match x {
+        ResumeCause::Emergency => RrcEstablishmentCause::Emergency,
+        ResumeCause::HighPriorityAccess => RrcEstablishmentCause::HighPriorityAccess,
+        ResumeCause::MtAccess => RrcEstablishmentCause::MtAccess,
+        ResumeCause::MoSignalling => RrcEstablishmentCause::MoSignalling,
+        ResumeCause::MoData => RrcEstablishmentCause::MoData,
+        ResumeCause::MoVoiceCall => RrcEstablishmentCause::MoVoiceCall,
+        ResumeCause::MoVideoCall => RrcEstablishmentCause::MoVideoCall,
+        ResumeCause::MoSms => RrcEstablishmentCause::MoSms,
+        ResumeCause::RnaUpdate => RrcEstablishmentCause::MoSignalling,
+        ResumeCause::MpsPriorityAccess => RrcEstablishmentCause::MpsPriorityAccess,
+        ResumeCause::McsPriorityAccess => RrcEstablishmentCause::McsPriorityAccess,
+        // A spare value means a cause from a later release than ours.
+        _ => RrcEstablishmentCause::MoSignalling,
+    }
+}
+
+// The 48 bit 5G-S-TMSI is the AMF Set ID (10 bits), AMF Pointer (6 bits) and 5G-TMSI (32 bits) concatenated.
+fn five_g_s_tmsi(bits: &BitSlice<u8, Msb0>) -> Result<FiveGSTmsi> {
+    if bits.len() != 48 {
+        return Err(anyhow!("5G-S-TMSI of length {}", bits.len()));
+    }
+    let five_g_tmsi: u32 = bits[16..].load_be();
+    Ok(FiveGSTmsi {
+        amf_set_id: AmfSetId(bits[..10].to_bitvec()),
+        amf_pointer: AmfPointer(bits[10..16].to_bitvec()),
+        five_g_tmsi: FiveGTmsi(five_g_tmsi.to_be_bytes()),
+    })
+}
+
+enum InitialRrcMessage {
+    RrcSetupRequest(RrcSetupRequest),
+    RrcResumeRequest(ResumeRequest),
+}
+
+// The DU passes on the UE's message from either UL-CCCH or UL-CCCH1, which are told apart at the MAC by their
+// LCIDs (TS 38.321, 6.2.1).  This version of F1AP has no IE to carry that through, so the message is
+// identified as follows.
+// -  An Initial UL RRC Message Transfer that also carries the RRC Setup Complete is for an RRC Setup Request,
+//    on UL-CCCH.
+// -  Otherwise, the DU sends each CCCH SDU whole, and its size identifies the logical channel, since a UL-CCCH
+//    message is always 48 bits and a UL-CCCH1 message 64 bits (TS 38.331, 6.2.1).  Any other size is
+//    rejected rather than guessed at.
+fn decode_initial_rrc_message(r: &InitialUlRrcMessageTransfer) -> Result<InitialRrcMessage> {
+    let message = &r.rrc_container.0;
+    let ul_ccch1 = match (&r.rrc_container_rrc_setup_complete, message.len()) {
+        (Some(_), _) | (None, 6) => false,
+        (None, 8) => true,
+        (None, n) => bail!("Initial RRC message of {n} bytes is on neither UL-CCCH nor UL-CCCH1"),
+    };
+
+    if ul_ccch1 {
+        return match UlCcch1Message::from_bytes(message)? {
+            UlCcch1Message {
+                message:
+                    UlCcch1MessageType::C1(C1_5::RrcResumeRequest1(RrcResumeRequest1 {
+                        rrc_resume_request_1:
+                            RrcResumeRequest1IEs {
+                                resume_identity,
+                                resume_mac_i,
+                                resume_cause,
+                                ..
+                            },
+                    })),
+            } => Ok(InitialRrcMessage::RrcResumeRequest(ResumeRequest {
+                resume_identity: ResumeIdentity::Full(resume_identity.0.load_be()),
+                resume_mac_i,
+                resume_cause,
+            })),
+            m => Err(anyhow!(format!("Not yet implemented Rrc message {:?}", m))),
+        };
+    }
+
     match UlCcchMessage::from_bytes(message)? {
         UlCcchMessage {
             message: UlCcchMessageType::C1(C1_4::RrcSetupRequest(x)),
-        } => Ok(x),
+        } => Ok(InitialRrcMessage::RrcSetupRequest(x)),
+        UlCcchMessage {
+            message:
+                UlCcchMessageType::C1(C1_4::RrcResumeRequest(RrcResumeRequest {
+                    rrc_resume_request:
+                        RrcResumeRequestIEs {
+                            resume_identity,
+                            resume_mac_i,
+                            resume_cause,
+                            ..
+                        },
+                })),
+        } => Ok(InitialRrcMessage::RrcResumeRequest(ResumeRequest {
+            resume_identity: ResumeIdentity::Short(resume_identity.0.load_be()),
+            resume_mac_i,
+            resume_cause,
+        })),
         m => Err(anyhow!(format!("Not yet implemented Rrc message {:?}", m))),
     }
 }
@@ -1,27 +1,39 @@
 //! initial_context_setup - in which the secure signaling channel is established between UE and 5G core through the GNB
 
-use super::{GnbCuCp, Workflow};
+use super::{
+    pdu_session_resource_setup::{remember_pdu_sessions, requested_pdu_sessions, Stage3},
+    GnbCuCp, Workflow,
+};
+use crate::datastore::{PduSessionState, UeState};
 use anyhow::Result;
-use f1ap::SrbId;
+use asn1_per::NonEmpty;
+use f1ap::{CellGroupConfig, SrbId};
 use net::ResponseAction;
 use ngap::*;
 use rrc::*;
-use slog::debug;
+use slog::{debug, warn};
 
 impl<'a, G: GnbCuCp> Workflow<'a, G> {
     // Initial context setup procedure.
     // 1.    Ngap InitialContextSetupRequest(maybe Nas) <<
     // --- CONDITIONAL IF PDUS ARE PRESENT -----
-    // 2. << F1ap UeContextSetup(Rrc SecurityModeCommand)
-    // 3. >> F1ap Ue Context Setup Response
+    // 2. << E1ap BearerContextSetupRequest
+    // 3. >> E1ap BearerContextSetupResponse
+    // 4. << F1ap UeContextSetup(Rrc SecurityModeCommand)
+    // 5. >> F1ap Ue Context Setup Response
+    // ---- NO PDUS ---
+    // 4. << Rrc SecurityModeCommand
     // -----------------------------------------
-    // 4. >> Rrc SecurityModeComplete
+    // 6. >> Rrc SecurityModeComplete
     // --- CONDITIONAL IF PDUS ARE PRESENT -----
-    // 5. << Rrc RrcReconfiguration(maybe Nas)
-    // 6. >> Rrc RrcReconfigurationComplete
+    // 7. << E1ap BearerContextModificationRequest
+    // 8. >> E1ap BearerContextModificationResponse
+    // 9. << Rrc RrcReconfiguration(maybe Nas)
+    // 10.>> Rrc RrcReconfigurationComplete
     // ---- NO PDUS ---
+    // 9. << Rrc DlInformationTransfer(Nas)
     // -----------------------------------------
-    // 7.    Ngap InitialContextSetupResponse >>
+    // 11.   Ngap InitialContextSetupResponse >>
     pub async fn initial_context_setup(
         &self,
         r: &InitialContextSetupRequest,
@@ -32,7 +44,7 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
 
         // Retrieve UE context by ran_ue_ngap_id.
         debug!(self.logger, "Retrieve UE {:#010x}", ue_key);
-        let ue = self
+        let mut ue = self
             .retrieve(&ue_key)
             .await
             .map_err(|_| Cause::RadioNetwork(CauseRadioNetwork::UnknownLocalUeNgapId))?;
@@ -42,17 +54,44 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
         let rrc_container = super::build_rrc::build_rrc_security_mode_command(0)
             .map_err(|_| Cause::Misc(CauseMisc::Unspecified))?;
 
-        if let Some(_sessions) = &r.pdu_session_resource_setup_list_cxt_req {
+        // Save off the session IDs in case of error.
+        let requested_session_ids: Vec<xxap::PduSessionId> = r
+            .pdu_session_resource_setup_list_cxt_req
+            .iter()
+            .flat_map(|x| x.0.iter().map(|item| item.pdu_session_id))
+            .collect();
+
+        let mut requested_sessions = vec![];
+        let staged_sessions = if let Some(sessions) = &r.pdu_session_resource_setup_list_cxt_req {
             // --- Sessions needed ---
-            // TODO
-            self.log_message_error("Combined context and session setup not suppported");
-            return Err(Cause::Misc(CauseMisc::Unspecified));
+            // The Security Mode Command goes to the UE in the F1 Ue Context Setup for the sessions.
+            let sessions = sessions.0.clone().map(su_req_from_cxt_req);
+            requested_sessions = requested_pdu_sessions(&ue, &sessions, self.logger);
+            match self
+                .initial_context_session_stages(
+                    &mut ue,
+                    sessions,
+                    &requested_sessions,
+                    rrc_container.clone(),
+                )
+                .await
+            {
+                Ok(x) => Some(x),
+                Err(e) => {
+                    warn!(self.logger, "Error processing session setup - {}", e);
+                    None
+                }
+            }
         } else {
-            // --- No sessions needed ---
+            None
+        };
+
+        if staged_sessions.is_none() {
+            // --- No sessions set up ---
             self.log_message("<< SecurityModeCommand");
             self.send_rrc_to_ue(&ue, SrbId(1), rrc_container, self.logger)
                 .await;
-        };
+        }
 
         // Receive Security Mode Complete.
         let _rrc_security_mode_complete = rrc_transaction
@@ -61,34 +100,162 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
             .map_err(|_| Cause::Misc(CauseMisc::Unspecified))?;
         self.log_message(">> SecurityModeComplete");
 
-        if let Some(nas) = r.nas_pdu.clone() {
-            if let Err(e) = self.send_nas_to_ue(&ue, DedicatedNasMessage(nas.0)).await {
-                debug!(self.logger, "Failed to send NAS to UE- {:?}", e)
+        let nas_messages: Vec<Vec<u8>> = r.nas_pdu.iter().map(|x| x.0.clone()).collect();
+        let ok_sessions = match staged_sessions {
+            Some((sessions, cell_group_config)) => {
+                // The Nas message goes to the UE in the same Rrc Reconfiguration as the sessions.
+                match self
+                    .initial_context_session_later_stages(
+                        &ue,
+                        sessions,
+                        &requested_sessions,
+                        cell_group_config,
+                        nas_messages,
+                    )
+                    .await
+                {
+                    Ok(x) => x.into(),
+                    Err(e) => {
+                        warn!(self.logger, "Error processing session setup - {}", e);
+                        if let Some(gnb_cu_up_ue_e1ap_id) = ue.gnb_cu_up_ue_e1ap_id.take() {
+                            self.perform_e1_bearer_release(&ue, gnb_cu_up_ue_e1ap_id)
+                                .await;
+                        }
+                        vec![]
+                    }
+                }
             }
-        } else {
-            debug!(
-                self.logger,
-                "No Nas and no sessions on initial context create"
-            );
-        }
+            None => {
+                if let Some(nas) = r.nas_pdu.clone() {
+                    if let Err(e) = self.send_nas_to_ue(&ue, DedicatedNasMessage(nas.0)).await {
+                        debug!(self.logger, "Failed to send NAS to UE- {:?}", e)
+                    }
+                } else {
+                    debug!(
+                        self.logger,
+                        "No Nas and no sessions on initial context create"
+                    );
+                }
+                vec![]
+            }
+        };
 
-        // Write back UE.
+        // Update and write back UE.
+        remember_pdu_sessions(
+            &mut ue,
+            requested_sessions,
+            ok_sessions.iter().map(|x| x.pdu_session_id.0),
+        );
         debug!(self.logger, "Store UE {:#010x}", ue_key);
         if let Err(e) = self.store(ue_key, ue, self.config().ue_ttl_secs).await {
             debug!(self.logger, "Failed to write back UE- {:?}", e)
         }
 
+        // Any requested sessions that didn't make it through the setup process go in the failed list.
+        let failed_sessions: Vec<PduSessionResourceFailedToSetupItemCxtRes> = requested_session_ids
+            .into_iter()
+            .filter(|x| ok_sessions.iter().all(|item| item.pdu_session_id.0 != x.0))
+            .filter_map(|x| {
+                match super::build_ngap::build_pdu_session_resource_setup_unsuccessful_transfer(
+                    Cause::RadioNetwork(CauseRadioNetwork::Unspecified),
+                ) {
+                    Ok(transfer) => Some(PduSessionResourceFailedToSetupItemCxtRes {
+                        pdu_session_id: x,
+                        pdu_session_resource_setup_unsuccessful_transfer: transfer,
+                    }),
+                    Err(e) => {
+                        warn!(self.logger, "Build failed to setup item failed - {e}");
+                        None
+                    }
+                }
+            })
+            .collect();
+
         // Reply to the AMF.
         self.log_message("InitialContextSetupResponse >>");
         Ok((
             InitialContextSetupResponse {
                 amf_ue_ngap_id: r.amf_ue_ngap_id,
                 ran_ue_ngap_id: RanUeNgapId(ue_key),
-                pdu_session_resource_setup_list_cxt_res: None,
-                pdu_session_resource_failed_to_setup_list_cxt_res: None,
+                pdu_session_resource_setup_list_cxt_res: NonEmpty::from_vec(ok_sessions)
+                    .map(PduSessionResourceSetupListCxtRes),
+                pdu_session_resource_failed_to_setup_list_cxt_res: NonEmpty::from_vec(
+                    failed_sessions,
+                )
+                .map(PduSessionResourceFailedToSetupListCxtRes),
                 criticality_diagnostics: None,
             },
             None,
         ))
     }
+
+    // Steps 2-5 with sessions.  On failure, the Security Mode Command has not been sent.
+    async fn initial_context_session_stages(
+        &self,
+        ue: &mut UeState,
+        sessions: NonEmpty<PduSessionResourceSetupItemSuReq>,
+        requested_sessions: &[PduSessionState],
+        security_mode_command: f1ap::RrcContainer,
+    ) -> Result<(Vec<Stage3>, CellGroupConfig)> {
+        let sessions = self
+            .e1_context_setup(ue, sessions, requested_sessions)
+            .await?;
+        match self
+            .f1_context_setup(
+                ue,
+                sessions,
+                requested_sessions,
+                Some(security_mode_command),
+            )
+            .await
+        {
+            Ok(x) => Ok(x),
+            Err(e) => {
+                // Don't leave behind a bearer context on the CU-UP that the UE state knows nothing about.
+                if let Some(gnb_cu_up_ue_e1ap_id) = ue.gnb_cu_up_ue_e1ap_id.take() {
+                    self.perform_e1_bearer_release(ue, gnb_cu_up_ue_e1ap_id)
+                        .await;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    // Steps 7-10 with sessions.
+    async fn initial_context_session_later_stages(
+        &self,
+        ue: &UeState,
+        sessions: Vec<Stage3>,
+        requested_sessions: &[PduSessionState],
+        cell_group_config: CellGroupConfig,
+        nas_messages: Vec<Vec<u8>>,
+    ) -> Result<NonEmpty<PduSessionResourceSetupItemCxtRes>> {
+        let sessions = self.e1_context_modify(ue, sessions).await?;
+        let sessions = self
+            .rrc_reconfiguration(
+                ue,
+                sessions,
+                requested_sessions,
+                cell_group_config,
+                nas_messages,
+            )
+            .await?;
+        Ok(self
+            .ngap_responses(ue, sessions)
+            .await?
+            .map(|x| PduSessionResourceSetupItemCxtRes {
+                pdu_session_id: x.pdu_session_id,
+                pdu_session_resource_setup_response_transfer: x
+                    .pdu_session_resource_setup_response_transfer,
+            }))
+    }
+}
+
+fn su_req_from_cxt_req(x: PduSessionResourceSetupItemCxtReq) -> PduSessionResourceSetupItemSuReq {
+    PduSessionResourceSetupItemSuReq {
+        pdu_session_id: x.pdu_session_id,
+        pdu_session_nas_pdu: x.nas_pdu,
+        snssai: x.snssai,
+        pdu_session_resource_setup_request_transfer: x.pdu_session_resource_setup_request_transfer,
+    }
 }
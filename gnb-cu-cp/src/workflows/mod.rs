@@ -1,6 +1,7 @@
 use super::GnbCuCp;
 use slog::{debug, warn, Logger};
 
+mod amf_configuration_update;
 mod amf_status_indication;
 mod build_e1ap;
 mod build_f1ap;
@@ -8,6 +9,7 @@ mod build_ngap;
 mod build_rrc;
 mod downlink_nas;
 mod e1_setup;
+mod f1_removal;
 mod f1_setup;
 mod gnb_cu_configuration_update;
 mod gnb_cu_cp_configuration_update;
@@ -15,11 +17,17 @@ mod gnb_du_configuration_update;
 mod initial_access;
 mod initial_context_setup;
 mod ng_setup;
+mod paging;
+mod pdu_session_resource_modify;
 mod pdu_session_resource_release;
 mod pdu_session_resource_setup;
 mod ran_configuration_update;
+mod rrc_inactive;
+mod ue_context_release;
 mod uplink_nas;
 
+pub use gnb_cu_configuration_update::CellConfiguration;
+
 pub struct Workflow<'a, G: GnbCuCp> {
     gnb_cu_cp: &'a G,
     logger: &'a Logger,
@@ -1,11 +1,10 @@
 //! ng_setup - the initial handshake that establishes an instance of the NG reference point between GNB and AMF
 
 use super::{GnbCuCp, Workflow};
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use asn1_per::*;
 use ngap::*;
 use slog::info;
-use xxap::Snssai;
 
 impl<'a, G: GnbCuCp> Workflow<'a, G> {
     // Ng Setup Procedure
@@ -19,40 +18,31 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
             .await
             .map_err(|_e| anyhow!("Failed to connect to AMF {} (will retry)", amf_ip_address))?;
 
-        // This uses the default expected values of free5GC.
+        let ran = &self.config().ran;
         let ng_setup_request = NgSetupRequest {
             global_ran_node_id: super::build_ngap::build_global_ran_node_id(self.gnb_cu_cp),
             ran_node_name: self.config().name.clone().map(RanNodeName),
-            supported_ta_list: SupportedTaList(nonempty![SupportedTaItem {
-                tac: Tac([0, 0, 1]),
-                broadcast_plmn_list: BroadcastPlmnList(nonempty![BroadcastPlmnItem {
-                    plmn_identity: PlmnIdentity(self.config().plmn),
-                    tai_slice_support_list: SliceSupportList(nonempty![
-                        SliceSupportItem {
-                            snssai: Snssai(1, None).into(),
-                        },
-                        SliceSupportItem {
-                            snssai: Snssai(1, Some([0, 0, 0])).into(),
-                        },
-                        SliceSupportItem {
-                            snssai: Snssai(1, Some([0, 0, 1])).into(),
-                        }
-                    ]),
-                    npn_support: None,
-                    extended_tai_slice_support_list: None,
-                }]),
-                configured_tac_indication: None,
-                rat_information: None,
-            }]),
-            default_paging_drx: PagingDrx::V128,
+            supported_ta_list: ran.try_into()?,
+            default_paging_drx: ran.default_paging_drx.into(),
             ue_retention_information: None,
             nb_iot_default_paging_drx: None,
             extended_ran_node_name: None,
         };
         self.log_message("NgSetupRequest >>");
-        let response = self
+        let response = match self
             .ngap_request::<NgSetupProcedure>(ng_setup_request, self.logger)
-            .await?;
+            .await
+        {
+            Ok(response) => response,
+            Err(RequestError::Timeout) => {
+                self.log_message_error("NgSetupResponse timer expired");
+                bail!(
+                    "No NG Setup response from AMF {} (will retry)",
+                    amf_ip_address
+                )
+            }
+            Err(e) => return Err(e.into()),
+        };
         self.log_message("NgSetupResponse <<");
         info!(
             self.logger,
@@ -0,0 +1,125 @@
+//! paging - AMF asks the gNB to page an idle mode UE in the cells of a set of tracking areas
+
+use super::{GnbCuCp, Workflow};
+use crate::datastore::ServedCell;
+use anyhow::{anyhow, Result};
+use asn1_per::NonEmpty;
+use bitvec::prelude::*;
+use f1ap::{
+    CnUePagingIdentity, PagingCellItem, PagingCellList, PagingIdentity, PagingProcedure,
+    UeIdentityIndexValue,
+};
+use ngap::{FiveGSTmsi, Paging, TaiListForPaging, UePagingIdentity};
+use slog::debug;
+
+impl<'a, G: GnbCuCp> Workflow<'a, G> {
+    // Paging procedure.
+    // See TS 38.413, 8.5.1.
+    //
+    // 1.    Ngap Paging <<
+    // 2. << F1ap Paging (to each connected DU serving a cell in the paging TAI list)
+    //
+    // Each DU has a TNLA to every worker, so the worker that the AMF sends the paging to can reach all of
+    // them.  A DU without a TNLA to this worker is skipped.
+    pub async fn paging(&self, r: Paging) -> Result<()> {
+        self.log_message("Paging <<");
+
+        let UePagingIdentity::FiveGSTmsi(five_g_s_tmsi) = &r.ue_paging_identity;
+        let ue_identity_index_value = ue_identity_index_value(five_g_s_tmsi);
+        let paging_identity = PagingIdentity::CnUePagingIdentity(CnUePagingIdentity::FiveGSTmsi(
+            five_g_s_tmsi_bits(five_g_s_tmsi),
+        ));
+
+        // Page in every cell that is in one of the paging tracking areas, DU by DU.  A cell that the operator
+        // has deactivated or barred is skipped, since the UE cannot be camped on it.
+        let mut paged = false;
+        for du in self.retrieve_all_dus().await? {
+            if !self.is_du_connected(&du.address).await {
+                debug!(
+                    self.logger,
+                    "Not paging DU {} - not connected to this worker", du.gnb_du_id.0
+                );
+                continue;
+            }
+            let paging_cells = du
+                .served_cells
+                .iter()
+                .filter(|cell| cell.active && !cell.barred)
+                .filter(|cell| in_tai_list(cell, &r.tai_list_for_paging))
+                .map(|cell| PagingCellItem {
+                    nr_cgi: cell.nr_cgi.clone(),
+                })
+                .collect();
+            let Some(paging_cells) = NonEmpty::from_vec(paging_cells) else {
+                continue;
+            };
+
+            debug!(
+                self.logger,
+                "Page UE in {} cells of DU {}",
+                paging_cells.len(),
+                du.gnb_du_id.0
+            );
+            let paging = f1ap::Paging {
+                ue_identity_index_value: ue_identity_index_value.clone(),
+                paging_identity: paging_identity.clone(),
+                paging_drx: r
+                    .paging_drx
+                    .and_then(|x| f1ap::PagingDrx::try_from(x as u8).ok()),
+                paging_priority: r
+                    .paging_priority
+                    .and_then(|x| f1ap::PagingPriority::try_from(x as u8).ok()),
+                paging_cell_list: PagingCellList(paging_cells),
+                paging_origin: r.paging_origin.map(|_| f1ap::PagingOrigin::Non3gpp),
+            };
+
+            self.log_message("<< F1ap Paging");
+            match self
+                .f1ap_indication_to_du::<PagingProcedure>(paging, &du.address, self.logger)
+                .await
+            {
+                Ok(()) => paged = true,
+                Err(e) => {
+                    self.log_message_error(&format!("Failed to page DU {} - {e}", du.gnb_du_id.0))
+                }
+            }
+        }
+
+        if paged {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "No connected DU serves an active, unbarred cell in the paging TAI list"
+            ))
+        }
+    }
+}
+
+fn in_tai_list(cell: &ServedCell, tai_list: &TaiListForPaging) -> bool {
+    let Some(five_gs_tac) = &cell.five_gs_tac else {
+        return false;
+    };
+    tai_list.0.iter().any(|item| {
+        item.tai.tac.0 == five_gs_tac.0
+            && cell
+                .served_plmns
+                .iter()
+                .any(|x| x.0 == item.tai.plmn_identity.0)
+    })
+}
+
+// TS 38.304, 7.1: UE_ID = 5G-S-TMSI mod 1024.  This is the bottom 10 bits of the 5G-TMSI.
+fn ue_identity_index_value(five_g_s_tmsi: &FiveGSTmsi) -> UeIdentityIndexValue {
+    let five_g_tmsi = u32::from_be_bytes(five_g_s_tmsi.five_g_tmsi.0);
+    let mut index = bitvec![u8, Msb0; 0; 10];
+    index.store_be(five_g_tmsi % 1024);
+    UeIdentityIndexValue::IndexLength10(index)
+}
+
+// The 48 bit 5G-S-TMSI is the AMF Set ID, AMF Pointer and 5G-TMSI concatenated.
+fn five_g_s_tmsi_bits(five_g_s_tmsi: &FiveGSTmsi) -> BitVec<u8, Msb0> {
+    let mut bits = five_g_s_tmsi.amf_set_id.0.clone();
+    bits.extend_from_bitslice(&five_g_s_tmsi.amf_pointer.0);
+    bits.extend_from_raw_slice(&five_g_s_tmsi.five_g_tmsi.0);
+    bits
+}
@@ -0,0 +1,384 @@
+//! pdu_session_resource_modify - AMF orders changes to the QoS flows and bit rates of PDU sessions
+
+use super::{build_e1ap, build_f1ap, build_ngap, build_rrc, GnbCuCp, Workflow};
+use crate::datastore::{PduSessionState, QosFlowState, UeState};
+use anyhow::{anyhow, bail, Result};
+use asn1_per::*;
+use e1ap::{PduSessionResourceModifiedItem, UpTnlInformation};
+use f1ap::{DrbsToBeModifiedItem, DrbsToBeModifiedList, SrbId, UeContextModificationProcedure};
+use ngap::{
+    Cause, CauseRadioNetwork, PduSessionResourceFailedToModifyItemModRes,
+    PduSessionResourceFailedToModifyListModRes, PduSessionResourceModifyItemModReq,
+    PduSessionResourceModifyItemModRes, PduSessionResourceModifyListModRes,
+    PduSessionResourceModifyRequest, PduSessionResourceModifyRequestTransfer,
+    PduSessionResourceModifyResponse,
+};
+use slog::{debug, warn};
+use xxap::{GtpTunnel, PduSessionId};
+
+// The outcome of applying an AMF modify request item to a session.
+struct SessionModification {
+    session: PduSessionState,
+    pdu_session_resource_dl_ambr: Option<e1ap::BitRate>,
+    // The QoS flows added to and released from the session's DRBs, as (DRB ID, QFI) pairs.
+    qfis_added: Vec<(u8, u8)>,
+    qfis_added_or_modified: Vec<u8>,
+    qfis_released: Vec<(u8, u8)>,
+    nas_pdu: Option<Vec<u8>>,
+}
+
+impl<'a, G: GnbCuCp> Workflow<'a, G> {
+    // Pdu session resource modify procedure.
+    // See TS 38.401, figure 8.9.2-1.
+    //
+    // 1.    Ngap PduSessionResourceModifyRequest(Nas) <<
+    // 2. << E1ap BearerContextModificationRequest
+    // 3. >> E1ap BearerContextModificationResponse
+    // 4. << F1ap UeContextModificationRequest
+    // 5. >> F1ap UeContextModificationResponse
+    // 6. << Dl Rrc Message Transfer + Rrc Reconfiguration + Nas PDU Session Modification Command
+    // 7. >> Ul Rrc Message Transfer + Rrc Reconfiguration Complete
+    // 8.    Ngap PduSessionResourceModifyResponse >>
+    pub async fn pdu_session_resource_modify(
+        &self,
+        r: PduSessionResourceModifyRequest,
+    ) -> PduSessionResourceModifyResponse {
+        self.log_message("PduSessionResourceModifyRequest(Nas) <<");
+
+        let amf_ue_ngap_id = r.amf_ue_ngap_id;
+        let ran_ue_ngap_id = r.ran_ue_ngap_id;
+
+        // Save off the sessions IDs in case of error.
+        let requested_session_ids: Vec<PduSessionId> = r
+            .pdu_session_resource_modify_list_mod_req
+            .0
+            .iter()
+            .map(|item| item.pdu_session_id)
+            .collect();
+
+        let (ok_sessions, mut failed_sessions) = self
+            .pdu_session_resource_modify_inner(r)
+            .await
+            .unwrap_or_else(|e| {
+                warn!(self.logger, "Error processing session modify - {}", e);
+                (vec![], vec![])
+            });
+
+        // Any requested session that neither succeeded nor failed with a specific cause
+        // failed for an unspecified reason.
+        for pdu_session_id in requested_session_ids {
+            if ok_sessions
+                .iter()
+                .all(|x| x.pdu_session_id.0 != pdu_session_id.0)
+                && failed_sessions
+                    .iter()
+                    .all(|x| x.pdu_session_id.0 != pdu_session_id.0)
+            {
+                match build_ngap::build_pdu_session_resource_failed_to_modify_item_mod_res(
+                    pdu_session_id,
+                    Cause::RadioNetwork(CauseRadioNetwork::Unspecified),
+                ) {
+                    Ok(x) => failed_sessions.push(x),
+                    Err(e) => warn!(self.logger, "Build failed to modify item failed - {e}"),
+                }
+            }
+        }
+
+        self.log_message("PduSessionResourceModifyResponse >>");
+        PduSessionResourceModifyResponse {
+            amf_ue_ngap_id,
+            ran_ue_ngap_id,
+            pdu_session_resource_modify_list_mod_res: NonEmpty::from_vec(ok_sessions)
+                .map(PduSessionResourceModifyListModRes),
+            pdu_session_resource_failed_to_modify_list_mod_res: NonEmpty::from_vec(failed_sessions)
+                .map(PduSessionResourceFailedToModifyListModRes),
+            user_location_information: None,
+            criticality_diagnostics: None,
+        }
+    }
+
+    async fn pdu_session_resource_modify_inner(
+        &self,
+        r: PduSessionResourceModifyRequest,
+    ) -> Result<(
+        Vec<PduSessionResourceModifyItemModRes>,
+        Vec<PduSessionResourceFailedToModifyItemModRes>,
+    )> {
+        // Load UE.
+        debug!(self.logger, "Retrieve UE {:#010x}", r.ran_ue_ngap_id.0);
+        let mut ue = self.retrieve(&r.ran_ue_ngap_id.0).await?;
+
+        // Work out what each session looks like after the modification.
+        let mut modifications = vec![];
+        let mut failed_sessions = vec![];
+        for item in r.pdu_session_resource_modify_list_mod_req.0 {
+            let pdu_session_id = item.pdu_session_id;
+            let Some(session) = ue
+                .pdu_sessions
+                .iter()
+                .find(|x| x.pdu_session_id == pdu_session_id.0)
+            else {
+                warn!(self.logger, "Unknown PDU session {}", pdu_session_id.0);
+                failed_sessions.push(
+                    build_ngap::build_pdu_session_resource_failed_to_modify_item_mod_res(
+                        pdu_session_id,
+                        Cause::RadioNetwork(CauseRadioNetwork::UnknownPduSessionId),
+                    )?,
+                );
+                continue;
+            };
+            match session_modification(session.clone(), item) {
+                Ok(x) => modifications.push(x),
+                Err(e) => warn!(
+                    self.logger,
+                    "Can't modify session {} - {}", pdu_session_id.0, e
+                ),
+            }
+        }
+        let Some(modifications) = NonEmpty::from_vec(modifications) else {
+            return Ok((vec![], failed_sessions));
+        };
+
+        // Change the QoS flows on the CU-UP, and get back the uplink tunnel of each DRB.
+        let drbs = self.e1_qos_flow_modify(&ue, &modifications).await?;
+
+        // Change the QoS flows on the DU.
+        self.f1_qos_flow_modify(&ue, drbs).await?;
+
+        // Change the UE's QoS flow to DRB mapping and pass on the Nas messages.
+        self.rrc_qos_flow_reconfiguration(&ue, &modifications)
+            .await?;
+
+        // Update and write back UE.
+        let mut ok_sessions = vec![];
+        for modification in modifications {
+            let pdu_session_id = modification.session.pdu_session_id;
+            if let Some(session) = ue.pdu_session_mut(pdu_session_id) {
+                *session = modification.session;
+            }
+            ok_sessions.push(build_ngap::build_pdu_session_resource_modify_item_mod_res(
+                PduSessionId(pdu_session_id),
+                modification.qfis_added_or_modified,
+            )?);
+        }
+        debug!(self.logger, "Store UE {:#010x}", ue.key);
+        self.store(ue.key, ue, self.config().ue_ttl_secs).await?;
+
+        Ok((ok_sessions, failed_sessions))
+    }
+
+    async fn e1_qos_flow_modify(
+        &self,
+        ue: &UeState,
+        modifications: &NonEmpty<SessionModification>,
+    ) -> Result<NonEmpty<DrbsToBeModifiedItem>> {
+        let mut items = vec![];
+        for x in modifications.iter() {
+            items.push(build_e1ap::build_e1_qos_flow_modify_item(
+                &x.session,
+                x.pdu_session_resource_dl_ambr,
+            )?);
+        }
+        let items = NonEmpty::from_vec(items).ok_or_else(|| anyhow!("No E1 modify items"))?;
+        let modified_items = self
+            .perform_bearer_context_modification(ue, None, items)
+            .await?;
+
+        // Each DRB needs to be told its uplink tunnel afresh when it is modified on the DU.
+        let mut drbs = vec![];
+        for modification in modifications.iter() {
+            let session = &modification.session;
+            for drb in session.drbs.iter() {
+                let gtp_tunnel = ul_tunnel(session.pdu_session_id, drb.drb_id, &modified_items)?;
+                drbs.push(build_f1ap::build_drb_to_be_modified_item(
+                    session, drb, gtp_tunnel,
+                )?);
+            }
+        }
+        NonEmpty::from_vec(drbs).ok_or_else(|| anyhow!("No DRBs to modify"))
+    }
+
+    async fn f1_qos_flow_modify(
+        &self,
+        ue: &UeState,
+        drbs: NonEmpty<DrbsToBeModifiedItem>,
+    ) -> Result<()> {
+        let ue_context_modification_request =
+            build_f1ap::build_ue_context_modification_request(ue, Some(DrbsToBeModifiedList(drbs)));
+
+        self.log_message("<< F1ap UeContextModificationRequest");
+        let ue_context_modification_response = self
+            .f1ap_request::<UeContextModificationProcedure>(
+                ue_context_modification_request,
+                self.logger,
+            )
+            .await?;
+        self.log_message(">> F1ap UeContextModificationResponse");
+
+        if let Some(failed) = ue_context_modification_response.drbs_failed_to_be_modified_list {
+            bail!(
+                "DU failed to modify DRBs {:?}",
+                failed.0.iter().map(|x| x.drb_id.0).collect::<Vec<_>>()
+            );
+        }
+        Ok(())
+    }
+
+    async fn rrc_qos_flow_reconfiguration(
+        &self,
+        ue: &UeState,
+        modifications: &NonEmpty<SessionModification>,
+    ) -> Result<()> {
+        let nas_messages = NonEmpty::from_vec(
+            modifications
+                .iter()
+                .filter_map(|x| x.nas_pdu.clone())
+                .collect(),
+        );
+        let drbs = NonEmpty::from_vec(
+            modifications
+                .iter()
+                .flat_map(|x| {
+                    x.session.drbs.iter().filter_map(|drb| {
+                        let qfis_added = qfis_of_drb(&x.qfis_added, drb.drb_id);
+                        let qfis_released = qfis_of_drb(&x.qfis_released, drb.drb_id);
+                        (!qfis_added.is_empty() || !qfis_released.is_empty()).then(|| {
+                            build_rrc::build_drb_qos_flow_mapping(
+                                &x.session,
+                                drb,
+                                qfis_added,
+                                qfis_released,
+                            )
+                        })
+                    })
+                })
+                .collect(),
+        );
+
+        // Nothing changes from the UE's point of view - for example, just a change of bit rate.
+        if nas_messages.is_none() && drbs.is_none() {
+            return Ok(());
+        }
+
+        let rrc_transaction = self.new_rrc_transaction(ue).await;
+        let rrc_container = build_rrc::build_rrc_reconfiguration_drbs(0, nas_messages, drbs)?;
+        self.log_message("<< RrcReconfiguration");
+        self.send_rrc_to_ue(ue, SrbId(1), rrc_container, self.logger)
+            .await;
+        let _rrc_reconfiguration_complete = rrc_transaction.recv().await?;
+        self.log_message(">> RrcReconfigurationComplete");
+        Ok(())
+    }
+}
+
+// Apply the AMF's requested QoS flow changes to a copy of the session.
+fn session_modification(
+    mut session: PduSessionState,
+    item: PduSessionResourceModifyItemModReq,
+) -> Result<SessionModification> {
+    let transfer = PduSessionResourceModifyRequestTransfer::from_bytes(
+        &item.pdu_session_resource_modify_request_transfer,
+    )?;
+
+    // TODO - support a change of the 5GC's uplink tunnel (UL NG-U UP TNL Modify List).
+
+    let mut qfis_released = vec![];
+    if let Some(x) = transfer.qos_flow_to_release_list {
+        for flow in x.0 {
+            let qfi = flow.qos_flow_identifier.0;
+            session.qos_flows.retain(|x| x.qfi != qfi);
+            for drb in session.drbs.iter_mut().filter(|x| x.qfis.contains(&qfi)) {
+                drb.qfis.retain(|x| *x != qfi);
+                qfis_released.push((drb.drb_id, qfi));
+            }
+        }
+    }
+
+    let mut qfis_added = vec![];
+    let mut qfis_added_or_modified = vec![];
+    if let Some(x) = transfer.qos_flow_add_or_modify_request_list {
+        for flow in x.0 {
+            let qfi = flow.qos_flow_identifier.0;
+            let existing = session.qos_flows.iter_mut().find(|x| x.qfi == qfi);
+            match (existing, flow.qos_flow_level_qos_parameters) {
+                (Some(existing), Some(params)) => *existing = QosFlowState::new(qfi, &params),
+                (Some(_), None) => (),
+                (None, Some(params)) => {
+                    // A new QoS flow goes on the DRB that carries the session's other QoS flows with the
+                    // same 5QI, or else on the default DRB.
+                    let flow = QosFlowState::new(qfi, &params);
+                    let drb_index = session
+                        .drbs
+                        .iter()
+                        .position(|drb| {
+                            session
+                                .drb_qos_flows(drb)
+                                .any(|x| x.five_qi == flow.five_qi)
+                        })
+                        .unwrap_or(0);
+                    let drb = session
+                        .drbs
+                        .get_mut(drb_index)
+                        .ok_or_else(|| anyhow!("Session has no DRBs"))?;
+                    drb.qfis.push(qfi);
+                    qfis_added.push((drb.drb_id, qfi));
+                    session.qos_flows.push(flow);
+                }
+                (None, None) => bail!("New QoS flow {} has no QoS parameters", qfi),
+            }
+            qfis_added_or_modified.push(qfi);
+        }
+    }
+
+    // We don't remove DRBs, so each DRB must keep at least one QoS flow.
+    if let Some(drb) = session.drbs.iter().find(|x| x.qfis.is_empty()) {
+        bail!(
+            "Modification would leave DRB {} with no QoS flows",
+            drb.drb_id
+        );
+    }
+
+    Ok(SessionModification {
+        session,
+        pdu_session_resource_dl_ambr: transfer
+            .pdu_session_aggregate_maximum_bit_rate
+            .map(|x| e1ap::BitRate(x.pdu_session_aggregate_maximum_bit_rate_dl.0)),
+        qfis_added,
+        qfis_added_or_modified,
+        qfis_released,
+        nas_pdu: item.nas_pdu.map(|x| x.0),
+    })
+}
+
+fn qfis_of_drb(drb_qfis: &[(u8, u8)], drb_id: u8) -> Vec<u8> {
+    drb_qfis
+        .iter()
+        .filter(|(x, _)| *x == drb_id)
+        .map(|(_, qfi)| *qfi)
+        .collect()
+}
+
+// Find the uplink tunnel of a session's DRB in the CU-UP's bearer context modification response.
+pub fn ul_tunnel(
+    pdu_session_id: u8,
+    drb_id: u8,
+    items: &NonEmpty<PduSessionResourceModifiedItem>,
+) -> Result<GtpTunnel> {
+    let item = items
+        .iter()
+        .find(|x| x.pdu_session_id.0 == pdu_session_id)
+        .ok_or_else(|| anyhow!("CU-UP did not modify session {}", pdu_session_id))?;
+    let drb = item
+        .drb_modified_list_ng_ran
+        .as_ref()
+        .and_then(|x| x.0.iter().find(|x| x.drb_id.0 == drb_id))
+        .ok_or_else(|| anyhow!("CU-UP did not modify DRB {}", drb_id))?;
+    let UpTnlInformation::GtpTunnel(gtp_tunnel) = &drb
+        .ul_up_transport_parameters
+        .as_ref()
+        .ok_or_else(|| anyhow!("CU-UP did not supply UL tunnel of DRB {}", drb_id))?
+        .0
+        .first()
+        .up_tnl_information;
+    Ok(gtp_tunnel.clone())
+}
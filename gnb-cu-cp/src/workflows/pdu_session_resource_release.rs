@@ -93,11 +93,16 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
 
         // Update and write back UE.
         ue.gnb_cu_up_ue_e1ap_id = None;
+        ue.pdu_sessions.clear();
         debug!(self.logger, "Store UE {:#010x}", ue.key);
         self.store(ue.key, ue, self.config().ue_ttl_secs).await
     }
 
-    async fn perform_e1_bearer_release(&self, ue: &UeState, gnb_cu_up_ue_e1ap_id: GnbCuUpUeE1apId) {
+    pub async fn perform_e1_bearer_release(
+        &self,
+        ue: &UeState,
+        gnb_cu_up_ue_e1ap_id: GnbCuUpUeE1apId,
+    ) {
         let bearer_context_release_command = BearerContextReleaseCommand {
             gnb_cu_cp_ue_e1ap_id: GnbCuCpUeE1apId(ue.key),
             gnb_cu_up_ue_e1ap_id,
@@ -1,27 +1,32 @@
 //! pdu_session_resource_setup - AMF orders setup of PDU sessions and DRBs
 
 use super::{build_e1ap, GnbCuCp, Workflow};
-use crate::datastore::UeState;
+use crate::datastore::{DrbState, PduSessionState, QosFlowState, UeState};
 use anyhow::{anyhow, bail, Result};
 use asn1_per::*;
 use e1ap::*;
 use f1ap::{
-    CellGroupConfig, DrbsSetupItem, DrbsToBeSetupItem, DrbsToBeSetupList, UeContextSetupProcedure,
+    CellGroupConfig, DrbsSetupItem, DrbsToBeSetupItem, DrbsToBeSetupList, RrcContainer,
+    UeContextSetupProcedure,
 };
 use ngap::{
     PduSessionResourceFailedToSetupItemSuRes, PduSessionResourceFailedToSetupListSuRes,
-    PduSessionResourceSetupItemSuReq, PduSessionResourceSetupItemSuRes,
-    PduSessionResourceSetupListSuRes, PduSessionResourceSetupRequest,
+    PduSessionResourceSetupItemSuRes, PduSessionResourceSetupListSuRes,
+    PduSessionResourceSetupRequest, PduSessionResourceSetupRequestTransfer,
     PduSessionResourceSetupResponse,
 };
 use slog::{debug, warn, Logger};
 use xxap::*;
 
-type Stage1 = ngap::PduSessionResourceSetupItemSuReq;
-type Stage2 = (Stage1, e1ap::PduSessionResourceSetupItem);
-type Stage3 = (Stage2, f1ap::DrbsSetupItem);
-type Stage4 = (Stage3, e1ap::PduSessionResourceModifiedItem);
-type Stage5 = e1ap::PduSessionResourceSetupItem;
+pub type Stage1 = ngap::PduSessionResourceSetupItemSuReq;
+pub type Stage2 = (Stage1, e1ap::PduSessionResourceSetupItem);
+pub type Stage3 = (Stage2, Vec<f1ap::DrbsSetupItem>);
+pub type Stage4 = (Stage3, e1ap::PduSessionResourceModifiedItem);
+pub type Stage5 = e1ap::PduSessionResourceSetupItem;
+
+// The range of DRB-Identity in TS 38.331.
+const MIN_DRB_ID: u8 = 1;
+const MAX_DRB_ID: u8 = 32;
 
 impl<'a, G: GnbCuCp> Workflow<'a, G> {
     // Pdu session resource setup procedure.
@@ -70,9 +75,19 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
         let failed_sessions: Vec<PduSessionResourceFailedToSetupItemSuRes> = requested_session_ids
             .into_iter()
             .filter(|x| ok_sessions.iter().all(|item| item.pdu_session_id.0 != x.0))
-            .map(|x| PduSessionResourceFailedToSetupItemSuRes {
-                pdu_session_id: x,
-                pdu_session_resource_setup_unsuccessful_transfer: vec![], // TODO,
+            .filter_map(|x| {
+                match super::build_ngap::build_pdu_session_resource_setup_unsuccessful_transfer(
+                    ngap::Cause::RadioNetwork(ngap::CauseRadioNetwork::Unspecified),
+                ) {
+                    Ok(transfer) => Some(PduSessionResourceFailedToSetupItemSuRes {
+                        pdu_session_id: x,
+                        pdu_session_resource_setup_unsuccessful_transfer: transfer,
+                    }),
+                    Err(e) => {
+                        warn!(self.logger, "Build failed to setup item failed - {e}");
+                        None
+                    }
+                }
             })
             .collect();
 
@@ -99,30 +114,73 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
         // Load UE.
         debug!(self.logger, "Retrieve UE {:#010x}", r.ran_ue_ngap_id.0);
         let mut ue = self.retrieve(&r.ran_ue_ngap_id.0).await?;
+        let had_bearer_context = ue.gnb_cu_up_ue_e1ap_id.is_some();
+        let requested_sessions = requested_pdu_sessions(
+            &ue,
+            &r.pdu_session_resource_setup_list_su_req.0,
+            self.logger,
+        );
 
         let sessions = self
-            .e1_context_setup(&mut ue, r.pdu_session_resource_setup_list_su_req.0)
-            .await?;
-        let (sessions, cell_group_config) = self.f1_context_setup(&ue, sessions).await?;
-        let sessions = self.e1_context_modify(&ue, sessions).await?;
-        let sessions = self
-            .rrc_reconfiguration(&ue, sessions, cell_group_config)
+            .e1_context_setup(
+                &mut ue,
+                r.pdu_session_resource_setup_list_su_req.0,
+                &requested_sessions,
+            )
             .await?;
-        let sessions = self.ngap_responses(&ue, sessions).await?;
 
-        // Write back UE.
+        let sessions = match self
+            .pdu_session_resource_setup_later_stages(&ue, sessions, &requested_sessions)
+            .await
+        {
+            Ok(sessions) => sessions,
+            Err(e) => {
+                // Don't leave behind a bearer context on the CU-UP that the UE state knows nothing about.
+                if !had_bearer_context {
+                    if let Some(gnb_cu_up_ue_e1ap_id) = ue.gnb_cu_up_ue_e1ap_id {
+                        self.perform_e1_bearer_release(&ue, gnb_cu_up_ue_e1ap_id)
+                            .await;
+                    }
+                }
+                return Err(e);
+            }
+        };
+
+        // Update and write back UE.
+        remember_pdu_sessions(
+            &mut ue,
+            requested_sessions,
+            sessions.iter().map(|x| x.pdu_session_id.0),
+        );
         debug!(self.logger, "Store UE {:#010x}", ue.key);
         self.store(ue.key, ue, self.config().ue_ttl_secs).await?;
 
         Ok(sessions)
     }
 
-    async fn e1_context_setup(
+    async fn pdu_session_resource_setup_later_stages(
+        &self,
+        ue: &UeState,
+        sessions: Vec<Stage2>,
+        requested_sessions: &[PduSessionState],
+    ) -> Result<NonEmpty<PduSessionResourceSetupItemSuRes>> {
+        let (sessions, cell_group_config) = self
+            .f1_context_setup(ue, sessions, requested_sessions, None)
+            .await?;
+        let sessions = self.e1_context_modify(ue, sessions).await?;
+        let sessions = self
+            .rrc_reconfiguration(ue, sessions, requested_sessions, cell_group_config, vec![])
+            .await?;
+        self.ngap_responses(ue, sessions).await
+    }
+
+    pub async fn e1_context_setup(
         &self,
         ue: &mut UeState,
         sessions: NonEmpty<Stage1>,
+        requested_sessions: &[PduSessionState],
     ) -> Result<Vec<Stage2>> {
-        let requested = build_e1_setup_items(ue, &sessions, self.logger)?;
+        let requested = build_e1_setup_items(&sessions, requested_sessions, self.logger)?;
         let successes = self.perform_bearer_context_setup(ue, requested).await?;
         Ok(keep_matching_items(
             sessions.into(),
@@ -131,40 +189,74 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
         ))
     }
 
-    async fn f1_context_setup(
+    // The Rrc container, if supplied, is passed to the UE by the DU.  This is how the Security Mode Command
+    // is sent when sessions are set up during initial context setup.
+    pub async fn f1_context_setup(
         &self,
         ue: &UeState,
         sessions: Vec<Stage2>,
+        requested_sessions: &[PduSessionState],
+        rrc_container: Option<RrcContainer>,
     ) -> Result<(Vec<Stage3>, CellGroupConfig)> {
-        let requested = build_drbs_to_be_setup_items(&sessions, self.logger)?;
-        let (successes, cell_group_config) = self.perform_ue_context_setup(ue, requested).await?;
-        let successful_sessions = keep_matching_items(sessions, successes.into(), self.logger);
+        let requested = build_drbs_to_be_setup_items(&sessions, requested_sessions, self.logger)?;
+        let (successes, cell_group_config) = self
+            .perform_ue_context_setup(ue, requested, rrc_container)
+            .await?;
+        let successful_sessions = keep_sessions_with_drbs(sessions, successes.into(), self.logger);
         Ok((successful_sessions, cell_group_config))
     }
 
-    async fn e1_context_modify(&self, ue: &UeState, sessions: Vec<Stage3>) -> Result<Vec<Stage4>> {
+    pub async fn e1_context_modify(
+        &self,
+        ue: &UeState,
+        sessions: Vec<Stage3>,
+    ) -> Result<Vec<Stage4>> {
         let requested = build_e1_modify_items(&sessions, self.logger)?;
         let successes = self
-            .perform_bearer_context_modification(ue, requested)
+            .perform_bearer_context_modification(ue, None, requested)
             .await?;
         Ok(keep_matching_items(sessions, successes.into(), self.logger))
     }
 
-    async fn rrc_reconfiguration(
+    // Any supplied Nas messages go to the UE ahead of the sessions' own Nas messages.
+    pub async fn rrc_reconfiguration(
         &self,
         ue: &UeState,
         mut sessions: Vec<Stage4>,
+        requested_sessions: &[PduSessionState],
         cell_group_config: f1ap::CellGroupConfig,
+        mut nas_messages: Vec<Vec<u8>>,
     ) -> Result<Vec<Stage5>> {
+        // The UE is told about the DRBs of each session and the QoS flows mapped to them.
+        let drbs = NonEmpty::from_vec(
+            requested_sessions
+                .iter()
+                .filter(|x| sessions.iter().any(|y| y.id() == x.pdu_session_id))
+                .flat_map(|session| {
+                    session
+                        .drbs
+                        .iter()
+                        .map(|drb| super::build_rrc::build_drb_to_add(session, drb))
+                })
+                .collect(),
+        )
+        .ok_or_else(|| anyhow!("No DRBs to add"))?;
+
         // Deconstruct the sessions input into a list of NAS messages (to give to UE)
         // and a list of E1 setup items (with downlink tunnel information to give back to 5G core).
-        let (nas_messages, sessions) = sessions
+        // A session without a Nas message is still set up.
+        let sessions = sessions
             .drain(..)
-            .filter_map(|(((a, b), _), _)| a.pdu_session_nas_pdu.map(|x| (x.0, b)))
-            .unzip();
+            .map(|(((a, b), _), _)| {
+                if let Some(x) = a.pdu_session_nas_pdu {
+                    nas_messages.push(x.0);
+                }
+                b
+            })
+            .collect();
 
         let _rrc_reconfiguration_complete = self
-            .perform_rrc_reconfiguration(ue, nas_messages, cell_group_config)
+            .perform_rrc_reconfiguration(ue, nas_messages, drbs, cell_group_config)
             .await?;
 
         Ok(sessions)
@@ -174,12 +266,13 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
         &self,
         ue: &UeState,
         items: NonEmpty<DrbsToBeSetupItem>,
+        rrc_container: Option<RrcContainer>,
     ) -> Result<(NonEmpty<DrbsSetupItem>, CellGroupConfig)> {
         let ue_context_setup_request = super::build_f1ap::build_ue_context_setup_request(
             self.gnb_cu_cp,
             ue,
             Some(DrbsToBeSetupList(items)),
-            None,
+            rrc_container,
         )?;
 
         // Send UeContextSetupRequest to DU.
@@ -213,9 +306,14 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
         ue: &mut UeState,
         items: NonEmpty<PduSessionResourceToSetupItem>,
     ) -> Result<NonEmpty<PduSessionResourceSetupItem>> {
+        // A UE that already has a bearer context stays on its CU-UP.
+        if ue.gnb_cu_up_ue_e1ap_id.is_none() {
+            self.select_cu_up(ue, &items).await?;
+        }
+
         // Send BearerContextSetup to CU-UP.
         let bearer_context_setup =
-            build_e1ap::build_bearer_context_setup(ue, PlmnIdentity(self.config().plmn), items);
+            build_e1ap::build_bearer_context_setup(ue, PlmnIdentity(self.config().ran.plmn), items);
 
         debug!(self.logger, "<< BearerContextSetupRequest");
         match self
@@ -252,17 +350,75 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
         }
     }
 
-    async fn perform_bearer_context_modification(
+    // Choose a CU-UP that supports the PLMN, slices and 5QIs of the sessions, using the capabilities that the
+    // CU-UPs gave in E1 Setup.  The UEs are spread over the CU-UPs that qualify.  A CU-UP that this worker has
+    // no TNLA to, for example because it has gone away, is passed over.
+    async fn select_cu_up(
         &self,
         ue: &UeState,
+        items: &NonEmpty<PduSessionResourceToSetupItem>,
+    ) -> Result<()> {
+        let slices: Vec<xxap::Snssai> = items.iter().map(|x| x.snssai.clone().into()).collect();
+        let five_qis: Vec<u8> = items
+            .iter()
+            .flat_map(|x| x.drb_to_setup_list_ng_ran.0.iter())
+            .flat_map(|x| x.qos_flow_information_to_be_setup.0.iter())
+            .filter_map(
+                |x| match &x.qos_flow_level_qos_parameters.qos_characteristics {
+                    QosCharacteristics::NonDynamic5qi(x) => Some(x.five_qi),
+                    QosCharacteristics::Dynamic5qi(_) => None,
+                },
+            )
+            .collect();
+        let plmn = self.config().ran.plmn;
+        let mut cu_ups: Vec<_> = self
+            .retrieve_all_cu_ups()
+            .await?
+            .into_iter()
+            .filter(|x| x.supports(&plmn, &slices, &five_qis))
+            .collect();
+        cu_ups.sort_by_key(|x| x.gnb_cu_up_id.0);
+
+        let start = ue.key as usize;
+        for i in 0..cu_ups.len() {
+            let cu_up = &cu_ups[(start + i) % cu_ups.len()];
+            match self.bind_ue_to_cu_up(ue.key, &cu_up.address).await {
+                Ok(()) => {
+                    debug!(
+                        self.logger,
+                        "Selected CU-UP {} for UE {:#010x}", cu_up.gnb_cu_up_id.0, ue.key
+                    );
+                    return Ok(());
+                }
+                Err(e) => debug!(
+                    self.logger,
+                    "CU-UP {} not reachable - {}", cu_up.gnb_cu_up_id.0, e
+                ),
+            }
+        }
+        bail!(
+            "No CU-UP supports slices {:?} and 5QIs {:?}",
+            slices,
+            five_qis
+        )
+    }
+
+    pub async fn perform_bearer_context_modification(
+        &self,
+        ue: &UeState,
+        bearer_context_status_change: Option<BearerContextStatusChange>,
         items: NonEmpty<PduSessionResourceToModifyItem>,
     ) -> Result<NonEmpty<PduSessionResourceModifiedItem>> {
         let Some(gnb_cu_up_ue_e1ap_id) = ue.gnb_cu_up_ue_e1ap_id else {
             bail!("No E1AP ID on UE");
         };
 
-        let bearer_context_modification =
-            build_e1ap::build_bearer_context_modification(ue, gnb_cu_up_ue_e1ap_id, items);
+        let bearer_context_modification = build_e1ap::build_bearer_context_modification(
+            ue,
+            gnb_cu_up_ue_e1ap_id,
+            bearer_context_status_change,
+            Some(items),
+        );
         debug!(self.logger, "<< BearerContextSetupRequest");
         let resource_modify_items = match self
                 .e1ap_request::<BearerContextModificationProcedure>(
@@ -297,13 +453,18 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
         &self,
         ue: &UeState,
         nas_messages: Vec<Vec<u8>>,
+        drbs: NonEmpty<rrc::DrbToAddMod>,
         cell_group_config: f1ap::CellGroupConfig,
     ) -> Result<rrc::UlDcchMessage> {
         // Perform Rrc Reconfiguration including the Nas messages from earlier and the cell group config received from the DU.
         let rrc_transaction = self.new_rrc_transaction(ue).await;
         let nas_messages = NonEmpty::from_vec(nas_messages);
-        let rrc_container =
-            super::build_rrc::build_rrc_reconfiguration(0, nas_messages, cell_group_config.0)?;
+        let rrc_container = super::build_rrc::build_rrc_reconfiguration(
+            0,
+            nas_messages,
+            drbs,
+            cell_group_config.0,
+        )?;
         self.log_message("<< RrcReconfiguration");
         self.send_rrc_to_ue(ue, f1ap::SrbId(1), rrc_container, self.logger)
             .await;
@@ -312,7 +473,7 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
         Ok(rrc_reconfiguration_complete)
     }
 
-    async fn ngap_responses(
+    pub async fn ngap_responses(
         &self,
         _ue: &UeState,
         mut sessions: Vec<Stage5>,
@@ -339,6 +500,92 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
     }
 }
 
+// The state of each requested session, to be remembered if the session is set up successfully.
+// The DRBs are given IDs not used by the UE's other sessions.
+pub fn requested_pdu_sessions(
+    ue: &UeState,
+    sessions: &NonEmpty<Stage1>,
+    logger: &Logger,
+) -> Vec<PduSessionState> {
+    let mut drb_ids_in_use: Vec<u8> = ue
+        .pdu_sessions
+        .iter()
+        .filter(|x| {
+            sessions
+                .iter()
+                .all(|y| y.pdu_session_id.0 != x.pdu_session_id)
+        })
+        .flat_map(|x| x.drbs.iter().map(|x| x.drb_id))
+        .collect();
+    sessions
+        .iter()
+        .flat_map(|x| {
+            pdu_session_state(x, &mut drb_ids_in_use)
+                .map_err(|e| warn!(logger, "Bad session setup request - {e}"))
+        })
+        .collect()
+}
+
+// QoS flows with the same 5QI are mapped to the same DRB.
+fn pdu_session_state(r: &Stage1, drb_ids_in_use: &mut Vec<u8>) -> Result<PduSessionState> {
+    let session_params = PduSessionResourceSetupRequestTransfer::from_bytes(
+        &r.pdu_session_resource_setup_request_transfer,
+    )?;
+    let xxap::Snssai(sst, sd) = r.snssai.clone().into();
+    let qos_flows: Vec<QosFlowState> = session_params
+        .qos_flow_setup_request_list
+        .0
+        .iter()
+        .map(|x| QosFlowState::new(x.qos_flow_identifier.0, &x.qos_flow_level_qos_parameters))
+        .collect();
+
+    let mut five_qi_drbs: Vec<(u8, DrbState)> = vec![];
+    for flow in qos_flows.iter() {
+        match five_qi_drbs.iter_mut().find(|(x, _)| *x == flow.five_qi) {
+            Some((_, drb)) => drb.qfis.push(flow.qfi),
+            None => {
+                let drb_id = (MIN_DRB_ID..=MAX_DRB_ID)
+                    .find(|x| !drb_ids_in_use.contains(x))
+                    .ok_or_else(|| anyhow!("No free DRB ID"))?;
+                drb_ids_in_use.push(drb_id);
+                five_qi_drbs.push((
+                    flow.five_qi,
+                    DrbState {
+                        drb_id,
+                        qfis: vec![flow.qfi],
+                    },
+                ));
+            }
+        }
+    }
+
+    Ok(PduSessionState {
+        pdu_session_id: r.pdu_session_id.0,
+        sst,
+        sd,
+        qos_flows,
+        drbs: five_qi_drbs.into_iter().map(|(_, drb)| drb).collect(),
+    })
+}
+
+// Add the sessions that were set up to the UE, replacing any earlier sessions with the same IDs.
+pub fn remember_pdu_sessions(
+    ue: &mut UeState,
+    requested_sessions: Vec<PduSessionState>,
+    set_up_session_ids: impl Iterator<Item = u8>,
+) {
+    for pdu_session_id in set_up_session_ids {
+        if let Some(session) = requested_sessions
+            .iter()
+            .find(|x| x.pdu_session_id == pdu_session_id)
+        {
+            ue.pdu_sessions
+                .retain(|x| x.pdu_session_id != pdu_session_id);
+            ue.pdu_sessions.push(session.clone());
+        }
+    }
+}
+
 trait HasId {
     fn id(&self) -> u8;
 }
@@ -364,6 +611,35 @@ fn keep_matching_items<T1: HasId, T2: HasId>(
     new_sessions
 }
 
+// Pairs each session with the DRBs that the DU set up for it.  A session is only kept if all of its
+// DRBs were set up.
+fn keep_sessions_with_drbs(
+    sessions: Vec<Stage2>,
+    mut drbs: Vec<f1ap::DrbsSetupItem>,
+    logger: &Logger,
+) -> Vec<Stage3> {
+    let mut new_sessions: Vec<Stage3> = vec![];
+    for session in sessions {
+        let drb_ids: Vec<u8> = session
+            .1
+            .drb_setup_list_ng_ran
+            .0
+            .iter()
+            .map(|x| x.drb_id.0)
+            .collect();
+        let (session_drbs, other_drbs): (Vec<_>, Vec<_>) = drbs
+            .into_iter()
+            .partition(|x| drb_ids.contains(&x.drb_id.0));
+        drbs = other_drbs;
+        if session_drbs.len() == drb_ids.len() {
+            new_sessions.push((session, session_drbs));
+        } else {
+            warn!(logger, "Not all DRBs of session {} set up", session.id());
+        }
+    }
+    new_sessions
+}
+
 impl HasId for Stage1 {
     fn id(&self) -> u8 {
         self.pdu_session_id.0
@@ -374,11 +650,6 @@ impl HasId for e1ap::PduSessionResourceSetupItem {
         self.pdu_session_id.0
     }
 }
-impl HasId for f1ap::DrbsSetupItem {
-    fn id(&self) -> u8 {
-        self.drb_id.0
-    }
-}
 impl HasId for e1ap::PduSessionResourceModifiedItem {
     fn id(&self) -> u8 {
         self.pdu_session_id.0
@@ -402,44 +673,48 @@ impl HasId for Stage4 {
 
 fn build_drbs_to_be_setup_items(
     sessions: &Vec<Stage2>,
+    requested_sessions: &[PduSessionState],
     logger: &Logger,
 ) -> Result<NonEmpty<DrbsToBeSetupItem>> {
     let mut items = vec![];
-    for session in sessions {
-        let (
-            PduSessionResourceSetupItemSuReq {
-                pdu_session_id,
-                snssai,
-                ..
-            },
-            PduSessionResourceSetupItem {
-                drb_setup_list_ng_ran,
-                ..
-            },
-        ) = session;
-        if drb_setup_list_ng_ran.0.len() > 1 {
-            warn!(
+    for (
+        _,
+        PduSessionResourceSetupItem {
+            pdu_session_id,
+            drb_setup_list_ng_ran,
+            ..
+        },
+    ) in sessions
+    {
+        let Some(session) = requested_sessions
+            .iter()
+            .find(|x| x.pdu_session_id == pdu_session_id.0)
+        else {
+            warn!(logger, "Session {} not requested", pdu_session_id.0);
+            continue;
+        };
+        for drb in drb_setup_list_ng_ran.0.iter() {
+            let Some(drb_state) = session.drb(drb.drb_id.0) else {
+                warn!(logger, "DRB {} not requested", drb.drb_id.0);
+                continue;
+            };
+            let UpTnlInformation::GtpTunnel(gtp_tunnel) =
+                &drb.ul_up_transport_parameters.0.head.up_tnl_information;
+
+            debug!(
                 logger,
-                "More than 1 DRB not supported - later items are ignored"
-            )
-        }
-        let UpTnlInformation::GtpTunnel(gtp_tunnel) =
-            &drb_setup_list_ng_ran.0[0].ul_up_transport_parameters.0[0].up_tnl_information;
-
-        debug!(
-            logger,
-            "Pass through UL tunnel information from CU-UP to DU - {}/{:?}",
-            gtp_tunnel.transport_layer_address.to_string(),
-            gtp_tunnel.gtp_teid.0
-        );
-        let snssai: xxap::Snssai = snssai.clone().into();
-        match super::build_f1ap::build_drb_to_be_setup_item(
-            f1ap::DrbId(pdu_session_id.0),
-            snssai,
-            gtp_tunnel.clone(),
-        ) {
-            Ok(item) => items.push(item),
-            Err(e) => warn!(logger, "Build Drb setup item failed {:?}", e),
+                "Pass through UL tunnel information from CU-UP to DU - {}/{:?}",
+                gtp_tunnel.transport_layer_address.to_string(),
+                gtp_tunnel.gtp_teid.0
+            );
+            match super::build_f1ap::build_drb_to_be_setup_item(
+                session,
+                drb_state,
+                gtp_tunnel.clone(),
+            ) {
+                Ok(item) => items.push(item),
+                Err(e) => warn!(logger, "Build Drb setup item failed {:?}", e),
+            }
         }
     }
 
@@ -447,17 +722,22 @@ fn build_drbs_to_be_setup_items(
 }
 
 fn build_e1_setup_items(
-    ue: &UeState,
     sessions: &NonEmpty<Stage1>,
+    requested_sessions: &[PduSessionState],
     logger: &Logger,
 ) -> Result<NonEmpty<PduSessionResourceToSetupItem>> {
     let items: Vec<PduSessionResourceToSetupItem> = sessions
         .iter()
         .flat_map(|x| {
-            build_e1ap::build_e1_setup_item(ue, x, logger).map_err(|e| {
-                warn!(logger, "Build E1 setup item failed {:?}", e);
-                e
-            })
+            requested_sessions
+                .iter()
+                .find(|y| y.pdu_session_id == x.pdu_session_id.0)
+                .ok_or_else(|| anyhow!("No state for session {}", x.pdu_session_id.0))
+                .and_then(|session| build_e1ap::build_e1_setup_item(x, session, logger))
+                .map_err(|e| {
+                    warn!(logger, "Build E1 setup item failed {:?}", e);
+                    e
+                })
         })
         .collect();
     NonEmpty::from_vec(items).ok_or(anyhow!("No E1 setup items built successfully"))
@@ -469,16 +749,22 @@ fn build_e1_modify_items(
 ) -> Result<NonEmpty<PduSessionResourceToModifyItem>> {
     let mut items = vec![];
     for session in sessions {
-        let f1ap::UpTransportLayerInformation::GtpTunnel(gtp_tunnel) = &session
-            // Get the tunnel information returned by the DU...
+        let drbs = session
+            // Get the tunnel information returned by the DU for each DRB...
             .1
-            .dl_up_tnl_information_to_be_setup_list
-            .0
-            .first()
-            // ...reformulate to give it to the CU-UP
-            .dl_up_tnl_information;
+            .iter()
+            .map(|drb| {
+                let f1ap::UpTransportLayerInformation::GtpTunnel(gtp_tunnel) = &drb
+                    .dl_up_tnl_information_to_be_setup_list
+                    .0
+                    .first()
+                    .dl_up_tnl_information;
+                (DrbId(drb.drb_id.0), gtp_tunnel.clone())
+            })
+            .collect();
 
-        match build_e1ap::build_e1_modify_item(PduSessionId(session.id()), gtp_tunnel.clone()) {
+        // ...reformulate to give it to the CU-UP
+        match build_e1ap::build_e1_modify_item(PduSessionId(session.id()), drbs) {
             // ...and store in the list
             Ok(item) => items.push(item),
             Err(e) => {
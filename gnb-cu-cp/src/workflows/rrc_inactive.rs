@@ -0,0 +1,343 @@
+//! rrc_inactive - UE is suspended into RRC_INACTIVE and later resumes
+
+use super::{
+    build_e1ap, build_f1ap, build_rrc, pdu_session_resource_modify::ul_tunnel, GnbCuCp, Workflow,
+};
+use crate::datastore::UeState;
+use anyhow::{anyhow, bail, ensure, Result};
+use asn1_per::{nonempty, NonEmpty};
+use bitvec::prelude::*;
+use e1ap::{BearerContextModificationProcedure, BearerContextStatusChange};
+use f1ap::{
+    DrbActivity, DrbsSetupItem, DrbsToBeSetupItem, DrbsToBeSetupList, FiveGsTac,
+    InitialUlRrcMessageTransfer, SrbId, UeContextSetupProcedure, UeInactivityNotification,
+    UpTransportLayerInformation,
+};
+use ngap::NasPdu;
+use rand::Rng;
+use rrc::*;
+use slog::debug;
+use xxap::PduSessionId;
+
+/// The identity that a UE presents in its resume request.
+pub enum ResumeIdentity {
+    /// Full I-RNTI from an RrcResumeRequest1.
+    Full(u64),
+    /// Short I-RNTI from an RrcResumeRequest.
+    Short(u32),
+}
+
+/// The parts of a UE's resume request that are needed to find and authenticate it, and its cause, which is
+/// passed on to the AMF if the UE falls back to setup.
+pub struct ResumeRequest {
+    pub resume_identity: ResumeIdentity,
+    pub resume_mac_i: BitVec<u8, Msb0>,
+    pub resume_cause: ResumeCause,
+}
+
+impl<'a, G: GnbCuCp> Workflow<'a, G> {
+    // UE inactivity procedure.
+    // See TS 38.401, figure 8.6.1-1.
+    //
+    // 1. >> F1ap UeInactivityNotification
+    // --- CONDITIONAL IF THE UE HAS A BEARER CONTEXT -----
+    // 2. << E1ap BearerContextModificationRequest(Suspend)
+    // 3. >> E1ap BearerContextModificationResponse
+    // ----------------------------------------------------
+    // 4. << F1ap UeContextReleaseCommand(RrcRelease(SuspendConfig))
+    // 5. >> F1ap UeContextReleaseComplete
+    pub async fn ue_inactivity_notification(&self, i: UeInactivityNotification) -> Result<()> {
+        self.log_message(">> F1ap UeInactivityNotification");
+
+        if i.drb_activity_list
+            .0
+            .iter()
+            .any(|x| matches!(x.drb_activity, Some(DrbActivity::Active)))
+        {
+            debug!(self.logger, "UE still has active DRBs - nothing to do");
+            return Ok(());
+        }
+
+        debug!(self.logger, "Retrieve UE {:#010x}", i.gnb_cu_ue_f1ap_id.0);
+        let mut ue = self.retrieve(&i.gnb_cu_ue_f1ap_id.0).await?;
+
+        // TODO - check that the AMF supplied Core Network Assistance Information for RRC INACTIVE.
+
+        // The UE's RAN notification area is the tracking area of its cell.
+        let served_cell = self.served_cell(&ue.nr_cgi).await?;
+        let resume_identity = new_resume_identity(ue.key);
+        let suspend_config =
+            build_suspend_config(resume_identity, served_cell.five_gs_tac.as_ref());
+
+        // Steps 2-3.  The DU is about to release the UE, so the CU-UP must stop sending it downlink data.
+        if ue.gnb_cu_up_ue_e1ap_id.is_some() {
+            self.perform_bearer_context_status_change(&ue, BearerContextStatusChange::Suspend)
+                .await?;
+        }
+
+        // Steps 4-5.
+        self.perform_f1_context_release_with_rrc_release(&ue, Some(suspend_config))
+            .await?;
+
+        // Write back the UE with its resume identity, so that any worker can resume it.
+        ue.resume_identity = Some(resume_identity);
+        debug!(self.logger, "Store UE {:#010x}", ue.key);
+        self.store(ue.key, ue, self.config().ue_ttl_secs).await
+    }
+
+    // Find the UE that sent a resume request, and check that it is genuine.  On failure, the caller falls
+    // back to Rrc setup.
+    pub async fn retrieve_suspended_ue(&self, r: &ResumeRequest) -> Result<UeState> {
+        let ue_key = match r.resume_identity {
+            ResumeIdentity::Full(x) => x as u32,
+            // The short I-RNTI is too short to hold the UE's key, so is looked up instead.
+            ResumeIdentity::Short(x) => self
+                .ue_with_short_i_rnti(x)
+                .await?
+                .ok_or_else(|| anyhow!("No UE has short I-RNTI {:#x}", x))?,
+        };
+
+        debug!(self.logger, "Retrieve UE {:#010x}", ue_key);
+        let ue = self.retrieve(&ue_key).await?;
+        let suspended_with_identity = match r.resume_identity {
+            ResumeIdentity::Full(x) => ue.resume_identity == Some(x),
+            ResumeIdentity::Short(x) => ue.short_i_rnti() == Some(x),
+        };
+        ensure!(
+            suspended_with_identity,
+            "UE {:#010x} was not suspended with this resume identity",
+            ue_key
+        );
+        check_resume_mac_i(&r.resume_mac_i)?;
+        Ok(ue)
+    }
+
+    // Rrc resume procedure.
+    // See TS 38.401, figure 8.6.2-1.
+    //
+    // 1. >> F1ap InitialUlRrcMessageTransfer(RrcResumeRequest)
+    // --- CONDITIONAL IF THE UE HAS A BEARER CONTEXT -----
+    // 2. << E1ap BearerContextModificationRequest(Resume)
+    // 3. >> E1ap BearerContextModificationResponse
+    // ----------------------------------------------------
+    // 4. << F1ap UeContextSetupRequest(Rrc RrcResume)
+    // 5. >> F1ap UeContextSetupResponse
+    // 6. >> Rrc RrcResumeComplete(maybe Nas)
+    // --- CONDITIONAL IF THE RRC RESUME COMPLETE HAS A NAS MESSAGE -----
+    // 7.    Ngap UplinkNasTransport(Nas) >>
+    // ------------------------------------------------------------------
+    // --- CONDITIONAL IF THE UE HAS SESSIONS -----
+    // 8. << E1ap BearerContextModificationRequest
+    // 9. >> E1ap BearerContextModificationResponse
+    // --------------------------------------------
+    // 10.<< Rrc RrcReconfiguration
+    // 11.>> Rrc RrcReconfigurationComplete
+    //
+    // The DU released the UE when it was suspended, and so sets it up afresh, with SRB2 and the DRBs of
+    // its sessions.  The CU-UP kept the bearer context, and is told the DRBs' new downlink tunnels.
+    pub async fn rrc_resume(&self, r: &InitialUlRrcMessageTransfer, mut ue: UeState) -> Result<()> {
+        // The UE may have resumed in a different cell, and in any case has a new DU UE ID.
+        ue.gnb_du_ue_f1ap_id = r.gnb_du_ue_f1ap_id;
+        ue.nr_cgi = r.nr_cgi.clone();
+        ue.resume_identity = None;
+
+        // Steps 2-3.
+        let drbs = self.e1_resume_ul_tunnels(&ue).await?;
+
+        // Steps 4-6.
+        let rrc_transaction = self.new_rrc_transaction(&ue).await;
+        let rrc_resume = build_rrc::build_rrc_resume(0, &ue.pdu_sessions)?;
+        let ue_context_setup_request = build_f1ap::build_ue_context_setup_request(
+            self.gnb_cu_cp,
+            &ue,
+            NonEmpty::from_vec(drbs).map(DrbsToBeSetupList),
+            Some(rrc_resume),
+        )?;
+        self.log_message("<< F1ap UeContextSetupRequest(RrcResume)");
+        let ue_context_setup_response = self
+            .f1ap_request::<UeContextSetupProcedure>(ue_context_setup_request, self.logger)
+            .await?;
+        self.log_message(">> F1ap UeContextSetupResponse");
+
+        let rrc_resume_complete = rrc_transaction.recv().await.and_then(|x| match x.message {
+            UlDcchMessageType::C1(C1_6::RrcResumeComplete(RrcResumeComplete {
+                critical_extensions: CriticalExtensions20::RrcResumeComplete(x),
+                ..
+            })) => Ok(x),
+            _ => Err(anyhow!("Expected Rrc Resume complete")),
+        })?;
+        self.log_message(">> RrcResumeComplete");
+
+        // Step 7.
+        if let Some(x) = rrc_resume_complete.dedicated_nas_message {
+            self.send_uplink_nas(&ue, NasPdu(x.0)).await;
+        }
+
+        // Steps 8-9.
+        let drbs_setup: Vec<DrbsSetupItem> = ue_context_setup_response
+            .drbs_setup_list
+            .map(|x| x.0.into())
+            .unwrap_or_default();
+        let num_drbs = ue.drb_ids().count();
+        ensure!(
+            drbs_setup.len() == num_drbs,
+            "DU set up {} of the UE's {} DRBs",
+            drbs_setup.len(),
+            num_drbs
+        );
+        self.e1_resume_dl_tunnels(&ue, drbs_setup).await?;
+
+        // Steps 10-11.  TS 38.473, 8.3.1.2 - the cell group config from the DU goes to the UE.
+        let rrc_transaction = self.new_rrc_transaction(&ue).await;
+        let rrc_container = build_rrc::build_rrc_reconfiguration_cell_group(
+            0,
+            ue_context_setup_response
+                .du_to_cu_rrc_information
+                .cell_group_config
+                .0,
+        )?;
+        self.log_message("<< RrcReconfiguration");
+        self.send_rrc_to_ue(&ue, SrbId(1), rrc_container, self.logger)
+            .await;
+        let _rrc_reconfiguration_complete = rrc_transaction.recv().await?;
+        self.log_message(">> RrcReconfigurationComplete");
+
+        debug!(self.logger, "Store UE {:#010x}", ue.key);
+        self.store(ue.key, ue, self.config().ue_ttl_secs).await
+    }
+
+    // Resume the bearer context.  At the same time, get the CU-UP's uplink tunnel of each DRB, by restating
+    // the QoS flows mapped to it, and build the DRBs for the DU to set up.
+    async fn e1_resume_ul_tunnels(&self, ue: &UeState) -> Result<Vec<DrbsToBeSetupItem>> {
+        if ue.gnb_cu_up_ue_e1ap_id.is_none() {
+            return Ok(vec![]);
+        }
+        let items = ue
+            .pdu_sessions
+            .iter()
+            .map(|x| build_e1ap::build_e1_qos_flow_modify_item(x, None))
+            .collect::<Result<Vec<_>>>()?;
+        let Some(items) = NonEmpty::from_vec(items) else {
+            self.perform_bearer_context_status_change(ue, BearerContextStatusChange::Resume)
+                .await?;
+            return Ok(vec![]);
+        };
+        let modified_items = self
+            .perform_bearer_context_modification(ue, Some(BearerContextStatusChange::Resume), items)
+            .await?;
+
+        ue.pdu_sessions
+            .iter()
+            .flat_map(|session| session.drbs.iter().map(move |drb| (session, drb)))
+            .map(|(session, drb)| {
+                let gtp_tunnel = ul_tunnel(session.pdu_session_id, drb.drb_id, &modified_items)?;
+                build_f1ap::build_drb_to_be_setup_item(session, drb, gtp_tunnel)
+            })
+            .collect()
+    }
+
+    // Give the CU-UP the DU's downlink tunnel of each DRB, grouped by session.
+    async fn e1_resume_dl_tunnels(&self, ue: &UeState, drbs: Vec<DrbsSetupItem>) -> Result<()> {
+        let mut items = vec![];
+        for session in ue.pdu_sessions.iter() {
+            let session_drbs = drbs
+                .iter()
+                .filter(|drb| session.drb(drb.drb_id.0).is_some())
+                .map(|drb| {
+                    let UpTransportLayerInformation::GtpTunnel(gtp_tunnel) = &drb
+                        .dl_up_tnl_information_to_be_setup_list
+                        .0
+                        .head
+                        .dl_up_tnl_information;
+                    (e1ap::DrbId(drb.drb_id.0), gtp_tunnel.clone())
+                })
+                .collect();
+            items.push(build_e1ap::build_e1_modify_item(
+                PduSessionId(session.pdu_session_id),
+                session_drbs,
+            )?);
+        }
+        let Some(items) = NonEmpty::from_vec(items) else {
+            return Ok(());
+        };
+        self.perform_bearer_context_modification(ue, None, items)
+            .await?;
+        Ok(())
+    }
+
+    async fn perform_bearer_context_status_change(
+        &self,
+        ue: &UeState,
+        bearer_context_status_change: BearerContextStatusChange,
+    ) -> Result<()> {
+        let Some(gnb_cu_up_ue_e1ap_id) = ue.gnb_cu_up_ue_e1ap_id else {
+            bail!("No E1AP ID on UE");
+        };
+        let bearer_context_modification = build_e1ap::build_bearer_context_modification(
+            ue,
+            gnb_cu_up_ue_e1ap_id,
+            Some(bearer_context_status_change),
+            None,
+        );
+        debug!(
+            self.logger,
+            "<< BearerContextModificationRequest({:?})", bearer_context_status_change
+        );
+        self.e1ap_request::<BearerContextModificationProcedure>(
+            bearer_context_modification,
+            self.logger,
+        )
+        .await?;
+        debug!(self.logger, ">> BearerContextModificationResponse");
+        Ok(())
+    }
+}
+
+// TS 38.331, 5.3.13.3 - the resumeMAC-I is the 16 least significant bits of the MAC-I that the UE calculates
+// over VarResumeMAC-Input with the K_RRCint and integrity protection algorithm of its stored AS security
+// context.  We do not keep the K_gNB from the AMF, and our Security Mode Command leaves the UE on the
+// null algorithms, so the MAC-I is that of NIA0, which is all zeros (TS 33.501, D.1).  A UE that
+// presents any other resumeMAC-I is not resumed.
+fn check_resume_mac_i(resume_mac_i: &BitSlice<u8, Msb0>) -> Result<()> {
+    ensure!(
+        resume_mac_i.len() == 16 && resume_mac_i.not_any(),
+        "resumeMAC-I {} does not match the UE's AS security context",
+        resume_mac_i
+    );
+    Ok(())
+}
+
+// The full I-RNTI is 40 bits.  We put the UE key in the bottom 32 bits, so that the UE can be found
+// from its resume request, and a random value in the top 8 bits so that the identity changes
+// on each suspension.
+fn new_resume_identity(ue_key: u32) -> u64 {
+    let prefix: u8 = rand::thread_rng().gen();
+    ((prefix as u64) << 32) | ue_key as u64
+}
+
+fn build_suspend_config(resume_identity: u64, five_gs_tac: Option<&FiveGsTac>) -> SuspendConfig {
+    let mut full_i_rnti = bitvec![u8, Msb0; 0; 40];
+    full_i_rnti.store_be(resume_identity);
+    let short_i_rnti = full_i_rnti[16..].to_bitvec();
+
+    SuspendConfig {
+        full_i_rnti: IRntiValue(full_i_rnti),
+        short_i_rnti: ShortIRntiValue(short_i_rnti),
+        ran_paging_cycle: PagingCycle::Rf256,
+        // The UE need not notify the RAN while it stays within its cell's tracking area in the registered
+        // PLMN.  See TS 38.331, 5.3.13.8.
+        ran_notification_area_info: five_gs_tac.map(|x| {
+            RanNotificationAreaInfo::RanAreaConfigList(PlmnRanAreaConfigList(nonempty![
+                PlmnRanAreaConfig {
+                    plmn_identity: None,
+                    ran_area: nonempty![RanAreaConfig {
+                        tracking_area_code: TrackingAreaCode(BitVec::from_slice(&x.0)),
+                        ran_area_code_list: None,
+                    }],
+                }
+            ]))
+        }),
+        t380: None,
+        next_hop_chaining_count: NextHopChainingCount(0),
+    }
+}
@@ -0,0 +1,273 @@
+//! ue_context_release - AMF orders or gNB requests release of a UE
+
+use super::{GnbCuCp, Workflow};
+use crate::datastore::UeState;
+use anyhow::{bail, ensure, Result};
+use asn1_per::NonEmpty;
+use f1ap::{
+    Cause as F1Cause, CauseRadioNetwork as F1CauseRadioNetwork, GnbCuUeF1apId, SrbId,
+    UeContextReleaseCommand as F1UeContextReleaseCommand,
+    UeContextReleaseProcedure as F1UeContextReleaseProcedure,
+    UeContextReleaseRequest as F1UeContextReleaseRequest,
+};
+use ngap::{
+    AmfUeNgapId, Cause, CauseRadioNetwork, ErrorIndication, ErrorIndicationProcedure,
+    PduSessionResourceItemCxtRelCpl, PduSessionResourceListCxtRelCpl, RanUeNgapId,
+    UeContextReleaseCommand, UeContextReleaseComplete, UeContextReleaseRequest,
+    UeContextReleaseRequestProcedure, UeNgapIDs,
+};
+use rrc::SuspendConfig;
+use slog::{debug, warn};
+use xxap::PduSessionId;
+
+impl<'a, G: GnbCuCp> Workflow<'a, G> {
+    // AMF initiated UE context release procedure.
+    // See TS 38.401, figure 8.3.3-1.
+    //
+    // 1.    Ngap UeContextReleaseCommand <<
+    // 2. << E1ap BearerContextReleaseCommand
+    // 3. >> E1ap BearerContextReleaseComplete
+    // 4. << F1ap UeContextReleaseCommand(RrcRelease)
+    // 5. >> F1ap UeContextReleaseComplete
+    // 6.    Ngap UeContextReleaseComplete >>
+    //
+    // The AMF may give just the AMF UE NGAP ID, in which case the UE is looked up by it.  An unknown UE, or one
+    // whose AMF UE NGAP ID does not match, is reported in an Ngap ErrorIndication instead of the
+    // UeContextReleaseComplete.  See TS 38.413, 10.6.
+    //
+    // The UeContextReleaseComplete lists the UE's PDU sessions, which are released along with it.
+    pub async fn ue_context_release(
+        &self,
+        r: UeContextReleaseCommand,
+    ) -> Result<UeContextReleaseComplete> {
+        self.log_message("UeContextReleaseCommand <<");
+
+        let (amf_ue_ngap_id, ue) = match r.ue_ngap_i_ds {
+            UeNgapIDs::UeNgapIdPair(pair) => {
+                let ue_key = pair.ran_ue_ngap_id.0;
+                debug!(self.logger, "Retrieve UE {:#010x}", ue_key);
+                match self.retrieve(&ue_key).await {
+                    Ok(ue) if ue.amf_ue_ngap_id.map(|x| x.0) == Some(pair.amf_ue_ngap_id.0) => {
+                        (pair.amf_ue_ngap_id, ue)
+                    }
+                    Ok(_) => {
+                        self.send_error_indication(
+                            Some(pair.amf_ue_ngap_id),
+                            Some(pair.ran_ue_ngap_id),
+                            CauseRadioNetwork::InconsistentRemoteUeNgapId,
+                        )
+                        .await;
+                        bail!(
+                            "Can't release UE {:#010x} - it has a different AMF UE NGAP ID",
+                            ue_key
+                        )
+                    }
+                    Err(e) => {
+                        self.send_error_indication(
+                            Some(pair.amf_ue_ngap_id),
+                            Some(pair.ran_ue_ngap_id),
+                            CauseRadioNetwork::UnknownLocalUeNgapId,
+                        )
+                        .await;
+                        bail!("Can't release unknown UE {:#010x} - {e}", ue_key)
+                    }
+                }
+            }
+            UeNgapIDs::AmfUeNgapId(amf_ue_ngap_id) => {
+                match self.retrieve_ue_by_amf_ue_ngap_id(&amf_ue_ngap_id).await {
+                    Ok(ue) => (amf_ue_ngap_id, ue),
+                    Err(e) => {
+                        self.send_error_indication(
+                            Some(amf_ue_ngap_id),
+                            None,
+                            CauseRadioNetwork::InconsistentRemoteUeNgapId,
+                        )
+                        .await;
+                        bail!(
+                            "Can't release UE with AMF UE NGAP ID {:#x} - {e}",
+                            amf_ue_ngap_id.0
+                        )
+                    }
+                }
+            }
+        };
+
+        let ran_ue_ngap_id = RanUeNgapId(ue.key);
+        let pdu_session_resource_list_cxt_rel_cpl = NonEmpty::from_vec(
+            ue.pdu_sessions
+                .iter()
+                .map(|x| PduSessionResourceItemCxtRelCpl {
+                    pdu_session_id: PduSessionId(x.pdu_session_id),
+                    pdu_session_resource_release_response_transfer: None,
+                })
+                .collect(),
+        )
+        .map(PduSessionResourceListCxtRelCpl);
+        if let Err(e) = self.ue_context_release_inner(ue).await {
+            warn!(self.logger, "Error during UE context release {e}")
+        }
+
+        self.log_message("UeContextReleaseComplete >>");
+        Ok(UeContextReleaseComplete {
+            amf_ue_ngap_id,
+            ran_ue_ngap_id,
+            user_location_information: None,
+            info_on_recommended_cells_and_ran_nodes_for_paging: None,
+            pdu_session_resource_list_cxt_rel_cpl,
+            criticality_diagnostics: None,
+            paging_assis_datafor_c_ecapab_ue: None,
+        })
+    }
+
+    async fn retrieve_ue_by_amf_ue_ngap_id(&self, amf_ue_ngap_id: &AmfUeNgapId) -> Result<UeState> {
+        let Some(ue_key) = self.ue_with_amf_ue_ngap_id(amf_ue_ngap_id).await? else {
+            bail!("No UE has this AMF UE NGAP ID")
+        };
+        debug!(self.logger, "Retrieve UE {:#010x}", ue_key);
+        let ue = self.retrieve(&ue_key).await?;
+
+        // The index may be out of date if the AMF has since given the UE a new ID.
+        ensure!(
+            ue.amf_ue_ngap_id.map(|x| x.0) == Some(amf_ue_ngap_id.0),
+            "UE {:#010x} has a different AMF UE NGAP ID",
+            ue_key
+        );
+        Ok(ue)
+    }
+
+    async fn send_error_indication(
+        &self,
+        amf_ue_ngap_id: Option<AmfUeNgapId>,
+        ran_ue_ngap_id: Option<RanUeNgapId>,
+        cause: CauseRadioNetwork,
+    ) {
+        let error_indication = ErrorIndication {
+            amf_ue_ngap_id,
+            ran_ue_ngap_id,
+            cause: Some(Cause::RadioNetwork(cause)),
+            criticality_diagnostics: None,
+            five_g_s_tmsi: None,
+        };
+        self.log_message("Ngap ErrorIndication >>");
+        self.ngap_indication::<ErrorIndicationProcedure>(error_indication, self.logger)
+            .await;
+    }
+
+    async fn ue_context_release_inner(&self, ue: UeState) -> Result<()> {
+        // Release the bearer context in the CU-UP first, so that no more downlink
+        // data is sent to the DU after the UE has been told to go.
+        if let Some(gnb_cu_up_ue_e1ap_id) = ue.gnb_cu_up_ue_e1ap_id {
+            self.perform_e1_bearer_release(&ue, gnb_cu_up_ue_e1ap_id)
+                .await;
+        }
+
+        // Release the UE context in the DU, sending the UE an Rrc Release.
+        if let Err(e) = self
+            .perform_f1_context_release_with_rrc_release(&ue, None)
+            .await
+        {
+            warn!(
+                self.logger,
+                "Error during F1 Ue Context Release procedure - {e}"
+            )
+        }
+
+        // Delete the UE.
+        debug!(self.logger, "Delete UE {:#010x}", ue.key);
+        self.delete(&ue.key).await
+    }
+
+    pub async fn perform_f1_context_release_with_rrc_release(
+        &self,
+        ue: &UeState,
+        suspend_config: Option<SuspendConfig>,
+    ) -> Result<()> {
+        let rrc_container = super::build_rrc::build_rrc_release(0, suspend_config)?;
+
+        let ue_context_release_command = F1UeContextReleaseCommand {
+            gnb_cu_ue_f1ap_id: GnbCuUeF1apId(ue.key),
+            gnb_du_ue_f1ap_id: ue.gnb_du_ue_f1ap_id,
+            cause: F1Cause::RadioNetwork(F1CauseRadioNetwork::NormalRelease),
+            rrc_container: Some(rrc_container),
+            srb_id: Some(SrbId(1)),
+            old_gnb_du_ue_f1ap_id: None,
+            execute_duplication: None,
+            rrc_delivery_status_request: None,
+            target_cells_to_cancel: None,
+        };
+
+        self.log_message("<< F1ap UeContextReleaseCommand(RrcRelease)");
+        self.f1ap_request::<F1UeContextReleaseProcedure>(ue_context_release_command, self.logger)
+            .await?;
+        self.log_message(">> F1ap UeContextReleaseComplete");
+        Ok(())
+    }
+
+    // gNB initiated UE context release procedure.
+    // See TS 38.401, figure 8.3.2-1.
+    //
+    // 1. >> F1ap UeContextReleaseRequest
+    // 2.    Ngap UeContextReleaseRequest >>
+    //
+    // The AMF then triggers the AMF initiated procedure above.
+    pub async fn ue_context_release_request(&self, r: F1UeContextReleaseRequest) -> Result<()> {
+        self.log_message(">> F1ap UeContextReleaseRequest");
+
+        debug!(self.logger, "Retrieve UE {:#010x}", r.gnb_cu_ue_f1ap_id.0);
+        let ue = self.retrieve(&r.gnb_cu_ue_f1ap_id.0).await?;
+        self.request_ue_context_release(&ue, ngap_cause(&r.cause))
+            .await
+    }
+
+    // Ask the AMF to release the UE.  If the AMF doesn't know about the UE yet, release it locally.
+    pub async fn request_ue_context_release(&self, ue: &UeState, cause: Cause) -> Result<()> {
+        let Some(amf_ue_ngap_id) = ue.amf_ue_ngap_id else {
+            // The AMF doesn't know about this UE yet, so there is nobody to ask.  Release it locally.
+            if let Err(e) = self
+                .perform_f1_context_release_with_rrc_release(ue, None)
+                .await
+            {
+                warn!(
+                    self.logger,
+                    "Error during F1 Ue Context Release procedure - {e}"
+                )
+            }
+            debug!(self.logger, "Delete UE {:#010x}", ue.key);
+            return self.delete(&ue.key).await;
+        };
+
+        self.send_ngap_ue_context_release_request(ue, amf_ue_ngap_id, cause)
+            .await;
+        Ok(())
+    }
+
+    async fn send_ngap_ue_context_release_request(
+        &self,
+        ue: &UeState,
+        amf_ue_ngap_id: AmfUeNgapId,
+        cause: Cause,
+    ) {
+        let ue_context_release_request = UeContextReleaseRequest {
+            amf_ue_ngap_id,
+            ran_ue_ngap_id: RanUeNgapId(ue.key),
+            pdu_session_resource_list_cxt_rel_req: None,
+            cause,
+        };
+        self.log_message("Ngap UeContextReleaseRequest >>");
+        self.ngap_indication::<UeContextReleaseRequestProcedure>(
+            ue_context_release_request,
+            self.logger,
+        )
+        .await;
+    }
+}
+
+// Map the DU's reason for wanting the UE released into the cause we give the AMF.
+fn ngap_cause(f1_cause: &F1Cause) -> Cause {
+    match f1_cause {
+        F1Cause::RadioNetwork(F1CauseRadioNetwork::RlFailureRlc) => {
+            Cause::RadioNetwork(CauseRadioNetwork::RadioConnectionWithUeLost)
+        }
+        _ => Cause::RadioNetwork(CauseRadioNetwork::ReleaseDueToNgranGeneratedReason),
+    }
+}
@@ -27,7 +27,11 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
         };
 
         self.log_message(">> UlInformationTransfer(Nas)");
+        self.send_uplink_nas(&ue, nas_pdu).await
+    }
 
+    // Pass on a Nas message from the UE to the AMF.
+    pub async fn send_uplink_nas(&self, ue: &UeState, nas_pdu: NasPdu) {
         let amf_ue_ngap_id = match ue.amf_ue_ngap_id {
             Some(x) => x,
             None => {
@@ -41,7 +45,7 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
 
         // Todo - should be from Ue context
         let nr_cgi = ngap::NrCgi {
-            plmn_identity: ngap::PlmnIdentity(self.config().plmn),
+            plmn_identity: ngap::PlmnIdentity(self.config().ran.plmn),
             nr_cell_identity: ngap::NrCellIdentity(bitvec![u8,Msb0;0;36]),
         };
 
@@ -53,7 +57,7 @@ impl<'a, G: GnbCuCp> Workflow<'a, G> {
                 UserLocationInformationNr {
                     nr_cgi,
                     tai: Tai {
-                        plmn_identity: ngap::PlmnIdentity(self.config().plmn),
+                        plmn_identity: ngap::PlmnIdentity(self.config().ran.plmn),
                         tac: Tac([0, 0, 1]),
                     },
                     time_stamp: None,
@@ -2,10 +2,13 @@
 
 use std::net::{IpAddr, Ipv4Addr};
 
-use asn1_per::nonempty;
-use e1ap::{PlmnIdentity, SupportedPlmnsItem, SupportedPlmnsList};
+use anyhow::Result;
+use e1ap::SupportedPlmnsList;
+use serde::Deserialize;
+use xxap::RanConfig;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     // The IP address that the CU-UP instance binds its SCTP E1 port to,
     // and uses for nanomsg pub-sub.
@@ -20,19 +23,18 @@ pub struct Config {
 
     // Human readable name of this GNB-CU-UP.
     pub name: Option<String>,
+
+    // The PLMNs and slices of the gNB, which the CU-UP signals as supported in E1 Setup.  This should
+    // be the same as the CU-CP's.
+    pub ran: RanConfig,
+
+    // How often the CU-UP logs the depth and wait counts of the send queue of its E1AP TNLA.
+    pub send_queue_stats_interval_secs: u64,
 }
 
 impl Config {
-    pub fn plmns(&self) -> SupportedPlmnsList {
-        SupportedPlmnsList(nonempty![SupportedPlmnsItem {
-            plmn_identity: PlmnIdentity([1, 2, 3]),
-            slice_support_list: None,
-            nr_cgi_support_list: None,
-            qos_parameters_support_list: None,
-            npn_support_info: None,
-            extended_slice_support_list: None,
-            extended_nr_cgi_support_list: None
-        }])
+    pub fn plmns(&self) -> Result<SupportedPlmnsList> {
+        (&self.ran).try_into()
     }
 }
 
@@ -43,6 +45,8 @@ impl Default for Config {
             userplane_ip_address: Ipv4Addr::LOCALHOST.into(),
             cp_ip_address: Ipv4Addr::LOCALHOST.into(),
             name: Some("Alsoran UP".to_string()),
+            ran: RanConfig::default(),
+            send_queue_stats_interval_secs: 60,
         }
     }
 }
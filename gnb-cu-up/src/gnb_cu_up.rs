@@ -13,10 +13,24 @@ use xxap::GtpTeid;
 pub trait GnbCuUp: Send + Sync + Clone + 'static {
     fn config(&self) -> &Config;
     fn new_ue_ap_id(&self) -> GnbCuUpUeE1apId;
-    fn create_uplink_teid(&self, ue_id: u32, session_id: u8) -> GtpTeid;
-    fn create_downlink_teid(&self, ue_id: u32, session_id: u8) -> GtpTeid;
-    async fn set_uplink_forwarding_action(&self, gtp_teid: GtpTeid, action: ForwardingAction);
-    async fn set_downlink_forwarding_action(&self, gtp_teid: GtpTeid, action: ForwardingAction);
+    fn create_uplink_teid(&self, ue_id: u32, drb_id: u8) -> GtpTeid;
+    fn create_downlink_teid(&self, ue_id: u32, pdu_session_id: u8) -> GtpTeid;
+    async fn add_session(&self, ue_id: u32, pdu_session_id: u8, uplink_action: ForwardingAction);
+    async fn add_drb(
+        &self,
+        ue_id: u32,
+        pdu_session_id: u8,
+        drb_id: u8,
+        qfis: Vec<u8>,
+    ) -> Result<()>;
+    async fn set_downlink_forwarding_action(
+        &self,
+        ue_id: u32,
+        drb_id: u8,
+        action: ForwardingAction,
+    ) -> Result<()>;
+    async fn set_qos_flows(&self, ue_id: u32, drb_id: u8, qfis: Vec<u8>) -> Result<()>;
+    async fn suspend_bearer_context(&self, ue_id: u32);
     fn bearer_context_exists(&self, ue_id: u32) -> bool;
     async fn delete_bearer_context(&self, ue_id: u32);
     async fn e1ap_connect(&self, cp_address: &IpAddr) -> Result<()>;
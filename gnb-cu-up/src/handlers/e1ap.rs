@@ -30,6 +30,18 @@ impl<G: GnbCuUp> EventHandler for E1apHandler<G> {
                 )
             }
             TnlaEvent::Terminated => info!(logger, "E1AP TNLA {} closed", tnla_id),
+            TnlaEvent::PathUp(addr) => info!(logger, "E1AP TNLA {} path to {} up", tnla_id, addr),
+            TnlaEvent::PathDown(addr) => {
+                warn!(logger, "E1AP TNLA {} path to {} down", tnla_id, addr)
+            }
+            TnlaEvent::Restarted => warn!(logger, "E1AP TNLA {} peer restarted", tnla_id),
+            TnlaEvent::PeerShutdown => info!(logger, "E1AP TNLA {} shutting down", tnla_id),
+            TnlaEvent::SendFailed(stream_id) => {
+                warn!(
+                    logger,
+                    "E1AP TNLA {} send failed on stream {}", tnla_id, stream_id
+                )
+            }
         };
     }
 }
@@ -7,4 +7,5 @@ mod workflows;
 
 use crate::gnb_cu_up::GnbCuUp;
 pub use config::Config;
-pub use worker::spawn;
+pub use xxap::RanConfig;
+pub use worker::{spawn, spawn_with_transport};
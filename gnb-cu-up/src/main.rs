@@ -1,17 +1,65 @@
 use anyhow::Result;
-use common::{logging, panic, signal};
+use clap::Parser;
+use common::{config_file, logging, panic, signal};
 use gnb_cu_up::Config;
 use slog::info;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// TOML file giving the CU-UP's configuration.  The other options override the settings in it.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Local IP address to bind the E1AP port to.  Default 127.0.0.1.
+    #[arg(short, long)]
+    local_ip: Option<IpAddr>,
+
+    /// Local IP address that terminates GTP-U.  Default 127.0.0.1.
+    #[arg(short, long)]
+    userplane_ip: Option<IpAddr>,
+
+    /// IP address of the GNB-CU-CP.  Default 127.0.0.1.
+    #[arg(long)]
+    cp_ip: Option<IpAddr>,
+
+    /// Name signalled to the GNB-CU-CP in E1 Setup.
+    #[arg(short, long)]
+    name: Option<String>,
+}
 
 #[async_std::main]
 async fn main() -> Result<()> {
     panic::exit_on_panic();
 
+    let args = Args::parse();
+    let config = config(args)?;
     let root_logger = logging::init();
-    let config = Config::default();
     let shutdown_handle = gnb_cu_up::spawn(config, root_logger.clone()).await?;
     let s = signal::wait_for_signal().await?;
     info!(root_logger, "Caught signal {} - terminate", s);
     shutdown_handle.graceful_shutdown().await;
     Ok(())
 }
+
+fn config(args: Args) -> Result<Config> {
+    let mut config: Config = match &args.config {
+        Some(path) => config_file::load(path)?,
+        None => Config::default(),
+    };
+    if let Some(local_ip) = args.local_ip {
+        config.local_ip_address = local_ip;
+    }
+    if let Some(userplane_ip) = args.userplane_ip {
+        config.userplane_ip_address = userplane_ip;
+    }
+    if let Some(cp_ip) = args.cp_ip {
+        config.cp_ip_address = cp_ip;
+    }
+    if args.name.is_some() {
+        config.name = args.name;
+    }
+    Ok(config)
+}
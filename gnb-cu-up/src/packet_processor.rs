@@ -1,7 +1,7 @@
 #![allow(clippy::unusual_byte_groupings)]
 use std::sync::Arc;
 
-use anyhow::{ensure, Context, Result};
+use anyhow::{anyhow, ensure, Context, Result};
 use async_net::{IpAddr, UdpSocket};
 use async_std::{
     sync::Mutex,
@@ -10,7 +10,7 @@ use async_std::{
 use slog::{debug, info, Logger};
 use socket2::{Domain, Protocol, Socket, Type};
 use std::net::SocketAddr;
-use xxap::{GtpTeid, GtpTunnel};
+use xxap::{GtpTeid, GtpTunnel, TransportLayerAddress};
 
 const GTPU_PORT: u16 = 2152; // TS29.281
 
@@ -20,11 +20,28 @@ pub struct PacketProcessor {
     _forwarding_task: Arc<JoinHandle<()>>,
 }
 
-// The rule for forwarding a packet.
-#[derive(Clone)]
+// The rules for forwarding the packets of a UE.  A packet's session or DRB is given by the
+// top byte of its GTP TEID - see documentation/design/Ue and TeId allocation scheme.md.
+#[derive(Clone, Default)]
 pub struct ForwardingContext {
-    pub session_1_downlink: Option<ForwardingAction>,
-    pub session_1_uplink: Option<ForwardingAction>,
+    pub sessions: Vec<SessionForwardingContext>,
+}
+
+#[derive(Clone)]
+pub struct SessionForwardingContext {
+    pub pdu_session_id: u8,
+    // Uplink packets of all the session's DRBs go to the UPF's N3 tunnel.
+    pub uplink: ForwardingAction,
+    pub drbs: Vec<DrbForwardingContext>,
+}
+
+#[derive(Clone)]
+pub struct DrbForwardingContext {
+    pub drb_id: u8,
+    // Downlink packets go to the DU's F1-U tunnel, which is not known until the DU has set up the DRB.
+    pub downlink: Option<ForwardingAction>,
+    // QFIs of the QoS flows mapped to the DRB.  Packets of other QoS flows are dropped.
+    pub qos_flows: Vec<u8>,
 }
 
 #[derive(Clone)]
@@ -49,7 +66,6 @@ impl PacketProcessor {
             IpAddr::V4(_) => Domain::IPV4,
             IpAddr::V6(_) => Domain::IPV6,
         };
-        ensure!(matches!(local_ip, IpAddr::V4(_)));
         let gtpu_socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
         gtpu_socket.set_reuse_port(true)?;
         info!(logger, "Serving GTP-U on {transport_address}");
@@ -60,15 +76,13 @@ impl PacketProcessor {
         let gtpu_socket = UdpSocket::try_from(gtpu_socket)?;
 
         let forwarding_table = Arc::new(Mutex::new(ForwardingTable(vec![
-            ForwardingContext {
-                session_1_downlink: None,
-                session_1_uplink: None
-            };
+            ForwardingContext::default();
             CAPACITY
         ])));
 
-        let _forwarding_task =
-            Arc::new(start_forwarding(gtpu_socket, forwarding_table.clone(), logger).await);
+        let _forwarding_task = Arc::new(
+            start_forwarding(gtpu_socket, local_ip, forwarding_table.clone(), logger).await,
+        );
 
         Ok(PacketProcessor {
             forwarding_table,
@@ -76,60 +90,221 @@ impl PacketProcessor {
         })
     }
 
-    pub async fn set_uplink_forwarding_action(
+    // Add a session, replacing any earlier session of the UE with the same ID.
+    pub async fn add_session(
         &self,
-        gtp_teid: GtpTeid,
-        action: ForwardingAction,
+        ue_id: u32,
+        pdu_session_id: u8,
+        uplink: ForwardingAction,
         logger: &Logger,
     ) {
-        let gtp_teid_u32 = u32::from_be_bytes(gtp_teid.0);
-        let key = ((gtp_teid_u32 >> 1) & CAPACITY_MASK) as usize;
         debug!(
             logger,
-            "Install uplink forwarding action {:?}->{}/{:?}",
-            gtp_teid.0,
-            action
+            "Install uplink forwarding action for UE {} session {}->{}/{:?}",
+            ue_id,
+            pdu_session_id,
+            uplink
                 .remote_tunnel_info
                 .transport_layer_address
                 .to_string(),
-            action.remote_tunnel_info.gtp_teid.0
+            uplink.remote_tunnel_info.gtp_teid.0
         );
-        self.forwarding_table.lock().await.0[key].session_1_uplink = Some(action);
+        let context = &mut self.forwarding_table.lock().await.0[key(ue_id)];
+        context
+            .sessions
+            .retain(|x| x.pdu_session_id != pdu_session_id);
+        context.sessions.push(SessionForwardingContext {
+            pdu_session_id,
+            uplink,
+            drbs: vec![],
+        });
+    }
+
+    pub async fn add_drb(
+        &self,
+        ue_id: u32,
+        pdu_session_id: u8,
+        drb_id: u8,
+        qfis: Vec<u8>,
+        logger: &Logger,
+    ) -> Result<()> {
+        debug!(
+            logger,
+            "Add DRB {} with QoS flows {:?} to UE {} session {}",
+            drb_id,
+            qfis,
+            ue_id,
+            pdu_session_id
+        );
+        let context = &mut self.forwarding_table.lock().await.0[key(ue_id)];
+        ensure!(
+            context.drb_mut(drb_id).is_none(),
+            "UE {} already has DRB {}",
+            ue_id,
+            drb_id
+        );
+        let session = context
+            .sessions
+            .iter_mut()
+            .find(|x| x.pdu_session_id == pdu_session_id)
+            .ok_or_else(|| anyhow!("UE {} has no session {}", ue_id, pdu_session_id))?;
+        session.drbs.push(DrbForwardingContext {
+            drb_id,
+            downlink: None,
+            qos_flows: qfis,
+        });
+        Ok(())
     }
 
     pub async fn set_downlink_forwarding_action(
         &self,
-        gtp_teid: GtpTeid,
+        ue_id: u32,
+        drb_id: u8,
         action: ForwardingAction,
         logger: &Logger,
-    ) {
-        let gtp_teid_u32 = u32::from_be_bytes(gtp_teid.0);
-        let key = ((gtp_teid_u32 >> 1) & CAPACITY_MASK) as usize;
+    ) -> Result<()> {
         debug!(
             logger,
-            "Install downlink forwarding action {:?}->{}/{:?}",
-            gtp_teid.0,
+            "Install downlink forwarding action for UE {} DRB {}->{}/{:?}",
+            ue_id,
+            drb_id,
             action
                 .remote_tunnel_info
                 .transport_layer_address
                 .to_string(),
             action.remote_tunnel_info.gtp_teid.0
         );
-        self.forwarding_table.lock().await.0[key].session_1_downlink = Some(action);
+        self.forwarding_table.lock().await.0[key(ue_id)]
+            .drb_mut(drb_id)
+            .ok_or_else(|| anyhow!("UE {} has no DRB {}", ue_id, drb_id))?
+            .downlink = Some(action);
+        Ok(())
+    }
+
+    pub async fn set_qos_flows(
+        &self,
+        ue_id: u32,
+        drb_id: u8,
+        qfis: Vec<u8>,
+        logger: &Logger,
+    ) -> Result<()> {
+        debug!(
+            logger,
+            "Map QoS flows {:?} to UE {} DRB {}", qfis, ue_id, drb_id
+        );
+        self.forwarding_table.lock().await.0[key(ue_id)]
+            .drb_mut(drb_id)
+            .ok_or_else(|| anyhow!("UE {} has no DRB {}", ue_id, drb_id))?
+            .qos_flows = qfis;
+        Ok(())
+    }
+
+    // Downlink packets are dropped until the DU's new tunnels are supplied.
+    pub async fn clear_downlink_forwarding_actions(&self, ue_id: u32, logger: &Logger) {
+        debug!(
+            logger,
+            "Remove downlink forwarding actions for UE {}", ue_id
+        );
+        self.forwarding_table.lock().await.0[key(ue_id)].clear_downlink();
     }
 
-    pub async fn clear_forwarding_actions(&self, gtp_teid: GtpTeid) {
-        let gtp_teid_u32 = u32::from_be_bytes(gtp_teid.0);
-        let key = ((gtp_teid_u32 >> 1) & CAPACITY_MASK) as usize;
-        let context = &mut self.forwarding_table.lock().await.0[key];
-        context.session_1_downlink = None;
-        context.session_1_uplink = None;
+    pub async fn clear_forwarding_actions(&self, ue_id: u32) {
+        self.forwarding_table.lock().await.0[key(ue_id)]
+            .sessions
+            .clear();
     }
 }
 
+impl ForwardingContext {
+    // Downlink packets arrive on the session's N3 tunnel and go to the DRB that their QoS flow is mapped to.
+    fn downlink_action(&self, pdu_session_id: u8, qfi: u8) -> Option<&ForwardingAction> {
+        self.sessions
+            .iter()
+            .find(|x| x.pdu_session_id == pdu_session_id)?
+            .drbs
+            .iter()
+            .find(|x| x.qos_flows.contains(&qfi))?
+            .downlink
+            .as_ref()
+    }
+
+    // Uplink packets arrive on a DRB's F1-U tunnel and go to the N3 tunnel of the DRB's session.
+    fn uplink_action(&self, drb_id: u8, qfi: u8) -> Option<&ForwardingAction> {
+        self.sessions.iter().find_map(|session| {
+            session
+                .drbs
+                .iter()
+                .any(|x| x.drb_id == drb_id && x.qos_flows.contains(&qfi))
+                .then_some(&session.uplink)
+        })
+    }
+
+    fn clear_downlink(&mut self) {
+        for drb in self.sessions.iter_mut().flat_map(|x| x.drbs.iter_mut()) {
+            drb.downlink = None;
+        }
+    }
+
+    fn drb_mut(&mut self, drb_id: u8) -> Option<&mut DrbForwardingContext> {
+        self.sessions
+            .iter_mut()
+            .flat_map(|x| x.drbs.iter_mut())
+            .find(|x| x.drb_id == drb_id)
+    }
+}
+
+// The forwarding table is indexed by the bottom bits of the UE ID.
+fn key(ue_id: u32) -> usize {
+    (ue_id & CAPACITY_MASK) as usize
+}
+
+// The GTP TEID is made up of the session or DRB index in the top byte, the 23 LSBs of the UE ID, and a
+// bit indicating direction.  Downlink packets arrive from the UPF on a session's N3 tunnel, so the index
+// of a downlink TEID is the PDU session ID.  Uplink packets arrive from the DU on a DRB's F1-U tunnel, so
+// the index of an uplink TEID is the DRB ID.
+//
+// TODO - this breaks the rule from 29.281, 5.1 of not using all 0s, and also breaks the
+// rule about predictability - given the CuUpE1apId from which it is generated is predictable.
+pub fn uplink_teid(ue_id: u32, drb_id: u8) -> GtpTeid {
+    GtpTeid([
+        drb_id,
+        ((ue_id & 0x7f8000) >> 15) as u8,
+        ((ue_id & 0x7f80) >> 7) as u8,
+        ((ue_id & 0x7f) << 1) as u8, // LSB clear for uplink
+    ])
+}
+
+pub fn downlink_teid(ue_id: u32, pdu_session_id: u8) -> GtpTeid {
+    GtpTeid([
+        pdu_session_id,
+        ((ue_id & 0x7f8000) >> 15) as u8,
+        ((ue_id & 0x7f80) >> 7) as u8,
+        (((ue_id & 0x7f) << 1) | 1) as u8, // LSB set for downlink
+    ])
+}
+
+// Splits a received GTP TEID into the forwarding table key, the session or DRB index, and whether it is
+// downlink - the inverse of uplink_teid() and downlink_teid().
+fn decode_teid(gtp_teid: u32) -> (usize, u8, bool) {
+    let key = ((gtp_teid >> 1) & CAPACITY_MASK) as usize;
+    let index = (gtp_teid >> 24) as u8;
+    let downlink = (gtp_teid & 1) == 1;
+    (key, index, downlink)
+}
+
 const HEADROOM: usize = 8;
+// Pick the remote address in the same family as our socket.  An IPv6 socket (for example one bound
+// to ::) can also reach an IPv4-only peer through its IPv4-mapped address.
+fn dest_ip(remote: &TransportLayerAddress, local_ip: &IpAddr) -> Option<IpAddr> {
+    remote.ip_addr_for(local_ip).ok().or_else(|| match local_ip {
+        IpAddr::V6(_) => remote.ipv4().map(|x| IpAddr::V6(x.to_ipv6_mapped())),
+        IpAddr::V4(_) => None,
+    })
+}
+
 async fn start_forwarding(
     gtpu_socket: UdpSocket,
+    local_ip: IpAddr,
     forwarding_table: Arc<Mutex<ForwardingTable>>,
     logger: Logger,
 ) -> JoinHandle<()> {
@@ -147,9 +322,7 @@ async fn start_forwarding(
             }
 
             let gtp_header = parse_gtp(&buf[offset..offset + GTP_HEADER_MIN_SIZE]);
-            let gtp_teid = gtp_header.teid;
-            let key = ((gtp_teid >> 1) & CAPACITY_MASK) as usize;
-            let downlink = (gtp_teid & 1) == 1;
+            let (key, index, downlink) = decode_teid(gtp_header.teid);
 
             debug!(
                 logger,
@@ -157,24 +330,37 @@ async fn start_forwarding(
                 gtp_header.teid.to_be_bytes()
             );
 
+            // The QFI is in the N3 PDU session container (downlink) or the SDAP header (uplink).
+            let qfi = if downlink {
+                buf[offset + 14] & QFI_MASK
+            } else {
+                buf[offset + 8] & QFI_MASK
+            };
+
             let context = &forwarding_table.lock().await.0[key];
 
             // LSB clear for uplink; set for downlink
             let action = if downlink {
-                &context.session_1_downlink
+                context.downlink_action(index, qfi)
             } else {
-                &context.session_1_uplink
+                context.uplink_action(index, qfi)
             };
             let Some(action) = action else {
+                debug!(logger, "No forwarding action for QoS flow {}", qfi);
                 continue;   // TODO update stat
             };
 
-            let dest_ip: IpAddr = action
-                .remote_tunnel_info
-                .transport_layer_address
-                .clone()
-                .try_into()
-                .unwrap(); // TODO don't unwrap
+            let Some(dest_ip) =
+                dest_ip(&action.remote_tunnel_info.transport_layer_address, &local_ip)
+            else {
+                debug!(
+                    logger,
+                    "Can't reach {} from {}",
+                    action.remote_tunnel_info.transport_layer_address.to_string(),
+                    local_ip
+                );
+                continue; // TODO update stat
+            };
             let dest_sock_addr = SocketAddr::new(dest_ip, GTPU_PORT);
 
             debug!(
@@ -187,12 +373,14 @@ async fn start_forwarding(
                     &mut buf,
                     &mut offset,
                     &action.remote_tunnel_info.gtp_teid.0,
+                    qfi,
                 );
             } else {
                 replace_f1_with_n3_headers(
                     &mut buf,
                     &mut offset,
                     &action.remote_tunnel_info.gtp_teid.0,
+                    qfi,
                 );
             }
 
@@ -208,8 +396,14 @@ async fn start_forwarding(
 }
 
 const GTP_MESSAGE_TYPE_GPU: u8 = 255; // TS29.281, table 6.1-1
-
-fn replace_n3_with_f1_headers(buf: &mut [u8; 2000], offset: &mut usize, gtp_teid: &[u8; 4]) {
+const QFI_MASK: u8 = 0b00_111111;
+
+fn replace_n3_with_f1_headers(
+    buf: &mut [u8; 2000],
+    offset: &mut usize,
+    gtp_teid: &[u8; 4],
+    qfi: u8,
+) {
     // On the N3 side, there should be
     // - a 12-byte GTP header
     // - a 4-byte PDU session container
@@ -242,7 +436,7 @@ fn replace_n3_with_f1_headers(buf: &mut [u8; 2000], offset: &mut usize, gtp_teid
     buf[7] = gtp_teid[3];
 
     // ---- SDAP DOWNLINK DATA PDU ----
-    buf[8] = 0b0_0_000001; // RDI, RQI, QFI - see TS37.324
+    buf[8] = qfi; // RDI=0, RQI=0, QFI - see TS37.324
 
     // ---- PDCP Data PDU for DRB with 12 bit PDCP SN ----
     // TODO: handle PDCP sequence number correctly
@@ -250,7 +444,12 @@ fn replace_n3_with_f1_headers(buf: &mut [u8; 2000], offset: &mut usize, gtp_teid
     buf[10] = 0b00000001; // SN
 }
 
-fn replace_f1_with_n3_headers(buf: &mut [u8; 2000], offset: &mut usize, gtp_teid: &[u8; 4]) {
+fn replace_f1_with_n3_headers(
+    buf: &mut [u8; 2000],
+    offset: &mut usize,
+    gtp_teid: &[u8; 4],
+    qfi: u8,
+) {
     // The inverse of the case above - so we need to grow the packet by 5 bytes.
 
     let gtp_header = &mut buf[*offset..];
@@ -281,7 +480,7 @@ fn replace_f1_with_n3_headers(buf: &mut [u8; 2000], offset: &mut usize, gtp_teid
     // ---- PDU session container, TS38.415 ----
     buf[12] = 1; // length of PDU session container = 4 bytes
     buf[13] = 0b0001_0_0_0_0; // PDU type = UL PDU SESSION INFORMATION, QMP, DL delay, UL delay, SNP
-    buf[14] = 0b0_0_000001; // N3 delay, new IE, QFI=1,
+    buf[14] = qfi; // N3 delay=0, new IE=0, QFI
     buf[15] = 0; // next extension type = none
 }
 
@@ -308,3 +507,113 @@ fn parse_gtp(packet: &[u8]) -> GtpHeader {
         teid,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(teid: u8) -> ForwardingAction {
+        ForwardingAction {
+            remote_tunnel_info: GtpTunnel {
+                transport_layer_address: "127.0.0.1".try_into().unwrap(),
+                gtp_teid: GtpTeid([0, 0, 0, teid]),
+            },
+        }
+    }
+
+    fn drb(
+        drb_id: u8,
+        qos_flows: Vec<u8>,
+        downlink: Option<ForwardingAction>,
+    ) -> DrbForwardingContext {
+        DrbForwardingContext {
+            drb_id,
+            downlink,
+            qos_flows,
+        }
+    }
+
+    // Session 1 has DRB 1 for QoS flow 1 and DRB 2 for QoS flows 2 and 3.  Session 2 has DRB 3 for QoS flow 1,
+    // which the DU has not set up yet, and DRB 4 for QoS flow 5.  Each tunnel's TEID is numbered after its
+    // session or DRB, with 10 added for the sessions.
+    fn two_sessions() -> ForwardingContext {
+        ForwardingContext {
+            sessions: vec![
+                SessionForwardingContext {
+                    pdu_session_id: 1,
+                    uplink: action(11),
+                    drbs: vec![
+                        drb(1, vec![1], Some(action(1))),
+                        drb(2, vec![2, 3], Some(action(2))),
+                    ],
+                },
+                SessionForwardingContext {
+                    pdu_session_id: 2,
+                    uplink: action(12),
+                    drbs: vec![drb(3, vec![1], None), drb(4, vec![5], Some(action(4)))],
+                },
+            ],
+        }
+    }
+
+    fn teid(action: Option<&ForwardingAction>) -> Option<u8> {
+        action.map(|x| x.remote_tunnel_info.gtp_teid.0[3])
+    }
+
+    #[test]
+    fn downlink_packets_go_to_drb_of_their_qos_flow() {
+        let context = two_sessions();
+        assert_eq!(teid(context.downlink_action(1, 1)), Some(1));
+        assert_eq!(teid(context.downlink_action(1, 3)), Some(2));
+        assert_eq!(teid(context.downlink_action(2, 5)), Some(4));
+
+        // The same QFI in another session goes to that session's DRB, here one with no F1-U tunnel yet.
+        assert_eq!(teid(context.downlink_action(2, 1)), None);
+
+        // Unmapped QoS flows and unknown sessions are dropped.
+        assert_eq!(teid(context.downlink_action(1, 5)), None);
+        assert_eq!(teid(context.downlink_action(3, 1)), None);
+    }
+
+    #[test]
+    fn uplink_packets_go_to_session_of_their_drb() {
+        let context = two_sessions();
+        assert_eq!(teid(context.uplink_action(1, 1)), Some(11));
+        assert_eq!(teid(context.uplink_action(2, 3)), Some(11));
+        assert_eq!(teid(context.uplink_action(3, 1)), Some(12));
+        assert_eq!(teid(context.uplink_action(4, 5)), Some(12));
+
+        // A QoS flow that is not mapped to the DRB, or an unknown DRB, is dropped.
+        assert_eq!(teid(context.uplink_action(1, 2)), None);
+        assert_eq!(teid(context.uplink_action(5, 1)), None);
+    }
+
+    #[test]
+    fn suspended_ue_gets_no_downlink_packets() {
+        let mut context = two_sessions();
+        context.clear_downlink();
+        assert_eq!(teid(context.downlink_action(1, 1)), None);
+        assert_eq!(teid(context.downlink_action(2, 5)), None);
+
+        // Uplink packets still go to the UPF.
+        assert_eq!(teid(context.uplink_action(1, 1)), Some(11));
+    }
+
+    #[test]
+    fn teids_round_trip() {
+        for ue_id in [0, 1, 0x1ff, 0x7fffff] {
+            for index in [1, 2, 15] {
+                let GtpTeid(uplink) = uplink_teid(ue_id, index);
+                assert_eq!(
+                    decode_teid(u32::from_be_bytes(uplink)),
+                    (key(ue_id), index, false)
+                );
+                let GtpTeid(downlink) = downlink_teid(ue_id, index);
+                assert_eq!(
+                    decode_teid(u32::from_be_bytes(downlink)),
+                    (key(ue_id), index, true)
+                );
+            }
+        }
+    }
+}
@@ -5,27 +5,27 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use crate::handlers::E1apHandler;
-use crate::packet_processor::ForwardingAction;
+use crate::packet_processor::{self, ForwardingAction};
 use crate::workflows::Workflow;
 use crate::GnbCuUp;
 use crate::{config::Config, packet_processor::PacketProcessor};
 use anyhow::Result;
 use asn1_per::{Procedure, RequestError, RequestProvider};
-use async_net::IpAddr;
+use async_net::{IpAddr, SocketAddr};
 use async_trait::async_trait;
 use dashmap::DashMap;
 use e1ap::GnbCuUpUeE1apId;
 use futures::{pin_mut, select, FutureExt};
-use net::{SctpTransportProvider, ShutdownHandle, Stack};
+use net::{SctpConfig, SctpTransportProvider, ShutdownHandle, Stack, TransportProvider};
 use slog::{debug, info, warn, Logger};
 use stop_token::{StopSource, StopToken};
 use xxap::GtpTeid;
 
 const RETRY_SECS: u64 = 10;
 #[derive(Clone)]
-pub struct Worker {
+pub struct Worker<T: TransportProvider> {
     config: Config,
-    e1ap: Stack,
+    e1ap: Stack<T>,
     packet_processor: PacketProcessor,
     logger: Logger,
     ue_ap_id_generator: Arc<AtomicU32>,
@@ -37,10 +37,18 @@ const E1AP_SCTP_PPID: u32 = 64;
 const E1AP_BIND_PORT: u16 = 38462;
 
 pub async fn spawn(config: Config, logger: Logger) -> Result<ShutdownHandle> {
+    spawn_with_transport::<SctpTransportProvider>(config, logger).await
+}
+
+/// Spawn a worker whose E1AP runs over the given transport rather than SCTP.
+pub async fn spawn_with_transport<T: TransportProvider>(
+    config: Config,
+    logger: Logger,
+) -> Result<ShutdownHandle> {
     let stop_source = StopSource::new();
     let stop_token = stop_source.token();
     info!(&logger, "Starting gNB-CU-UP worker");
-    let worker = Worker::new(config, logger.clone()).await?;
+    let worker = Worker::<T>::new(config, logger.clone()).await?;
 
     let handle = async_std::task::spawn(async move {
         worker
@@ -52,12 +60,13 @@ pub async fn spawn(config: Config, logger: Logger) -> Result<ShutdownHandle> {
     Ok(ShutdownHandle::new(handle, stop_source))
 }
 
-impl Worker {
-    async fn new(config: Config, logger: Logger) -> Result<Worker> {
+impl<T: TransportProvider> Worker<T> {
+    async fn new(config: Config, logger: Logger) -> Result<Self> {
+        config.ran.validate()?;
         let userplane_ip_address = config.userplane_ip_address;
         Ok(Worker {
             config,
-            e1ap: Stack::new(SctpTransportProvider::new()),
+            e1ap: Stack::new(T::new_with_config(SctpConfig::default())),
             packet_processor: PacketProcessor::new(userplane_ip_address, logger.clone()).await?,
             logger,
             ue_ap_id_generator: Arc::new(AtomicU32::new(1)),
@@ -67,13 +76,15 @@ impl Worker {
 
     async fn run(self, stop_token: StopToken) -> Result<()> {
         let logger = &self.logger;
+        self.log_periodic_send_queue_stats(stop_token.clone());
 
         // Infinitely retry to connect to GNB-CU-CP
         let stop_token = stop_token.fuse();
         pin_mut!(stop_token);
+        let supported_plmns = self.config.plmns()?;
         loop {
             match Workflow::new(&self, &logger.clone())
-                .gnb_cu_up_e1_setup(&self.config.cp_ip_address, 1, self.config.plmns())
+                .gnb_cu_up_e1_setup(&self.config.cp_ip_address, 1, supported_plmns.clone())
                 .await
             {
                 Ok(_) => {
@@ -103,77 +114,103 @@ impl Worker {
         self.e1ap.graceful_shutdown().await;
         Ok(())
     }
+
+    fn log_periodic_send_queue_stats(&self, stop_token: StopToken) {
+        let clone = self.clone();
+        async_std::task::spawn(async move {
+            let interval = Duration::from_secs(clone.config.send_queue_stats_interval_secs);
+            while async_std::future::timeout(interval, stop_token.clone())
+                .await
+                .is_err()
+            {
+                clone.e1ap.log_send_queue_stats("E1AP", &clone.logger).await;
+            }
+        });
+    }
 }
 
 #[async_trait]
-impl GnbCuUp for Worker {
+impl<T: TransportProvider> GnbCuUp for Worker<T> {
     fn config(&self) -> &Config {
         &self.config
     }
 
-    async fn set_uplink_forwarding_action(&self, gtp_teid: GtpTeid, action: ForwardingAction) {
+    async fn add_session(&self, ue_id: u32, pdu_session_id: u8, uplink_action: ForwardingAction) {
         self.packet_processor
-            .set_uplink_forwarding_action(gtp_teid, action, &self.logger)
+            .add_session(ue_id, pdu_session_id, uplink_action, &self.logger)
             .await
     }
 
-    async fn set_downlink_forwarding_action(&self, gtp_teid: GtpTeid, action: ForwardingAction) {
+    async fn add_drb(
+        &self,
+        ue_id: u32,
+        pdu_session_id: u8,
+        drb_id: u8,
+        qfis: Vec<u8>,
+    ) -> Result<()> {
         self.packet_processor
-            .set_downlink_forwarding_action(gtp_teid, action, &self.logger)
+            .add_drb(ue_id, pdu_session_id, drb_id, qfis, &self.logger)
             .await
     }
 
-    fn bearer_context_exists(&self, ue_id: u32) -> bool {
-        self.ues.contains_key(&ue_id)
+    async fn set_downlink_forwarding_action(
+        &self,
+        ue_id: u32,
+        drb_id: u8,
+        action: ForwardingAction,
+    ) -> Result<()> {
+        self.packet_processor
+            .set_downlink_forwarding_action(ue_id, drb_id, action, &self.logger)
+            .await
     }
 
-    async fn delete_bearer_context(&self, ue_id: u32) {
-        // Determine all the sessions for this UE and remove all the
-        // forwarding actions
+    async fn set_qos_flows(&self, ue_id: u32, drb_id: u8, qfis: Vec<u8>) -> Result<()> {
+        self.packet_processor
+            .set_qos_flows(ue_id, drb_id, qfis, &self.logger)
+            .await
+    }
 
-        // TODO: cope with > 1 session
-        let uplink_teid_session_1 = self.create_uplink_teid(ue_id, 1);
+    async fn suspend_bearer_context(&self, ue_id: u32) {
+        // The DU has released the UE, so its downlink tunnels are no longer valid.
         self.packet_processor
-            .clear_forwarding_actions(uplink_teid_session_1)
+            .clear_downlink_forwarding_actions(ue_id, &self.logger)
             .await;
     }
 
+    fn bearer_context_exists(&self, ue_id: u32) -> bool {
+        self.ues.contains_key(&ue_id)
+    }
+
+    async fn delete_bearer_context(&self, ue_id: u32) {
+        // Remove the forwarding actions of all the UE's sessions.
+        self.packet_processor.clear_forwarding_actions(ue_id).await;
+    }
+
     fn new_ue_ap_id(&self) -> GnbCuUpUeE1apId {
         let ue_id = GnbCuUpUeE1apId(self.ue_ap_id_generator.fetch_add(1, Ordering::Relaxed));
         self.ues.insert(ue_id.0, ());
         ue_id
     }
 
-    // TODO - move to packet processor
-    // TODO - this breaks the rule from 29.281, 5.1 of not using all 0s, and also breaks the
-    // rule about predictability - given the CuUpE1apId from which it is generated is predictable.
-    fn create_uplink_teid(&self, ue_id: u32, session_id: u8) -> GtpTeid {
-        // 8 bits of session ID, the 23 LSBs of UE ID, 1 bit indicating direction
-        GtpTeid([
-            session_id,
-            ((ue_id & 0x7f8000) >> 15) as u8,
-            ((ue_id & 0x7f80) >> 7) as u8,
-            ((ue_id & 0x7f) << 1) as u8, // LSB clear for uplink
-        ])
+    fn create_uplink_teid(&self, ue_id: u32, drb_id: u8) -> GtpTeid {
+        packet_processor::uplink_teid(ue_id, drb_id)
     }
 
-    fn create_downlink_teid(&self, ue_id: u32, session_id: u8) -> GtpTeid {
-        // 8 bits of session ID, the 23 LSBs of UE ID, 1 bit indicating direction
-        GtpTeid([
-            session_id,
-            ((ue_id & 0x7f8000) >> 15) as u8,
-            ((ue_id & 0x7f80) >> 7) as u8,
-            (((ue_id & 0x7f) << 1) | 1) as u8, // LSB set for downlink
-        ])
+    fn create_downlink_teid(&self, ue_id: u32, pdu_session_id: u8) -> GtpTeid {
+        packet_processor::downlink_teid(ue_id, pdu_session_id)
     }
 
     async fn e1ap_connect(&self, cp_address: &IpAddr) -> Result<()> {
-        let cp_address = format!("{}:{}", cp_address, E1AP_BIND_PORT);
+        let bind_address = match cp_address {
+            IpAddr::V4(_) => "0.0.0.0",
+            IpAddr::V6(_) => "::",
+        };
+        let cp_address = SocketAddr::new(*cp_address, E1AP_BIND_PORT).to_string();
 
         self.e1ap
             .connect(
                 &cp_address,
-                "0.0.0.0",
+                bind_address,
                 E1AP_SCTP_PPID,
                 E1apHandler::new_e1ap_application(self.clone()),
                 self.logger.clone(),
@@ -186,7 +223,7 @@ impl GnbCuUp for Worker {
         r: P::Request,
         logger: &Logger,
     ) -> Result<P::Success, RequestError<P::Failure>> {
-        <Stack as RequestProvider<P>>::request(&self.e1ap, r, logger)
+        <Stack<T> as RequestProvider<P>>::request(&self.e1ap, r, logger)
             .await
             .map(|(x, _)| x)
     }
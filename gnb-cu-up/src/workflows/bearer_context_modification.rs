@@ -6,7 +6,7 @@ use crate::packet_processor::ForwardingAction;
 use anyhow::{bail, Result, ensure};
 use e1ap::*;
 use slog::debug;
-use xxap::PduSessionId;
+use xxap::{GtpTunnel, TransportLayerAddress};
 
 impl<'a, G: GnbCuUp> Workflow<'a, G> {
     pub async fn bearer_context_modification(
@@ -19,17 +19,31 @@ impl<'a, G: GnbCuUp> Workflow<'a, G> {
         debug!(&self.logger, "Modification of UE context {}",r.gnb_cu_up_ue_e1ap_id.0);
         ensure!(self.bearer_context_exists(r.gnb_cu_up_ue_e1ap_id.0));
 
-        let Some(SystemBearerContextModificationRequest::NgRanBearerContextModificationRequest(
-            NgRanBearerContextModificationRequest{ 
-            pdu_session_resource_to_modify_list: Some(pdu_session_resource_to_modify_list), 
-            ..
-            })) = r.system_bearer_context_modification_request 
-        else {
-            bail!("Not an NgRanBearerContextModificationRequestRequest");
+        // When the UE is suspended, the DU releases it, so downlink packets have nowhere to go.  When it
+        // resumes, the GNB-CU-CP supplies the DU's new downlink tunnels in this or a later request.
+        match r.bearer_context_status_change {
+            Some(BearerContextStatusChange::Suspend) => {
+                debug!(&self.logger, "Suspend UE context {}", r.gnb_cu_up_ue_e1ap_id.0);
+                self.suspend_bearer_context(r.gnb_cu_up_ue_e1ap_id.0).await;
+            }
+            Some(BearerContextStatusChange::Resume) => {
+                debug!(&self.logger, "Resume UE context {}", r.gnb_cu_up_ue_e1ap_id.0);
+            }
+            None => (),
+        }
+
+        // A request that only changes the status of the bearer context modifies no sessions.
+        let mut to_mod_items: Vec<PduSessionResourceToModifyItem> = match r.system_bearer_context_modification_request {
+            Some(SystemBearerContextModificationRequest::NgRanBearerContextModificationRequest(
+                NgRanBearerContextModificationRequest{
+                pdu_session_resource_to_modify_list: Some(pdu_session_resource_to_modify_list),
+                ..
+                })) => pdu_session_resource_to_modify_list.0.into(),
+            None if r.bearer_context_status_change.is_some() => vec![],
+            _ => bail!("Not an NgRanBearerContextModificationRequestRequest"),
         };
 
         let mut mod_items = vec![];
-        let mut to_mod_items: Vec<PduSessionResourceToModifyItem> = pdu_session_resource_to_modify_list.0.into();
         for to_mod_item in to_mod_items.drain(..) {
             let mod_item = self.modify_session(r.gnb_cu_up_ue_e1ap_id, to_mod_item).await?;
             mod_items.push(mod_item);
@@ -59,40 +73,92 @@ impl<'a, G: GnbCuUp> Workflow<'a, G> {
         ue_id: GnbCuUpUeE1apId,
         mod_item: PduSessionResourceToModifyItem,
     ) -> Result<PduSessionResourceModifiedItem> {
-        // TODO: support > 1 session.
-
-        // We have already signalled a downlink GTP TEID back to the GNB-CU-CP at setup time.
-        // Now we have enough info need to program a forwarding action for it.  Our GTP TEIDs are
-        // encoded deterministically from other info so we can simply reconsistute it here.
-        let session_1_downlink_gtp_teid = self.create_downlink_teid(ue_id.0, 1);
-
         let Some(modify_list) = mod_item.drb_to_modify_list_ng_ran else {
             bail!("No modify list on PduSessionResourceToModifyItem")
         };
 
-        let Some(up_parameters) = modify_list.0.head.dl_up_parameters else {
-            bail!("No UP parameters on DrbToModifyItemNgRan")
-        };
-
-        let UpTnlInformation::GtpTunnel(remote_tunnel_info) = up_parameters.0.head.up_tnl_information;
-
-        let forwarding_action = ForwardingAction {
-            remote_tunnel_info,
-        };
-
-        // Install it in the packet processor.
-        self.set_downlink_forwarding_action(session_1_downlink_gtp_teid, forwarding_action).await;
+        let mut drb_modified_items = vec![];
+        for drb in modify_list.0 {
+            if let Some(drb_modified_item) = self.modify_drb(ue_id, drb).await? {
+                drb_modified_items.push(drb_modified_item);
+            }
+        }
+        let drb_modified_list_ng_ran =
+            NonEmpty::from_vec(drb_modified_items).map(DrbModifiedListNgRan);
 
         Ok(PduSessionResourceModifiedItem {
-            pdu_session_id: PduSessionId(1), // TODO
+            pdu_session_id: mod_item.pdu_session_id,
             ng_dl_up_tnl_information: None,
             security_result: None,
             pdu_session_data_forwarding_information_response: None,
             drb_setup_list_ng_ran: None,
             drb_failed_list_ng_ran: None,
-            drb_modified_list_ng_ran: None,
+            drb_modified_list_ng_ran,
             drb_failed_to_modify_list_ng_ran: None,
             redundant_n_g_dl_up_tnl_information: None,
         })
     }
+
+    async fn modify_drb(
+        &self,
+        ue_id: GnbCuUpUeE1apId,
+        drb: DrbToModifyItemNgRan,
+    ) -> Result<Option<DrbModifiedItemNgRan>> {
+        // The GNB-CU-CP either tells us the DU's tunnel info, once the DU has set up the DRB, or
+        // changes the QoS flows mapped to the DRB, when the AMF modifies the session.
+        ensure!(
+            drb.dl_up_parameters.is_some() || drb.flow_mapping_information.is_some(),
+            "Neither UP parameters nor flow mapping on DrbToModifyItemNgRan"
+        );
+
+        if let Some(up_parameters) = drb.dl_up_parameters {
+            // We have already signalled a downlink GTP TEID for the session back to the GNB-CU-CP at
+            // setup time.  Now we have enough info to program a forwarding action for the DRB.
+            let UpTnlInformation::GtpTunnel(remote_tunnel_info) =
+                up_parameters.0.head.up_tnl_information;
+
+            let forwarding_action = ForwardingAction {
+                remote_tunnel_info,
+            };
+
+            // Install it in the packet processor.
+            self.set_downlink_forwarding_action(ue_id.0, drb.drb_id.0, forwarding_action)
+                .await?;
+        }
+
+        let Some(flow_mapping_information) = drb.flow_mapping_information else {
+            return Ok(None);
+        };
+
+        // Remap the QoS flows.
+        let flows = flow_mapping_information.0.map(|x| x.qos_flow_identifier);
+        self.set_qos_flows(ue_id.0, drb.drb_id.0, flows.iter().map(|x| x.0).collect())
+            .await?;
+
+        // Give back our uplink tunnel info, which the GNB-CU-CP passes on to the DU
+        // when it modifies the DRB there.  Our GTP TEIDs are encoded deterministically
+        // from other info so we can simply reconstitute it here.
+        let my_f1u_address: TransportLayerAddress = self.config().userplane_ip_address.into();
+        Ok(Some(DrbModifiedItemNgRan {
+            drb_id: drb.drb_id,
+            ul_up_transport_parameters: Some(UpParameters(nonempty![UpParametersItem {
+                up_tnl_information: UpTnlInformation::GtpTunnel(GtpTunnel {
+                    transport_layer_address: my_f1u_address,
+                    gtp_teid: self.create_uplink_teid(ue_id.0, drb.drb_id.0),
+                }),
+                cell_group_id: CellGroupId(1),
+                qos_mapping_information: None,
+            }])),
+            pdcp_sn_status_information: None,
+            flow_setup_list: Some(QosFlowList(flows.map(|qos_flow_identifier| {
+                QosFlowItem {
+                    qos_flow_identifier,
+                    qos_flow_mapping_indication: None,
+                }
+            }))),
+            flow_failed_list: None,
+            early_forwarding_count_info: None,
+            old_qos_flow_map_ul_endmarkerexpected: None,
+        }))
+    }
 }
@@ -3,7 +3,7 @@
 
 use super::{GnbCuUp, Workflow};
 use crate::packet_processor::ForwardingAction;
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use asn1_per::*;
 use e1ap::*;
 use slog::debug;
@@ -59,55 +59,89 @@ impl<'a, G: GnbCuUp> Workflow<'a, G> {
         ue_id: GnbCuUpUeE1apId,
         setup_item: &PduSessionResourceToSetupItem,
     ) -> Result<PduSessionResourceSetupItem> {
-        // Create the uplink forwarding rule.
-        let session_1_uplink_gtp_teid = self.create_uplink_teid(ue_id.0, 1);
-        let forwarding_action = create_forwarding_action(setup_item);
+        let pdu_session_id = setup_item.pdu_session_id;
 
-        // Install it in the packet processor.
-        self.set_uplink_forwarding_action(session_1_uplink_gtp_teid.clone(), forwarding_action)
+        // Create the uplink forwarding rule and install it in the packet processor.  This is shared
+        // by all of the session's DRBs.
+        let forwarding_action = create_forwarding_action(setup_item);
+        self.add_session(ue_id.0, pdu_session_id.0, forwarding_action)
             .await;
 
-        // We also need to supply our downlink TEID now, even though we program a rule for this
-        // until we learn about the DU's TEID at the later modification stage.
-        let session_1_downlink_gtp_teid = self.create_downlink_teid(ue_id.0, 1);
-
-        // Form response
-
         // The CU-UP just supports a single IP address, so the uplink and downlink IPs are the same.
         let my_n3_address: TransportLayerAddress = self.config().userplane_ip_address.into();
         let my_f1u_address = my_n3_address.clone();
 
+        let mut drb_setup_items = vec![];
+        for drb in setup_item.drb_to_setup_list_ng_ran.0.iter() {
+            let drb_setup_item = self
+                .setup_drb(ue_id, pdu_session_id, drb, &my_f1u_address)
+                .await?;
+            drb_setup_items.push(drb_setup_item);
+        }
+
+        // We also need to supply our downlink TEID now, even though we can't program a rule for this
+        // until we learn about the DU's TEIDs at the later modification stage.
+        let downlink_gtp_teid = self.create_downlink_teid(ue_id.0, pdu_session_id.0);
+
+        // Form response
         Ok(PduSessionResourceSetupItem {
-            pdu_session_id: PduSessionId(1),
+            pdu_session_id,
             security_result: None,
             ng_dl_up_tnl_information: UpTnlInformation::GtpTunnel(GtpTunnel {
                 transport_layer_address: my_n3_address,
-                gtp_teid: session_1_downlink_gtp_teid,
+                gtp_teid: downlink_gtp_teid,
             }),
             pdu_session_data_forwarding_information_response: None,
             ng_dl_up_unchanged: None,
-            drb_setup_list_ng_ran: DrbSetupListNgRan(nonempty![DrbSetupItemNgRan {
-                drb_id: DrbId(1),
-                drb_data_forwarding_information_response: None,
-                ul_up_transport_parameters: UpParameters(nonempty![UpParametersItem {
-                    up_tnl_information: UpTnlInformation::GtpTunnel(GtpTunnel {
-                        transport_layer_address: my_f1u_address,
-                        gtp_teid: session_1_uplink_gtp_teid,
-                    }),
-                    cell_group_id: CellGroupId(1),
-                    qos_mapping_information: None,
-                }]),
-                flow_setup_list: QosFlowList(nonempty![QosFlowItem {
-                    qos_flow_identifier: QosFlowIdentifier(1),
-                    qos_flow_mapping_indication: None,
-                }]),
-                flow_failed_list: None,
-            }]),
+            drb_setup_list_ng_ran: DrbSetupListNgRan(
+                NonEmpty::from_vec(drb_setup_items).ok_or_else(|| anyhow!("No DRBs to set up"))?,
+            ),
             drb_failed_list_ng_ran: None,
             redundant_n_g_dl_up_tnl_information: None,
             redundant_pdu_session_information_used: None,
         })
     }
+
+    async fn setup_drb(
+        &self,
+        ue_id: GnbCuUpUeE1apId,
+        pdu_session_id: PduSessionId,
+        drb: &DrbToSetupItemNgRan,
+        my_f1u_address: &TransportLayerAddress,
+    ) -> Result<DrbSetupItemNgRan> {
+        // Only the QoS flows mapped to the DRB are let through.
+        let qfis: Vec<u8> = drb
+            .qos_flow_information_to_be_setup
+            .0
+            .iter()
+            .map(|flow| flow.qos_flow_identifier.0)
+            .collect();
+        self.add_drb(ue_id.0, pdu_session_id.0, drb.drb_id.0, qfis)
+            .await?;
+
+        // The DU sends the DRB's uplink packets to this TEID.
+        let uplink_gtp_teid = self.create_uplink_teid(ue_id.0, drb.drb_id.0);
+
+        Ok(DrbSetupItemNgRan {
+            drb_id: drb.drb_id,
+            drb_data_forwarding_information_response: None,
+            ul_up_transport_parameters: UpParameters(nonempty![UpParametersItem {
+                up_tnl_information: UpTnlInformation::GtpTunnel(GtpTunnel {
+                    transport_layer_address: my_f1u_address.clone(),
+                    gtp_teid: uplink_gtp_teid,
+                }),
+                cell_group_id: CellGroupId(1),
+                qos_mapping_information: None,
+            }]),
+            flow_setup_list: QosFlowList(drb.qos_flow_information_to_be_setup.0.clone().map(
+                |flow| QosFlowItem {
+                    qos_flow_identifier: flow.qos_flow_identifier,
+                    qos_flow_mapping_indication: None,
+                },
+            )),
+            flow_failed_list: None,
+        })
+    }
 }
 
 fn create_forwarding_action(setup_item: &PduSessionResourceToSetupItem) -> ForwardingAction {
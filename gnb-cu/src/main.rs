@@ -3,56 +3,124 @@
 #![allow(unused_parens)]
 use anyhow::{bail, ensure, Result};
 use clap::Parser;
-use common::{logging, panic, signal, ShutdownHandle};
+use common::{config_file, logging, panic, signal, ShutdownHandle};
 use coordinator::Config as CoordinatorConfig;
-use gnb_cu_cp::{Config as CpConfig, MockUeStore, WorkerConnectionManagementConfig};
-use gnb_cu_cp::{ConnectionControlConfig, ConnectionStyle};
+use gnb_cu_cp::{
+    Config as CpConfig, MockCuUpStore, MockDuStore, MockUeStore, WorkerConnectionManagementConfig,
+};
+use gnb_cu_cp::{ConnectionControlConfig, ConnectionStyle, RanConfig, Snssai};
 use gnb_cu_up::Config as UpConfig;
+use serde::Deserialize;
 use slog::{info, o, warn, Logger};
 use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
 use std::time::Duration;
 use uuid::Uuid;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// TOML file giving the GNB-CU's configuration.  The other options override the settings in it.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
     /// Local IP address of GNB-CU, both for control plane (F1AP, E1AP, NGAP) and userplane protocols (GTP-U).
-    #[arg(long, default_value_t = IpAddr::V4(Ipv4Addr::UNSPECIFIED))]
-    local_ip: IpAddr,
+    /// Default 0.0.0.0.
+    #[arg(long)]
+    local_ip: Option<IpAddr>,
 
-    /// AMF's NGAP IP address to connect to.
-    #[arg(long, default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
-    amf_ip: IpAddr,
+    /// AMF's NGAP IP address to connect to.  Default 127.0.0.1.
+    #[arg(long)]
+    amf_ip: Option<IpAddr>,
 
-    /// Mobile Country Code part of the PLMN ID (Public Land Mobile Network ID).  
+    /// Mobile Country Code part of the PLMN ID (Public Land Mobile Network ID).
     /// A string of three digits.
-    #[arg(long)]
-    mcc: String,
+    #[arg(long, requires = "mnc")]
+    mcc: Option<String>,
 
-    /// Mobile Network Code part of the PLMN ID (Public Land Mobile Network ID).  
+    /// Mobile Network Code part of the PLMN ID (Public Land Mobile Network ID).
     /// A string of two or three digits.
+    #[arg(long, requires = "mcc")]
+    mnc: Option<String>,
+
+    /// Tracking Area Code served by the gNB.  Default 1.
     #[arg(long)]
-    mnc: String,
+    tac: Option<u32>,
+
+    /// Slice supported in the tracking area, given as SST, or as SST:SD with the SD in hex.  May be repeated.
+    /// If none is given, the gNB supports the slices that free5GC is set up for by default.
+    #[arg(long)]
+    slice: Vec<String>,
+
+    /// gNB ID, as signalled to the AMF in the Global RAN Node ID.  Default 0x3fffff.
+    #[arg(long)]
+    gnb_id: Option<u32>,
+
+    /// Length of the gNB ID in bits, from 22 to 32.  Default 22.
+    #[arg(long)]
+    gnb_id_length: Option<u8>,
+
+    /// Port on which the CU-CP serves the Connection API.  Default 50312.
+    #[arg(long)]
+    connection_api_port: Option<u16>,
+
+    /// Port on which the coordinator serves the Coordination API.  Default 65232.
+    #[arg(long)]
+    coordination_api_port: Option<u16>,
+}
+
+// The configuration file of the GNB-CU.  The CU-CP and CU-UP get their addresses and RAN configuration from
+// the top level settings, so these are ignored if given in the cu_cp and cu_up tables, as is the CU-CP's
+// connection style.  Of the coordinator's settings, the AMF address is likewise taken from amf_ip.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    local_ip: IpAddr,
+    amf_ip: IpAddr,
+    connection_api_port: u16,
+    coordination_api_port: u16,
+    ran: RanConfig,
+    coordinator: ConnectionControlConfig,
+    cu_cp: CpConfig,
+    cu_up: UpConfig,
 }
 
-const CONNECTION_API_PORT: u16 = 50312;
-const COORDINATION_API_PORT: u16 = 65232;
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            local_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            amf_ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            connection_api_port: 50312,
+            coordination_api_port: 65232,
+            ran: RanConfig::default(),
+            coordinator: ConnectionControlConfig {
+                worker_refresh_interval_secs: 10,
+                fast_start: true,
+                ..ConnectionControlConfig::default()
+            },
+            cu_cp: CpConfig::default(),
+            cu_up: UpConfig::default(),
+        }
+    }
+}
 
 #[async_std::main]
 async fn main() -> Result<()> {
     panic::exit_on_panic();
     let args = Args::parse();
+    let config = config(args)?;
     let root_logger = logging::init();
 
-    // Attempt to bind a new coordinator to 0.0.0.0:65232.
-    let maybe_coordinator = spawn_coordinator(&args, root_logger.new(o!("coord" => 1))).await;
+    // Attempt to bind a new coordinator to 0.0.0.0:<coordination API port>.
+    let maybe_coordinator = spawn_coordinator(&config, root_logger.new(o!("coord" => 1))).await;
 
-    let (cp_shutdown_handle, local_ip) = spawn_cp(&args, root_logger.new(o!("cu-cp" => 1))).await?;
+    let (cp_shutdown_handle, local_ip) =
+        spawn_cp(&config, root_logger.new(o!("cu-cp" => 1))).await?;
 
     // Wait a couple of seconds for the CP to bind its E1AP socket to avoid a retry and warning.
     async_std::task::sleep(Duration::from_secs(2)).await;
 
-    let cu_shutdown_handle = spawn_up(local_ip, root_logger.new(o!("cu-up" => 1))).await?;
+    let cu_shutdown_handle = spawn_up(&config, local_ip, root_logger.new(o!("cu-up" => 1))).await?;
     let s = signal::wait_for_signal().await?;
     info!(root_logger, "Caught signal {} - terminate", s);
     cp_shutdown_handle.graceful_shutdown().await;
@@ -64,14 +132,62 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-async fn spawn_coordinator(args: &Args, logger: Logger) -> Result<ShutdownHandle> {
+fn config(args: Args) -> Result<Config> {
+    let mut config: Config = match &args.config {
+        Some(path) => config_file::load(path)?,
+        None => Config::default(),
+    };
+    if let Some(local_ip) = args.local_ip {
+        config.local_ip = local_ip;
+    }
+    if let Some(amf_ip) = args.amf_ip {
+        config.amf_ip = amf_ip;
+    }
+    if let Some(port) = args.connection_api_port {
+        config.connection_api_port = port;
+    }
+    if let Some(port) = args.coordination_api_port {
+        config.coordination_api_port = port;
+    }
+    override_ran_config(&mut config.ran, &args)?;
+    Ok(config)
+}
+
+// The RAN options on the command line describe a gNB with a single tracking area, in which it broadcasts
+// the PLMN given by the MCC and MNC.  They override the gNB's identity and its first tracking area.
+fn override_ran_config(ran: &mut RanConfig, args: &Args) -> Result<()> {
+    if let (Some(mcc), Some(mnc)) = (&args.mcc, &args.mnc) {
+        ran.plmn = gnb_cu_cp::plmn_from_mcc_mnc(mcc, mnc)?;
+        ran.supported_tas[0].broadcast_plmns[0].plmn = ran.plmn;
+    }
+    if let Some(gnb_id) = args.gnb_id {
+        ran.gnb_id = gnb_id;
+    }
+    if let Some(gnb_id_length) = args.gnb_id_length {
+        ran.gnb_id_length = gnb_id_length;
+    }
+    let ta = &mut ran.supported_tas[0];
+    if let Some(tac) = args.tac {
+        ensure!(tac < (1 << 24), "TAC must be at most three bytes");
+        ta.tac.copy_from_slice(&tac.to_be_bytes()[1..]);
+    }
+    if !args.slice.is_empty() {
+        ta.broadcast_plmns[0].slices = args
+            .slice
+            .iter()
+            .map(|x| x.parse())
+            .collect::<Result<Vec<Snssai>>>()?;
+    }
+    ran.validate()
+}
+
+async fn spawn_coordinator(config: &Config, logger: Logger) -> Result<ShutdownHandle> {
     let maybe_coordinator = coordinator::spawn(
         CoordinatorConfig {
-            bind_port: COORDINATION_API_PORT,
+            bind_port: config.coordination_api_port,
             connection_control_config: ConnectionControlConfig {
-                amf_address: args.amf_ip.to_string(),
-                worker_refresh_interval_secs: 10,
-                fast_start: true,
+                amf_address: config.amf_ip.to_string(),
+                ..config.coordinator.clone()
             },
         },
         logger.clone(),
@@ -83,30 +199,38 @@ async fn spawn_coordinator(args: &Args, logger: Logger) -> Result<ShutdownHandle
     maybe_coordinator
 }
 
-async fn spawn_cp(args: &Args, logger: Logger) -> Result<(ShutdownHandle, IpAddr)> {
-    let plmn = convert_mcc_mnc_to_plmn_array(&args.mcc, &args.mnc).unwrap();
-    let ip_addr = args.local_ip;
-    let cp_config = CpConfig {
+fn cp_config(config: &Config, ip_addr: IpAddr) -> CpConfig {
+    let connection_api_port = config.connection_api_port;
+    let coordination_api_port = config.coordination_api_port;
+    CpConfig {
         ip_addr,
         connection_style: ConnectionStyle::Coordinated(WorkerConnectionManagementConfig {
-            connection_api_bind_port: CONNECTION_API_PORT, // TODO - make configurable
-            connection_api_base_path: format!("http://{ip_addr}:{CONNECTION_API_PORT}"),
-            coordinator_base_path: format!("http://127.0.0.1:{COORDINATION_API_PORT}"),
+            connection_api_bind_port: connection_api_port,
+            connection_api_base_path: format!("http://{ip_addr}:{connection_api_port}"),
+            coordinator_base_path: format!("http://127.0.0.1:{coordination_api_port}"),
+            refresh_interval_secs: config.coordinator.worker_refresh_interval_secs,
         }),
-        plmn,
-        ..CpConfig::default()
-    };
+        ran: config.ran.clone(),
+        ..config.cu_cp.clone()
+    }
+}
+
+async fn spawn_cp(config: &Config, logger: Logger) -> Result<(ShutdownHandle, IpAddr)> {
+    let ip_addr = config.local_ip;
     gnb_cu_cp::spawn(
         Uuid::new_v4(),
-        cp_config,
+        cp_config(config, ip_addr),
         MockUeStore::new(),
+        MockDuStore::new(),
+        MockCuUpStore::new(),
         logger.clone(),
     )
     .await
     .map(|h| (h, ip_addr))
 }
 
-async fn spawn_up(local_ip: IpAddr, logger: Logger) -> Result<ShutdownHandle> {
+// The CU-UP is given the same RAN configuration as the CU-CP, so that it supports the same PLMNs and slices.
+async fn spawn_up(config: &Config, local_ip: IpAddr, logger: Logger) -> Result<ShutdownHandle> {
     let cp_ip_address = if local_ip.is_unspecified() {
         IpAddr::V4(Ipv4Addr::LOCALHOST)
     } else {
@@ -116,84 +240,39 @@ async fn spawn_up(local_ip: IpAddr, logger: Logger) -> Result<ShutdownHandle> {
         local_ip_address: local_ip,
         userplane_ip_address: local_ip,
         cp_ip_address,
-        name: None,
+        ran: config.ran.clone(),
+        ..config.cu_up.clone()
     };
 
     gnb_cu_up::spawn(up_config, logger).await
 }
 
-fn convert_mcc_mnc_to_plmn_array(mcc: &str, mnc: &str) -> Result<[u8; 3]> {
-    ensure!(mcc.len() == 3, "MCC must be three decimal digits");
-
-    let mut plmn_digits = [0u8; 6];
-    let mut plmn = [0u8; 3];
-    for (n, c) in mcc.chars().enumerate() {
-        let Some(digit) = c.to_digit(10) else {
-            bail!("MCC must be three decimal digits");
-        };
-        plmn_digits[n] = digit as u8;
-    }
-    let offset = match mnc.len() {
-        2 => {
-            plmn_digits[3] = 0x0f;
-            4
-        }
-        3 => 3,
-        _ => bail!("MNC must be two or three digits"),
-    };
-    for (n, c) in mnc.chars().enumerate() {
-        let Some(digit) = c.to_digit(10) else {
-            bail!("MNC must be two or three digits")
-        };
-        plmn_digits[n + offset] = digit as u8;
-    }
-    for (n, digit) in plmn_digits.iter().enumerate() {
-        let index = n / 2;
-        plmn[index] = if (n % 2) == 0 {
-            *digit
-        } else {
-            plmn[index] | (digit << 4)
-        };
-    }
-    Ok(plmn)
-}
-
 // This code is currently unused but provides a convenient way to run multiple local GNB-CUs
 // without needing to specify IP addresses.  This is only useful if
 // both 5GC and GNB-DU are running in the same network namespace as the GNB-CUs
 // (and hence can contact localhost addresses).
 #[allow(dead_code)]
 async fn spawn_cp_on_random_local_addr(
-    args: &Args,
+    config: &Config,
     logger: Logger,
 ) -> Result<(ShutdownHandle, IpAddr)> {
-    let plmn = convert_mcc_mnc_to_plmn_array(&args.mcc, &args.mnc).unwrap();
-
     // If no local address is specified on the command line, we search for one, starting at 127.0.0.1.
     // For each address we will try to bind the SCTP ports.
     for attempt in 1..10 {
         let ip_addr = IpAddr::from(Ipv4Addr::new(127, 0, 0, attempt));
-        let cp_config = CpConfig {
-            ip_addr,
-            connection_style: ConnectionStyle::Coordinated(WorkerConnectionManagementConfig {
-                connection_api_bind_port: CONNECTION_API_PORT, // TODO - make configurable
-                connection_api_base_path: format!("http://{ip_addr}:{CONNECTION_API_PORT}"),
-                coordinator_base_path: format!("http://127.0.0.1:{COORDINATION_API_PORT}"),
-            }),
-            plmn,
-            ..CpConfig::default()
-        };
         match gnb_cu_cp::spawn(
             Uuid::new_v4(),
-            cp_config,
+            cp_config(config, ip_addr),
             MockUeStore::new(),
+            MockDuStore::new(),
+            MockCuUpStore::new(),
             logger.clone(),
         )
         .await
         {
             Ok(x) => return Ok((x, ip_addr)),
             Err(e) => {
-                if !args.local_ip.is_unspecified() {
+                if !config.local_ip.is_unspecified() {
                     return Err(e);
                 }
                 warn!(
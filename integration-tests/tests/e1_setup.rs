@@ -0,0 +1,107 @@
+mod test;
+use anyhow::Result;
+use e1ap::{Cause, CauseMisc, CauseRadioNetwork, CnSupport};
+use mocks::MockCuUp;
+pub use test::*;
+use xxap::{RanConfig, Snssai};
+
+// The PLMN of the CU-CP under test.
+fn plmn() -> [u8; 3] {
+    RanConfig::default().plmn
+}
+
+#[async_std::test]
+async fn e1_setup_rejected_for_epc_only_cu_up() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::AmfConnected)
+        .spawn()
+        .await?;
+    let mut cu_up = MockCuUp::new(&tc.logger).await;
+
+    let request = MockCuUp::build_e1_setup_request(2, CnSupport::CEpc, plmn(), &[]);
+    let cause = cu_up
+        .perform_rejected_e1_setup(&tc.worker_ip(0), request)
+        .await?;
+    assert!(matches!(cause, Cause::Misc(CauseMisc::Unspecified)));
+
+    cu_up.terminate().await;
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn e1_setup_rejected_for_plmn_mismatch() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::AmfConnected)
+        .spawn()
+        .await?;
+    let mut cu_up = MockCuUp::new(&tc.logger).await;
+
+    let other_plmn = [0, 1, 2];
+    assert_ne!(other_plmn, plmn());
+    let request = MockCuUp::build_e1_setup_request(2, CnSupport::C5gc, other_plmn, &[]);
+    let cause = cu_up
+        .perform_rejected_e1_setup(&tc.worker_ip(0), request)
+        .await?;
+    assert!(matches!(
+        cause,
+        Cause::RadioNetwork(CauseRadioNetwork::Unspecified)
+    ));
+
+    cu_up.terminate().await;
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn e1_setup_rejected_for_slice_mismatch() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::AmfConnected)
+        .spawn()
+        .await?;
+    let mut cu_up = MockCuUp::new(&tc.logger).await;
+
+    // The CU-CP does not support SST 2.
+    let request = MockCuUp::build_e1_setup_request(2, CnSupport::C5gc, plmn(), &[Snssai(2, None)]);
+    let cause = cu_up
+        .perform_rejected_e1_setup(&tc.worker_ip(0), request)
+        .await?;
+    assert!(matches!(
+        cause,
+        Cause::RadioNetwork(CauseRadioNetwork::ResourcesNotAvailableForTheSlice)
+    ));
+
+    cu_up.terminate().await;
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn unsuitable_cu_up_not_selected() -> Result<()> {
+    let mut tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+
+    // This CU-UP supports one of the CU-CP's slices, so is accepted, but not the slice of the AMF's sessions.
+    // Its ID differs from that of the real CU-UP, which it would otherwise replace.
+    let mut cu_up = MockCuUp::new(&tc.logger).await;
+    let request = MockCuUp::build_e1_setup_request(2, CnSupport::C5gc, plmn(), &[Snssai(1, None)]);
+    cu_up
+        .perform_e1_setup_with(&tc.worker_ip(0), request)
+        .await?;
+
+    // The session is set up on the real CU-UP, and the mock CU-UP hears nothing.
+    let ue = tc
+        .create_and_register_ue(1)
+        .await?
+        .establish_pdu_session(&mut tc)
+        .await?;
+    ue.uplink_data_packet(&tc).await?;
+    assert!(cu_up.receive_pdu().await.is_err());
+
+    ue.release_ue_context(&tc).await?;
+    cu_up.terminate().await;
+    tc.terminate().await;
+    Ok(())
+}
@@ -1,5 +1,9 @@
 mod test;
 use anyhow::Result;
+use f1ap::{CellBarred, CellsToBeActivatedList, CellsToBeBarredList, CellsToBeDeactivatedList};
+use futures_lite::future::zip;
+use management_api::models::{CellConfiguration, Sib2};
+use management_api::ConfigureCellResponse;
 pub use test::*;
 
 #[async_std::test]
@@ -14,3 +18,174 @@ async fn gnb_du_configuration_update() -> Result<()> {
     tc.terminate().await;
     Ok(())
 }
+
+#[async_std::test]
+async fn served_cell_lifecycle() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+    let ue = tc.create_and_register_ue(1).await?;
+
+    // A new cell is activated.
+    tc.du.add_served_cell(1).await?;
+
+    // Deleting the UE's cell causes the UE to be released.
+    tc.du.delete_served_cell(0).await?;
+    ue.cu_initiated_release(&tc).await?;
+
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn du_identified_by_tnla() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+    let du_2 = tc.start_other_du(456, 1).await?;
+
+    // The second DU adds a cell without saying which DU it is.
+    du_2.add_served_cell_without_gnb_du_id(2).await?;
+
+    // The cell is now the second DU's, so its configuration goes to the second DU.
+    let (response, update) = zip(
+        tc.configure_cell(CellConfiguration {
+            active: Some(false),
+            ..CellConfiguration::new("001".to_string(), "020".to_string(), 2)
+        }),
+        du_2.handle_cell_configuration_update(),
+    )
+    .await;
+    assert!(matches!(response?, ConfigureCellResponse::Success));
+    assert!(update?.cells_to_be_deactivated_list.is_some());
+
+    du_2.terminate().await;
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn f1_removal() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+    let du_2 = tc.start_other_du(456, 1).await?;
+    du_2.perform_f1_removal().await?;
+
+    // The second DU's cell is no longer served.
+    let response = tc
+        .configure_cell(CellConfiguration {
+            active: Some(false),
+            ..CellConfiguration::new("001".to_string(), "020".to_string(), 1)
+        })
+        .await?;
+    assert!(matches!(response, ConfigureCellResponse::Failure(_)));
+
+    du_2.terminate().await;
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn operator_configures_cell() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+
+    // The mock DU's cell 0 is in PLMN 001-020.
+    let cell_configuration = CellConfiguration::new("001".to_string(), "020".to_string(), 0);
+
+    // Deactivate and bar the cell.
+    let (response, update) = zip(
+        tc.configure_cell(CellConfiguration {
+            active: Some(false),
+            barred: Some(true),
+            ..cell_configuration.clone()
+        }),
+        tc.du.handle_cell_configuration_update(),
+    )
+    .await;
+    assert!(matches!(response?, ConfigureCellResponse::Success));
+    let update = update?;
+    assert!(update.cells_to_be_activated_list.is_none());
+    assert!(update.cells_to_be_deactivated_list.is_some());
+    let Some(CellsToBeBarredList(barred)) = update.cells_to_be_barred_list else {
+        panic!("Cell not barred")
+    };
+    assert!(matches!(barred.head.cell_barred, CellBarred::Barred));
+
+    // Activate it again with a new SIB2.
+    let (response, update) = zip(
+        tc.configure_cell(CellConfiguration {
+            active: Some(true),
+            sib2: Some(Sib2::new(4, 3, 2, -60, 5, 1)),
+            ..cell_configuration.clone()
+        }),
+        tc.du.handle_cell_configuration_update(),
+    )
+    .await;
+    assert!(matches!(response?, ConfigureCellResponse::Success));
+    let Some(CellsToBeActivatedList(activated)) = update?.cells_to_be_activated_list else {
+        panic!("Cell not activated")
+    };
+    let sib = activated
+        .head
+        .gnb_cu_system_information
+        .expect("No system information")
+        .sib_type_to_be_updated_list
+        .head;
+    assert_eq!(sib.sib_type, 2);
+    assert_eq!(sib.value_tag, 1);
+
+    // A bad SIB2 is rejected without troubling the DU.
+    let response = tc
+        .configure_cell(CellConfiguration {
+            sib2: Some(Sib2::new(7, 3, 2, -60, 5, 1)),
+            ..cell_configuration
+        })
+        .await?;
+    assert!(matches!(response, ConfigureCellResponse::Failure(_)));
+
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn operator_configures_cell_of_second_du() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+    let du_2 = tc.start_other_du(456, 1).await?;
+
+    // Each DU gets the update for its own cell.  If the update for the second DU's cell went to the first DU,
+    // the first DU would receive the wrong cell next.
+    for (du, nr_cell_identity) in [(&du_2, 1), (&tc.du, 0)] {
+        let (response, update) = zip(
+            tc.configure_cell(CellConfiguration {
+                active: Some(false),
+                ..CellConfiguration::new("001".to_string(), "020".to_string(), nr_cell_identity)
+            }),
+            du.handle_cell_configuration_update(),
+        )
+        .await;
+        assert!(matches!(response?, ConfigureCellResponse::Success));
+        let Some(CellsToBeDeactivatedList(deactivated)) = update?.cells_to_be_deactivated_list
+        else {
+            panic!("Cell not deactivated")
+        };
+        // The 36 bit cell identities are 0 and 1, so they differ only in their last bit.
+        assert_eq!(
+            deactivated.head.nr_cgi.nr_cell_identity.0[35],
+            nr_cell_identity == 1
+        );
+    }
+
+    du_2.terminate().await;
+    tc.terminate().await;
+    Ok(())
+}
@@ -2,6 +2,7 @@ mod test;
 use std::time::Duration;
 
 use anyhow::Result;
+use net::ChannelTransportProvider;
 pub use test::*;
 
 #[async_std::test]
@@ -49,3 +50,21 @@ async fn ue_can_register() -> Result<()> {
     tc.terminate().await;
     Ok(())
 }
+
+// The mocks, CU-CP and CU-UP talk over channels instead of SCTP, so this runs without kernel SCTP support.
+#[async_std::test]
+async fn ue_can_get_session_over_channel_transport() -> Result<()> {
+    let mut tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn_with_transport::<ChannelTransportProvider>()
+        .await?;
+    let ue = tc
+        .create_and_register_ue(1)
+        .await?
+        .establish_pdu_session(&mut tc)
+        .await?;
+    ue.uplink_data_packet(&tc).await?;
+    ue.downlink_data_packet(&tc).await?;
+    tc.terminate().await;
+    Ok(())
+}
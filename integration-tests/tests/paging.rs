@@ -0,0 +1,79 @@
+mod test;
+use anyhow::Result;
+use futures_lite::future::zip;
+use management_api::models::CellConfiguration;
+use management_api::ConfigureCellResponse;
+pub use test::*;
+
+#[async_std::test]
+async fn paging() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+
+    // The mock DU serves a single cell with TAC 1 in PLMN 0-1-2.
+    let five_g_tmsi = [1, 2, 3, 4];
+    tc.amf
+        .send_paging(five_g_tmsi, [0, 1, 2], [0, 0, 1])
+        .await?;
+    tc.du.receive_paging(five_g_tmsi).await?;
+
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn paging_reaches_each_du() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+
+    // The second DU's cell is in the same tracking area.  Each DU checks that the paging it receives is for
+    // its own cell.
+    let du_2 = tc.start_other_du(456, 1).await?;
+    let five_g_tmsi = [1, 2, 3, 4];
+    tc.amf
+        .send_paging(five_g_tmsi, [0, 1, 2], [0, 0, 1])
+        .await?;
+    tc.du.receive_paging(five_g_tmsi).await?;
+    du_2.receive_paging(five_g_tmsi).await?;
+
+    du_2.terminate().await;
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn barred_cell_not_paged() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+
+    // The operator bars the second DU's cell, which is in the same tracking area as the first DU's.
+    let du_2 = tc.start_other_du(456, 1).await?;
+    let (response, update) = zip(
+        tc.configure_cell(CellConfiguration {
+            barred: Some(true),
+            ..CellConfiguration::new("001".to_string(), "020".to_string(), 1)
+        }),
+        du_2.handle_cell_configuration_update(),
+    )
+    .await;
+    assert!(matches!(response?, ConfigureCellResponse::Success));
+    assert!(update?.cells_to_be_barred_list.is_some());
+
+    // Only the first DU is paged.
+    let five_g_tmsi = [1, 2, 3, 4];
+    tc.amf
+        .send_paging(five_g_tmsi, [0, 1, 2], [0, 0, 1])
+        .await?;
+    tc.du.receive_paging(five_g_tmsi).await?;
+    assert!(du_2.receive_pdu().await.is_err());
+
+    du_2.terminate().await;
+    tc.terminate().await;
+    Ok(())
+}
@@ -22,3 +22,50 @@ async fn successful_pdu_session_setup_and_release() -> Result<()> {
     tc.terminate().await;
     Ok(())
 }
+
+#[async_std::test]
+async fn pdu_session_setup_during_initial_context_setup() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+    let ue = tc
+        .new_ue(1)
+        .await?
+        .initial_access(&tc)
+        .await?
+        .register_with_pdu_session(&tc)
+        .await?;
+
+    ue.uplink_data_packet(&tc).await?;
+    ue.downlink_data_packet(&tc).await?;
+
+    let _ue = ue.release_pdu_session(&tc).await?;
+
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn pdu_session_modify_adds_qos_flow() -> Result<()> {
+    let mut tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+    let mut ue = tc
+        .create_and_register_ue(1)
+        .await?
+        .establish_pdu_session(&mut tc)
+        .await?;
+
+    ue.add_qos_flow(&tc, 2).await?;
+
+    // The original QoS flow still carries data.
+    ue.uplink_data_packet(&tc).await?;
+    ue.downlink_data_packet(&tc).await?;
+
+    let _ue = ue.release_pdu_session(&tc).await?;
+
+    tc.terminate().await;
+    Ok(())
+}
@@ -0,0 +1,116 @@
+mod test;
+use anyhow::Result;
+pub use test::*;
+
+#[async_std::test]
+async fn ue_suspend_and_resume() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+    let ue = tc
+        .create_and_register_ue(1)
+        .await?
+        .become_inactive(&tc)
+        .await?
+        .resume(&tc)
+        .await?;
+
+    // Check that the UE can still be reached from the AMF.
+    let nas = vec![1, 2, 3];
+    tc.amf
+        .send_downlink_nas_transport(&ue.amf_ue_context, nas.clone())
+        .await?;
+    assert_eq!(tc.du.receive_nas(&ue.du_ue_context).await?, nas);
+
+    ue.release_ue_context(&tc).await?;
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn ue_resumes_with_nas() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+    let ue = tc
+        .create_and_register_ue(1)
+        .await?
+        .become_inactive(&tc)
+        .await?
+        .resume_with_nas(&tc, vec![4, 5, 6])
+        .await?;
+
+    ue.release_ue_context(&tc).await?;
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn ue_resumes_on_different_worker() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .worker_count(2)
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+
+    // UE 1 registers on worker 0.
+    let mut ue = tc
+        .create_and_register_ue(1)
+        .await?
+        .become_inactive(&tc)
+        .await?;
+
+    // It then resumes on worker 1, which has to get its state from the shared datastore.
+    tc.use_worker_for_ue(1, &mut ue).await?;
+    let ue = ue.resume(&tc).await?;
+
+    ue.release_ue_context(&tc).await?;
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn ue_resumes_with_short_i_rnti() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+    let ue = tc
+        .create_and_register_ue(1)
+        .await?
+        .become_inactive(&tc)
+        .await?
+        .resume_with_short_i_rnti(&tc)
+        .await?;
+
+    ue.release_ue_context(&tc).await?;
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn ue_with_session_resumes() -> Result<()> {
+    let mut tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+    let ue = tc
+        .create_and_register_ue(1)
+        .await?
+        .establish_pdu_session(&mut tc)
+        .await?
+        .become_inactive(&tc)
+        .await?
+        .resume(&tc)
+        .await?;
+
+    // The DU has set up the session's DRB afresh, and the CU-UP has been given its new downlink tunnel.
+    ue.uplink_data_packet(&tc).await?;
+    ue.downlink_data_packet(&tc).await?;
+
+    ue.release_ue_context(&tc).await?;
+    tc.terminate().await;
+    Ok(())
+}
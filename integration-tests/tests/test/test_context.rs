@@ -7,19 +7,34 @@ use coordinator::Config as CoordinatorConfig;
 use gnb_cu_cp::{
     Config, ConnectionControlConfig, ConnectionStyle, WorkerConnectionManagementConfig,
 };
-use gnb_cu_cp::{MockUeStore, RedisUeStore};
+use gnb_cu_cp::{
+    MockCuUpStore, MockDuStore, MockUeStore, RedisCuUpStore, RedisDuStore, RedisUeStore,
+};
+use management_api::{models::CellConfiguration, Api, ConfigureCellResponse};
 use mocks::{Mock5gc, MockDu}; // MockCuUp
+use net::{SctpTransportProvider, TransportProvider};
 use rand::Rng;
 use slog::{debug, info, o, warn, Logger};
 use std::time::Duration;
+use swagger::{AuthData, ContextBuilder, EmptyContext, Push, XSpanIdString};
 use uuid::Uuid;
+use xxap::RanConfig;
 
 const IP_OR_PORT_RETRIES: usize = 10;
 const CONNECTION_API_PORT: u16 = 50312;
-
-pub struct TestContext {
-    pub amf: Mock5gc,
-    pub du: MockDu,
+const MANAGEMENT_API_PORT: u16 = 50313;
+
+type ClientContext = swagger::make_context_ty!(
+    ContextBuilder,
+    EmptyContext,
+    Option<AuthData>,
+    XSpanIdString
+);
+
+// The mocks and the gNB-CU talk over SCTP, unless the test picks another transport.
+pub struct TestContext<T: TransportProvider = SctpTransportProvider> {
+    pub amf: Mock5gc<T>,
+    pub du: MockDu<T>,
     //pub cu_up: MockCuUp,
     pub logger: Logger,
     workers: Vec<InternalWorkerInfo>,
@@ -84,6 +99,11 @@ impl TestContextBuilder {
     }
 
     pub async fn spawn(&self) -> Result<TestContext> {
+        self.spawn_with_transport().await
+    }
+
+    // For example, ChannelTransportProvider runs the test without kernel SCTP support.
+    pub async fn spawn_with_transport<T: TransportProvider>(&self) -> Result<TestContext<T>> {
         common::panic::exit_on_panic();
         let logger = common::logging::test_init();
 
@@ -113,10 +133,10 @@ impl Default for TestContextBuilder {
 
 pub enum WorkerDatastoreSetup {
     RedisPort(u16),
-    MockUeStore(MockUeStore),
+    MockStores(MockUeStore, MockDuStore, MockCuUpStore),
 }
 
-impl TestContext {
+impl<T: TransportProvider> TestContext<T> {
     async fn start_cu(&mut self, builder: &TestContextBuilder) -> Result<()> {
         // Start CU-CP coordinator if there will be multiple CU-CP workers.
         if builder.worker_count > 1 {
@@ -127,7 +147,11 @@ impl TestContext {
         let datastore = if let Some(port) = builder.redis_port {
             WorkerDatastoreSetup::RedisPort(port)
         } else {
-            WorkerDatastoreSetup::MockUeStore(MockUeStore::new())
+            WorkerDatastoreSetup::MockStores(
+                MockUeStore::new(),
+                MockDuStore::new(),
+                MockCuUpStore::new(),
+            )
         };
 
         // Start CU-CP workers
@@ -140,8 +164,10 @@ impl TestContext {
 
         // Start a CU-UP pointing at the first worker.
         let first_worker_ip = self.workers[0].config.ip_addr;
-        self.cu_ups
-            .push(start_cu_up_on_random_ip(first_worker_ip, &self.logger).await?);
+        self.cu_ups.push(
+            start_cu_up_on_random_ip::<T>(first_worker_ip, ran_config(&self.amf), &self.logger)
+                .await?,
+        );
 
         Ok(())
     }
@@ -186,6 +212,10 @@ impl TestContext {
                         "http://127.0.0.1:{}",
                         coordinator.config.bind_port
                     ),
+                    refresh_interval_secs: coordinator
+                        .config
+                        .connection_control_config
+                        .worker_refresh_interval_secs,
                 })
             } else {
                 ConnectionStyle::Autonomous(ConnectionControlConfig {
@@ -198,6 +228,8 @@ impl TestContext {
             let config = Config {
                 ip_addr: worker_ip.parse().unwrap(),
                 connection_style: connection_style.clone(),
+                management_api_bind_port: Some(MANAGEMENT_API_PORT),
+                ran: ran_config(&self.amf),
                 ..Config::default()
             };
 
@@ -209,17 +241,26 @@ impl TestContext {
             let worker_logger = self.logger.new(o!("cu-cp-w"=> worker_id.to_string()));
             match match datastore {
                 WorkerDatastoreSetup::RedisPort(port) => {
-                    gnb_cu_cp::spawn(
+                    gnb_cu_cp::spawn_with_transport::<T, _, _, _>(
                         worker_id,
                         config.clone(),
                         RedisUeStore::new(*port).unwrap(),
+                        RedisDuStore::new(*port).unwrap(),
+                        RedisCuUpStore::new(*port).unwrap(),
                         worker_logger,
                     )
                     .await
                 }
-                WorkerDatastoreSetup::MockUeStore(ue_store) => {
-                    gnb_cu_cp::spawn(worker_id, config.clone(), ue_store.clone(), worker_logger)
-                        .await
+                WorkerDatastoreSetup::MockStores(ue_store, du_store, cu_up_store) => {
+                    gnb_cu_cp::spawn_with_transport::<T, _, _, _>(
+                        worker_id,
+                        config.clone(),
+                        ue_store.clone(),
+                        du_store.clone(),
+                        cu_up_store.clone(),
+                        worker_logger,
+                    )
+                    .await
                 }
             } {
                 Ok(shutdown_handle) => {
@@ -240,6 +281,22 @@ impl TestContext {
         self.workers[worker_index].config.ip_addr.to_string()
     }
 
+    // Configure a cell through the management API of the first worker.
+    pub async fn configure_cell(
+        &self,
+        cell_configuration: CellConfiguration,
+    ) -> Result<ConfigureCellResponse> {
+        let base_path = format!("http://{}:{}", self.worker_ip(0), MANAGEMENT_API_PORT);
+        let client = management_api::Client::try_new_http(&base_path)?;
+        let context: ClientContext = swagger::make_context!(
+            ContextBuilder,
+            EmptyContext,
+            None as Option<AuthData>,
+            XSpanIdString::default()
+        );
+        Ok(client.configure_cell(cell_configuration, &context).await?)
+    }
+
     pub async fn interface_setup_stage<'a>(
         &'a mut self,
         worker_index: usize,
@@ -311,6 +368,14 @@ impl TestContext {
         Ok(self)
     }
 
+    // Start another DU, with its own gNB-DU ID and cell, and set it up with the first worker.  The test
+    // terminates it.
+    pub async fn start_other_du(&self, gnb_du_id: u64, nr_cell_identity: u64) -> Result<MockDu<T>> {
+        let mut du = start_other_du_on_random_ip(gnb_du_id, nr_cell_identity, &self.logger).await;
+        du.perform_f1_setup(&self.worker_ip(0)).await?;
+        Ok(du)
+    }
+
     pub async fn new_ue(&self, ue_id: u32) -> Result<DetachedUe> {
         assert!(ue_id > 0);
         let worker_ip = self.worker_ip((ue_id - 1) as usize);
@@ -318,12 +383,12 @@ impl TestContext {
         Ok(DetachedUe::new(ue_id, du_ue_context))
     }
 
-    pub async fn use_worker_for_ue<T: RebindUe>(
+    pub async fn use_worker_for_ue<R: RebindUe>(
         &self,
         worker_index: usize,
-        ue: &mut T,
+        ue: &mut R,
     ) -> Result<()> {
-        ue.rebind(&self, &self.worker_ip(worker_index)).await
+        ue.rebind(self, &self.worker_ip(worker_index)).await
     }
 
     pub async fn create_and_register_ue(&self, ue_id: u32) -> Result<RegisteredUe> {
@@ -362,7 +427,10 @@ impl TestContext {
     }
 }
 
-async fn start_amf_with_random_ips(logger: &Logger, num_endpoints: usize) -> Mock5gc {
+async fn start_amf_with_random_ips<T: TransportProvider>(
+    logger: &Logger,
+    num_endpoints: usize,
+) -> Mock5gc<T> {
     assert!(num_endpoints > 0);
     let mut maybe_amf = None;
     for _ in 0..IP_OR_PORT_RETRIES {
@@ -384,7 +452,7 @@ async fn start_amf_with_random_ips(logger: &Logger, num_endpoints: usize) -> Moc
     panic!("Failed to bind to {} random IPs", num_endpoints)
 }
 
-async fn start_du_on_random_ip(logger: &Logger) -> MockDu {
+async fn start_du_on_random_ip<T: TransportProvider>(logger: &Logger) -> MockDu<T> {
     for _ in 0..IP_OR_PORT_RETRIES {
         if let Ok(du) = MockDu::new(&random_local_ip(), logger).await {
             return du;
@@ -393,8 +461,36 @@ async fn start_du_on_random_ip(logger: &Logger) -> MockDu {
     panic!("Failed to find IP for DU")
 }
 
-async fn start_cu_up_on_random_ip(
+async fn start_other_du_on_random_ip<T: TransportProvider>(
+    gnb_du_id: u64,
+    nr_cell_identity: u64,
+    logger: &Logger,
+) -> MockDu<T> {
+    for _ in 0..IP_OR_PORT_RETRIES {
+        if let Ok(du) =
+            MockDu::new_with_id(&random_local_ip(), gnb_du_id, nr_cell_identity, logger).await
+        {
+            return du;
+        }
+    }
+    panic!("Failed to find IP for DU")
+}
+
+// The CU-CP and CU-UP also serve the slice that the mock AMF sets up PDU sessions in.  Otherwise, no CU-UP
+// would be selected for them.
+fn ran_config<T: TransportProvider>(amf: &Mock5gc<T>) -> RanConfig {
+    let mut ran = RanConfig::default();
+    for ta in ran.supported_tas.iter_mut() {
+        for broadcast_plmn in ta.broadcast_plmns.iter_mut() {
+            broadcast_plmn.slices.push(amf.snssai());
+        }
+    }
+    ran
+}
+
+async fn start_cu_up_on_random_ip<T: TransportProvider>(
     cp_ip_address: IpAddr,
+    ran: RanConfig,
     logger: &Logger,
 ) -> Result<ShutdownHandle> {
     debug!(logger, "Spawn CU-UP");
@@ -406,10 +502,12 @@ async fn start_cu_up_on_random_ip(
             userplane_ip_address: ip_address,
             cp_ip_address,
             name: None,
+            ran: ran.clone(),
+            ..gnb_cu_up::Config::default()
         };
         let logger = logger.new(o!("cu-up"=> ip_address.to_string()));
 
-        if let Ok(cu_up) = gnb_cu_up::spawn(config, logger).await {
+        if let Ok(cu_up) = gnb_cu_up::spawn_with_transport::<T>(config, logger).await {
             return Ok(cu_up);
         }
     }
@@ -2,6 +2,7 @@ use crate::TestContext;
 use anyhow::Result;
 use async_trait::async_trait;
 use mocks::{AmfUeContext, DuUeContext, NgcSession, SecurityModeCommand};
+use net::TransportProvider;
 use slog::info;
 
 // This module has a succession of struct representing the UE's progress towards registered.
@@ -14,7 +15,11 @@ pub struct DetachedUe {
 
 #[async_trait]
 pub trait RebindUe {
-    async fn rebind(&mut self, tc: &TestContext, ip_addr: &str) -> Result<()>;
+    async fn rebind<T: TransportProvider>(
+        &mut self,
+        tc: &TestContext<T>,
+        ip_addr: &str,
+    ) -> Result<()>;
 }
 
 impl DetachedUe {
@@ -25,7 +30,10 @@ impl DetachedUe {
         }
     }
 
-    pub async fn initial_access(mut self, tc: &TestContext) -> Result<SetupUe> {
+    pub async fn initial_access<T: TransportProvider>(
+        mut self,
+        tc: &TestContext<T>,
+    ) -> Result<SetupUe> {
         tc.du
             .perform_rrc_setup(&mut self.du_ue_context, Vec::new())
             .await?;
@@ -41,7 +49,10 @@ impl DetachedUe {
 /// SetupUe - UE that has underone RRC setup.  Call initiate_registration() to get a HalfRegisteredUe.
 pub struct SetupUe(WithAmfContext);
 impl SetupUe {
-    pub async fn initiate_registration(self, tc: &TestContext) -> Result<HalfRegisteredUe> {
+    pub async fn initiate_registration<T: TransportProvider>(
+        self,
+        tc: &TestContext<T>,
+    ) -> Result<HalfRegisteredUe> {
         tc.amf
             .send_initial_context_setup_request(&self.0.amf_ue_context, vec![])
             .await?;
@@ -51,11 +62,51 @@ impl SetupUe {
             .await?;
         Ok(HalfRegisteredUe(self.0, security_mode_command))
     }
+
+    /// Registration in which the AMF asks for a PDU session in the Initial Context Setup, as
+    /// happens on a service request.
+    pub async fn register_with_pdu_session<T: TransportProvider>(
+        mut self,
+        tc: &TestContext<T>,
+    ) -> Result<UeWithSession> {
+        let gtp_teid = tc
+            .amf
+            .send_initial_context_setup_request_with_session(&self.0.amf_ue_context, vec![])
+            .await?;
+        let security_mode_command = tc
+            .du
+            .handle_ue_context_setup_with_security_mode_command(&mut self.0.du_ue_context)
+            .await?;
+        tc.du
+            .send_security_mode_complete(&self.0.du_ue_context, &security_mode_command)
+            .await?;
+        let _nas = tc
+            .du
+            .receive_rrc_reconfiguration(&self.0.du_ue_context)
+            .await?;
+        tc.du
+            .send_rrc_reconfiguration_complete(&self.0.du_ue_context)
+            .await?;
+        let ngc_session = tc
+            .amf
+            .receive_initial_context_setup_response_with_session(&self.0.amf_ue_context, gtp_teid)
+            .await?;
+        Ok(UeWithSession {
+            ue_id: self.0.ue_id,
+            amf_ue_context: self.0.amf_ue_context,
+            du_ue_context: self.0.du_ue_context,
+            ngc_session,
+        })
+    }
 }
 
 #[async_trait]
 impl RebindUe for SetupUe {
-    async fn rebind(&mut self, tc: &TestContext, ip_addr: &str) -> Result<()> {
+    async fn rebind<T: TransportProvider>(
+        &mut self,
+        tc: &TestContext<T>,
+        ip_addr: &str,
+    ) -> Result<()> {
         tc.amf
             .rebind(&mut self.0.amf_ue_context.binding, ip_addr)
             .await?;
@@ -69,7 +120,10 @@ impl RebindUe for SetupUe {
 /// Call complete_registration() to get a RegisteredUe.
 pub struct HalfRegisteredUe(WithAmfContext, SecurityModeCommand);
 impl HalfRegisteredUe {
-    pub async fn complete_registration(self, tc: &TestContext) -> Result<RegisteredUe> {
+    pub async fn complete_registration<T: TransportProvider>(
+        self,
+        tc: &TestContext<T>,
+    ) -> Result<RegisteredUe> {
         tc.du
             .send_security_mode_complete(&self.0.du_ue_context, &self.1)
             .await?;
@@ -87,7 +141,10 @@ pub struct RegisteredUe {
     pub amf_ue_context: AmfUeContext,
 }
 impl RegisteredUe {
-    pub async fn establish_pdu_session(mut self, tc: &mut TestContext) -> Result<UeWithSession> {
+    pub async fn establish_pdu_session<T: TransportProvider>(
+        mut self,
+        tc: &mut TestContext<T>,
+    ) -> Result<UeWithSession> {
         let logger = &tc.logger;
         info!(logger, "Establish PDU session for UE {}", self.ue_id);
         let gtp_teid = tc
@@ -125,6 +182,98 @@ impl RegisteredUe {
     }
 }
 
+impl RegisteredUe {
+    pub async fn release_ue_context<T: TransportProvider>(self, tc: &TestContext<T>) -> Result<()> {
+        release_ue_context(tc, &self.du_ue_context, &self.amf_ue_context, &[]).await
+    }
+
+    pub async fn du_initiated_release<T: TransportProvider>(
+        self,
+        tc: &TestContext<T>,
+    ) -> Result<()> {
+        du_initiated_release(tc, &self.du_ue_context, &self.amf_ue_context).await
+    }
+
+    // The gNB-CU asks the AMF to release the UE, for example because the UE's cell has been deleted.
+    pub async fn cu_initiated_release<T: TransportProvider>(
+        self,
+        tc: &TestContext<T>,
+    ) -> Result<()> {
+        tc.amf
+            .receive_ue_context_release_request(&self.amf_ue_context)
+            .await?;
+        release_ue_context(tc, &self.du_ue_context, &self.amf_ue_context, &[]).await
+    }
+
+    pub async fn become_inactive<T: TransportProvider>(
+        mut self,
+        tc: &TestContext<T>,
+    ) -> Result<InactiveUe> {
+        tc.du
+            .send_ue_inactivity_notification(&self.du_ue_context)
+            .await?;
+        tc.du
+            .handle_ue_context_release_with_suspend(&mut self.du_ue_context)
+            .await?;
+        Ok(InactiveUe(self))
+    }
+}
+
+/// InactiveUe - UE that has been suspended into RRC_INACTIVE.  Call resume() to get back a RegisteredUe.
+pub struct InactiveUe(WithAmfContext);
+impl InactiveUe {
+    pub async fn resume<T: TransportProvider>(
+        mut self,
+        tc: &TestContext<T>,
+    ) -> Result<RegisteredUe> {
+        tc.du.perform_rrc_resume(&mut self.0.du_ue_context).await?;
+        Ok(self.0)
+    }
+
+    // The UE resumes to send a Nas message, which the gNB-CU passes on to the AMF.
+    pub async fn resume_with_nas<T: TransportProvider>(
+        mut self,
+        tc: &TestContext<T>,
+        nas: Vec<u8>,
+    ) -> Result<RegisteredUe> {
+        tc.du
+            .perform_rrc_resume_with_nas(&mut self.0.du_ue_context, nas.clone())
+            .await?;
+        let received = tc
+            .amf
+            .receive_uplink_nas_transport(&self.0.amf_ue_context)
+            .await?;
+        assert_eq!(received, nas);
+        Ok(self.0)
+    }
+
+    pub async fn resume_with_short_i_rnti<T: TransportProvider>(
+        mut self,
+        tc: &TestContext<T>,
+    ) -> Result<RegisteredUe> {
+        tc.du
+            .perform_rrc_resume_with_short_i_rnti(&mut self.0.du_ue_context)
+            .await?;
+        Ok(self.0)
+    }
+}
+
+#[async_trait]
+impl RebindUe for InactiveUe {
+    async fn rebind<T: TransportProvider>(
+        &mut self,
+        tc: &TestContext<T>,
+        ip_addr: &str,
+    ) -> Result<()> {
+        tc.amf
+            .rebind(&mut self.0.amf_ue_context.binding, ip_addr)
+            .await?;
+        tc.du
+            .rebind(&mut self.0.du_ue_context.binding, ip_addr)
+            .await
+    }
+}
+
 // The RegisteredUe fields get reused in some of the other structs too.  Supply a more generic name for that case.
 type WithAmfContext = RegisteredUe;
 
@@ -137,15 +286,63 @@ pub struct UeWithSession {
 }
 
 impl UeWithSession {
-    pub async fn uplink_data_packet(&self, tc: &TestContext) -> Result<()> {
+    pub async fn uplink_data_packet<T: TransportProvider>(
+        &self,
+        tc: &TestContext<T>,
+    ) -> Result<()> {
         tc.du.send_data_packet(&self.du_ue_context).await?;
         tc.amf.recv_data_packet(&self.ngc_session).await
     }
-    pub async fn downlink_data_packet(&self, tc: &TestContext) -> Result<()> {
+    pub async fn downlink_data_packet<T: TransportProvider>(
+        &self,
+        tc: &TestContext<T>,
+    ) -> Result<()> {
         tc.amf.send_data_packet(&self.ngc_session).await?;
         tc.du.recv_data_packet(&self.du_ue_context).await
     }
-    pub async fn release_pdu_session(self, tc: &TestContext) -> Result<RegisteredUe> {
+    pub async fn become_inactive<T: TransportProvider>(
+        mut self,
+        tc: &TestContext<T>,
+    ) -> Result<InactiveUeWithSession> {
+        tc.du
+            .send_ue_inactivity_notification(&self.du_ue_context)
+            .await?;
+        tc.du
+            .handle_ue_context_release_with_suspend(&mut self.du_ue_context)
+            .await?;
+        Ok(InactiveUeWithSession(self))
+    }
+    pub async fn add_qos_flow<T: TransportProvider>(
+        &mut self,
+        tc: &TestContext<T>,
+        qfi: u8,
+    ) -> Result<()> {
+        info!(&tc.logger, "Add QoS flow {} for UE {}", qfi, self.ue_id);
+        tc.amf
+            .send_pdu_session_resource_modify(&self.amf_ue_context, &self.ngc_session, qfi)
+            .await?;
+        tc.du
+            .handle_ue_context_modification(&mut self.du_ue_context)
+            .await?;
+        let _nas = tc
+            .du
+            .receive_rrc_reconfiguration(&self.du_ue_context)
+            .await?;
+        tc.du
+            .send_rrc_reconfiguration_complete(&self.du_ue_context)
+            .await?;
+        tc.amf
+            .receive_pdu_session_resource_modify_response(
+                &self.amf_ue_context,
+                &self.ngc_session,
+                qfi,
+            )
+            .await
+    }
+    pub async fn release_pdu_session<T: TransportProvider>(
+        self,
+        tc: &TestContext<T>,
+    ) -> Result<RegisteredUe> {
         let UeWithSession {
             ue_id,
             du_ue_context,
@@ -170,4 +367,64 @@ impl UeWithSession {
             amf_ue_context,
         })
     }
+    pub async fn release_ue_context<T: TransportProvider>(self, tc: &TestContext<T>) -> Result<()> {
+        release_ue_context(
+            tc,
+            &self.du_ue_context,
+            &self.amf_ue_context,
+            &[&self.ngc_session],
+        )
+        .await
+    }
+    pub async fn du_initiated_release<T: TransportProvider>(
+        self,
+        tc: &TestContext<T>,
+    ) -> Result<()> {
+        du_initiated_release(tc, &self.du_ue_context, &self.amf_ue_context).await
+    }
+}
+
+/// InactiveUeWithSession - UE with a session that has been suspended into RRC_INACTIVE.  Call resume() to get
+/// back a UeWithSession.
+pub struct InactiveUeWithSession(UeWithSession);
+impl InactiveUeWithSession {
+    pub async fn resume<T: TransportProvider>(
+        mut self,
+        tc: &TestContext<T>,
+    ) -> Result<UeWithSession> {
+        tc.du.perform_rrc_resume(&mut self.0.du_ue_context).await?;
+        Ok(self.0)
+    }
+}
+
+// AMF initiated UE context release, of a UE with the given sessions.  The UE is gone after this, so it is consumed
+// by the callers above.
+async fn release_ue_context<T: TransportProvider>(
+    tc: &TestContext<T>,
+    du_ue_context: &DuUeContext,
+    amf_ue_context: &AmfUeContext,
+    sessions: &[&NgcSession],
+) -> Result<()> {
+    tc.amf
+        .send_ue_context_release_command(amf_ue_context)
+        .await?;
+    tc.du
+        .handle_ue_context_release_with_rrc_release(du_ue_context)
+        .await?;
+    tc.amf
+        .receive_ue_context_release_complete(amf_ue_context, sessions)
+        .await
+}
+
+// DU initiated UE context release, which the gNB-CU passes on to the AMF, which then releases the UE.
+async fn du_initiated_release<T: TransportProvider>(
+    tc: &TestContext<T>,
+    du_ue_context: &DuUeContext,
+    amf_ue_context: &AmfUeContext,
+) -> Result<()> {
+    tc.du.send_ue_context_release_request(du_ue_context).await?;
+    tc.amf
+        .receive_ue_context_release_request(amf_ue_context)
+        .await?;
+    release_ue_context(tc, du_ue_context, amf_ue_context, &[]).await
 }
@@ -0,0 +1,47 @@
+mod test;
+use anyhow::Result;
+pub use test::*;
+
+#[async_std::test]
+async fn amf_initiated_ue_context_release() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+    let ue = tc.create_and_register_ue(1).await?;
+    ue.release_ue_context(&tc).await?;
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn amf_initiated_ue_context_release_with_session() -> Result<()> {
+    let mut tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+    let ue = tc
+        .create_and_register_ue(1)
+        .await?
+        .establish_pdu_session(&mut tc)
+        .await?;
+    ue.release_ue_context(&tc).await?;
+    tc.terminate().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn du_initiated_ue_context_release() -> Result<()> {
+    let tc = TestContextBuilder::new()
+        .stage(Stage::DuConnected)
+        .spawn()
+        .await?;
+    let ue = tc.create_and_register_ue(1).await?;
+    ue.du_initiated_release(&tc).await?;
+
+    // Check that nothing was left over on the interfaces and another UE can register.
+    let _ue = tc.create_and_register_ue(2).await?;
+
+    tc.terminate().await;
+    Ok(())
+}
@@ -0,0 +1,423 @@
+#![allow(unused_qualifications)]
+
+use crate::models;
+#[cfg(any(feature = "client", feature = "server"))]
+use crate::header;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "conversion", derive(frunk::LabelledGeneric))]
+pub struct CellConfiguration {
+    #[serde(rename = "mcc")]
+    pub mcc: String,
+
+    #[serde(rename = "mnc")]
+    pub mnc: String,
+
+    /// The 36 bit NR Cell Identity.
+    #[serde(rename = "nrCellIdentity")]
+    pub nr_cell_identity: i64,
+
+    /// Activate the cell if true, or deactivate it if false.  Left as it is if absent.
+    #[serde(rename = "active")]
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub active: Option<bool>,
+
+    /// Bar the cell if true, or unbar it if false.  Left as it is if absent.
+    #[serde(rename = "barred")]
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub barred: Option<bool>,
+
+    #[serde(rename = "sib2")]
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub sib2: Option<models::Sib2>,
+
+}
+
+impl CellConfiguration {
+    #[allow(clippy::new_without_default)]
+    pub fn new(mcc: String, mnc: String, nr_cell_identity: i64, ) -> CellConfiguration {
+        CellConfiguration {
+            mcc,
+            mnc,
+            nr_cell_identity,
+            active: None,
+            barred: None,
+            sib2: None,
+        }
+    }
+}
+
+/// Converts the CellConfiguration value to the Query Parameters representation (style=form, explode=false)
+/// specified in https://swagger.io/docs/specification/serialization/
+/// Should be implemented in a serde serializer
+impl std::string::ToString for CellConfiguration {
+    fn to_string(&self) -> String {
+        let params: Vec<Option<String>> = vec![
+
+            Some("mcc".to_string()),
+            Some(self.mcc.to_string()),
+
+
+            Some("mnc".to_string()),
+            Some(self.mnc.to_string()),
+
+
+            Some("nrCellIdentity".to_string()),
+            Some(self.nr_cell_identity.to_string()),
+
+
+            self.active.as_ref().map(|active| {
+                vec![
+                    "active".to_string(),
+                    active.to_string(),
+                ].join(",")
+            }),
+
+
+            self.barred.as_ref().map(|barred| {
+                vec![
+                    "barred".to_string(),
+                    barred.to_string(),
+                ].join(",")
+            }),
+
+            // Skipping sib2 in query parameter serialization
+
+        ];
+
+        params.into_iter().flatten().collect::<Vec<_>>().join(",")
+    }
+}
+
+/// Converts Query Parameters representation (style=form, explode=false) to a CellConfiguration value
+/// as specified in https://swagger.io/docs/specification/serialization/
+/// Should be implemented in a serde deserializer
+impl std::str::FromStr for CellConfiguration {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        /// An intermediate representation of the struct to use for parsing.
+        #[derive(Default)]
+        #[allow(dead_code)]
+        struct IntermediateRep {
+            pub mcc: Vec<String>,
+            pub mnc: Vec<String>,
+            pub nr_cell_identity: Vec<i64>,
+            pub active: Vec<bool>,
+            pub barred: Vec<bool>,
+            pub sib2: Vec<models::Sib2>,
+        }
+
+        let mut intermediate_rep = IntermediateRep::default();
+
+        // Parse into intermediate representation
+        let mut string_iter = s.split(',');
+        let mut key_result = string_iter.next();
+
+        while key_result.is_some() {
+            let val = match string_iter.next() {
+                Some(x) => x,
+                None => return std::result::Result::Err("Missing value while parsing CellConfiguration".to_string())
+            };
+
+            if let Some(key) = key_result {
+                #[allow(clippy::match_single_binding)]
+                match key {
+                    #[allow(clippy::redundant_clone)]
+                    "mcc" => intermediate_rep.mcc.push(<String as std::str::FromStr>::from_str(val).map_err(|x| x.to_string())?),
+                    #[allow(clippy::redundant_clone)]
+                    "mnc" => intermediate_rep.mnc.push(<String as std::str::FromStr>::from_str(val).map_err(|x| x.to_string())?),
+                    #[allow(clippy::redundant_clone)]
+                    "nrCellIdentity" => intermediate_rep.nr_cell_identity.push(<i64 as std::str::FromStr>::from_str(val).map_err(|x| x.to_string())?),
+                    #[allow(clippy::redundant_clone)]
+                    "active" => intermediate_rep.active.push(<bool as std::str::FromStr>::from_str(val).map_err(|x| x.to_string())?),
+                    #[allow(clippy::redundant_clone)]
+                    "barred" => intermediate_rep.barred.push(<bool as std::str::FromStr>::from_str(val).map_err(|x| x.to_string())?),
+                    #[allow(clippy::redundant_clone)]
+                    "sib2" => intermediate_rep.sib2.push(<models::Sib2 as std::str::FromStr>::from_str(val).map_err(|x| x.to_string())?),
+                    _ => return std::result::Result::Err("Unexpected key while parsing CellConfiguration".to_string())
+                }
+            }
+
+            // Get the next key
+            key_result = string_iter.next();
+        }
+
+        // Use the intermediate representation to return the struct
+        std::result::Result::Ok(CellConfiguration {
+            mcc: intermediate_rep.mcc.into_iter().next().ok_or_else(|| "mcc missing in CellConfiguration".to_string())?,
+            mnc: intermediate_rep.mnc.into_iter().next().ok_or_else(|| "mnc missing in CellConfiguration".to_string())?,
+            nr_cell_identity: intermediate_rep.nr_cell_identity.into_iter().next().ok_or_else(|| "nrCellIdentity missing in CellConfiguration".to_string())?,
+            active: intermediate_rep.active.into_iter().next(),
+            barred: intermediate_rep.barred.into_iter().next(),
+            sib2: intermediate_rep.sib2.into_iter().next(),
+        })
+    }
+}
+
+// Methods for converting between header::IntoHeaderValue<CellConfiguration> and hyper::header::HeaderValue
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl std::convert::TryFrom<header::IntoHeaderValue<CellConfiguration>> for hyper::header::HeaderValue {
+    type Error = String;
+
+    fn try_from(hdr_value: header::IntoHeaderValue<CellConfiguration>) -> std::result::Result<Self, Self::Error> {
+        let hdr_value = hdr_value.to_string();
+        match hyper::header::HeaderValue::from_str(&hdr_value) {
+             std::result::Result::Ok(value) => std::result::Result::Ok(value),
+             std::result::Result::Err(e) => std::result::Result::Err(
+                 format!("Invalid header value for CellConfiguration - value: {} is invalid {}",
+                     hdr_value, e))
+        }
+    }
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl std::convert::TryFrom<hyper::header::HeaderValue> for header::IntoHeaderValue<CellConfiguration> {
+    type Error = String;
+
+    fn try_from(hdr_value: hyper::header::HeaderValue) -> std::result::Result<Self, Self::Error> {
+        match hdr_value.to_str() {
+             std::result::Result::Ok(value) => {
+                    match <CellConfiguration as std::str::FromStr>::from_str(value) {
+                        std::result::Result::Ok(value) => std::result::Result::Ok(header::IntoHeaderValue(value)),
+                        std::result::Result::Err(err) => std::result::Result::Err(
+                            format!("Unable to convert header value '{}' into CellConfiguration - {}",
+                                value, err))
+                    }
+             },
+             std::result::Result::Err(e) => std::result::Result::Err(
+                 format!("Unable to convert header: {:?} to string: {}",
+                     hdr_value, e))
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "conversion", derive(frunk::LabelledGeneric))]
+pub struct Error(String);
+
+impl std::convert::From<String> for Error {
+    fn from(x: String) -> Self {
+        Error(x)
+    }
+}
+
+impl std::string::ToString for Error {
+    fn to_string(&self) -> String {
+       self.0.to_string()
+    }
+}
+
+impl std::str::FromStr for Error {
+    type Err = std::string::ParseError;
+    fn from_str(x: &str) -> std::result::Result<Self, Self::Err> {
+        std::result::Result::Ok(Error(x.to_string()))
+    }
+}
+
+impl std::convert::From<Error> for String {
+    fn from(x: Error) -> Self {
+        x.0
+    }
+}
+
+impl std::ops::Deref for Error {
+    type Target = String;
+    fn deref(&self) -> &String {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for Error {
+    fn deref_mut(&mut self) -> &mut String {
+        &mut self.0
+    }
+}
+
+
+/// The cell reselection parameters broadcast in SIB2.  See TS 38.331, 6.3.1.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "conversion", derive(frunk::LabelledGeneric))]
+pub struct Sib2 {
+    /// Hysteresis in dB - from 0 to 6, or an even number from 8 to 24.
+    #[serde(rename = "qHyst")]
+    pub q_hyst: i32,
+
+    /// From 0 to 7.
+    #[serde(rename = "cellReselectionPriority")]
+    pub cell_reselection_priority: i32,
+
+    /// From 0 to 31, in units of 2 dB.
+    #[serde(rename = "threshServingLowP")]
+    pub thresh_serving_low_p: i32,
+
+    /// From -70 to -22, in units of 2 dBm.
+    #[serde(rename = "qRxLevMin")]
+    pub q_rx_lev_min: i32,
+
+    /// From 0 to 31, in units of 2 dB.
+    #[serde(rename = "sIntraSearchP")]
+    pub s_intra_search_p: i32,
+
+    /// From 0 to 7 seconds.
+    #[serde(rename = "tReselectionNr")]
+    pub t_reselection_nr: i32,
+
+}
+
+impl Sib2 {
+    #[allow(clippy::new_without_default)]
+    pub fn new(q_hyst: i32, cell_reselection_priority: i32, thresh_serving_low_p: i32, q_rx_lev_min: i32, s_intra_search_p: i32, t_reselection_nr: i32, ) -> Sib2 {
+        Sib2 {
+            q_hyst,
+            cell_reselection_priority,
+            thresh_serving_low_p,
+            q_rx_lev_min,
+            s_intra_search_p,
+            t_reselection_nr,
+        }
+    }
+}
+
+/// Converts the Sib2 value to the Query Parameters representation (style=form, explode=false)
+/// specified in https://swagger.io/docs/specification/serialization/
+/// Should be implemented in a serde serializer
+impl std::string::ToString for Sib2 {
+    fn to_string(&self) -> String {
+        let params: Vec<Option<String>> = vec![
+
+            Some("qHyst".to_string()),
+            Some(self.q_hyst.to_string()),
+
+
+            Some("cellReselectionPriority".to_string()),
+            Some(self.cell_reselection_priority.to_string()),
+
+
+            Some("threshServingLowP".to_string()),
+            Some(self.thresh_serving_low_p.to_string()),
+
+
+            Some("qRxLevMin".to_string()),
+            Some(self.q_rx_lev_min.to_string()),
+
+
+            Some("sIntraSearchP".to_string()),
+            Some(self.s_intra_search_p.to_string()),
+
+
+            Some("tReselectionNr".to_string()),
+            Some(self.t_reselection_nr.to_string()),
+
+        ];
+
+        params.into_iter().flatten().collect::<Vec<_>>().join(",")
+    }
+}
+
+/// Converts Query Parameters representation (style=form, explode=false) to a Sib2 value
+/// as specified in https://swagger.io/docs/specification/serialization/
+/// Should be implemented in a serde deserializer
+impl std::str::FromStr for Sib2 {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        /// An intermediate representation of the struct to use for parsing.
+        #[derive(Default)]
+        #[allow(dead_code)]
+        struct IntermediateRep {
+            pub q_hyst: Vec<i32>,
+            pub cell_reselection_priority: Vec<i32>,
+            pub thresh_serving_low_p: Vec<i32>,
+            pub q_rx_lev_min: Vec<i32>,
+            pub s_intra_search_p: Vec<i32>,
+            pub t_reselection_nr: Vec<i32>,
+        }
+
+        let mut intermediate_rep = IntermediateRep::default();
+
+        // Parse into intermediate representation
+        let mut string_iter = s.split(',');
+        let mut key_result = string_iter.next();
+
+        while key_result.is_some() {
+            let val = match string_iter.next() {
+                Some(x) => x,
+                None => return std::result::Result::Err("Missing value while parsing Sib2".to_string())
+            };
+
+            if let Some(key) = key_result {
+                #[allow(clippy::match_single_binding)]
+                match key {
+                    #[allow(clippy::redundant_clone)]
+                    "qHyst" => intermediate_rep.q_hyst.push(<i32 as std::str::FromStr>::from_str(val).map_err(|x| x.to_string())?),
+                    #[allow(clippy::redundant_clone)]
+                    "cellReselectionPriority" => intermediate_rep.cell_reselection_priority.push(<i32 as std::str::FromStr>::from_str(val).map_err(|x| x.to_string())?),
+                    #[allow(clippy::redundant_clone)]
+                    "threshServingLowP" => intermediate_rep.thresh_serving_low_p.push(<i32 as std::str::FromStr>::from_str(val).map_err(|x| x.to_string())?),
+                    #[allow(clippy::redundant_clone)]
+                    "qRxLevMin" => intermediate_rep.q_rx_lev_min.push(<i32 as std::str::FromStr>::from_str(val).map_err(|x| x.to_string())?),
+                    #[allow(clippy::redundant_clone)]
+                    "sIntraSearchP" => intermediate_rep.s_intra_search_p.push(<i32 as std::str::FromStr>::from_str(val).map_err(|x| x.to_string())?),
+                    #[allow(clippy::redundant_clone)]
+                    "tReselectionNr" => intermediate_rep.t_reselection_nr.push(<i32 as std::str::FromStr>::from_str(val).map_err(|x| x.to_string())?),
+                    _ => return std::result::Result::Err("Unexpected key while parsing Sib2".to_string())
+                }
+            }
+
+            // Get the next key
+            key_result = string_iter.next();
+        }
+
+        // Use the intermediate representation to return the struct
+        std::result::Result::Ok(Sib2 {
+            q_hyst: intermediate_rep.q_hyst.into_iter().next().ok_or_else(|| "qHyst missing in Sib2".to_string())?,
+            cell_reselection_priority: intermediate_rep.cell_reselection_priority.into_iter().next().ok_or_else(|| "cellReselectionPriority missing in Sib2".to_string())?,
+            thresh_serving_low_p: intermediate_rep.thresh_serving_low_p.into_iter().next().ok_or_else(|| "threshServingLowP missing in Sib2".to_string())?,
+            q_rx_lev_min: intermediate_rep.q_rx_lev_min.into_iter().next().ok_or_else(|| "qRxLevMin missing in Sib2".to_string())?,
+            s_intra_search_p: intermediate_rep.s_intra_search_p.into_iter().next().ok_or_else(|| "sIntraSearchP missing in Sib2".to_string())?,
+            t_reselection_nr: intermediate_rep.t_reselection_nr.into_iter().next().ok_or_else(|| "tReselectionNr missing in Sib2".to_string())?,
+        })
+    }
+}
+
+// Methods for converting between header::IntoHeaderValue<Sib2> and hyper::header::HeaderValue
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl std::convert::TryFrom<header::IntoHeaderValue<Sib2>> for hyper::header::HeaderValue {
+    type Error = String;
+
+    fn try_from(hdr_value: header::IntoHeaderValue<Sib2>) -> std::result::Result<Self, Self::Error> {
+        let hdr_value = hdr_value.to_string();
+        match hyper::header::HeaderValue::from_str(&hdr_value) {
+             std::result::Result::Ok(value) => std::result::Result::Ok(value),
+             std::result::Result::Err(e) => std::result::Result::Err(
+                 format!("Invalid header value for Sib2 - value: {} is invalid {}",
+                     hdr_value, e))
+        }
+    }
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl std::convert::TryFrom<hyper::header::HeaderValue> for header::IntoHeaderValue<Sib2> {
+    type Error = String;
+
+    fn try_from(hdr_value: hyper::header::HeaderValue) -> std::result::Result<Self, Self::Error> {
+        match hdr_value.to_str() {
+             std::result::Result::Ok(value) => {
+                    match <Sib2 as std::str::FromStr>::from_str(value) {
+                        std::result::Result::Ok(value) => std::result::Result::Ok(header::IntoHeaderValue(value)),
+                        std::result::Result::Err(err) => std::result::Result::Err(
+                            format!("Unable to convert header value '{}' into Sib2 - {}",
+                                value, err))
+                    }
+             },
+             std::result::Result::Err(e) => std::result::Result::Err(
+                 format!("Unable to convert header: {:?} to string: {}",
+                     hdr_value, e))
+        }
+    }
+}
+
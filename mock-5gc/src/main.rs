@@ -21,7 +21,7 @@ async fn main() -> Result<()> {
     let logger = common::logging::init();
     let args = Args::parse();
 
-    let mut amf = Mock5gc::new(&args.local_ip.to_string(), &logger).await?;
+    let mut amf: Mock5gc = Mock5gc::new(&args.local_ip.to_string(), &logger).await?;
     amf.disable_receive_timeouts();
 
     // Wait for connection and do NG Setup.
@@ -1,20 +1,22 @@
 //! mock - 'base class' for the mocks
 
 use anyhow::{bail, Result};
+use asn1_per::TransactionKeyed;
 use async_channel::{Receiver, Sender};
 use async_trait::async_trait;
 use net::{
-    Binding, SctpTransportProvider, SerDes, ShutdownHandle, TnlaEvent, TnlaEventHandler,
-    TransportProvider,
+    stream_for_pdu, Binding, SctpConfig, SctpTransportProvider, SerDes, ShutdownHandle, StreamId,
+    TnlaEvent, TnlaEventHandler, TransportProvider,
 };
 use slog::{debug, info, Logger};
 use std::fmt::Debug;
 
-pub trait Pdu: SerDes + 'static + Send + Sync + Clone + Debug {}
+pub trait Pdu: SerDes + TransactionKeyed + 'static + Send + Sync + Clone + Debug {}
 
-/// Base struct for building mocks
-pub struct Mock<P: Pdu> {
-    pub transport: SctpTransportProvider,
+/// Base struct for building mocks.  The transport is SCTP unless the test picks another, such as
+/// ChannelTransportProvider to run without kernel SCTP support.
+pub struct Mock<P: Pdu, T: TransportProvider = SctpTransportProvider> {
+    pub transport: T,
     receiver: Receiver<MockEvent<P>>,
     pub logger: Logger,
     handler: Handler<P>,
@@ -30,6 +32,7 @@ pub enum MockEvent<P: Pdu> {
 pub struct ReceivedPdu<P: Pdu> {
     pub pdu: P,
     pub assoc_id: u32,
+    pub stream_id: StreamId,
 }
 
 #[derive(Debug, Clone)]
@@ -49,11 +52,11 @@ pub struct Handler<P: Pdu>(pub Sender<MockEvent<P>>);
 //     }
 // }
 
-impl<P: Pdu> Mock<P> {
+impl<P: Pdu, T: TransportProvider> Mock<P, T> {
     pub async fn new(logger: Logger) -> Self {
         let (sender, receiver) = async_channel::unbounded();
         //let receiver = DebugReceiver(receiver, logger.clone());
-        let transport = SctpTransportProvider::new();
+        let transport = T::new_with_config(SctpConfig::default());
 
         Mock {
             transport,
@@ -121,10 +124,11 @@ impl<P: Pdu> Mock<P> {
         }
     }
 
-    pub async fn send<T: SerDes>(&self, pdu: T, assoc_id: Option<u32>) {
+    pub async fn send<S: SerDes + TransactionKeyed>(&self, pdu: S, assoc_id: Option<u32>) {
+        let stream = stream_for_pdu(pdu.transaction_key());
         let message = pdu.into_bytes().unwrap();
         self.transport
-            .send_message(message, assoc_id, &self.logger)
+            .send_message(message, assoc_id, stream, &self.logger)
             .await
             .expect("Failed to send message");
     }
@@ -164,14 +168,31 @@ impl<P: Pdu> TnlaEventHandler for Handler<P> {
                 .await
                 .expect("Channel closed"),
             TnlaEvent::Terminated => info!(logger, "TNLA {} closed", tnla_id),
+            TnlaEvent::PathUp(addr) => info!(logger, "TNLA {} path to {} up", tnla_id, addr),
+            TnlaEvent::PathDown(addr) => info!(logger, "TNLA {} path to {} down", tnla_id, addr),
+            TnlaEvent::Restarted => info!(logger, "TNLA {} peer restarted", tnla_id),
+            TnlaEvent::PeerShutdown => info!(logger, "TNLA {} shutting down", tnla_id),
+            TnlaEvent::SendFailed(stream_id) => {
+                info!(
+                    logger,
+                    "TNLA {} send failed on stream {}", tnla_id, stream_id
+                )
+            }
         }
     }
 
-    async fn handle_message(&self, message: Vec<u8>, tnla_id: u32, _logger: &Logger) {
+    async fn handle_message(
+        &self,
+        message: Vec<u8>,
+        tnla_id: u32,
+        stream_id: StreamId,
+        _logger: &Logger,
+    ) {
         self.0
             .send(MockEvent::Pdu(ReceivedPdu {
                 pdu: P::from_bytes(&message).unwrap(),
                 assoc_id: tnla_id,
+                stream_id,
             }))
             .await
             .unwrap();
@@ -6,7 +6,7 @@ use crate::{
 };
 use anyhow::{anyhow, bail, Result};
 use asn1_per::*;
-use net::{Binding, SerDes, TransportProvider};
+use net::{Binding, SctpTransportProvider, SerDes, TransportProvider};
 use ngap::*;
 use slog::{debug, info, o, Logger};
 use std::ops::{Deref, DerefMut};
@@ -14,8 +14,8 @@ use xxap::{GtpTeid, GtpTunnel, PduSessionId};
 
 impl Pdu for NgapPdu {}
 
-pub struct Mock5gc {
-    mock: Mock<NgapPdu>,
+pub struct Mock5gc<T: TransportProvider = SctpTransportProvider> {
+    mock: Mock<NgapPdu, T>,
     ips: Vec<String>,
     userplane: MockUserplane,
 }
@@ -37,14 +37,14 @@ pub struct Session {
     session_id: PduSessionId,
 }
 
-impl Deref for Mock5gc {
-    type Target = Mock<NgapPdu>;
+impl<T: TransportProvider> Deref for Mock5gc<T> {
+    type Target = Mock<NgapPdu, T>;
 
     fn deref(&self) -> &Self::Target {
         &self.mock
     }
 }
-impl DerefMut for Mock5gc {
+impl<T: TransportProvider> DerefMut for Mock5gc<T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.mock
     }
@@ -53,9 +53,9 @@ impl DerefMut for Mock5gc {
 const NGAP_SCTP_PPID: u32 = 60;
 const NGAP_BIND_PORT: u16 = 38412;
 
-impl Mock5gc {
-    pub async fn new(userplane_ip: &str, logger: &Logger) -> Result<Mock5gc> {
-        Ok(Mock5gc {
+impl<T: TransportProvider> Mock5gc<T> {
+    pub async fn new(userplane_ip: &str, logger: &Logger) -> Result<Self> {
+        Ok(Self {
             mock: Mock::new(logger.new(o!("amf" => 1))).await,
             ips: vec![],
             userplane: MockUserplane::new(
@@ -88,7 +88,7 @@ impl Mock5gc {
         let logger = &self.logger;
         debug!(logger, "Wait for NG Setup from GNB");
 
-        let ReceivedPdu { pdu, assoc_id } = self.receive_pdu_with_assoc_id().await.unwrap();
+        let ReceivedPdu { pdu, assoc_id, .. } = self.receive_pdu_with_assoc_id().await.unwrap();
 
         let NgapPdu::InitiatingMessage(InitiatingMessage::NgSetupRequest(_ng_setup)) = pdu
         else {
@@ -131,7 +131,7 @@ impl Mock5gc {
         let logger = &self.logger;
         debug!(logger, "Wait for RAN Configuration Update from GNB");
 
-        let ReceivedPdu { pdu, assoc_id } = self.receive_pdu_with_assoc_id().await.unwrap();
+        let ReceivedPdu { pdu, assoc_id, .. } = self.receive_pdu_with_assoc_id().await.unwrap();
 
         let NgapPdu::InitiatingMessage(InitiatingMessage::RanConfigurationUpdate(
             _ran_configuration_update,
@@ -167,7 +167,7 @@ impl Mock5gc {
         PlmnIdentity([2, 3, 2])
     }
 
-    fn snssai(&self) -> xxap::Snssai {
+    pub fn snssai(&self) -> xxap::Snssai {
         xxap::Snssai(1, Some([0x02, 0x03, 0x04])) // (Necessary for ODU interop when using AMF-SIM)
     }
 
@@ -181,6 +181,7 @@ impl Mock5gc {
                         ..
                     })),
                 assoc_id,
+                ..
             } => {
                 info!(logger, ">> InitialUeMessage");
                 debug!(logger, "UE Id {:?}", ran_ue_ngap_id);
@@ -202,6 +203,38 @@ impl Mock5gc {
         &self,
         ue_context: &UeContext,
         nas_pdu: Vec<u8>,
+    ) -> Result<()> {
+        self.send_initial_context_setup_request_inner(ue_context, nas_pdu, None)
+            .await
+    }
+
+    pub async fn send_initial_context_setup_request_with_session(
+        &self,
+        ue_context: &UeContext,
+        nas_pdu: Vec<u8>,
+    ) -> Result<GtpTeid> {
+        let gtp_teid = GtpTeid([1, 2, 3, 4]);
+        let pdu_session_resource_setup_request_transfer =
+            self.build_pdu_session_resource_setup_request_transfer(&gtp_teid)?;
+        let dummy_nas_session_establishment_accept = NasPdu(vec![1]);
+        let sessions = PduSessionResourceSetupListCxtReq(nonempty![
+            PduSessionResourceSetupItemCxtReq {
+                pdu_session_id: PduSessionId(1),
+                nas_pdu: Some(dummy_nas_session_establishment_accept),
+                snssai: self.snssai().into(),
+                pdu_session_resource_setup_request_transfer,
+            }
+        ]);
+        self.send_initial_context_setup_request_inner(ue_context, nas_pdu, Some(sessions))
+            .await?;
+        Ok(gtp_teid)
+    }
+
+    async fn send_initial_context_setup_request_inner(
+        &self,
+        ue_context: &UeContext,
+        nas_pdu: Vec<u8>,
+        pdu_session_resource_setup_list_cxt_req: Option<PduSessionResourceSetupListCxtReq>,
     ) -> Result<()> {
         let logger = &self.logger;
         let pdu = NgapPdu::InitiatingMessage(InitiatingMessage::InitialContextSetupRequest(
@@ -212,7 +245,7 @@ impl Mock5gc {
                 ue_aggregate_maximum_bit_rate: None,
                 core_network_assistance_information_for_inactive: None,
                 guami: self.guami(),
-                pdu_session_resource_setup_list_cxt_req: None,
+                pdu_session_resource_setup_list_cxt_req,
                 allowed_nssai: AllowedNssai(nonempty![AllowedNssaiItem {
                     snssai: self.snssai().into(),
                 }]),
@@ -280,6 +313,37 @@ impl Mock5gc {
         Ok(())
     }
 
+    pub async fn receive_initial_context_setup_response_with_session(
+        &self,
+        ue_context: &UeContext,
+        local_gtp_teid: GtpTeid,
+    ) -> Result<Session> {
+        let pdu = self.receive_pdu().await.unwrap();
+        let NgapPdu::SuccessfulOutcome(SuccessfulOutcome::InitialContextSetupResponse(
+            InitialContextSetupResponse {
+                amf_ue_ngap_id,
+                pdu_session_resource_setup_list_cxt_res: Some(xs),
+                ..
+            },
+        )) = pdu else {
+            bail!(
+                "Expecting InitialContextSetupResponse with session, got unexpected message {:?}",
+                pdu
+            )
+        };
+        info!(&self.logger, ">> InitialContextSetupResponse");
+        assert_eq!(amf_ue_ngap_id.0, ue_context.ue_id.into());
+
+        // There should be exactly one successful session setup.
+        assert!(xs.0.len() == 1);
+        let setup = &xs.0[0];
+        self.session_from_response_transfer(
+            setup.pdu_session_id,
+            &setup.pdu_session_resource_setup_response_transfer,
+            local_gtp_teid,
+        )
+    }
+
     pub async fn send_status_indication(&self) -> Result<()> {
         info!(&self.logger, "<< AmfStatusIndication");
         let pdu = NgapPdu::InitiatingMessage(InitiatingMessage::AmfStatusIndication(
@@ -295,6 +359,42 @@ impl Mock5gc {
         Ok(())
     }
 
+    pub async fn send_paging(
+        &self,
+        five_g_tmsi: [u8; 4],
+        plmn_identity: [u8; 3],
+        tac: [u8; 3],
+    ) -> Result<()> {
+        info!(&self.logger, "<< Paging");
+        let guami = self.guami();
+        let pdu = NgapPdu::InitiatingMessage(InitiatingMessage::Paging(Paging {
+            ue_paging_identity: UePagingIdentity::FiveGSTmsi(FiveGSTmsi {
+                amf_set_id: guami.amf_set_id,
+                amf_pointer: guami.amf_pointer,
+                five_g_tmsi: FiveGTmsi(five_g_tmsi),
+            }),
+            paging_drx: None,
+            tai_list_for_paging: TaiListForPaging(nonempty![TaiListForPagingItem {
+                tai: Tai {
+                    plmn_identity: PlmnIdentity(plmn_identity),
+                    tac: Tac(tac),
+                },
+            }]),
+            paging_priority: None,
+            ue_radio_capability_for_paging: None,
+            paging_origin: None,
+            assistance_data_for_paging: None,
+            nb_iot_paging_e_drx_info: None,
+            nb_iot_paging_drx: None,
+            enhanced_coverage_restriction: None,
+            wus_assistance_information: None,
+            paginge_drx_information: None,
+            c_emode_brestricted: None,
+        }));
+        self.send(pdu, None).await;
+        Ok(())
+    }
+
     pub async fn send_downlink_nas_transport(
         &self,
         ue_context: &UeContext,
@@ -343,10 +443,39 @@ impl Mock5gc {
     pub async fn send_pdu_session_resource_setup(&self, ue_context: &UeContext) -> Result<GtpTeid> {
         info!(&self.logger, "<< PduSessionResourceSetupRequest");
 
-        let transport_layer_address = (*self.userplane.local_ip()).into();
         let gtp_teid = GtpTeid([1, 2, 3, 4]);
+        let pdu_session_resource_setup_request_transfer =
+            self.build_pdu_session_resource_setup_request_transfer(&gtp_teid)?;
+
+        let dummy_nas_session_establishment_accept = NasPdu(vec![1]);
+
+        let pdu = NgapPdu::InitiatingMessage(InitiatingMessage::PduSessionResourceSetupRequest(
+            PduSessionResourceSetupRequest {
+                amf_ue_ngap_id: ue_context.amf_ue_ngap_id(),
+                ran_ue_ngap_id: ue_context.ran_ue_ngap_id,
+                ran_paging_priority: None,
+                nas_pdu: None,
+                pdu_session_resource_setup_list_su_req: PduSessionResourceSetupListSuReq(
+                    nonempty![PduSessionResourceSetupItemSuReq {
+                        pdu_session_id: PduSessionId(1),
+                        pdu_session_nas_pdu: Some(dummy_nas_session_establishment_accept),
+                        snssai: self.snssai().into(),
+                        pdu_session_resource_setup_request_transfer,
+                    },],
+                ),
+                ue_aggregate_maximum_bit_rate: None,
+            },
+        ));
+        self.send(pdu, Some(ue_context.binding.assoc_id)).await;
+        Ok(gtp_teid)
+    }
 
-        let pdu_session_resource_setup_request_transfer = PduSessionResourceSetupRequestTransfer {
+    fn build_pdu_session_resource_setup_request_transfer(
+        &self,
+        gtp_teid: &GtpTeid,
+    ) -> Result<Vec<u8>> {
+        let transport_layer_address = (*self.userplane.local_ip()).into();
+        Ok(PduSessionResourceSetupRequestTransfer {
             pdu_session_aggregate_maximum_bit_rate: None,
             ul_ngu_up_tnl_information: UpTransportLayerInformation::GtpTunnel(GtpTunnel {
                 transport_layer_address,
@@ -394,29 +523,7 @@ impl Mock5gc {
             redundant_common_network_instance: None,
             redundant_pdu_session_information: None,
         }
-        .into_bytes()?;
-
-        let dummy_nas_session_establishment_accept = NasPdu(vec![1]);
-
-        let pdu = NgapPdu::InitiatingMessage(InitiatingMessage::PduSessionResourceSetupRequest(
-            PduSessionResourceSetupRequest {
-                amf_ue_ngap_id: ue_context.amf_ue_ngap_id(),
-                ran_ue_ngap_id: ue_context.ran_ue_ngap_id,
-                ran_paging_priority: None,
-                nas_pdu: None,
-                pdu_session_resource_setup_list_su_req: PduSessionResourceSetupListSuReq(
-                    nonempty![PduSessionResourceSetupItemSuReq {
-                        pdu_session_id: PduSessionId(1),
-                        pdu_session_nas_pdu: Some(dummy_nas_session_establishment_accept),
-                        snssai: self.snssai().into(),
-                        pdu_session_resource_setup_request_transfer,
-                    },],
-                ),
-                ue_aggregate_maximum_bit_rate: None,
-            },
-        ));
-        self.send(pdu, Some(ue_context.binding.assoc_id)).await;
-        Ok(gtp_teid)
+        .into_bytes()?)
     }
 
     pub async fn receive_pdu_session_resource_setup_response(
@@ -438,18 +545,11 @@ impl Mock5gc {
                     // There should be exactly one successful session setup.
                     assert!(xs.0.len() == 1);
                     let setup = &xs.0[0];
-                    let transfer = PduSessionResourceSetupResponseTransfer::from_bytes(
+                    self.session_from_response_transfer(
+                        setup.pdu_session_id,
                         &setup.pdu_session_resource_setup_response_transfer,
-                    )?;
-                    let UpTransportLayerInformation::GtpTunnel(remote_tunnel_info) = transfer
-                        .dl_qos_flow_per_tnl_information
-                        .up_transport_layer_information;
-
-                    Session {
-                        remote_tunnel_info,
                         local_gtp_teid,
-                        session_id: setup.pdu_session_id,
-                    }
+                    )?
                 } else {
                     panic!("Expected pdu_session_resource_setup_list_su_res on PduSessionResourceSetupResponse")
                 };
@@ -462,6 +562,138 @@ impl Mock5gc {
         }
     }
 
+    fn session_from_response_transfer(
+        &self,
+        session_id: PduSessionId,
+        pdu_session_resource_setup_response_transfer: &[u8],
+        local_gtp_teid: GtpTeid,
+    ) -> Result<Session> {
+        let transfer = PduSessionResourceSetupResponseTransfer::from_bytes(
+            pdu_session_resource_setup_response_transfer,
+        )?;
+        let UpTransportLayerInformation::GtpTunnel(remote_tunnel_info) = transfer
+            .dl_qos_flow_per_tnl_information
+            .up_transport_layer_information;
+
+        Ok(Session {
+            remote_tunnel_info,
+            local_gtp_teid,
+            session_id,
+        })
+    }
+
+    // Add a QoS flow to a session, and change its aggregate maximum bit rate.
+    pub async fn send_pdu_session_resource_modify(
+        &self,
+        ue_context: &UeContext,
+        session: &Session,
+        qfi: u8,
+    ) -> Result<()> {
+        info!(&self.logger, "<< PduSessionResourceModifyRequest");
+
+        let transfer = PduSessionResourceModifyRequestTransfer {
+            pdu_session_aggregate_maximum_bit_rate: Some(PduSessionAggregateMaximumBitRate {
+                pdu_session_aggregate_maximum_bit_rate_dl: BitRate(2000000),
+                pdu_session_aggregate_maximum_bit_rate_ul: BitRate(1000000),
+            }),
+            ul_ngu_up_tnl_modify_list: None,
+            network_instance: None,
+            qos_flow_add_or_modify_request_list: Some(QosFlowAddOrModifyRequestList(nonempty![
+                QosFlowAddOrModifyRequestItem {
+                    qos_flow_identifier: QosFlowIdentifier(qfi),
+                    qos_flow_level_qos_parameters: Some(QosFlowLevelQosParameters {
+                        qos_characteristics: QosCharacteristics::NonDynamic5qi(
+                            NonDynamic5qiDescriptor {
+                                five_qi: FiveQi(5),
+                                priority_level_qos: None,
+                                averaging_window: None,
+                                maximum_data_burst_volume: None,
+                                cn_packet_delay_budget_dl: None,
+                                cn_packet_delay_budget_ul: None,
+                            },
+                        ),
+                        allocation_and_retention_priority: AllocationAndRetentionPriority {
+                            priority_level_arp: PriorityLevelArp(1),
+                            pre_emption_capability: PreEmptionCapability::ShallNotTriggerPreEmption,
+                            pre_emption_vulnerability: PreEmptionVulnerability::NotPreEmptable,
+                        },
+                        gbr_qos_information: None,
+                        reflective_qos_attribute: None,
+                        additional_qos_flow_information: None,
+                        qos_monitoring_request: None,
+                        qos_monitoring_reporting_frequency: None,
+                    }),
+                    e_rab_id: None,
+                    tsc_traffic_characteristics: None,
+                    redundant_qos_flow_indicator: None,
+                }
+            ])),
+            qos_flow_to_release_list: None,
+            additional_ul_ngu_up_tnl_information: None,
+            common_network_instance: None,
+            additional_redundant_ul_ngu_up_tnl_information: None,
+            redundant_common_network_instance: None,
+            redundant_ul_ngu_up_tnl_information: None,
+            security_indication: None,
+        };
+
+        let dummy_nas_session_modification_command = NasPdu(vec![2]);
+
+        let pdu = NgapPdu::InitiatingMessage(InitiatingMessage::PduSessionResourceModifyRequest(
+            PduSessionResourceModifyRequest {
+                amf_ue_ngap_id: ue_context.amf_ue_ngap_id(),
+                ran_ue_ngap_id: ue_context.ran_ue_ngap_id,
+                ran_paging_priority: None,
+                pdu_session_resource_modify_list_mod_req: PduSessionResourceModifyListModReq(
+                    nonempty![PduSessionResourceModifyItemModReq {
+                        pdu_session_id: session.session_id,
+                        nas_pdu: Some(dummy_nas_session_modification_command),
+                        pdu_session_resource_modify_request_transfer: transfer.into_bytes()?,
+                        snssai: None,
+                    }],
+                ),
+            },
+        ));
+
+        self.send(pdu, Some(ue_context.binding.assoc_id)).await;
+        Ok(())
+    }
+
+    pub async fn receive_pdu_session_resource_modify_response(
+        &self,
+        ue_context: &UeContext,
+        session: &Session,
+        qfi: u8,
+    ) -> Result<()> {
+        match self.receive_pdu().await.unwrap() {
+            NgapPdu::SuccessfulOutcome(SuccessfulOutcome::PduSessionResourceModifyResponse(
+                PduSessionResourceModifyResponse {
+                    amf_ue_ngap_id,
+                    pdu_session_resource_modify_list_mod_res: Some(xs),
+                    pdu_session_resource_failed_to_modify_list_mod_res: None,
+                    ..
+                },
+            )) => {
+                info!(&self.logger, ">> PduSessionResourceModifyResponse");
+                assert_eq!(amf_ue_ngap_id.0, ue_context.ue_id.into());
+                assert!(xs.0.len() == 1);
+                let modified = &xs.0[0];
+                assert_eq!(modified.pdu_session_id.0, session.session_id.0);
+                let transfer = PduSessionResourceModifyResponseTransfer::from_bytes(
+                    &modified.pdu_session_resource_modify_response_transfer,
+                )?;
+                let Some(flows) = transfer.qos_flow_add_or_modify_response_list else {
+                    bail!("Expected added QoS flow in PduSessionResourceModifyResponseTransfer")
+                };
+                assert!(flows.0.iter().any(|x| x.qos_flow_identifier.0 == qfi));
+                Ok(())
+            }
+            m => {
+                bail!("Unexpected message {:?}", m);
+            }
+        }
+    }
+
     pub async fn send_pdu_session_resource_release(
         &self,
         ue_context: &UeContext,
@@ -511,6 +743,72 @@ impl Mock5gc {
         }
     }
 
+    pub async fn send_ue_context_release_command(&self, ue_context: &UeContext) -> Result<()> {
+        info!(&self.logger, "<< UeContextReleaseCommand");
+        let pdu = NgapPdu::InitiatingMessage(InitiatingMessage::UeContextReleaseCommand(
+            UeContextReleaseCommand {
+                ue_ngap_i_ds: UeNgapIDs::UeNgapIdPair(UeNgapIdPair {
+                    amf_ue_ngap_id: ue_context.amf_ue_ngap_id(),
+                    ran_ue_ngap_id: ue_context.ran_ue_ngap_id,
+                }),
+                cause: Cause::Nas(CauseNas::NormalRelease),
+            },
+        ));
+        self.send(pdu, Some(ue_context.binding.assoc_id)).await;
+        Ok(())
+    }
+
+    // The sessions are those that the UE had, which the gNB-CU lists as released along with it.
+    pub async fn receive_ue_context_release_complete(
+        &self,
+        ue_context: &UeContext,
+        sessions: &[&Session],
+    ) -> Result<()> {
+        let pdu = self.receive_pdu().await.unwrap();
+        let NgapPdu::SuccessfulOutcome(SuccessfulOutcome::UeContextReleaseComplete(
+            UeContextReleaseComplete {
+                amf_ue_ngap_id,
+                ran_ue_ngap_id,
+                pdu_session_resource_list_cxt_rel_cpl,
+                ..
+            },
+        )) = pdu else {
+            bail!(
+                "Expecting UeContextReleaseComplete, got unexpected message {:?}",
+                pdu
+            )
+        };
+        info!(&self.logger, ">> UeContextReleaseComplete");
+        assert_eq!(amf_ue_ngap_id.0, ue_context.ue_id.into());
+        assert_eq!(ran_ue_ngap_id.0, ue_context.ran_ue_ngap_id.0);
+        let released: Vec<u8> = pdu_session_resource_list_cxt_rel_cpl
+            .map(|x| x.0.iter().map(|x| x.pdu_session_id.0).collect())
+            .unwrap_or_default();
+        let expected: Vec<u8> = sessions.iter().map(|x| x.session_id.0).collect();
+        assert_eq!(released, expected);
+        Ok(())
+    }
+
+    pub async fn receive_ue_context_release_request(&self, ue_context: &UeContext) -> Result<()> {
+        let pdu = self.receive_pdu().await.unwrap();
+        let NgapPdu::InitiatingMessage(InitiatingMessage::UeContextReleaseRequest(
+            UeContextReleaseRequest {
+                amf_ue_ngap_id,
+                ran_ue_ngap_id,
+                ..
+            },
+        )) = pdu else {
+            bail!(
+                "Expecting UeContextReleaseRequest, got unexpected message {:?}",
+                pdu
+            )
+        };
+        info!(&self.logger, ">> UeContextReleaseRequest");
+        assert_eq!(amf_ue_ngap_id.0, ue_context.ue_id.into());
+        assert_eq!(ran_ue_ngap_id.0, ue_context.ran_ue_ngap_id.0);
+        Ok(())
+    }
+
     pub async fn send_data_packet(&self, session: &Session) -> Result<()> {
         self.userplane
             .send_n3_data_packet(
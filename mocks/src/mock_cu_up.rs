@@ -48,36 +48,81 @@ impl MockCuUp {
     }
 
     pub async fn perform_e1_setup(&mut self, worker_ip: &String) -> Result<()> {
-        let transport_address = format!("{}:{}", worker_ip, E1AP_BIND_PORT);
-        info!(self.logger, "Connect to CU-CP {}", transport_address);
-        self.connect(&transport_address, "0.0.0.0", E1AP_SCTP_PPID)
-            .await;
-        self.send_e1_setup_request().await?;
+        let request = Self::build_e1_setup_request(232, CnSupport::C5gc, [0, 1, 2], &[]);
+        self.perform_e1_setup_with(worker_ip, request).await
+    }
+
+    /// Performs E1 Setup with the given request, which the CU-CP is expected to accept.
+    pub async fn perform_e1_setup_with(
+        &mut self,
+        worker_ip: &str,
+        request: GnbCuUpE1SetupRequest,
+    ) -> Result<()> {
+        self.connect_to_cu_cp(worker_ip).await;
+        self.send_e1_setup_request(request).await?;
         self.receive_e1_setup_response().await
     }
 
-    async fn send_e1_setup_request(&self) -> Result<()> {
+    /// Performs E1 Setup with the given request, which the CU-CP is expected to reject.  Returns the
+    /// cause.
+    pub async fn perform_rejected_e1_setup(
+        &mut self,
+        worker_ip: &str,
+        request: GnbCuUpE1SetupRequest,
+    ) -> Result<Cause> {
+        self.connect_to_cu_cp(worker_ip).await;
+        self.send_e1_setup_request(request).await?;
+        self.receive_e1_setup_failure().await
+    }
+
+    /// Builds an E1 Setup Request for a CU-UP that supports a single PLMN, and the given slices in it.
+    /// With no slices, the CU-UP does not list them, and so supports any slice.
+    pub fn build_e1_setup_request(
+        gnb_cu_up_id: u64,
+        cn_support: CnSupport,
+        plmn: [u8; 3],
+        slices: &[xxap::Snssai],
+    ) -> GnbCuUpE1SetupRequest {
+        let slice_support_list = NonEmpty::from_vec(
+            slices
+                .iter()
+                .map(|x| SliceSupportItem {
+                    snssai: (*x).into(),
+                })
+                .collect(),
+        )
+        .map(SliceSupportList);
         let supported_plmns = SupportedPlmnsList(nonempty![SupportedPlmnsItem {
-            plmn_identity: PlmnIdentity([0, 1, 2]),
-            slice_support_list: None,
+            plmn_identity: PlmnIdentity(plmn),
+            slice_support_list,
             nr_cgi_support_list: None,
             qos_parameters_support_list: None,
             npn_support_info: None,
             extended_slice_support_list: None,
             extended_nr_cgi_support_list: None,
         }]);
-        let pdu = e1ap::E1apPdu::InitiatingMessage(InitiatingMessage::GnbCuUpE1SetupRequest(
-            GnbCuUpE1SetupRequest {
-                transaction_id: TransactionId(0),
-                gnb_cu_up_id: GnbCuUpId(232),
-                gnb_cu_up_name: Some(GnbCuUpName("TestCuUp".to_string())),
-                cn_support: CnSupport::C5gc,
-                supported_plmns,
-                gnb_cu_up_capacity: None,
-                transport_layer_address_info: None,
-                extended_gnb_cu_up_name: None,
-            },
-        ));
+        GnbCuUpE1SetupRequest {
+            transaction_id: TransactionId(0),
+            gnb_cu_up_id: GnbCuUpId(gnb_cu_up_id),
+            gnb_cu_up_name: Some(GnbCuUpName("TestCuUp".to_string())),
+            cn_support,
+            supported_plmns,
+            gnb_cu_up_capacity: None,
+            transport_layer_address_info: None,
+            extended_gnb_cu_up_name: None,
+        }
+    }
+
+    async fn connect_to_cu_cp(&mut self, worker_ip: &str) {
+        let transport_address = format!("{}:{}", worker_ip, E1AP_BIND_PORT);
+        info!(self.logger, "Connect to CU-CP {}", transport_address);
+        self.connect(&transport_address, "0.0.0.0", E1AP_SCTP_PPID)
+            .await;
+    }
+
+    async fn send_e1_setup_request(&self, request: GnbCuUpE1SetupRequest) -> Result<()> {
+        let pdu =
+            e1ap::E1apPdu::InitiatingMessage(InitiatingMessage::GnbCuUpE1SetupRequest(request));
         info!(self.logger, "GnbCuUpE1SetupRequest >>");
         self.send(pdu, None).await;
         Ok(())
@@ -93,6 +138,16 @@ impl MockCuUp {
         Ok(())
     }
 
+    async fn receive_e1_setup_failure(&self) -> Result<Cause> {
+        let pdu = self.receive_pdu().await?;
+        let E1apPdu::UnsuccessfulOutcome(UnsuccessfulOutcome::GnbCuUpE1SetupFailure(failure)) = pdu
+        else {
+            bail!("Expected GnbCuUpE1SetupFailure, got {:?}", pdu)
+        };
+        info!(self.logger, "GnbCuUpE1SetupFailure <<");
+        Ok(failure.cause)
+    }
+
     pub async fn handle_cu_cp_configuration_update(
         &mut self,
         expected_addr_string: &str,
@@ -114,7 +169,7 @@ impl MockCuUp {
         expected_address: &TransportLayerAddress,
     ) -> Result<(TransactionId, u32)> {
         debug!(self.logger, "Wait for Cu Cp Configuration Update");
-        let ReceivedPdu { pdu, assoc_id } = self.receive_pdu_with_assoc_id().await.unwrap();
+        let ReceivedPdu { pdu, assoc_id, .. } = self.receive_pdu_with_assoc_id().await.unwrap();
 
         let E1apPdu::InitiatingMessage(InitiatingMessage::GnbCuCpConfigurationUpdate(cu_cp_configuration_update)) = pdu
         else {
@@ -168,7 +223,7 @@ impl MockCuUp {
     }
 
     pub async fn handle_bearer_context_setup(&mut self, ue_id: u32) -> Result<UeContext> {
-        let ReceivedPdu { pdu, assoc_id } = self.receive_pdu_with_assoc_id().await.unwrap();
+        let ReceivedPdu { pdu, assoc_id, .. } = self.receive_pdu_with_assoc_id().await.unwrap();
         let ue_context = self.process_bearer_context_setup(pdu, ue_id).await?;
         info!(self.logger, "BearerContextSetupRequest <<");
         let pdu = self.build_bearer_context_setup_response(&ue_context);
@@ -239,7 +294,7 @@ impl MockCuUp {
     }
 
     pub async fn handle_bearer_context_modification(&self, ue_context: &UeContext) -> Result<()> {
-        let ReceivedPdu { pdu, assoc_id } = self.receive_pdu_with_assoc_id().await.unwrap();
+        let ReceivedPdu { pdu, assoc_id, .. } = self.receive_pdu_with_assoc_id().await.unwrap();
         self.check_bearer_context_modification(pdu, ue_context)
             .await?;
         info!(self.logger, "BearerContextModificationRequest <<");
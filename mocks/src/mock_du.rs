@@ -6,7 +6,7 @@ use anyhow::{anyhow, bail, ensure, Result};
 use asn1_per::*;
 use async_net::IpAddr;
 use f1ap::*;
-use net::{Binding, SerDes, TransportProvider};
+use net::{Binding, SctpTransportProvider, SerDes, TransportProvider, NON_UE_STREAM_ID};
 use pdcp::PdcpPdu;
 use rand::Rng;
 use rrc::*;
@@ -19,10 +19,14 @@ const F1AP_BIND_PORT: u16 = 38472;
 
 impl Pdu for F1apPdu {}
 
-pub struct MockDu {
-    mock: Mock<F1apPdu>,
+pub struct MockDu<T: TransportProvider = SctpTransportProvider> {
+    mock: Mock<F1apPdu, T>,
     local_ip: String,
     userplane: MockUserplane,
+    gnb_du_id: u64,
+
+    // The NR cell identity of the cell that the DU serves from the start, and that its UEs are in.
+    nr_cell_identity: u64,
 }
 
 pub struct UeContext {
@@ -30,6 +34,8 @@ pub struct UeContext {
     gnb_cu_ue_f1ap_id: Option<GnbCuUeF1apId>,
     pub binding: Binding,
     drb: Option<Drb>,
+    i_rnti: Option<IRntiValue>,
+    short_i_rnti: Option<ShortIRntiValue>,
 }
 
 pub struct Drb {
@@ -38,28 +44,41 @@ pub struct Drb {
     drb_id: DrbId,
 }
 
-impl Deref for MockDu {
-    type Target = Mock<F1apPdu>;
+impl<T: TransportProvider> Deref for MockDu<T> {
+    type Target = Mock<F1apPdu, T>;
 
     fn deref(&self) -> &Self::Target {
         &self.mock
     }
 }
 
-impl DerefMut for MockDu {
+impl<T: TransportProvider> DerefMut for MockDu<T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.mock
     }
 }
 
-impl MockDu {
-    pub async fn new(local_ip: &str, logger: &Logger) -> Result<MockDu> {
-        let logger = logger.new(o!("du" => 1));
+impl<T: TransportProvider> MockDu<T> {
+    pub async fn new(local_ip: &str, logger: &Logger) -> Result<Self> {
+        Self::new_with_id(local_ip, 123, 0, logger).await
+    }
+
+    /// A DU with the given gNB-DU ID, serving a cell with the given NR cell identity.  This lets a test
+    /// have more than one DU.
+    pub async fn new_with_id(
+        local_ip: &str,
+        gnb_du_id: u64,
+        nr_cell_identity: u64,
+        logger: &Logger,
+    ) -> Result<Self> {
+        let logger = logger.new(o!("du" => gnb_du_id));
         let mock = Mock::new(logger.clone()).await;
-        Ok(MockDu {
+        Ok(Self {
             mock,
             local_ip: local_ip.to_string(),
             userplane: MockUserplane::new(local_ip, logger.clone()).await?,
+            gnb_du_id,
+            nr_cell_identity,
         })
     }
 
@@ -73,6 +92,8 @@ impl MockDu {
             binding: self.transport.new_ue_binding_from_ip(worker_ip).await?,
             gnb_cu_ue_f1ap_id: None,
             drb: None,
+            i_rnti: None,
+            short_i_rnti: None,
         })
     }
 
@@ -90,13 +111,18 @@ impl MockDu {
         let pdu =
             f1ap::F1apPdu::InitiatingMessage(InitiatingMessage::F1SetupRequest(F1SetupRequest {
                 transaction_id: TransactionId(0),
-                gnb_du_id: GnbDuId(123),
+                gnb_du_id: GnbDuId(self.gnb_du_id),
                 gnb_du_rrc_version: RrcVersion {
                     latest_rrc_version: bitvec![u8, Msb0;0, 0, 0],
                     latest_rrc_version_enhanced: None,
                 },
                 gnb_du_name: None,
-                gnb_du_served_cells_list: None,
+                gnb_du_served_cells_list: Some(GnbDuServedCellsList(nonempty![
+                    GnbDuServedCellsItem {
+                        served_cell_information: make_served_cell_information(self.nr_cell_identity),
+                        gnb_du_system_information: None,
+                    }
+                ])),
                 transport_layer_address_info: None,
                 bap_address: None,
                 extended_gnb_cu_name: None,
@@ -107,7 +133,11 @@ impl MockDu {
     }
 
     async fn receive_f1_setup_response(&self) -> Result<()> {
-        let pdu = self.receive_pdu().await.unwrap();
+        let ReceivedPdu { pdu, stream_id, .. } = self.receive_pdu_with_assoc_id().await.unwrap();
+
+        // Check that the non UE-associated PDU arrived on stream 0.
+        assert_eq!(stream_id, NON_UE_STREAM_ID);
+
         let F1apPdu::SuccessfulOutcome(SuccessfulOutcome::F1SetupResponse(_)) = pdu
         else {
             bail!("Unexpected F1ap message {:?}", pdu)
@@ -116,6 +146,23 @@ impl MockDu {
         Ok(())
     }
 
+    pub async fn perform_f1_removal(&self) -> Result<()> {
+        let pdu = f1ap::F1apPdu::InitiatingMessage(InitiatingMessage::F1RemovalRequest(
+            F1RemovalRequest {
+                transaction_id: TransactionId(2),
+            },
+        ));
+        info!(self.logger, "F1RemovalRequest >>");
+        self.send(pdu, None).await;
+
+        let pdu = self.receive_pdu().await.unwrap();
+        let F1apPdu::SuccessfulOutcome(SuccessfulOutcome::F1RemovalResponse(_)) = pdu else {
+            bail!("Unexpected F1ap message {:?}", pdu)
+        };
+        info!(self.logger, "F1RemovalResponse <<");
+        Ok(())
+    }
+
     pub async fn perform_rrc_setup(
         &self,
         ue_context: &mut UeContext,
@@ -149,10 +196,7 @@ impl MockDu {
         let f1_indication = F1apPdu::InitiatingMessage(
             InitiatingMessage::InitialUlRrcMessageTransfer(InitialUlRrcMessageTransfer {
                 gnb_du_ue_f1ap_id: GnbDuUeF1apId(ue_context.ue_id),
-                nr_cgi: NrCgi {
-                    plmn_identity: PlmnIdentity([0, 1, 2]),
-                    nr_cell_identity: NrCellIdentity(bitvec![u8,Msb0;0;36]),
-                },
+                nr_cgi: make_nr_cgi(self.nr_cell_identity),
                 c_rnti: CRnti(14),
                 rrc_container: RrcContainer(rrc_setup_request),
                 du_to_cu_rrc_container,
@@ -276,10 +320,15 @@ impl MockDu {
     }
 
     async fn receive_dl_rrc(&self, ue_context: &UeContext) -> Result<DlRrcMessageTransfer> {
-        let ReceivedPdu { pdu, assoc_id } = self.receive_pdu_with_assoc_id().await.unwrap();
+        let ReceivedPdu {
+            pdu,
+            assoc_id,
+            stream_id,
+        } = self.receive_pdu_with_assoc_id().await.unwrap();
 
-        // Check that the PDU arrived on the expected binding.
+        // Check that the PDU arrived on the expected binding, and not on the non UE-associated stream.
         assert_eq!(assoc_id, ue_context.binding.assoc_id);
+        assert_ne!(stream_id, NON_UE_STREAM_ID);
 
         let F1apPdu::InitiatingMessage(InitiatingMessage::DlRrcMessageTransfer(dl_rrc_message_transfer)) = pdu
         else {
@@ -311,15 +360,58 @@ impl MockDu {
     }
 
     pub async fn handle_ue_context_setup(&self, ue_context: &mut UeContext) -> Result<()> {
-        let ReceivedPdu { pdu, assoc_id } = self.receive_pdu_with_assoc_id().await.unwrap();
+        self.handle_ue_context_setup_inner(ue_context).await?;
+        ensure!(ue_context.drb.is_some(), "No Drbs supplied");
+        Ok(())
+    }
+
+    // For a UE Context Setup that carries the Security Mode Command, as happens when sessions are set up
+    // during Initial Context Setup.
+    pub async fn handle_ue_context_setup_with_security_mode_command(
+        &self,
+        ue_context: &mut UeContext,
+    ) -> Result<SecurityModeCommand> {
+        let Some(rrc_container) = self.handle_ue_context_setup_inner(ue_context).await? else {
+            bail!("No Rrc container in UeContextSetupRequest")
+        };
+        ensure!(ue_context.drb.is_some(), "No Drbs supplied");
+        let message = rrc_from_container(rrc_container)?.message;
+        let DlDcchMessageType::C1(C1_2::SecurityModeCommand(security_mode_command)) = message else {
+            bail!("Expected security mode command - got {:?}", message)
+        };
+        info!(&self.logger, "SecurityModeCommand <<");
+        Ok(security_mode_command)
+    }
+
+    async fn handle_ue_context_setup_inner(
+        &self,
+        ue_context: &mut UeContext,
+    ) -> Result<Option<RrcContainer>> {
+        let ReceivedPdu { pdu, assoc_id, .. } = self.receive_pdu_with_assoc_id().await.unwrap();
         let ue_context_setup_request = self.check_ue_context_setup_request(pdu, ue_context)?;
         info!(&self.logger, "UeContextSetupRequest <<");
 
         ensure!(ue_context.drb.is_none());
-        let Some(drbs_to_be_setup_list) = ue_context_setup_request.drbs_to_be_setup_list else {
-            bail!("No Drbs supplied")
-        };
 
+        // For a resuming UE, this is the first message from the CU with the CU's UE ID.
+        ue_context.gnb_cu_ue_f1ap_id = Some(ue_context_setup_request.gnb_cu_ue_f1ap_id);
+
+        if let Some(drbs_to_be_setup_list) = &ue_context_setup_request.drbs_to_be_setup_list {
+            self.setup_drb(ue_context, drbs_to_be_setup_list)?;
+        }
+
+        let ue_context_setup_response = self.build_ue_context_setup_response(ue_context)?;
+        info!(&self.logger, "UeContextSetupResponse >>");
+        self.send(ue_context_setup_response, Some(assoc_id)).await;
+
+        Ok(ue_context_setup_request.rrc_container)
+    }
+
+    fn setup_drb(
+        &self,
+        ue_context: &mut UeContext,
+        drbs_to_be_setup_list: &DrbsToBeSetupList,
+    ) -> Result<()> {
         let first_drb = &drbs_to_be_setup_list.0[0];
         let first_tnl_of_first_drb = &first_drb.ul_up_tnl_information_to_be_setup_list.0[0];
         let UpTransportLayerInformation::GtpTunnel(remote_tunnel_info) =
@@ -335,11 +427,6 @@ impl MockDu {
             remote_tunnel_info: remote_tunnel_info.clone(),
             local_teid: GtpTeid(rand::thread_rng().gen::<[u8; 4]>()),
         });
-
-        let ue_context_setup_response = self.build_ue_context_setup_response(ue_context)?;
-        info!(&self.logger, "UeContextSetupResponse >>");
-        self.send(ue_context_setup_response, Some(assoc_id)).await;
-
         Ok(())
     }
 
@@ -365,9 +452,6 @@ impl MockDu {
         let Some(gnb_cu_ue_f1ap_id) = ue_context.gnb_cu_ue_f1ap_id else {
             bail!("CU F1AP ID should be set on UE");
         };
-        let Some(drb) = &ue_context.drb else {
-            bail!("Drb should be set on UE");
-        };
         let cell_group_config = f1ap::CellGroupConfig(make_rrc_cell_group_config().into_bytes()?);
         let transport_layer_address = TransportLayerAddress::try_from(&self.local_ip)?;
         Ok(F1apPdu::SuccessfulOutcome(
@@ -396,7 +480,73 @@ impl MockDu {
                 c_rnti: None,
                 resource_coordination_transfer_container: None,
                 full_configuration: None,
-                drbs_setup_list: Some(DrbsSetupList(nonempty![DrbsSetupItem {
+                drbs_setup_list: ue_context.drb.as_ref().map(|drb| {
+                    DrbsSetupList(nonempty![DrbsSetupItem {
+                        drb_id: drb.drb_id,
+                        lcid: None,
+                        dl_up_tnl_information_to_be_setup_list: DlUpTnlInformationToBeSetupList(
+                            nonempty![DlUpTnlInformationToBeSetupItem {
+                                dl_up_tnl_information: UpTransportLayerInformation::GtpTunnel(
+                                    GtpTunnel {
+                                        transport_layer_address,
+                                        gtp_teid: drb.local_teid.clone(),
+                                    },
+                                ),
+                            },]
+                        ),
+                        additional_pdcp_duplication_tnl_list: None,
+                        current_qos_para_set_index: None,
+                    }])
+                }),
+                srbs_failed_to_be_setup_list: None,
+                drbs_failed_to_be_setup_list: None,
+                s_cell_failedto_setup_list: None,
+                inactivity_monitoring_response: None,
+                criticality_diagnostics: None,
+                srbs_setup_list: None,
+                bh_channels_setup_list: None,
+                bh_channels_failed_to_be_setup_list: None,
+                sl_drbs_setup_list: None,
+                sl_drbs_failed_to_be_setup_list: None,
+                requested_target_cell_global_id: None,
+            }),
+        ))
+    }
+
+    pub async fn handle_ue_context_modification(&self, ue_context: &mut UeContext) -> Result<()> {
+        let ReceivedPdu { pdu, assoc_id, .. } = self.receive_pdu_with_assoc_id().await.unwrap();
+        let F1apPdu::InitiatingMessage(InitiatingMessage::UeContextModificationRequest(r)) = pdu
+        else {
+            bail!("Unexpected F1ap message {:?}", pdu)
+        };
+        info!(&self.logger, "UeContextModificationRequest <<");
+        ensure!(ue_context.ue_id == r.gnb_du_ue_f1ap_id.0);
+
+        let Some(drb) = &mut ue_context.drb else {
+            bail!("Drb should be set on UE");
+        };
+        let Some(drbs_to_be_modified_list) = r.drbs_to_be_modified_list else {
+            bail!("No Drbs to modify")
+        };
+        let drb_to_be_modified = &drbs_to_be_modified_list.0[0];
+        ensure!(drb_to_be_modified.drb_id.0 == drb.drb_id.0, "Unknown DRB");
+
+        // Pick up the uplink tunnel, in case it has changed.
+        let UpTransportLayerInformation::GtpTunnel(remote_tunnel_info) = &drb_to_be_modified
+            .ul_up_tnl_information_to_be_setup_list
+            .0[0]
+            .ul_up_tnl_information;
+        drb.remote_tunnel_info = remote_tunnel_info.clone();
+
+        let transport_layer_address = TransportLayerAddress::try_from(&self.local_ip)?;
+        let ue_context_modification_response = F1apPdu::SuccessfulOutcome(
+            SuccessfulOutcome::UeContextModificationResponse(UeContextModificationResponse {
+                gnb_cu_ue_f1ap_id: r.gnb_cu_ue_f1ap_id,
+                gnb_du_ue_f1ap_id: r.gnb_du_ue_f1ap_id,
+                resource_coordination_transfer_container: None,
+                du_to_cu_rrc_information: None,
+                drbs_setup_mod_list: None,
+                drbs_modified_list: Some(DrbsModifiedList(nonempty![DrbsModifiedItem {
                     drb_id: drb.drb_id,
                     lcid: None,
                     dl_up_tnl_information_to_be_setup_list: DlUpTnlInformationToBeSetupList(
@@ -409,36 +559,103 @@ impl MockDu {
                             ),
                         },]
                     ),
+                    rlc_status: None,
                     additional_pdcp_duplication_tnl_list: None,
                     current_qos_para_set_index: None,
                 }])),
-                srbs_failed_to_be_setup_list: None,
-                drbs_failed_to_be_setup_list: None,
-                s_cell_failedto_setup_list: None,
+                srbs_failed_to_be_setup_mod_list: None,
+                drbs_failed_to_be_setup_mod_list: None,
+                s_cell_failedto_setup_mod_list: None,
+                drbs_failed_to_be_modified_list: None,
                 inactivity_monitoring_response: None,
                 criticality_diagnostics: None,
-                srbs_setup_list: None,
-                bh_channels_setup_list: None,
-                bh_channels_failed_to_be_setup_list: None,
-                sl_drbs_setup_list: None,
-                sl_drbs_failed_to_be_setup_list: None,
+                c_rnti: None,
+                associated_s_cell_list: None,
+                srbs_setup_mod_list: None,
+                srbs_modified_list: None,
+                full_configuration: None,
+                bh_channels_setup_mod_list: None,
+                bh_channels_modified_list: None,
+                bh_channels_failed_to_be_setup_mod_list: None,
+                bh_channels_failed_to_be_modified_list: None,
+                sl_drbs_setup_mod_list: None,
+                sl_drbs_modified_list: None,
+                sl_drbs_failed_to_be_setup_mod_list: None,
+                sl_drbs_failed_to_be_modified_list: None,
                 requested_target_cell_global_id: None,
             }),
-        ))
+        );
+        info!(&self.logger, "UeContextModificationResponse >>");
+        self.send(ue_context_modification_response, Some(assoc_id))
+            .await;
+        Ok(())
     }
 
     pub async fn handle_ue_context_release(&self, ue_context: &UeContext) -> Result<()> {
-        // Receive release command
-        let ReceivedPdu { pdu, assoc_id } = self.receive_pdu_with_assoc_id().await.unwrap();
+        let (r, assoc_id) = self.receive_ue_context_release_command(ue_context).await?;
+        info!(&self.logger, "UeContextReleaseCommand <<");
+        self.send_ue_context_release_complete(r, assoc_id).await
+    }
+
+    pub async fn handle_ue_context_release_with_rrc_release(
+        &self,
+        ue_context: &UeContext,
+    ) -> Result<()> {
+        let (r, assoc_id) = self.receive_ue_context_release_command(ue_context).await?;
+        let _rrc_release = rrc_release_from_command(&r)?;
+        info!(&self.logger, "UeContextReleaseCommand(RrcRelease) <<");
+        self.send_ue_context_release_complete(r, assoc_id).await
+    }
+
+    pub async fn handle_ue_context_release_with_suspend(
+        &self,
+        ue_context: &mut UeContext,
+    ) -> Result<()> {
+        let (r, assoc_id) = self.receive_ue_context_release_command(ue_context).await?;
+        let RrcRelease {
+            critical_extensions:
+                CriticalExtensions18::RrcRelease(RrcReleaseIEs {
+                    suspend_config: Some(suspend_config),
+                    ..
+                }),
+            ..
+        } = rrc_release_from_command(&r)?
+        else {
+            bail!("Expected RRC Release with suspend config")
+        };
+        info!(
+            &self.logger,
+            "UeContextReleaseCommand(RrcRelease(SuspendConfig)) <<"
+        );
+
+        // The UE holds on to its I-RNTIs so that it can resume later, whereas the DU forgets it.
+        ue_context.i_rnti = Some(suspend_config.full_i_rnti);
+        ue_context.short_i_rnti = Some(suspend_config.short_i_rnti);
+        ue_context.gnb_cu_ue_f1ap_id = None;
+        ue_context.drb = None;
+
+        self.send_ue_context_release_complete(r, assoc_id).await
+    }
+
+    async fn receive_ue_context_release_command(
+        &self,
+        ue_context: &UeContext,
+    ) -> Result<(UeContextReleaseCommand, u32)> {
+        let ReceivedPdu { pdu, assoc_id, .. } = self.receive_pdu_with_assoc_id().await.unwrap();
         let F1apPdu::InitiatingMessage(InitiatingMessage::UeContextReleaseCommand(r)) = pdu
         else {
             bail!("Unexpected F1ap message {:?}", pdu)
         };
-        info!(&self.logger, "UeContextReleaseCommand <<");
 
         ensure!(ue_context.ue_id == r.gnb_du_ue_f1ap_id.0);
+        Ok((r, assoc_id))
+    }
 
-        // Send release complete
+    async fn send_ue_context_release_complete(
+        &self,
+        r: UeContextReleaseCommand,
+        assoc_id: u32,
+    ) -> Result<()> {
         let ue_context_release_complete = F1apPdu::SuccessfulOutcome(
             SuccessfulOutcome::UeContextReleaseComplete(UeContextReleaseComplete {
                 gnb_cu_ue_f1ap_id: r.gnb_cu_ue_f1ap_id,
@@ -453,6 +670,184 @@ impl MockDu {
         Ok(())
     }
 
+    pub async fn send_ue_context_release_request(&self, ue_context: &UeContext) -> Result<()> {
+        let Some(gnb_cu_ue_f1ap_id) = ue_context.gnb_cu_ue_f1ap_id else {
+            bail!("CU F1AP ID should be set on UE");
+        };
+        let pdu = F1apPdu::InitiatingMessage(InitiatingMessage::UeContextReleaseRequest(
+            UeContextReleaseRequest {
+                gnb_cu_ue_f1ap_id,
+                gnb_du_ue_f1ap_id: GnbDuUeF1apId(ue_context.ue_id),
+                cause: f1ap::Cause::RadioNetwork(f1ap::CauseRadioNetwork::RlFailureRlc),
+                target_cells_to_cancel: None,
+            },
+        ));
+        info!(&self.logger, "UeContextReleaseRequest >>");
+        self.send(pdu, Some(ue_context.binding.assoc_id)).await;
+        Ok(())
+    }
+
+    pub async fn send_ue_inactivity_notification(&self, ue_context: &UeContext) -> Result<()> {
+        let Some(gnb_cu_ue_f1ap_id) = ue_context.gnb_cu_ue_f1ap_id else {
+            bail!("CU F1AP ID should be set on UE");
+        };
+        let pdu = F1apPdu::InitiatingMessage(InitiatingMessage::UeInactivityNotification(
+            UeInactivityNotification {
+                gnb_cu_ue_f1ap_id,
+                gnb_du_ue_f1ap_id: GnbDuUeF1apId(ue_context.ue_id),
+                drb_activity_list: DrbActivityList(nonempty![DrbActivityItem {
+                    drb_id: DrbId(1),
+                    drb_activity: Some(DrbActivity::NotActive),
+                }]),
+            },
+        ));
+        info!(&self.logger, "UeInactivityNotification >>");
+        self.send(pdu, Some(ue_context.binding.assoc_id)).await;
+        Ok(())
+    }
+
+    pub async fn perform_rrc_resume(&self, ue_context: &mut UeContext) -> Result<()> {
+        self.perform_rrc_resume_inner(ue_context, true, None).await
+    }
+
+    // The UE sends a Nas message in its Rrc Resume Complete, for example because it resumed to send one.
+    pub async fn perform_rrc_resume_with_nas(
+        &self,
+        ue_context: &mut UeContext,
+        nas: Vec<u8>,
+    ) -> Result<()> {
+        self.perform_rrc_resume_inner(ue_context, true, Some(nas))
+            .await
+    }
+
+    // As for a cell whose SIB1 does not set useFullResumeID, so that the UE resumes with its short I-RNTI.
+    pub async fn perform_rrc_resume_with_short_i_rnti(
+        &self,
+        ue_context: &mut UeContext,
+    ) -> Result<()> {
+        self.perform_rrc_resume_inner(ue_context, false, None).await
+    }
+
+    async fn perform_rrc_resume_inner(
+        &self,
+        ue_context: &mut UeContext,
+        use_full_resume_id: bool,
+        nas: Option<Vec<u8>>,
+    ) -> Result<()> {
+        self.send_rrc_resume_request(ue_context, use_full_resume_id)
+            .await?;
+        let rrc_resume = self
+            .handle_ue_context_setup_with_rrc_resume(ue_context)
+            .await?;
+        self.send_rrc_resume_complete(ue_context, rrc_resume, nas)
+            .await?;
+        self.receive_rrc_reconfiguration_with_cell_group(ue_context)
+            .await?;
+        self.send_rrc_reconfiguration_complete(ue_context).await
+    }
+
+    async fn send_rrc_resume_request(
+        &self,
+        ue_context: &UeContext,
+        use_full_resume_id: bool,
+    ) -> Result<()> {
+        let (Some(i_rnti), Some(short_i_rnti)) =
+            (ue_context.i_rnti.clone(), ue_context.short_i_rnti.clone())
+        else {
+            bail!("UE has not been suspended");
+        };
+
+        // A UE that uses its full I-RNTI sends an RRC Resume Request 1 on UL-CCCH1, and otherwise
+        // an RRC Resume Request on UL-CCCH.  Integrity protection is NIA0, so the resumeMAC-I is zero.
+        let rrc_resume_request = if use_full_resume_id {
+            UlCcch1Message {
+                message: UlCcch1MessageType::C1(C1_5::RrcResumeRequest1(RrcResumeRequest1 {
+                    rrc_resume_request_1: RrcResumeRequest1IEs {
+                        resume_identity: i_rnti,
+                        resume_mac_i: bitvec![u8, Msb0; 0; 16],
+                        resume_cause: ResumeCause::MoData,
+                        spare: bitvec![u8, Msb0; 0; 1],
+                    },
+                })),
+            }
+            .into_bytes()?
+        } else {
+            UlCcchMessage {
+                message: UlCcchMessageType::C1(C1_4::RrcResumeRequest(RrcResumeRequest {
+                    rrc_resume_request: RrcResumeRequestIEs {
+                        resume_identity: short_i_rnti,
+                        resume_mac_i: bitvec![u8, Msb0; 0; 16],
+                        resume_cause: ResumeCause::MoData,
+                        spare: bitvec![u8, Msb0; 0; 1],
+                    },
+                })),
+            }
+            .into_bytes()?
+        };
+
+        let f1_indication = F1apPdu::InitiatingMessage(
+            InitiatingMessage::InitialUlRrcMessageTransfer(InitialUlRrcMessageTransfer {
+                gnb_du_ue_f1ap_id: GnbDuUeF1apId(ue_context.ue_id),
+                nr_cgi: make_nr_cgi(self.nr_cell_identity),
+                c_rnti: CRnti(14),
+                rrc_container: RrcContainer(rrc_resume_request),
+                du_to_cu_rrc_container: Some(make_du_to_cu_rrc_container()),
+                sul_access_indication: None,
+                transaction_id: Some(TransactionId(1)),
+                ran_ue_id: None,
+                rrc_container_rrc_setup_complete: None,
+            }),
+        );
+
+        info!(
+            &self.logger,
+            "InitialUlRrcMessageTransfer(RrcResumeRequest) >>"
+        );
+        self.send(f1_indication, Some(ue_context.binding.assoc_id))
+            .await;
+        Ok(())
+    }
+
+    // The CU sets up the UE's context afresh, with the Rrc Resume for the DU to pass on.
+    async fn handle_ue_context_setup_with_rrc_resume(
+        &self,
+        ue_context: &mut UeContext,
+    ) -> Result<RrcResume> {
+        let Some(rrc_container) = self.handle_ue_context_setup_inner(ue_context).await? else {
+            bail!("No Rrc container in UeContextSetupRequest")
+        };
+        let message = rrc_from_container(rrc_container)?.message;
+        let DlDcchMessageType::C1(C1_2::RrcResume(rrc_resume)) = message else {
+            bail!("Expected Rrc Resume - got {:?}", message)
+        };
+        info!(&self.logger, "RrcResume <<");
+        Ok(rrc_resume)
+    }
+
+    async fn send_rrc_resume_complete(
+        &self,
+        ue_context: &UeContext,
+        rrc_resume: RrcResume,
+        nas: Option<Vec<u8>>,
+    ) -> Result<()> {
+        let rrc_resume_complete = UlDcchMessage {
+            message: UlDcchMessageType::C1(C1_6::RrcResumeComplete(RrcResumeComplete {
+                rrc_transaction_identifier: rrc_resume.rrc_transaction_identifier,
+                critical_extensions: CriticalExtensions20::RrcResumeComplete(
+                    RrcResumeCompleteIEs {
+                        dedicated_nas_message: nas.map(DedicatedNasMessage),
+                        selected_plmn_identity: None,
+                        uplink_tx_direct_current_list: None,
+                        late_non_critical_extension: None,
+                        non_critical_extension: None,
+                    },
+                ),
+            })),
+        };
+        info!(&self.logger, "UlRrcMessageTransfer(RrcResumeComplete) >>");
+        self.send_ul_rrc(ue_context, rrc_resume_complete).await
+    }
+
     pub async fn send_security_mode_complete(
         &self,
         ue_context: &UeContext,
@@ -505,6 +900,33 @@ impl MockDu {
         Ok(nas_messages.head.0)
     }
 
+    async fn receive_rrc_reconfiguration_with_cell_group(
+        &self,
+        ue_context: &UeContext,
+    ) -> Result<()> {
+        let dl_rrc_message_transfer = self.receive_dl_rrc(ue_context).await?;
+        let DlDcchMessageType::C1(C1_2::RrcReconfiguration(RrcReconfiguration {
+            critical_extensions:
+                CriticalExtensions15::RrcReconfiguration(RrcReconfigurationIEs {
+                    non_critical_extension:
+                        Some(RrcReconfigurationV1530IEs {
+                            master_cell_group: Some(_),
+                            ..
+                        }),
+                    ..
+                }),
+            ..
+        })) = rrc_from_container(dl_rrc_message_transfer.rrc_container)?.message
+        else {
+            bail!("Expected Rrc Reconfiguration with cell group config")
+        };
+        info!(
+            &self.logger,
+            "DlRrcMessageTransfer(RrcReconfiguration(CellGroupConfig)) <<"
+        );
+        Ok(())
+    }
+
     pub async fn send_rrc_reconfiguration_complete(&self, ue_context: &UeContext) -> Result<()> {
         let rrc_reconfiguration_complete = UlDcchMessage {
             message: UlDcchMessageType::C1(C1_6::RrcReconfigurationComplete(
@@ -527,6 +949,37 @@ impl MockDu {
             .await
     }
 
+    pub async fn receive_paging(&self, five_g_tmsi: [u8; 4]) -> Result<()> {
+        let pdu = self.receive_pdu().await.unwrap();
+        let F1apPdu::InitiatingMessage(InitiatingMessage::Paging(paging)) = pdu
+        else {
+            bail!("Expected Paging, got {:?}", pdu)
+        };
+        info!(self.logger, "Paging <<");
+
+        // The 5G-TMSI is the last 32 bits of the 5G-S-TMSI.
+        let PagingIdentity::CnUePagingIdentity(CnUePagingIdentity::FiveGSTmsi(five_g_s_tmsi)) =
+            paging.paging_identity
+        else {
+            bail!("Expected CN UE paging identity")
+        };
+        ensure!(
+            five_g_s_tmsi.len() == 48 && five_g_s_tmsi[16..] == five_g_tmsi.view_bits::<Msb0>(),
+            "Wrong UE paged - {:?}",
+            five_g_s_tmsi
+        );
+
+        let served_cell = make_nr_cgi(self.nr_cell_identity);
+        ensure!(
+            paging.paging_cell_list.0.iter().any(|x| {
+                x.nr_cgi.plmn_identity.0 == served_cell.plmn_identity.0
+                    && x.nr_cgi.nr_cell_identity.0 == served_cell.nr_cell_identity.0
+            }),
+            "Paging does not include our cell"
+        );
+        Ok(())
+    }
+
     pub async fn handle_cu_configuration_update(
         &mut self,
         expected_addr_string: &str,
@@ -537,22 +990,53 @@ impl MockDu {
             .await?;
         let transport_address = format!("{}:{}", expected_addr_string, F1AP_BIND_PORT);
         info!(self.logger, "Connect to CU {}", transport_address);
-        self.connect(&transport_address, "0.0.0.0", F1AP_SCTP_PPID)
+        let bind_address = self.local_ip.clone();
+        self.connect(&transport_address, &bind_address, F1AP_SCTP_PPID)
             .await;
+        let gnb_cu_tnl_association_setup_list = GnbCuTnlAssociationSetupList(nonempty![
+            GnbCuTnlAssociationSetupItem {
+                tnl_association_transport_layer_address:
+                    CpTransportLayerAddress::EndpointIpAddress(expected_address),
+            },
+        ]);
         self.send_gnb_cu_configuration_update_acknowledge(
             transaction_id,
-            expected_address,
+            Some(gnb_cu_tnl_association_setup_list),
             assoc_id,
         )
         .await
     }
 
+    // Receive a GnbCuConfigurationUpdate that changes the state of our cells, acknowledge it, and return it
+    // so that the test can check it.
+    pub async fn handle_cell_configuration_update(&self) -> Result<GnbCuConfigurationUpdate> {
+        debug!(self.logger, "Wait for Cu Configuration Update");
+        let ReceivedPdu { pdu, assoc_id, .. } = self.receive_pdu_with_assoc_id().await.unwrap();
+
+        let F1apPdu::InitiatingMessage(InitiatingMessage::GnbCuConfigurationUpdate(cu_configuration_update)) = pdu
+        else {
+            bail!("Expected GnbCuConfigurationUpdate, got {:?}", pdu)
+        };
+        info!(self.logger, "GnbCuConfigurationUpdate <<");
+        ensure!(cu_configuration_update
+            .gnb_cu_tnl_association_to_add_list
+            .is_none());
+
+        self.send_gnb_cu_configuration_update_acknowledge(
+            cu_configuration_update.transaction_id,
+            None,
+            assoc_id,
+        )
+        .await?;
+        Ok(cu_configuration_update)
+    }
+
     async fn receive_gnb_cu_configuration_update(
         &self,
         expected_address: &TransportLayerAddress,
     ) -> Result<(TransactionId, u32)> {
         debug!(self.logger, "Wait for Cu Configuration Update");
-        let ReceivedPdu { pdu, assoc_id } = self.receive_pdu_with_assoc_id().await.unwrap();
+        let ReceivedPdu { pdu, assoc_id, .. } = self.receive_pdu_with_assoc_id().await.unwrap();
 
         let F1apPdu::InitiatingMessage(InitiatingMessage::GnbCuConfigurationUpdate(cu_configuration_update)) = pdu
         else {
@@ -582,7 +1066,7 @@ impl MockDu {
     async fn send_gnb_cu_configuration_update_acknowledge(
         &self,
         transaction_id: TransactionId,
-        transport_layer_address: TransportLayerAddress,
+        gnb_cu_tnl_association_setup_list: Option<GnbCuTnlAssociationSetupList>,
         assoc_id: u32,
     ) -> Result<()> {
         let pdu = f1ap::F1apPdu::SuccessfulOutcome(
@@ -591,12 +1075,7 @@ impl MockDu {
                     transaction_id,
                     cells_failed_to_be_activated_list: None,
                     criticality_diagnostics: None,
-                    gnb_cu_tnl_association_setup_list: Some(GnbCuTnlAssociationSetupList(
-                        nonempty![GnbCuTnlAssociationSetupItem {
-                            tnl_association_transport_layer_address:
-                                CpTransportLayerAddress::EndpointIpAddress(transport_layer_address),
-                        },],
-                    )),
+                    gnb_cu_tnl_association_setup_list,
                     gnb_cu_tnl_association_failed_to_setup_list: None,
                     dedicated_si_delivery_needed_ue_list: None,
                     transport_layer_address_info: None,
@@ -610,20 +1089,72 @@ impl MockDu {
     }
 
     pub async fn perform_du_configuration_update(&self) -> Result<()> {
-        self.send_gnb_du_configuration_update().await?;
-        self.receive_gnb_du_configuration_update_acknowledge().await
+        self.send_gnb_du_configuration_update(Some(GnbDuId(self.gnb_du_id)), None, None)
+            .await?;
+        self.receive_gnb_du_configuration_update_acknowledge().await?;
+        Ok(())
+    }
+
+    // Add a cell with the given NR cell identity, and check that the CU activates it.
+    pub async fn add_served_cell(&self, nr_cell_identity: u64) -> Result<()> {
+        self.add_served_cell_with_id(nr_cell_identity, Some(GnbDuId(self.gnb_du_id)))
+            .await
+    }
+
+    // As add_served_cell(), but leaving out the optional gNB-DU ID, so that the CU has to tell which DU
+    // this is from the TNLA.
+    pub async fn add_served_cell_without_gnb_du_id(&self, nr_cell_identity: u64) -> Result<()> {
+        self.add_served_cell_with_id(nr_cell_identity, None).await
+    }
+
+    async fn add_served_cell_with_id(
+        &self,
+        nr_cell_identity: u64,
+        gnb_du_id: Option<GnbDuId>,
+    ) -> Result<()> {
+        let served_cell_information = make_served_cell_information(nr_cell_identity);
+        let added = ServedCellsToAddList(nonempty![ServedCellsToAddItem {
+            served_cell_information,
+            gnb_du_system_information: None,
+        }]);
+        self.send_gnb_du_configuration_update(gnb_du_id, Some(added), None)
+            .await?;
+        let ack = self.receive_gnb_du_configuration_update_acknowledge().await?;
+        let Some(CellsToBeActivatedList(cells)) = ack.cells_to_be_activated_list else {
+            bail!("Added cell not activated")
+        };
+        let expected = make_nr_cgi(nr_cell_identity);
+        ensure!(cells.len() == 1);
+        ensure!(cells.head.nr_cgi.nr_cell_identity.0 == expected.nr_cell_identity.0);
+        ensure!(cells.head.gnb_cu_system_information.is_some());
+        Ok(())
     }
 
-    async fn send_gnb_du_configuration_update(&self) -> Result<()> {
+    pub async fn delete_served_cell(&self, nr_cell_identity: u64) -> Result<()> {
+        let deleted = ServedCellsToDeleteList(nonempty![ServedCellsToDeleteItem {
+            old_nr_cgi: make_nr_cgi(nr_cell_identity),
+        }]);
+        self.send_gnb_du_configuration_update(Some(GnbDuId(self.gnb_du_id)), None, Some(deleted))
+            .await?;
+        self.receive_gnb_du_configuration_update_acknowledge().await?;
+        Ok(())
+    }
+
+    async fn send_gnb_du_configuration_update(
+        &self,
+        gnb_du_id: Option<GnbDuId>,
+        served_cells_to_add_list: Option<ServedCellsToAddList>,
+        served_cells_to_delete_list: Option<ServedCellsToDeleteList>,
+    ) -> Result<()> {
         let pdu = f1ap::F1apPdu::InitiatingMessage(InitiatingMessage::GnbDuConfigurationUpdate(
             GnbDuConfigurationUpdate {
                 transaction_id: TransactionId(1),
-                served_cells_to_add_list: None,
+                served_cells_to_add_list,
                 served_cells_to_modify_list: None,
-                served_cells_to_delete_list: None,
+                served_cells_to_delete_list,
                 cells_status_list: None,
                 dedicated_si_delivery_needed_ue_list: None,
-                gnb_du_id: None,
+                gnb_du_id,
                 gnb_du_tnl_association_to_remove_list: None,
                 transport_layer_address_info: None,
             },
@@ -633,14 +1164,16 @@ impl MockDu {
         Ok(())
     }
 
-    async fn receive_gnb_du_configuration_update_acknowledge(&self) -> Result<()> {
+    async fn receive_gnb_du_configuration_update_acknowledge(
+        &self,
+    ) -> Result<GnbDuConfigurationUpdateAcknowledge> {
         let pdu = self.receive_pdu().await.unwrap();
-        let F1apPdu::SuccessfulOutcome(SuccessfulOutcome::GnbDuConfigurationUpdateAcknowledge(_)) = pdu
+        let F1apPdu::SuccessfulOutcome(SuccessfulOutcome::GnbDuConfigurationUpdateAcknowledge(ack)) = pdu
         else {
             bail!("Unexpected F1ap message {:?}", pdu)
         };
         info!(self.logger, "GnbDuConfigurationUpdateAcknowledge <<");
-        Ok(())
+        Ok(ack)
     }
 
     pub async fn send_data_packet(&self, ue_context: &UeContext) -> Result<()> {
@@ -668,6 +1201,53 @@ impl MockDu {
     }
 }
 
+// A single TDD cell with TAC 1 in PLMN 0-1-2.
+fn make_served_cell_information(nr_cell_identity: u64) -> ServedCellInformation {
+    ServedCellInformation {
+        nr_cgi: make_nr_cgi(nr_cell_identity),
+        nr_pci: NrPci(1),
+        five_gs_tac: Some(FiveGsTac([0, 0, 1])),
+        configured_eps_tac: None,
+        served_plmns: ServedPlmnsList(nonempty![ServedPlmnsItem {
+            plmn_identity: PlmnIdentity([0, 1, 2]),
+            tai_slice_support_list: None,
+            npn_support_info: None,
+            extended_tai_slice_support_list: None,
+        }]),
+        nr_mode_info: NrModeInfo::Tdd(TddInfo {
+            nr_freq_info: f1ap::NrFreqInfo {
+                nr_arfcn: 632628,
+                sul_information: None,
+                freq_band_list_nr: nonempty![FreqBandNrItem {
+                    freq_band_indicator_nr: 78,
+                    supported_sul_band_list: vec![],
+                }],
+                frequency_shift7p5khz: None,
+            },
+            transmission_bandwidth: TransmissionBandwidth {
+                nr_scs: NrScs::Scs30,
+                nr_nr_b: NrNrB::Nrb273,
+            },
+            intended_tdd_dl_ul_config: None,
+            tdd_ul_dl_config_common_nr: None,
+            carrier_list: None,
+        }),
+        measurement_timing_configuration: vec![],
+        ranac: None,
+        extended_served_plmns_list: None,
+        cell_direction: None,
+        b_plmn_id_info_list: None,
+        cell_type: None,
+        configured_tac_indication: None,
+        aggressor_gnb_set_id: None,
+        victim_gnb_set_id: None,
+        iab_info_iab_du: None,
+        ssb_positions_in_burst: None,
+        nr_prach_config: None,
+        sfn_offset: None,
+    }
+}
+
 fn make_rrc_cell_group_config() -> rrc::CellGroupConfig {
     rrc::CellGroupConfig {
         cell_group_id: CellGroupId(1),
@@ -694,6 +1274,19 @@ fn rrc_from_container(rrc_container: RrcContainer) -> Result<DlDcchMessage> {
     Ok(m)
 }
 
+fn rrc_release_from_command(r: &UeContextReleaseCommand) -> Result<RrcRelease> {
+    // The RRC Release is a DlDcchMessage and so must be on SRB1.
+    ensure!(matches!(r.srb_id, Some(SrbId(1))));
+    let Some(rrc_container) = r.rrc_container.clone() else {
+        bail!("Expected RRC container on UeContextReleaseCommand")
+    };
+    let message = rrc_from_container(rrc_container)?.message;
+    let DlDcchMessageType::C1(C1_2::RrcRelease(rrc_release)) = message else {
+        bail!("Expected RRC Release - got {:?}", message)
+    };
+    Ok(rrc_release)
+}
+
 fn nas_from_dl_transfer_rrc_container(rrc_container: RrcContainer) -> Result<Vec<u8>> {
     match rrc_from_container(rrc_container)?.message {
         DlDcchMessageType::C1(C1_2::DlInformationTransfer(DlInformationTransfer {
@@ -707,3 +1300,13 @@ fn nas_from_dl_transfer_rrc_container(rrc_container: RrcContainer) -> Result<Vec
         x => Err(anyhow!("Unexpected RRC message {:?}", x)),
     }
 }
+
+// The CGI of a cell in PLMN 0-1-2, given its 36 bit NR cell identity.
+fn make_nr_cgi(nr_cell_identity: u64) -> NrCgi {
+    NrCgi {
+        plmn_identity: PlmnIdentity([0, 1, 2]),
+        nr_cell_identity: NrCellIdentity(
+            nr_cell_identity.to_be_bytes().view_bits::<Msb0>()[28..].to_bitvec(),
+        ),
+    }
+}
@@ -0,0 +1,441 @@
+//! channel_transport_provider - in-process TransportProvider that carries messages over channels instead of SCTP
+
+use crate::tnla_event_handler::{TnlaEvent, TnlaEventHandler};
+use crate::transport_provider::{ue_stream_id, AssocId, Binding, OutboundStream};
+use crate::ue_bindings::UeBindings;
+use crate::{SendQueueStats, ShutdownHandle, TransportProvider};
+use anyhow::{anyhow, bail, ensure, Result};
+use async_channel::{Receiver, Sender};
+use async_std::sync::{Arc, Mutex};
+use async_std::task;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use futures::pin_mut;
+use futures::stream::StreamExt;
+use sctp::{Message, SctpConfig, StreamId};
+use slog::{info, Logger};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU16, AtomicU32, Ordering};
+use std::sync::OnceLock;
+use stop_token::{StopSource, StopToken};
+
+// The number of messages that can be in flight in each direction of a TNLA before the sender waits.
+const CHANNEL_SIZE: usize = 256;
+
+// The number of streams that UEs are spread over, as for an SCTP association with default configuration.
+const NUM_STREAMS: u16 = 16;
+
+/// A TransportProvider for components in the same process, such as integration tests that need to run
+/// without kernel SCTP support, or co-located components that have no need of a socket between them.
+/// serve() registers the listen address in a process wide table, and connect() to that address links
+/// the two providers by a pair of channels.  Addresses are written as for SCTP, but nothing is bound.
+#[derive(Clone, Default)]
+pub struct ChannelTransportProvider {
+    tnlas: Arc<DashMap<AssocId, ChannelTnla>>,
+    tasks: Arc<Mutex<Vec<ShutdownHandle>>>,
+    next_ue_stream: Arc<AtomicU32>,
+    ue_bindings: UeBindings,
+}
+
+#[derive(Clone)]
+struct ChannelTnla {
+    remote_address: SocketAddr,
+    sender: Sender<(Message, StreamId)>,
+}
+
+// Handed to a listener by connect().
+struct Connection {
+    remote_address: SocketAddr,
+    sender: Sender<(Message, StreamId)>,
+    receiver: Receiver<(Message, StreamId)>,
+}
+
+// Listeners, by listen address.
+fn listeners() -> &'static DashMap<SocketAddr, Sender<Connection>> {
+    static LISTENERS: OnceLock<DashMap<SocketAddr, Sender<Connection>>> = OnceLock::new();
+    LISTENERS.get_or_init(DashMap::new)
+}
+
+// Association IDs are unique across the process, as they are for SCTP.
+static NEXT_ASSOC_ID: AtomicU32 = AtomicU32::new(1);
+
+// Stands in for the ephemeral port of the connecting end.
+static NEXT_PORT: AtomicU16 = AtomicU16::new(32768);
+
+impl ChannelTransportProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    async fn add_and_handle<H>(
+        &self,
+        remote_address: SocketAddr,
+        sender: Sender<(Message, StreamId)>,
+        receiver: Receiver<(Message, StreamId)>,
+        handler: H,
+        logger: Logger,
+    ) where
+        H: TnlaEventHandler,
+    {
+        let assoc_id = NEXT_ASSOC_ID.fetch_add(1, Ordering::Relaxed);
+        self.tnlas.insert(
+            assoc_id,
+            ChannelTnla {
+                remote_address,
+                sender,
+            },
+        );
+        let stop_source = StopSource::new();
+        let stop_token = stop_source.token();
+        let self_clone = self.clone();
+        let shutdown_handle = ShutdownHandle::new(
+            task::spawn(async move {
+                self_clone
+                    .handle_tnla(
+                        assoc_id,
+                        remote_address,
+                        receiver,
+                        handler,
+                        stop_token,
+                        logger,
+                    )
+                    .await;
+            }),
+            stop_source,
+        );
+        self.tasks.lock().await.push(shutdown_handle);
+    }
+
+    async fn handle_tnla<H>(
+        &self,
+        assoc_id: AssocId,
+        remote_address: SocketAddr,
+        receiver: Receiver<(Message, StreamId)>,
+        handler: H,
+        stop_token: StopToken,
+        logger: Logger,
+    ) where
+        H: TnlaEventHandler,
+    {
+        handler
+            .handle_event(TnlaEvent::Established(remote_address), assoc_id, &logger)
+            .await;
+
+        // The stream ends when the peer drops its sender, or we are stopped.
+        let message_stream = receiver.take_until(stop_token);
+        pin_mut!(message_stream);
+        while let Some((message, stream_id)) = message_stream.next().await {
+            handler
+                .handle_message(message, assoc_id, stream_id, &logger)
+                .await;
+        }
+
+        // Dropping our sender ends the peer's stream in turn.
+        self.tnlas.remove(&assoc_id);
+        self.ue_bindings.remove_assoc(assoc_id);
+        handler
+            .handle_event(TnlaEvent::Terminated, assoc_id, &logger)
+            .await;
+    }
+
+    fn binding(&self, assoc_id: AssocId, seed: u32) -> Result<Binding> {
+        let Some(tnla) = self.tnlas.get(&assoc_id) else {
+            bail!("No such association")
+        };
+        Ok(Binding {
+            assoc_id,
+            remote_ip: tnla.remote_address.ip().to_string(),
+            stream_id: ue_stream_id(seed, NUM_STREAMS),
+        })
+    }
+
+    fn remote_ips(&self) -> Vec<(AssocId, IpAddr)> {
+        self.tnlas
+            .iter()
+            .map(|x| (*x.key(), x.value().remote_address.ip()))
+            .collect()
+    }
+}
+
+fn parse_all(addr_string: &str) -> Result<Vec<SocketAddr>> {
+    addr_string
+        .split(',')
+        .map(|x| Ok(x.trim().parse()?))
+        .collect()
+}
+
+#[async_trait]
+impl TransportProvider for ChannelTransportProvider {
+    fn new_with_config(_config: SctpConfig) -> Self {
+        Self::new()
+    }
+
+    async fn send_message(
+        &self,
+        message: Message,
+        assoc_id: Option<u32>,
+        stream: OutboundStream,
+        _logger: &Logger,
+    ) -> Result<()> {
+        let Some(tnla) = assoc_id
+            .and_then(|x| self.tnlas.get(&x).map(|x| x.clone()))
+            .or_else(|| {
+                let id = self.tnlas.iter().map(|x| *x.key()).min()?;
+                self.tnlas.get(&id).map(|x| x.clone())
+            })
+        else {
+            bail!("No association found")
+        };
+        tnla.sender
+            .send((message, stream.stream_id(NUM_STREAMS)))
+            .await
+            .map_err(|_| anyhow!("Association closed"))
+    }
+
+    async fn serve<H>(
+        self,
+        listen_addr: String,
+        _ppid: u32,
+        handler: H,
+        logger: Logger,
+    ) -> Result<ShutdownHandle>
+    where
+        H: TnlaEventHandler,
+    {
+        let addrs = parse_all(&listen_addr)?;
+        let (connection_sender, connection_receiver) = async_channel::unbounded();
+        for addr in addrs.iter() {
+            ensure!(
+                !listeners().contains_key(addr),
+                "Address {} already in use",
+                addr
+            );
+        }
+        for addr in addrs.iter() {
+            listeners().insert(*addr, connection_sender.clone());
+        }
+
+        let stop_source = StopSource::new();
+        let stream = connection_receiver.take_until(stop_source.token());
+        let join_handle = task::spawn(async move {
+            pin_mut!(stream);
+            while let Some(connection) = stream.next().await {
+                self.add_and_handle(
+                    connection.remote_address,
+                    connection.sender,
+                    connection.receiver,
+                    handler.clone(),
+                    logger.clone(),
+                )
+                .await;
+            }
+            info!(logger, "End listen {}", listen_addr);
+            for addr in addrs.iter() {
+                listeners().remove(addr);
+            }
+            self.graceful_shutdown().await;
+        });
+        Ok(ShutdownHandle::new(join_handle, stop_source))
+    }
+
+    async fn connect<H>(
+        self,
+        connect_addr_string: &str,
+        bind_addr_string: &str,
+        _ppid: u32,
+        handler: H,
+        logger: Logger,
+    ) -> Result<()>
+    where
+        H: TnlaEventHandler,
+    {
+        let connect_addr = parse_all(connect_addr_string)?[0];
+        let bind_ip = bind_addr_string
+            .split(',')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .parse()?;
+        let local_addr = SocketAddr::new(bind_ip, NEXT_PORT.fetch_add(1, Ordering::Relaxed));
+        let Some(listener) = listeners().get(&connect_addr).map(|x| x.clone()) else {
+            bail!("Connection to {} refused", connect_addr)
+        };
+
+        let (sender, peer_receiver) = async_channel::bounded(CHANNEL_SIZE);
+        let (peer_sender, receiver) = async_channel::bounded(CHANNEL_SIZE);
+        listener
+            .send(Connection {
+                remote_address: local_addr,
+                sender: peer_sender,
+                receiver: peer_receiver,
+            })
+            .await
+            .map_err(|_| anyhow!("Connection to {} refused", connect_addr))?;
+        self.add_and_handle(connect_addr, sender, receiver, handler, logger)
+            .await;
+        Ok(())
+    }
+
+    async fn new_ue_binding(&self, seed: u32) -> Result<Binding> {
+        let Some(assoc_id) = self.ue_bindings.select(seed, &self.remote_ips()) else {
+            bail!("No associations up")
+        };
+        self.binding(assoc_id, seed)
+    }
+
+    async fn new_ue_binding_from_assoc(&self, assoc_id: &AssocId) -> Result<Binding> {
+        let seed = self.next_ue_stream.fetch_add(1, Ordering::Relaxed);
+        self.binding(*assoc_id, seed)
+    }
+
+    async fn new_ue_binding_from_ip(&self, ip_addr: &str) -> Result<Binding> {
+        let Some(assoc_id) = self
+            .tnlas
+            .iter()
+            .find(|x| x.value().remote_address.ip().to_string() == ip_addr)
+            .map(|x| *x.key())
+        else {
+            bail!("No such remote ip addr")
+        };
+        self.new_ue_binding_from_assoc(&assoc_id).await
+    }
+
+    async fn ue_binding(&self, ue_key: u32) -> Result<Binding> {
+        let Some(assoc_id) = self.ue_bindings.assoc_for(ue_key, &self.remote_ips()) else {
+            bail!("No associations up")
+        };
+        self.binding(assoc_id, ue_key)
+    }
+
+    async fn explicit_ue_binding(&self, ue_key: u32) -> Result<Binding> {
+        let Some(assoc_id) = self.ue_bindings.bound_assoc(ue_key, &self.remote_ips()) else {
+            bail!("UE not bound to an association that is up")
+        };
+        self.binding(assoc_id, ue_key)
+    }
+
+    async fn bind_ue(&self, ue_key: u32, assoc_id: AssocId) -> Result<()> {
+        ensure!(self.tnlas.contains_key(&assoc_id), "No such association");
+        self.ue_bindings.bind(ue_key, assoc_id);
+        Ok(())
+    }
+
+    async fn is_ue_bound(&self, ue_key: u32) -> bool {
+        self.ue_bindings.is_bound(ue_key)
+    }
+
+    async fn unbind_ue(&self, ue_key: u32) {
+        self.ue_bindings.unbind(ue_key)
+    }
+
+    async fn set_tnla_weight(&self, remote_ip: IpAddr, weight: u8) {
+        self.ue_bindings.set_weight(remote_ip, weight)
+    }
+
+    async fn remote_tnla_addresses(&self) -> Vec<(AssocId, SocketAddr)> {
+        self.tnlas
+            .iter()
+            .map(|x| (*x.key(), x.value().remote_address))
+            .collect()
+    }
+
+    // A message goes straight onto the channel, so there is no send queue.
+    async fn send_queue_stats(&self) -> Vec<(AssocId, SendQueueStats)> {
+        Vec::new()
+    }
+
+    async fn graceful_shutdown(self) {
+        for task in self.tasks.lock().await.drain(..) {
+            task.graceful_shutdown().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::o;
+
+    #[derive(Clone)]
+    struct Handler(Sender<(AssocId, Option<Message>)>);
+
+    #[async_trait]
+    impl TnlaEventHandler for Handler {
+        async fn handle_event(&self, event: TnlaEvent, tnla_id: u32, _logger: &Logger) {
+            if let TnlaEvent::Terminated = event {
+                self.0.send((tnla_id, None)).await.unwrap();
+            }
+        }
+
+        async fn handle_message(
+            &self,
+            message: Message,
+            tnla_id: u32,
+            _stream_id: StreamId,
+            _logger: &Logger,
+        ) {
+            self.0.send((tnla_id, Some(message))).await.unwrap();
+        }
+    }
+
+    #[async_std::test]
+    async fn connect_send_and_terminate() -> Result<()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let (server_sender, server_events) = async_channel::unbounded();
+        let (client_sender, client_events) = async_channel::unbounded();
+
+        let server = ChannelTransportProvider::new();
+        let listener = server
+            .clone()
+            .serve(
+                "127.0.0.1:38472".to_string(),
+                0,
+                Handler(server_sender),
+                logger.clone(),
+            )
+            .await?;
+        let client = ChannelTransportProvider::new();
+        client
+            .clone()
+            .connect(
+                "127.0.0.1:38472",
+                "127.0.0.2",
+                0,
+                Handler(client_sender),
+                logger.clone(),
+            )
+            .await?;
+
+        // Client to server.
+        client
+            .send_message(vec![1, 2], None, OutboundStream::Id(0), &logger)
+            .await?;
+        let (server_assoc_id, message) = server_events.recv().await?;
+        assert_eq!(message, Some(vec![1, 2]));
+        let remote_addresses = server.remote_tnla_addresses().await;
+        assert_eq!(remote_addresses.len(), 1);
+        assert_eq!(remote_addresses[0].1.ip().to_string(), "127.0.0.2");
+
+        // Server to client, on the association that the message came in on.
+        server
+            .send_message(
+                vec![3],
+                Some(server_assoc_id),
+                OutboundStream::Id(0),
+                &logger,
+            )
+            .await?;
+        let (_, message) = client_events.recv().await?;
+        assert_eq!(message, Some(vec![3]));
+
+        // Shutting down the server terminates the TNLA at both ends.
+        listener.graceful_shutdown().await;
+        assert_eq!(server_events.recv().await?, (server_assoc_id, None));
+        assert_eq!(client_events.recv().await?.1, None);
+        assert!(client
+            .send_message(vec![4], None, OutboundStream::Id(0), &logger)
+            .await
+            .is_err());
+        Ok(())
+    }
+}
@@ -1,14 +1,21 @@
+mod channel_transport_provider;
 mod sctp_tnla_pool;
 mod sctp_transport_provider;
+mod send_queue;
 mod stack;
 mod tnla_event_handler;
 mod transport_provider;
+mod ue_bindings;
 pub use asn1_per::{
     Indication, IndicationHandler, Procedure, RequestError, RequestProvider, ResponseAction, SerDes,
 };
+pub use channel_transport_provider::ChannelTransportProvider;
 pub use common::ShutdownHandle;
-pub use sctp::Message;
+pub use sctp::{Message, PeerAddrState, SctpConfig, SctpProtocolParameters, StreamId};
 pub use sctp_transport_provider::SctpTransportProvider;
-pub use stack::{Application, EventHandler, Stack};
+pub use send_queue::SendQueueStats;
+pub use stack::{Application, EventHandler, Stack, StackConfig};
 pub use tnla_event_handler::*;
-pub use transport_provider::{Binding, TransportProvider};
+pub use transport_provider::{
+    stream_for_pdu, ue_stream_id, Binding, OutboundStream, TransportProvider, NON_UE_STREAM_ID,
+};
@@ -1,8 +1,10 @@
 //! sctp_tnla_pool - global connection pool enabling a suitable TNLA to be selected for an outgoing message
 
 use crate::{
+    send_queue::{SendQueue, SendQueueStats},
     tnla_event_handler::{TnlaEvent, TnlaEventHandler},
-    transport_provider::{AssocId, Binding},
+    transport_provider::{ue_stream_id, AssocId, Binding, OutboundStream},
+    ue_bindings::UeBindings,
 };
 use anyhow::{bail, ensure, Result};
 use async_std::sync::{Arc, Mutex};
@@ -10,23 +12,40 @@ use common::ShutdownHandle;
 use dashmap::DashMap;
 use futures::pin_mut;
 use futures::stream::StreamExt;
-use sctp::{Message, SctpAssociation};
-use slog::Logger;
-use std::net::SocketAddr;
+use sctp::{
+    AssocChangeState, Message, MessageTooBig, Notification, PeerAddrState, Received,
+    SctpAssociation,
+};
+use slog::{debug, warn, Logger};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU32, Ordering};
 use stop_token::{StopSource, StopToken};
-type SharedAssocHash = Arc<DashMap<AssocId, Arc<SctpAssociation>>>;
+type SharedAssocHash = Arc<DashMap<AssocId, Tnla>>;
 
 #[derive(Clone)]
 pub struct SctpTnlaPool {
     assocs: SharedAssocHash,
     tasks: Arc<Mutex<Vec<ShutdownHandle>>>,
+    next_ue_stream: Arc<AtomicU32>,
+    send_queue_size: usize,
+    ue_bindings: UeBindings,
+}
+
+// An association and the queue of messages waiting to be sent on it.
+#[derive(Clone)]
+struct Tnla {
+    assoc: Arc<SctpAssociation>,
+    send_queue: Arc<SendQueue>,
 }
 
 impl SctpTnlaPool {
-    pub fn new() -> SctpTnlaPool {
+    pub fn new(send_queue_size: usize) -> SctpTnlaPool {
         SctpTnlaPool {
             assocs: Arc::new(DashMap::new()),
             tasks: Arc::new(Mutex::new(Vec::new())),
+            next_ue_stream: Arc::new(AtomicU32::new(0)),
+            send_queue_size,
+            ue_bindings: UeBindings::default(),
         }
     }
 
@@ -39,47 +58,110 @@ impl SctpTnlaPool {
     pub async fn remote_addresses(&self) -> Vec<(AssocId, SocketAddr)> {
         self.assocs
             .iter()
-            .map(|x| (*x.key(), x.value().remote_address))
+            .map(|x| (*x.key(), x.value().assoc.remote_address))
+            .collect()
+    }
+
+    pub fn send_queue_stats(&self) -> Vec<(AssocId, SendQueueStats)> {
+        self.assocs
+            .iter()
+            .map(|x| (*x.key(), x.value().send_queue.stats()))
+            .collect()
+    }
+
+    /// Picks a new binding (association and stream ID).  The same seed gets the same association for as long as
+    /// that association is up, however others come and go.  To load balance among associations and streams,
+    /// use a different seed.
+    pub fn new_ue_binding(&self, seed: u32) -> Result<Binding> {
+        let Some(assoc_id) = self.ue_bindings.select(seed, &self.remote_ips()) else {
+            bail!("No associations up")
+        };
+        self.binding(assoc_id, seed)
+    }
+
+    /// Returns the UE's binding.  The stream is derived from the UE key, so that it does not change either.
+    pub fn ue_binding(&self, ue_key: u32) -> Result<Binding> {
+        let Some(assoc_id) = self.ue_bindings.assoc_for(ue_key, &self.remote_ips()) else {
+            bail!("No associations up")
+        };
+        self.binding(assoc_id, ue_key)
+    }
+
+    /// Returns the binding of a UE that has been bound to an association that is still up.
+    pub fn explicit_ue_binding(&self, ue_key: u32) -> Result<Binding> {
+        let Some(assoc_id) = self.ue_bindings.bound_assoc(ue_key, &self.remote_ips()) else {
+            bail!("UE not bound to an association that is up")
+        };
+        self.binding(assoc_id, ue_key)
+    }
+
+    pub fn bind_ue(&self, ue_key: u32, assoc_id: AssocId) -> Result<()> {
+        ensure!(self.assocs.contains_key(&assoc_id), "No such association");
+        self.ue_bindings.bind(ue_key, assoc_id);
+        Ok(())
+    }
+
+    pub fn is_ue_bound(&self, ue_key: u32) -> bool {
+        self.ue_bindings.is_bound(ue_key)
+    }
+
+    pub fn unbind_ue(&self, ue_key: u32) {
+        self.ue_bindings.unbind(ue_key)
+    }
+
+    pub fn set_tnla_weight(&self, remote_ip: IpAddr, weight: u8) {
+        self.ue_bindings.set_weight(remote_ip, weight)
+    }
+
+    fn remote_ips(&self) -> Vec<(AssocId, IpAddr)> {
+        self.assocs
+            .iter()
+            .map(|x| (*x.key(), x.value().assoc.remote_address.ip()))
             .collect()
     }
 
-    /// Picks a new binding (association and in future stream ID).  
-    /// To load balance among different associations, use a different seed.
-    pub async fn new_ue_binding(&self, seed: u32) -> Result<Binding> {
-        let len = self.assocs.len();
-        ensure!(len > 0, "No associations up");
-        let nth = seed as usize % len;
-        let item = self.assocs.iter().nth(nth).unwrap();
+    fn binding(&self, assoc_id: AssocId, seed: u32) -> Result<Binding> {
+        let Some(tnla) = self.assocs.get(&assoc_id) else {
+            bail!("No such association")
+        };
+        let assoc = &tnla.assoc;
         Ok(Binding {
-            assoc_id: *item.key(),
-            remote_ip: item.value().remote_address.ip().to_string(),
+            assoc_id,
+            remote_ip: assoc.remote_address.ip().to_string(),
+            stream_id: ue_stream_id(seed, assoc.num_outbound_streams),
         })
     }
 
+    /// Binds to the given association.  The UE streams of the association are handed out in turn.
     pub async fn new_ue_binding_from_assoc(&self, assoc_id: &AssocId) -> Result<Binding> {
-        if let Some(assoc) = self.assocs.get(assoc_id) {
-            Ok(Binding {
-                assoc_id: *assoc_id,
-                remote_ip: assoc.remote_address.ip().to_string(),
-            })
-        } else {
-            bail!("No such association")
-        }
+        let seed = self.next_ue_stream.fetch_add(1, Ordering::Relaxed);
+        self.binding(*assoc_id, seed)
     }
 
     pub async fn send_message(
         &self,
         message: Message,
         assoc_id: Option<u32>,
-        _logger: &Logger,
+        stream: OutboundStream,
+        logger: &Logger,
     ) -> Result<()> {
-        let Some(assoc) = assoc_id
-            .and_then(|x| self.assocs.get(&x).map(|x| x.clone()))
-            .or(self.assocs.iter().next().map(|x| x.clone()))
+        // Otherwise use the association with the lowest ID, so that non UE-associated signalling sticks to one
+        // association rather than following the hash map's iteration order.
+        let Some((assoc_id, tnla)) = assoc_id
+            .and_then(|id| self.assocs.get(&id).map(|x| (id, x.clone())))
+            .or_else(|| {
+                let id = self.assocs.iter().map(|x| *x.key()).min()?;
+                self.assocs.get(&id).map(|x| (id, x.clone()))
+            })
         else {
             bail!("No association found")
         };
-        assoc.send_msg(message).await
+
+        // The peer may have negotiated fewer streams than the caller had in mind.
+        let stream_id = stream.stream_id(tnla.assoc.num_outbound_streams);
+        tnla.send_queue
+            .send(message, stream_id, assoc_id, logger)
+            .await
     }
 
     pub async fn add_and_handle<H>(
@@ -94,7 +176,14 @@ impl SctpTnlaPool {
         let stop_source = StopSource::new();
         let stop_token = stop_source.token();
         let self_clone = self.clone();
-        self.assocs.insert(assoc_id, assoc.clone());
+        let send_queue = Arc::new(SendQueue::new(assoc.clone(), self.send_queue_size));
+        self.assocs.insert(
+            assoc_id,
+            Tnla {
+                assoc: assoc.clone(),
+                send_queue,
+            },
+        );
         let shutdown_handle = ShutdownHandle::new(
             async_std::task::spawn(async move {
                 self_clone
@@ -129,18 +218,19 @@ impl SctpTnlaPool {
         pin_mut!(message_stream);
         loop {
             match message_stream.next().await {
-                // TODO - this does not implement graceful shutdown.  For one thing, the Terminated events below
-                // may overtake message events.  For another, graceful shutdown implies that procedures currently underway
-                // will allowed to complete sucessfully, which means we need to keep receiving on the association until
-                // they are complete.  But above, we just cut off the stream as soon as the stop token fired.
-
-                // Local shutdown
+                // Local shutdown.  For this to be graceful, the user drains its Stack first, so that we keep receiving
+                // until the procedures under way are complete, and only then fire the stop token.  The association is
+                // closed once its send queue has been emptied.
                 None => {
                     handler
                         .handle_event(TnlaEvent::Terminated, assoc_id, &logger)
                         .await;
                     break;
                 }
+                // Received a message that was too big - it has been discarded
+                Some(Err(e)) if e.is::<MessageTooBig>() => {
+                    warn!(logger, "{} on assoc {}", e, assoc_id)
+                }
                 // Remote end terminated connection
                 Some(Err(_)) => {
                     handler
@@ -149,13 +239,73 @@ impl SctpTnlaPool {
                     break;
                 }
                 // Received a message
-                Some(Ok(message)) => {
+                Some(Ok(Received::Message(message, stream_id))) => {
                     //debug!(logger, "Received message on assoc {}", assoc_id);
-                    handler.handle_message(message, assoc_id, &logger).await
+                    handler
+                        .handle_message(message, assoc_id, stream_id, &logger)
+                        .await
+                }
+                // Path status change
+                Some(Ok(Received::Notification(Notification::PeerAddrChange(addr, state)))) => {
+                    debug!(
+                        logger,
+                        "Peer address {} {:?} on assoc {}", addr, state, assoc_id
+                    );
+                    if let Some(event) = path_event(addr, state) {
+                        handler.handle_event(event, assoc_id, &logger).await
+                    }
+                }
+                // Association state change
+                Some(Ok(Received::Notification(Notification::AssocChange(state)))) => {
+                    debug!(logger, "Assoc {} {:?}", assoc_id, state);
+                    match state {
+                        AssocChangeState::CommUp => {}
+                        AssocChangeState::Restart => {
+                            handler
+                                .handle_event(TnlaEvent::Restarted, assoc_id, &logger)
+                                .await
+                        }
+                        AssocChangeState::CommLost
+                        | AssocChangeState::ShutdownComplete
+                        | AssocChangeState::CantStartAssoc => {
+                            handler
+                                .handle_event(TnlaEvent::Terminated, assoc_id, &logger)
+                                .await;
+                            break;
+                        }
+                    }
+                }
+                // Peer is shutting down the association
+                Some(Ok(Received::Notification(Notification::Shutdown))) => {
+                    handler
+                        .handle_event(TnlaEvent::PeerShutdown, assoc_id, &logger)
+                        .await
+                }
+                // A message we sent was not delivered
+                Some(Ok(Received::Notification(Notification::SendFailed { stream_id, error }))) => {
+                    debug!(
+                        logger,
+                        "Send failed on assoc {} stream {} - error {}", assoc_id, stream_id, error
+                    );
+                    handler
+                        .handle_event(TnlaEvent::SendFailed(stream_id), assoc_id, &logger)
+                        .await
                 }
             }
         }
 
+        // UEs bound to this association are unbound.
         self.assocs.remove(&assoc_id);
+        self.ue_bindings.remove_assoc(assoc_id);
+    }
+}
+
+fn path_event(addr: SocketAddr, state: PeerAddrState) -> Option<TnlaEvent> {
+    match state {
+        PeerAddrState::Available | PeerAddrState::Confirmed => Some(TnlaEvent::PathUp(addr)),
+        PeerAddrState::Unreachable | PeerAddrState::Removed | PeerAddrState::PotentiallyFailed => {
+            Some(TnlaEvent::PathDown(addr))
+        }
+        PeerAddrState::Added | PeerAddrState::MadePrimary => None,
     }
 }
@@ -1,8 +1,9 @@
 //! sctp_transport_provider - the standard TransportProvider used for SCTP-based protocols NGAP, F1AP and E1AP
 
 use super::sctp_tnla_pool::SctpTnlaPool;
+use super::send_queue::SendQueueStats;
 use super::tnla_event_handler::TnlaEventHandler;
-use crate::transport_provider::{AssocId, Binding};
+use crate::transport_provider::{AssocId, Binding, OutboundStream};
 use crate::{ShutdownHandle, TransportProvider};
 use anyhow::{anyhow, bail, Result};
 use async_std::sync::Arc;
@@ -10,24 +11,20 @@ use async_std::task;
 use async_trait::async_trait;
 use futures::pin_mut;
 use futures::stream::StreamExt;
-use sctp::{Message, SctpAssociation};
+use sctp::{Message, SctpAssociation, SctpConfig};
 use slog::{info, warn, Logger};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use stop_token::StopSource;
 
 #[derive(Clone)]
 pub struct SctpTransportProvider {
     tnla_pool: SctpTnlaPool,
+    config: SctpConfig,
 }
 
 impl SctpTransportProvider {
     pub fn new() -> SctpTransportProvider {
-        SctpTransportProvider {
-            tnla_pool: SctpTnlaPool::new(),
-        }
-    }
-    pub async fn graceful_shutdown(self) {
-        self.tnla_pool.graceful_shutdown().await
+        Self::new_with_config(SctpConfig::default())
     }
 }
 
@@ -37,30 +34,54 @@ impl Default for SctpTransportProvider {
     }
 }
 
+// The address strings may be comma separated lists, for a multihomed association.
 async fn resolve_and_connect(
     connect_addr_string: &str,
     bind_addr_string: &str,
     ppid: u32,
+    config: &SctpConfig,
     logger: &Logger,
 ) -> Result<SctpAssociation> {
-    let connect_addr = async_net::resolve(connect_addr_string)
-        .await?
-        .into_iter()
-        .next()
-        .ok_or(anyhow!("Address resolved to empty array"))?; // Don't know if this is actually hittable
-    let bind_addr = format!("{}:0", bind_addr_string).parse()?;
-    SctpAssociation::establish(connect_addr, bind_addr, ppid, logger).await
+    let connect_addrs = resolve_all(connect_addr_string).await?;
+    let bind_addrs = bind_addr_string
+        .split(',')
+        .map(|x| Ok(SocketAddr::new(x.trim().parse()?, 0)))
+        .collect::<Result<Vec<SocketAddr>>>()?;
+    SctpAssociation::establish(&connect_addrs, &bind_addrs, ppid, config, logger).await
+}
+
+async fn resolve_all(addr_string: &str) -> Result<Vec<SocketAddr>> {
+    let mut addrs = Vec::new();
+    for x in addr_string.split(',') {
+        let addr = async_net::resolve(x.trim())
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(anyhow!("Address resolved to empty array"))?; // Don't know if this is actually hittable
+        addrs.push(addr);
+    }
+    Ok(addrs)
 }
 
 #[async_trait]
 impl TransportProvider for SctpTransportProvider {
+    fn new_with_config(config: SctpConfig) -> SctpTransportProvider {
+        SctpTransportProvider {
+            tnla_pool: SctpTnlaPool::new(config.send_queue_size),
+            config,
+        }
+    }
+
     async fn send_message(
         &self,
         message: Message,
         assoc_id: Option<u32>,
+        stream: OutboundStream,
         logger: &Logger,
     ) -> Result<()> {
-        self.tnla_pool.send_message(message, assoc_id, logger).await
+        self.tnla_pool
+            .send_message(message, assoc_id, stream, logger)
+            .await
     }
 
     async fn connect<H>(
@@ -75,8 +96,14 @@ impl TransportProvider for SctpTransportProvider {
         H: TnlaEventHandler,
     {
         //let connect_addr_string = connect_addr_string.clone();
-        let assoc =
-            resolve_and_connect(connect_addr_string, bind_addr_string, ppid, &logger).await?;
+        let assoc = resolve_and_connect(
+            connect_addr_string,
+            bind_addr_string,
+            ppid,
+            &self.config,
+            &logger,
+        )
+        .await?;
         //let logger = logger.new(o!("connection" => assoc_id));
         self.tnla_pool
             .add_and_handle(
@@ -92,7 +119,7 @@ impl TransportProvider for SctpTransportProvider {
 
     // Pick a new UE binding.
     async fn new_ue_binding(&self, seed: u32) -> Result<Binding> {
-        self.tnla_pool.new_ue_binding(seed).await
+        self.tnla_pool.new_ue_binding(seed)
     }
 
     async fn new_ue_binding_from_assoc(&self, assoc_id: &AssocId) -> Result<Binding> {
@@ -112,11 +139,43 @@ impl TransportProvider for SctpTransportProvider {
         }
     }
 
+    async fn ue_binding(&self, ue_key: u32) -> Result<Binding> {
+        self.tnla_pool.ue_binding(ue_key)
+    }
+
+    async fn explicit_ue_binding(&self, ue_key: u32) -> Result<Binding> {
+        self.tnla_pool.explicit_ue_binding(ue_key)
+    }
+
+    async fn bind_ue(&self, ue_key: u32, assoc_id: AssocId) -> Result<()> {
+        self.tnla_pool.bind_ue(ue_key, assoc_id)
+    }
+
+    async fn is_ue_bound(&self, ue_key: u32) -> bool {
+        self.tnla_pool.is_ue_bound(ue_key)
+    }
+
+    async fn unbind_ue(&self, ue_key: u32) {
+        self.tnla_pool.unbind_ue(ue_key)
+    }
+
+    async fn set_tnla_weight(&self, remote_ip: IpAddr, weight: u8) {
+        self.tnla_pool.set_tnla_weight(remote_ip, weight)
+    }
+
     // Return the set of TNLA remote address to which we are currently connected
     async fn remote_tnla_addresses(&self) -> Vec<(AssocId, SocketAddr)> {
         self.tnla_pool.remote_addresses().await
     }
 
+    async fn send_queue_stats(&self) -> Vec<(AssocId, SendQueueStats)> {
+        self.tnla_pool.send_queue_stats()
+    }
+
+    async fn graceful_shutdown(self) {
+        self.tnla_pool.graceful_shutdown().await
+    }
+
     async fn serve<H>(
         self,
         listen_addr: String,
@@ -127,10 +186,16 @@ impl TransportProvider for SctpTransportProvider {
     where
         H: TnlaEventHandler,
     {
-        let addr = async_net::resolve(&listen_addr).await.map(|vec| vec[0])?;
+        let addrs = resolve_all(&listen_addr).await?;
         let stop_source = StopSource::new();
         let stop_token = stop_source.token();
-        let stream = sctp::new_listen(addr, ppid, MAX_LISTEN_BACKLOG, logger.clone())?;
+        let stream = sctp::new_listen(
+            &addrs,
+            ppid,
+            MAX_LISTEN_BACKLOG,
+            self.config.clone(),
+            logger.clone(),
+        )?;
         let stream = stream.take_until(stop_token);
 
         let join_handle = task::spawn(async move {
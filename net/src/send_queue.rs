@@ -0,0 +1,223 @@
+//! send_queue - bounded queue of messages waiting to be sent on an SCTP association
+
+use anyhow::{anyhow, bail, Result};
+use async_channel::{Receiver, Sender, TrySendError};
+use async_std::sync::Arc;
+use async_std::task;
+use async_trait::async_trait;
+use futures::channel::oneshot;
+use sctp::{Message, SctpAssociation, StreamId};
+use slog::{warn, Logger};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Metrics of the send queue of an association.
+#[derive(Debug, Clone, Default)]
+pub struct SendQueueStats {
+    // The number of messages currently waiting to be sent.
+    pub depth: usize,
+    // The greatest number of messages that have been waiting at once.
+    pub max_depth: usize,
+    pub messages_sent: u64,
+    pub send_failures: u64,
+    // The number of times a sender found the queue full, and had to wait.
+    pub full_waits: u64,
+}
+
+// Messages are taken off the queue and sent, in order, by a task per association.  That task waits for the
+// socket to become writable, so a burst of messages is held up rather than lost when the socket's send
+// buffer fills.  Each sender waits for its own message to be sent, and gets the result.
+#[derive(Debug)]
+pub struct SendQueue {
+    sender: Sender<QueuedMessage>,
+    counters: Arc<Counters>,
+}
+
+#[derive(Debug)]
+struct QueuedMessage {
+    message: Message,
+    stream_id: StreamId,
+    result: oneshot::Sender<Result<()>>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    max_depth: AtomicUsize,
+    messages_sent: AtomicU64,
+    send_failures: AtomicU64,
+    full_waits: AtomicU64,
+}
+
+// Where the queued messages go.  This is an SCTP association, other than in the tests.
+#[async_trait]
+trait MessageSink: Send + Sync + 'static {
+    async fn send_msg(&self, message: Message, stream_id: StreamId) -> Result<()>;
+}
+
+#[async_trait]
+impl MessageSink for SctpAssociation {
+    async fn send_msg(&self, message: Message, stream_id: StreamId) -> Result<()> {
+        SctpAssociation::send_msg(self, message, stream_id).await
+    }
+}
+
+impl SendQueue {
+    // Create the queue and spawn the task that sends its messages.  The task finishes once the queue
+    // is dropped.
+    pub fn new(assoc: Arc<SctpAssociation>, size: usize) -> Self {
+        Self::new_with_sink(assoc, size)
+    }
+
+    fn new_with_sink<S: MessageSink>(sink: Arc<S>, size: usize) -> Self {
+        let (sender, receiver) = async_channel::bounded(size.max(1));
+        let counters = Arc::new(Counters::default());
+        task::spawn(send_queued(sink, receiver, counters.clone()));
+        SendQueue { sender, counters }
+    }
+
+    pub async fn send(
+        &self,
+        message: Message,
+        stream_id: StreamId,
+        assoc_id: u32,
+        logger: &Logger,
+    ) -> Result<()> {
+        let (result_sender, result) = oneshot::channel();
+        let queued = QueuedMessage {
+            message,
+            stream_id,
+            result: result_sender,
+        };
+        match self.sender.try_send(queued) {
+            Ok(()) => {}
+            Err(TrySendError::Full(queued)) => {
+                warn!(
+                    logger,
+                    "Send queue of assoc {} is full ({} messages) - waiting",
+                    assoc_id,
+                    self.sender.len()
+                );
+                self.counters.full_waits.fetch_add(1, Ordering::Relaxed);
+                if self.sender.send(queued).await.is_err() {
+                    bail!("Association closed")
+                }
+            }
+            Err(TrySendError::Closed(_)) => bail!("Association closed"),
+        }
+        self.counters
+            .max_depth
+            .fetch_max(self.sender.len(), Ordering::Relaxed);
+
+        result
+            .await
+            .unwrap_or_else(|_| Err(anyhow!("Association closed")))
+    }
+
+    pub fn stats(&self) -> SendQueueStats {
+        SendQueueStats {
+            depth: self.sender.len(),
+            max_depth: self.counters.max_depth.load(Ordering::Relaxed),
+            messages_sent: self.counters.messages_sent.load(Ordering::Relaxed),
+            send_failures: self.counters.send_failures.load(Ordering::Relaxed),
+            full_waits: self.counters.full_waits.load(Ordering::Relaxed),
+        }
+    }
+}
+
+async fn send_queued<S: MessageSink>(
+    sink: Arc<S>,
+    receiver: Receiver<QueuedMessage>,
+    counters: Arc<Counters>,
+) {
+    while let Ok(queued) = receiver.recv().await {
+        let result = sink.send_msg(queued.message, queued.stream_id).await;
+        let counter = if result.is_ok() {
+            &counters.messages_sent
+        } else {
+            &counters.send_failures
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        // The sender may have given up waiting.
+        let _ = queued.result.send(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task::JoinHandle;
+    use slog::o;
+    use std::time::Duration;
+
+    // Takes each message as soon as it is offered, but only finishes sending it when let through by the test,
+    // like a socket whose send buffer is full.
+    struct BlockedSocket {
+        received: Sender<Message>,
+        gate: Receiver<()>,
+    }
+
+    #[async_trait]
+    impl MessageSink for BlockedSocket {
+        async fn send_msg(&self, message: Message, _stream_id: StreamId) -> Result<()> {
+            self.received.send(message).await?;
+            self.gate.recv().await?;
+            Ok(())
+        }
+    }
+
+    async fn wait_for(queue: &SendQueue, condition: impl Fn(SendQueueStats) -> bool) {
+        while !condition(queue.stats()) {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[async_std::test]
+    async fn full_queue_makes_senders_wait() -> Result<()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let (received_sender, received) = async_channel::unbounded();
+        let (open_gate, gate) = async_channel::unbounded();
+        let socket = BlockedSocket {
+            received: received_sender,
+            gate,
+        };
+        let queue = Arc::new(SendQueue::new_with_sink(Arc::new(socket), 2));
+        let send = |n: u8| -> JoinHandle<Result<()>> {
+            let queue = queue.clone();
+            let logger = logger.clone();
+            task::spawn(async move { queue.send(vec![n], 0, 1, &logger).await })
+        };
+
+        // The first message is taken off the queue and held up at the socket...
+        let mut senders = vec![send(0)];
+        assert_eq!(received.recv().await?, vec![0]);
+
+        // ...the next two fill the queue...
+        senders.push(send(1));
+        wait_for(&queue, |x| x.depth == 1).await;
+        senders.push(send(2));
+        wait_for(&queue, |x| x.depth == 2).await;
+
+        // ...and the sender of the fourth has to wait.
+        senders.push(send(3));
+        wait_for(&queue, |x| x.full_waits == 1).await;
+        task::sleep(Duration::from_millis(50)).await;
+        assert_eq!(queue.stats().depth, 2);
+        assert!(received.is_empty());
+
+        // Once the socket is writable again, every message is sent, in order.
+        for _ in 0..4 {
+            open_gate.send(()).await?;
+        }
+        for sender in senders {
+            sender.await?;
+        }
+        for n in 1..4 {
+            assert_eq!(received.recv().await?, vec![n]);
+        }
+        let stats = queue.stats();
+        assert_eq!(stats.messages_sent, 4);
+        assert_eq!(stats.send_failures, 0);
+        assert_eq!(stats.max_depth, 2);
+        Ok(())
+    }
+}
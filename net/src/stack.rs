@@ -1,23 +1,194 @@
 // stack - transaction layer allowing workflow business logic to await a response to its ??AP requests
 
 use crate::tnla_event_handler::TnlaEventHandler;
-use crate::transport_provider::AssocId;
-use crate::{Message, SctpTransportProvider, ShutdownHandle, TnlaEvent, TransportProvider};
-use anyhow::Result;
+use crate::transport_provider::{stream_for_pdu, AssocId, Binding, OutboundStream};
+use crate::{
+    Message, SctpTransportProvider, ShutdownHandle, StreamId, TnlaEvent, TransportProvider,
+};
+use anyhow::{anyhow, bail, Result};
 use asn1_per::*;
-use async_channel::Sender;
+use async_channel::{Receiver, Sender};
 use async_net::SocketAddr;
+use async_std::future;
 use async_std::sync::{Arc, Mutex};
+use async_std::task;
+use async_std::task_local;
 use async_trait::async_trait;
-use slog::{debug, warn, Logger};
+use serde::{Deserialize, Deserializer};
+use slog::{debug, info, warn, Logger};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 
-type TransactionMatchFn = Box<dyn Fn(&Message) -> bool + Send + Sync>;
-type SharedTransactions = Arc<Mutex<Box<Vec<(TransactionMatchFn, Sender<Message>)>>>>;
+type ResponseKeyFn = fn(&Message) -> Option<TransactionKey>;
+type SharedTransactions = Arc<Mutex<Box<Vec<PendingRequest>>>>;
 
+const DEFAULT_RESPONSE_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_MAX_PENDING_REQUESTS: usize = 1024;
+
+// How often drain() checks whether the procedures under way have finished.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+task_local! {
+    // The TNLA that the request being handled by a workflow task arrived on.
+    static REQUEST_TNLA: Cell<Option<AssocId>> = Cell::new(None);
+}
+
+// Generic over the transport so that the in-memory ChannelTransportProvider can stand in for SCTP.
 #[derive(Clone)]
-pub struct Stack {
+pub struct Stack<T: TransportProvider = SctpTransportProvider> {
     pending_requests: SharedTransactions,
-    transport_provider: SctpTransportProvider,
+    workflows_in_progress: Arc<AtomicUsize>,
+    request_slots: RequestSlots,
+    config: StackConfig,
+    transport_provider: T,
+    ue_load_balancing: bool,
+}
+
+/// Configuration of the transaction layer.  In a config file, the timers are given in milliseconds.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StackConfig {
+    // How long to wait for the response to a request, unless overridden for the procedure in
+    // response_timeouts.
+    #[serde(
+        rename = "default_response_timeout_ms",
+        deserialize_with = "deserialize_millis"
+    )]
+    pub default_response_timeout: Duration,
+
+    // Per-procedure response timers, keyed by procedure code.  These play the role of the T-timers
+    // that supervise the various procedures of the ??AP specifications.
+    #[serde(
+        rename = "response_timeouts_ms",
+        deserialize_with = "deserialize_millis_by_procedure_code"
+    )]
+    pub response_timeouts: HashMap<u8, Duration>,
+
+    // The maximum number of requests that may be awaiting a response at any one time.  Once this is
+    // reached, further requests wait for a slot to become free.
+    pub max_pending_requests: usize,
+
+    // Procedure codes of the non UE-associated requests that are sent again on another TNLA if the TNLA
+    // they were sent on fails before the response arrives.  Only suitable for procedures that are safe to
+    // repeat, and for a stack whose TNLAs all lead to the same peer node.
+    pub retry_on_tnla_failure: HashSet<u8>,
+}
+
+impl Default for StackConfig {
+    fn default() -> Self {
+        StackConfig {
+            default_response_timeout: Duration::from_secs(DEFAULT_RESPONSE_TIMEOUT_SECS),
+            response_timeouts: HashMap::new(),
+            max_pending_requests: DEFAULT_MAX_PENDING_REQUESTS,
+            retry_on_tnla_failure: HashSet::new(),
+        }
+    }
+}
+
+impl StackConfig {
+    /// Sets the response timer for the procedure with the given procedure code.
+    pub fn with_response_timeout(mut self, procedure_code: u8, timeout: Duration) -> Self {
+        self.response_timeouts.insert(procedure_code, timeout);
+        self
+    }
+
+    /// Retries the request of the procedure with the given procedure code on another TNLA if its TNLA fails.
+    pub fn with_retry_on_tnla_failure(mut self, procedure_code: u8) -> Self {
+        self.retry_on_tnla_failure.insert(procedure_code);
+        self
+    }
+
+    fn response_timeout(&self, procedure_code: u8) -> Duration {
+        self.response_timeouts
+            .get(&procedure_code)
+            .copied()
+            .unwrap_or(self.default_response_timeout)
+    }
+}
+
+fn deserialize_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    Ok(Duration::from_millis(u64::deserialize(deserializer)?))
+}
+
+// The keys of a TOML table are strings, so the procedure codes are parsed out of them.
+fn deserialize_millis_by_procedure_code<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<u8, Duration>, D::Error> {
+    HashMap::<String, u64>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(procedure_code, millis)| {
+            let procedure_code = procedure_code.parse().map_err(|_| {
+                serde::de::Error::custom(format!("bad procedure code {procedure_code}"))
+            })?;
+            Ok((procedure_code, Duration::from_millis(millis)))
+        })
+        .collect()
+}
+
+// A request that is waiting for its response.  A response matches if it has the same procedure code and,
+// where the request carries a transaction key (UE AP ID or transaction ID), the same transaction key.
+// The response may arrive on another TNLA, but the request is failed if the TNLA it was sent on goes down.
+struct PendingRequest {
+    assoc_id: AssocId,
+    procedure_code: u8,
+    transaction_key: Option<TransactionKey>,
+    response_key: ResponseKeyFn,
+    sender: Sender<Message>,
+}
+
+impl PendingRequest {
+    // The caller has given up on the request without removing its entry.
+    fn is_abandoned(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
+
+// Bounds the number of outstanding requests.  A slot is taken by pushing a token into a bounded channel,
+// which blocks once the channel is full, and given back by removing a token when the slot is dropped.
+#[derive(Clone)]
+struct RequestSlots {
+    acquire: Sender<()>,
+    release: Receiver<()>,
+}
+
+struct RequestSlot(Receiver<()>);
+
+impl RequestSlots {
+    fn new(max_pending_requests: usize) -> Self {
+        let (acquire, release) = async_channel::bounded(max_pending_requests.max(1));
+        RequestSlots { acquire, release }
+    }
+
+    async fn acquire(&self) -> RequestSlot {
+        // We hold the receiver, so the channel cannot be closed.
+        let _ = self.acquire.send(()).await;
+        RequestSlot(self.release.clone())
+    }
+}
+
+impl Drop for RequestSlot {
+    fn drop(&mut self) {
+        let _ = self.0.try_recv();
+    }
+}
+
+// Counts a workflow task, started by a request from the peer, as in progress until it is dropped.
+struct WorkflowInProgress(Arc<AtomicUsize>);
+
+impl WorkflowInProgress {
+    fn new(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        WorkflowInProgress(counter.clone())
+    }
+}
+
+impl Drop for WorkflowInProgress {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 #[async_trait]
@@ -27,14 +198,31 @@ pub trait EventHandler: Clone + Send + Sync + 'static {
 
 pub trait Application: EventHandler + RequestMessageHandler {}
 
-impl Stack {
-    pub fn new(transport_provider: SctpTransportProvider) -> Self {
+impl<T: TransportProvider> Stack<T> {
+    pub fn new(transport_provider: T) -> Self {
+        Self::new_with_config(transport_provider, StackConfig::default())
+    }
+
+    pub fn new_with_config(transport_provider: T, config: StackConfig) -> Self {
         Self {
             transport_provider,
             pending_requests: Arc::new(Mutex::new(Box::default())),
+            workflows_in_progress: Arc::new(AtomicUsize::new(0)),
+            request_slots: RequestSlots::new(config.max_pending_requests),
+            config,
+            ue_load_balancing: false,
         }
     }
 
+    /// Gives a UE that is not bound to a TNLA one chosen by weighted rendezvous hashing, which also moves the UE
+    /// to another TNLA when its TNLA goes down.  This is only right for a stack whose TNLAs all lead to the same
+    /// peer node, such as the NGAP TNLAs to an AMF.  Without it, a UE's signalling fails unless the UE is bound
+    /// to a TNLA that is up - for example, on F1AP, where each TNLA may lead to a different DU.
+    pub fn with_ue_load_balancing(mut self) -> Self {
+        self.ue_load_balancing = true;
+        self
+    }
+
     pub async fn connect<A: Application>(
         &self,
         connect_address: &str,
@@ -47,6 +235,7 @@ impl Stack {
             application,
             transport_provider: self.transport_provider.clone(),
             pending_requests: self.pending_requests.clone(),
+            workflows_in_progress: self.workflows_in_progress.clone(),
         };
         self.transport_provider
             .clone()
@@ -65,6 +254,7 @@ impl Stack {
             application,
             transport_provider: self.transport_provider.clone(),
             pending_requests: self.pending_requests.clone(),
+            workflows_in_progress: self.workflows_in_progress.clone(),
         };
 
         self.transport_provider
@@ -77,68 +267,412 @@ impl Stack {
         self.transport_provider.remote_tnla_addresses().await
     }
 
+    /// Logs the depth and wait counts of the send queue of each TNLA, so that an operator can see when bursts
+    /// of signalling are filling the queues.
+    pub async fn log_send_queue_stats(&self, interface: &str, logger: &Logger) {
+        for (assoc_id, stats) in self.transport_provider.send_queue_stats().await {
+            info!(
+                logger,
+                "{} TNLA {} send queue - depth {}, max depth {}, sent {}, send failures {}, full waits {}",
+                interface,
+                assoc_id,
+                stats.depth,
+                stats.max_depth,
+                stats.messages_sent,
+                stats.send_failures,
+                stats.full_waits
+            );
+        }
+    }
+
+    /// The remote address of the TNLA that the request being handled arrived on.  This lets the workflow
+    /// for a non UE-associated request, such as a setup request, tell which peer sent it.  None if called
+    /// from outside a workflow task, or if the TNLA has since gone down.
+    pub async fn request_remote_address(&self) -> Option<SocketAddr> {
+        let tnla_id = REQUEST_TNLA.try_with(|x| x.get()).ok().flatten()?;
+        self.remote_tnla_addresses()
+            .await
+            .into_iter()
+            .find(|(assoc_id, _)| *assoc_id == tnla_id)
+            .map(|(_, addr)| addr)
+    }
+
     pub async fn graceful_shutdown(self) {
         self.transport_provider.graceful_shutdown().await
     }
-}
 
-#[async_trait]
-impl<P: Procedure> RequestProvider<P> for Stack {
-    async fn request(
+    /// Waits until the procedures under way on this stack have finished, or the timeout expires.  These are the
+    /// workflows started by requests from the peer, up to and including sending their responses, and our
+    /// requests that are waiting for a response.  Messages carry on being received meanwhile, since the procedures
+    /// need them to finish.  Returns false if procedures were still under way at the timeout.
+    ///
+    /// To shut down gracefully, drain the stacks before calling graceful_shutdown(), so that the associations
+    /// are not closed under the feet of a procedure.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        future::timeout(timeout, async {
+            while self.procedures_in_progress().await > 0 {
+                task::sleep(DRAIN_POLL_INTERVAL).await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+
+    async fn procedures_in_progress(&self) -> usize {
+        let pending_requests = self
+            .pending_requests
+            .lock()
+            .await
+            .iter()
+            .filter(|r| !r.is_abandoned())
+            .count();
+        self.workflows_in_progress.load(Ordering::Relaxed) + pending_requests
+    }
+
+    /// Binds a UE to the TNLA that its signalling currently uses, so that it stays there while the TNLA is up,
+    /// and returns the TNLA's remote IP address.  This is what gets stored in the UE state.
+    pub async fn bind_ue(&self, ue_key: u32) -> Result<String> {
+        let binding = self.ue_binding(ue_key).await?;
+        self.transport_provider
+            .bind_ue(ue_key, binding.assoc_id)
+            .await?;
+        Ok(binding.remote_ip)
+    }
+
+    /// Rebinds a UE to a TNLA with the given remote IP address, as stored in its UE state by another worker.
+    /// A UE that is already bound keeps its binding, since the peer may have since moved it.
+    pub async fn restore_ue_binding(&self, ue_key: u32, remote_ip: &str) -> Result<()> {
+        if self.transport_provider.is_ue_bound(ue_key).await {
+            return Ok(());
+        }
+        self.bind_ue_to_peer(ue_key, remote_ip).await
+    }
+
+    /// Binds a UE to a TNLA with the given remote IP address, replacing any binding it has.  This is how
+    /// a workflow chooses which of several peers gets the UE's signalling.
+    pub async fn bind_ue_to_peer(&self, ue_key: u32, remote_ip: &str) -> Result<()> {
+        let assoc_id = self.peer_assoc(remote_ip).await?;
+        self.transport_provider.bind_ue(ue_key, assoc_id).await
+    }
+
+    /// Sends a non UE-associated request to the peer with the given remote IP address, rather than on the TNLA
+    /// with the lowest ID.  This is how a workflow talks to one of several peers, such as a particular DU.  Fails
+    /// if this stack has no TNLA to the peer.
+    pub async fn request_to_peer<P: Procedure>(
         &self,
         r: P::Request,
+        remote_ip: &str,
         logger: &Logger,
-    ) -> Result<ResponseAction<P::Success>, RequestError<P::Failure>> {
-        let bytes = P::encode_request(r)?;
+    ) -> Result<P::Success, RequestError<P::Failure>> {
+        let assoc_id = self.peer_assoc(remote_ip).await?;
+        self.send_request::<P>(r, Some(assoc_id), logger)
+            .await
+            .map(|(x, _)| x)
+    }
+
+    /// Sends a non UE-associated indication to the peer with the given remote IP address.
+    pub async fn indicate_to_peer<I: Indication>(
+        &self,
+        i: I::Request,
+        remote_ip: &str,
+        logger: &Logger,
+    ) -> Result<()> {
+        let assoc_id = self.peer_assoc(remote_ip).await?;
+        self.send_indication::<I>(i, Some(assoc_id), logger).await
+    }
+
+    /// Whether this stack has a TNLA to the peer with the given remote IP address.
+    pub async fn has_peer(&self, remote_ip: &str) -> bool {
+        self.peer_assoc(remote_ip).await.is_ok()
+    }
 
-        // Create a channel to receive the response.
-        let (sender, receiver) = async_channel::bounded::<Vec<u8>>(1);
-        let match_fn = |m: &Message| ((m[0] != 0) && (m[1] == P::CODE));
+    // A TNLA to the peer with the given remote IP address.
+    async fn peer_assoc(&self, remote_ip: &str) -> Result<AssocId> {
+        let Some((assoc_id, _)) = self
+            .remote_tnla_addresses()
+            .await
+            .into_iter()
+            .find(|(_, x)| x.ip().to_string() == remote_ip)
+        else {
+            bail!("No TNLA to {}", remote_ip)
+        };
+        Ok(assoc_id)
+    }
+
+    /// Binds a UE to the TNLA that the request being handled arrived on.  For example, a UE's F1AP signalling
+    /// goes to the DU that sent its Initial UL RRC Message Transfer.
+    pub async fn bind_ue_to_request_tnla(&self, ue_key: u32) -> Result<()> {
+        let Some(tnla_id) = REQUEST_TNLA.try_with(|x| x.get()).ok().flatten() else {
+            bail!("Not handling a request")
+        };
+        self.transport_provider.bind_ue(ue_key, tnla_id).await
+    }
+
+    pub async fn unbind_ue(&self, ue_key: u32) {
+        self.transport_provider.unbind_ue(ue_key).await
+    }
+
+    /// Sets the weight factor of the TNLAs to a remote IP address, for the choice of TNLA for new UEs.
+    pub async fn set_tnla_weight(&self, remote_ip: IpAddr, weight: u8) {
+        self.transport_provider
+            .set_tnla_weight(remote_ip, weight)
+            .await
+    }
+
+    // The binding of a UE.  Without UE load balancing, only a UE bound to a TNLA that is up has one.
+    async fn ue_binding(&self, ue_key: u32) -> Result<Binding> {
+        if self.ue_load_balancing {
+            self.transport_provider.ue_binding(ue_key).await
+        } else {
+            self.transport_provider.explicit_ue_binding(ue_key).await
+        }
+    }
+
+    // UE-associated signalling goes on the UE's binding, and anything else on stream 0 of the TNLA with the
+    // lowest ID.
+    async fn route(
+        &self,
+        transaction_key: Option<TransactionKey>,
+    ) -> Result<(AssocId, OutboundStream)> {
+        if let Some(TransactionKey::Ue(ue_key)) = transaction_key {
+            let binding = self
+                .ue_binding(ue_key)
+                .await
+                .map_err(|e| anyhow!("No TNLA for UE {:#010x} - {}", ue_key, e))?;
+            return Ok((binding.assoc_id, OutboundStream::Ue(ue_key)));
+        }
+        let Some(assoc_id) = self.non_ue_assoc(None).await else {
+            bail!("No association found")
+        };
+        Ok((assoc_id, stream_for_pdu(transaction_key)))
+    }
+
+    // Non UE-associated signalling sticks to the TNLA with the lowest ID, other than one that has failed.
+    async fn non_ue_assoc(&self, failed: Option<AssocId>) -> Option<AssocId> {
+        self.remote_tnla_addresses()
+            .await
+            .into_iter()
+            .map(|(assoc_id, _)| assoc_id)
+            .filter(|assoc_id| Some(*assoc_id) != failed)
+            .min()
+    }
+
+    // Remove pending requests whose response receiver has been dropped.
+    async fn remove_abandoned_requests(&self) {
         self.pending_requests
             .lock()
             .await
-            .push((Box::new(match_fn), sender));
+            .retain(|r| !r.is_abandoned());
+    }
+
+    // Sends a request and waits for its response.  A request to a given peer is never retried on another TNLA,
+    // since that might lead to a different peer.
+    async fn send_request<P: Procedure>(
+        &self,
+        r: P::Request,
+        peer_assoc: Option<AssocId>,
+        logger: &Logger,
+    ) -> Result<ResponseAction<P::Success>, RequestError<P::Failure>> {
+        let bytes = P::encode_request(r)?;
+
+        // Decode our own request to find out its transaction key.  This saves every generated Procedure from
+        // having to know which of its IEs identify the transaction.
+        let transaction_key = P::TopPdu::from_bytes(&bytes)
+            .ok()
+            .and_then(|pdu| pdu.transaction_key());
+        let (mut assoc_id, stream) = match peer_assoc {
+            Some(assoc_id) => (assoc_id, stream_for_pdu(transaction_key)),
+            None => self.route(transaction_key).await?,
+        };
+        let response_timeout = self.config.response_timeout(P::CODE);
+        let mut may_retry = peer_assoc.is_none()
+            && !matches!(transaction_key, Some(TransactionKey::Ue(_)))
+            && self.config.retry_on_tnla_failure.contains(&P::CODE);
+
+        // Wait for room in the pending request table.  The slot is given back when it goes out of scope.
+        let _slot = future::timeout(response_timeout, self.request_slots.acquire())
+            .await
+            .map_err(|_| {
+                warn!(
+                    logger,
+                    "Timed out waiting to send request (procedure code {}) - too many pending requests",
+                    P::CODE
+                );
+                RequestError::Timeout
+            })?;
+
+        let response_key = |m: &Message| {
+            P::TopPdu::from_bytes(m)
+                .ok()
+                .and_then(|pdu| pdu.transaction_key())
+        };
+        loop {
+            // Create a channel to receive the response.
+            let (sender, receiver) = async_channel::bounded::<Vec<u8>>(1);
+            {
+                let mut pending_requests = self.pending_requests.lock().await;
 
+                // Clear out any requests whose callers have gone away without removing their entry.
+                pending_requests.retain(|r| !r.is_abandoned());
+                pending_requests.push(PendingRequest {
+                    assoc_id,
+                    procedure_code: P::CODE,
+                    transaction_key,
+                    response_key,
+                    sender,
+                });
+            }
+
+            if let Err(e) = self
+                .transport_provider
+                .send_message(bytes.clone(), Some(assoc_id), stream, logger)
+                .await
+            {
+                drop(receiver);
+                self.remove_abandoned_requests().await;
+                return Err(e.into());
+            }
+
+            match future::timeout(response_timeout, receiver.recv()).await {
+                Ok(Ok(msg)) => return P::decode_response(&msg).map(|x| (x, None)),
+
+                // The pending request was dropped because its TNLA went down.
+                Ok(Err(e)) => match self.non_ue_assoc(Some(assoc_id)).await {
+                    Some(other_assoc_id) if may_retry => {
+                        warn!(
+                            logger,
+                            "TNLA {} failed - retrying request (procedure code {}) on TNLA {}",
+                            assoc_id,
+                            P::CODE,
+                            other_assoc_id
+                        );
+                        assoc_id = other_assoc_id;
+                        may_retry = false;
+                    }
+                    _ => return Err(e.into()),
+                },
+                Err(_) => {
+                    warn!(
+                        logger,
+                        "No response to request (procedure code {}) after {:?}",
+                        P::CODE,
+                        response_timeout
+                    );
+                    drop(receiver);
+                    self.remove_abandoned_requests().await;
+                    return Err(RequestError::Timeout);
+                }
+            }
+        }
+    }
+
+    async fn send_indication<I: Indication>(
+        &self,
+        i: I::Request,
+        peer_assoc: Option<AssocId>,
+        logger: &Logger,
+    ) -> Result<()> {
+        let m = I::encode_request(i).map_err(|e| anyhow!("Error encoding indication - {:?}", e))?;
+        let transaction_key = I::TopPdu::from_bytes(&m)
+            .ok()
+            .and_then(|pdu| pdu.transaction_key());
+        let (assoc_id, stream) = match peer_assoc {
+            Some(assoc_id) => (assoc_id, stream_for_pdu(transaction_key)),
+            None => self.route(transaction_key).await?,
+        };
         self.transport_provider
-            .send_message(bytes, None, logger)
-            .await?;
+            .send_message(m, Some(assoc_id), stream, logger)
+            .await
+    }
+}
 
-        // TODO - timeout
-        let msg = receiver.recv().await?;
-        P::decode_response(&msg).map(|x| (x, None))
+#[async_trait]
+impl<P: Procedure, T: TransportProvider> RequestProvider<P> for Stack<T> {
+    async fn request(
+        &self,
+        r: P::Request,
+        logger: &Logger,
+    ) -> Result<ResponseAction<P::Success>, RequestError<P::Failure>> {
+        self.send_request::<P>(r, None, logger).await
     }
 }
 
 #[async_trait]
-impl<I: Indication> IndicationHandler<I> for Stack {
+impl<I: Indication, T: TransportProvider> IndicationHandler<I> for Stack<T> {
     async fn handle(&self, i: I::Request, logger: &Logger) {
-        match I::encode_request(i) {
-            Ok(m) => match self.transport_provider.send_message(m, None, logger).await {
-                Ok(()) => (),
-                Err(e) => warn!(logger, "Error sending indication - {:?}", e),
-            },
-            Err(e) => warn!(logger, "Error encoding indication - {:?}", e),
+        if let Err(e) = self.send_indication::<I>(i, None, logger).await {
+            warn!(logger, "Error sending indication - {:?}", e)
         }
     }
 }
 
 #[derive(Clone)]
-struct StackReceiver<A: Application> {
+struct StackReceiver<A: Application, T: TransportProvider> {
     application: A,
-    transport_provider: SctpTransportProvider,
+    transport_provider: T,
     pending_requests: SharedTransactions,
+    workflows_in_progress: Arc<AtomicUsize>,
 }
 
-impl<A: Application> StackReceiver<A> {
-    fn spawn_workflow_task(&self, message: Message, tnla_id: AssocId, logger: &Logger) {
+impl<A: Application, T: TransportProvider> StackReceiver<A, T> {
+    // Find and remove the pending request that this response belongs to.
+    async fn take_matching_request(&self, message: &Message) -> Option<Sender<Message>> {
+        let procedure_code = message[1];
+        let mut pending_requests = self.pending_requests.lock().await;
+
+        // The response's transaction key is only worked out if needed, since this means decoding it.
+        let mut response_key = None;
+        let index = pending_requests.iter().position(|r| {
+            r.procedure_code == procedure_code
+                && (r.transaction_key.is_none()
+                    || *response_key.get_or_insert_with(|| (r.response_key)(message))
+                        == r.transaction_key)
+        })?;
+        Some(pending_requests.swap_remove(index).sender)
+    }
+
+    // Drop the pending requests that were sent on a TNLA, returning how many there were.
+    async fn fail_requests_on(&self, assoc_id: AssocId) -> usize {
+        let mut pending_requests = self.pending_requests.lock().await;
+        let before = pending_requests.len();
+        pending_requests.retain(|r| r.assoc_id != assoc_id);
+        before - pending_requests.len()
+    }
+
+    // The response goes back on the stream that the request arrived on.
+    fn spawn_workflow_task(
+        &self,
+        message: Message,
+        tnla_id: AssocId,
+        stream_id: StreamId,
+        logger: &Logger,
+    ) {
         let application = self.application.clone();
         let logger = logger.clone();
         let transport_provider = self.transport_provider.clone();
-        async_std::task::spawn(async move {
+
+        // Counted from now, so that drain() cannot miss a workflow that has not yet started running.
+        let in_progress = WorkflowInProgress::new(&self.workflows_in_progress);
+        task::spawn(async move {
+            let _in_progress = in_progress;
+            REQUEST_TNLA.with(|x| x.set(Some(tnla_id)));
+            follow_peer_binding(
+                &application,
+                &transport_provider,
+                &message,
+                tnla_id,
+                &logger,
+            )
+            .await;
             let response_action = application.handle_request(&message, &logger).await;
             if let Some((response, future)) = response_action {
                 if let Err(e) = transport_provider
-                    .send_message(response, Some(tnla_id), &logger)
+                    .send_message(
+                        response,
+                        Some(tnla_id),
+                        OutboundStream::Id(stream_id),
+                        &logger,
+                    )
                     .await
                 {
                     warn!(logger, "Failed to send response - {}", e);
@@ -151,23 +685,39 @@ impl<A: Application> StackReceiver<A> {
     }
 }
 
+// A UE-associated request that arrives on a TNLA other than the one the UE is using moves the UE to that TNLA.
+// This is how the AMF rebinds a UE by triangular redirection - see TS 23.502, 4.2.7.2.4.
+async fn follow_peer_binding<A: Application, T: TransportProvider>(
+    application: &A,
+    transport_provider: &T,
+    message: &[u8],
+    tnla_id: AssocId,
+    logger: &Logger,
+) {
+    let Some(TransactionKey::Ue(ue_key)) = application.transaction_key(message) else {
+        return;
+    };
+    match transport_provider.explicit_ue_binding(ue_key).await {
+        Ok(binding) if binding.assoc_id == tnla_id => {}
+        _ => match transport_provider.bind_ue(ue_key, tnla_id).await {
+            Ok(()) => debug!(logger, "UE {} rebound to TNLA {} by peer", ue_key, tnla_id),
+            Err(e) => warn!(logger, "Failed to rebind UE {} - {}", ue_key, e),
+        },
+    }
+}
+
 #[async_trait]
-impl<A: Application> TnlaEventHandler for StackReceiver<A> {
+impl<A: Application, T: TransportProvider> TnlaEventHandler for StackReceiver<A, T> {
     async fn handle_event(&self, event: TnlaEvent, tnla_id: u32, logger: &Logger) {
-        if let TnlaEvent::Terminated = event {
-            // Drop all pending requests - which will fail all the workflows in progress
-            let mut found_request = false;
-            for r in self.pending_requests.lock().await.drain(..) {
-                drop(r);
-                found_request = true
-            }
-
-            if found_request {
+        // On termination or peer restart, the responses to the requests we sent on this TNLA are never coming.
+        if let TnlaEvent::Terminated | TnlaEvent::Restarted = event {
+            // Drop its pending requests - which fails the workflows waiting for them.
+            let failed = self.fail_requests_on(tnla_id).await;
+            if failed > 0 {
                 warn!(
                     logger,
-                    "Failing all requests because of TNLA {} termination. \
-                     Note that current blanket implementation may drop requests \
-                     on other TNLAs that could have survived",
+                    "Failing {} requests because of TNLA {} termination or restart",
+                    failed,
                     tnla_id
                 );
             }
@@ -175,32 +725,393 @@ impl<A: Application> TnlaEventHandler for StackReceiver<A> {
         self.application.handle_event(event, tnla_id, logger).await
     }
 
-    async fn handle_message(&self, message: Message, tnla_id: u32, logger: &Logger) {
-        // TODO figure out if it is a response and warn / drop if there are no matches
+    async fn handle_message(
+        &self,
+        message: Message,
+        tnla_id: u32,
+        stream_id: StreamId,
+        logger: &Logger,
+    ) {
+        // The first byte of an ??AP PDU is non zero for a successful or unsuccessful outcome.
+        if message.len() < 2 || message[0] == 0 {
+            // New request - spawn a new task to handle the workflow.
+            self.spawn_workflow_task(message, tnla_id, stream_id, logger);
+            return;
+        }
+
+        // Response - send it to the existing task that is waiting for it.
+        match self.take_matching_request(&message).await {
+            Some(response_channel) => response_channel
+                .send(message)
+                .await
+                .unwrap_or_else(|_| warn!(logger, "Internal response channel down")),
+            None => warn!(
+                logger,
+                "Dropping response (procedure code {}) that matches no pending request", message[1]
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChannelTransportProvider;
+    use slog::o;
 
-        // If it matches a pending request, route it back over the response channel.
+    // Responds to each request after a delay.
+    #[derive(Clone)]
+    struct SlowApplication;
 
-        let position = self
-            .pending_requests
-            .lock()
-            .await
+    #[async_trait]
+    impl EventHandler for SlowApplication {
+        async fn handle_event(&self, _event: TnlaEvent, _tnla_id: u32, _logger: &Logger) {}
+    }
+
+    #[async_trait]
+    impl RequestMessageHandler for SlowApplication {
+        async fn handle_request(
+            &self,
+            _message: &[u8],
+            _logger: &Logger,
+        ) -> Option<ResponseAction<Vec<u8>>> {
+            task::sleep(Duration::from_millis(200)).await;
+            Some((vec![1, 0], None))
+        }
+    }
+
+    impl Application for SlowApplication {}
+
+    #[derive(Clone)]
+    struct Client(Sender<Message>);
+
+    #[async_trait]
+    impl TnlaEventHandler for Client {
+        async fn handle_event(&self, _event: TnlaEvent, _tnla_id: u32, _logger: &Logger) {}
+
+        async fn handle_message(
+            &self,
+            message: Message,
+            _tnla_id: u32,
+            _stream_id: StreamId,
+            _logger: &Logger,
+        ) {
+            self.0.send(message).await.unwrap();
+        }
+    }
+
+    #[async_std::test]
+    async fn drain_waits_for_workflows() -> Result<()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let stack = Stack::new(ChannelTransportProvider::new());
+        let _listener = stack
+            .listen(
+                "127.0.0.1:38462".to_string(),
+                0,
+                SlowApplication,
+                logger.clone(),
+            )
+            .await?;
+        let (sender, responses) = async_channel::unbounded();
+        let client = ChannelTransportProvider::new();
+        client
+            .clone()
+            .connect(
+                "127.0.0.1:38462",
+                "127.0.0.2",
+                0,
+                Client(sender),
+                logger.clone(),
+            )
+            .await?;
+        client
+            .send_message(vec![0, 0], None, OutboundStream::Id(0), &logger)
+            .await?;
+
+        // Wait for the request to arrive.
+        while stack.procedures_in_progress().await == 0 {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!stack.drain(Duration::from_millis(10)).await);
+        assert!(stack.drain(Duration::from_secs(1)).await);
+        assert_eq!(responses.recv().await?, vec![1, 0]);
+        Ok(())
+    }
+
+    // Responds to each request with the last byte of the IP address of the peer that sent it.
+    #[derive(Clone)]
+    struct AddressApplication(Stack<ChannelTransportProvider>);
+
+    #[async_trait]
+    impl EventHandler for AddressApplication {
+        async fn handle_event(&self, _event: TnlaEvent, _tnla_id: u32, _logger: &Logger) {}
+    }
+
+    #[async_trait]
+    impl RequestMessageHandler for AddressApplication {
+        async fn handle_request(
+            &self,
+            _message: &[u8],
+            _logger: &Logger,
+        ) -> Option<ResponseAction<Vec<u8>>> {
+            let IpAddr::V4(ip) = self.0.request_remote_address().await?.ip() else {
+                return None;
+            };
+            Some((vec![1, ip.octets()[3]], None))
+        }
+    }
+
+    impl Application for AddressApplication {}
+
+    #[async_std::test]
+    async fn workflow_knows_request_remote_address() -> Result<()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let stack = Stack::new(ChannelTransportProvider::new());
+        let _listener = stack
+            .listen(
+                "127.0.0.1:38463".to_string(),
+                0,
+                AddressApplication(stack.clone()),
+                logger.clone(),
+            )
+            .await?;
+        let (sender, responses) = async_channel::unbounded();
+        let client = ChannelTransportProvider::new();
+        client
+            .clone()
+            .connect(
+                "127.0.0.1:38463",
+                "127.0.0.7",
+                0,
+                Client(sender),
+                logger.clone(),
+            )
+            .await?;
+        client
+            .send_message(vec![0, 0], None, OutboundStream::Id(0), &logger)
+            .await?;
+        assert_eq!(responses.recv().await?, vec![1, 7]);
+
+        // Outside a workflow task, there is no request.
+        assert!(stack.request_remote_address().await.is_none());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn ue_signalling_needs_binding_without_load_balancing() -> Result<()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let stack = Stack::new(ChannelTransportProvider::new());
+        let _listener = stack
+            .listen(
+                "127.0.0.1:38464".to_string(),
+                0,
+                SlowApplication,
+                logger.clone(),
+            )
+            .await?;
+        let mut clients = vec![];
+        for bind_address in ["127.0.0.2", "127.0.0.3"] {
+            let (sender, _) = async_channel::unbounded();
+            let client = ChannelTransportProvider::new();
+            client
+                .clone()
+                .connect(
+                    "127.0.0.1:38464",
+                    bind_address,
+                    0,
+                    Client(sender),
+                    logger.clone(),
+                )
+                .await?;
+            clients.push(client);
+        }
+        while stack.remote_tnla_addresses().await.len() < 2 {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+        let ue = Some(TransactionKey::Ue(5));
+        assert!(stack.route(ue).await.is_err());
+
+        // A bound UE's signalling goes to its peer.
+        stack.bind_ue_to_peer(5, "127.0.0.3").await?;
+        let (assoc_id, _) = stack.route(ue).await?;
+        let remote_addresses = stack.remote_tnla_addresses().await;
+        let (_, remote_address) = remote_addresses
             .iter()
-            .position(|(matches, _)| matches(&message));
-
-        match position {
-            Some(index) => {
-                // Response - send it to the existing task that is waiting for it.
-                let (_, response_channel) = self.pending_requests.lock().await.swap_remove(index);
-                response_channel
-                    .send(message)
-                    .await
-                    .unwrap_or_else(|_| warn!(logger, "Internal response channel down"));
-                // TODO - don't unwrap
-            }
-            _ => {
-                // New request - spawn a new task to handle the workflow.
-                self.spawn_workflow_task(message, tnla_id, logger);
-            }
+            .find(|(x, _)| *x == assoc_id)
+            .unwrap();
+        assert_eq!(remote_address.ip().to_string(), "127.0.0.3");
+
+        // When its TNLA goes down, it is not moved to another peer...
+        clients.pop().unwrap().graceful_shutdown().await;
+        while stack.remote_tnla_addresses().await.len() > 1 {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(stack.route(ue).await.is_err());
+
+        // ...unless the stack load balances UEs.
+        assert!(stack.with_ue_load_balancing().route(ue).await.is_ok());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn tnla_failure_only_fails_its_own_requests() {
+        let logger = Logger::root(slog::Discard, o!());
+        let receiver = StackReceiver {
+            application: SlowApplication,
+            transport_provider: ChannelTransportProvider::new(),
+            pending_requests: Arc::new(Mutex::new(Box::default())),
+            workflows_in_progress: Arc::new(AtomicUsize::new(0)),
+        };
+        let mut response_receivers = vec![];
+        for assoc_id in [1, 2, 1] {
+            let (sender, response_receiver) = async_channel::bounded(1);
+            receiver.pending_requests.lock().await.push(PendingRequest {
+                assoc_id,
+                procedure_code: 0,
+                transaction_key: None,
+                response_key: |_| None,
+                sender,
+            });
+            response_receivers.push(response_receiver);
         }
+
+        receiver
+            .handle_event(TnlaEvent::Terminated, 1, &logger)
+            .await;
+        let failed: Vec<bool> = response_receivers.iter().map(|x| x.is_closed()).collect();
+        assert_eq!(failed, vec![true, false, true]);
+
+        // The request on the surviving TNLA still gets its response.
+        receiver.handle_message(vec![1, 0], 2, 0, &logger).await;
+        assert_eq!(response_receivers[1].recv().await.unwrap(), vec![1, 0]);
+    }
+
+    // A procedure whose PDUs are just the two byte header that the stack looks at.
+    struct TestProcedure;
+
+    struct TestPdu;
+
+    impl SerDes for TestPdu {
+        fn into_bytes(self) -> Result<Vec<u8>, PerCodecError> {
+            Ok(vec![0, TestProcedure::CODE])
+        }
+        fn from_bytes(_bytes: &[u8]) -> Result<Self, PerCodecError> {
+            Ok(TestPdu)
+        }
+    }
+
+    impl TransactionKeyed for TestPdu {
+        fn transaction_key(&self) -> Option<TransactionKey> {
+            None
+        }
+    }
+
+    #[async_trait]
+    impl Procedure for TestProcedure {
+        const CODE: u8 = 7;
+        type TopPdu = TestPdu;
+        type Request = ();
+        type Success = ();
+        type Failure = ();
+        fn encode_request(_r: ()) -> Result<Vec<u8>, PerCodecError> {
+            TestPdu.into_bytes()
+        }
+        fn decode_response(_bytes: &[u8]) -> Result<(), RequestError<()>> {
+            Ok(())
+        }
+        async fn call_provider<T: RequestProvider<Self>>(
+            _provider: &T,
+            _req: (),
+            _logger: &Logger,
+        ) -> Option<ResponseAction<TestPdu>> {
+            None
+        }
+    }
+
+    // Connects a stack to a peer that passes on the requests it receives, for the test to answer.
+    async fn connect_to_peer(
+        port: u16,
+        config: StackConfig,
+        logger: &Logger,
+    ) -> Result<(
+        Stack<ChannelTransportProvider>,
+        ChannelTransportProvider,
+        ShutdownHandle,
+        Receiver<Message>,
+    )> {
+        let (sender, requests) = async_channel::unbounded();
+        let peer = ChannelTransportProvider::new();
+        let listen_address = format!("127.0.0.1:{port}");
+        let listener = peer
+            .clone()
+            .serve(listen_address.clone(), 0, Client(sender), logger.clone())
+            .await?;
+        let stack = Stack::new_with_config(ChannelTransportProvider::new(), config);
+        stack
+            .connect(
+                &listen_address,
+                "127.0.0.2",
+                0,
+                SlowApplication,
+                logger.clone(),
+            )
+            .await?;
+        Ok((stack, peer, listener, requests))
+    }
+
+    #[async_std::test]
+    async fn unanswered_request_times_out() -> Result<()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let config = StackConfig::default()
+            .with_response_timeout(TestProcedure::CODE, Duration::from_millis(50));
+        let (stack, _peer, _listener, requests) = connect_to_peer(38465, config, &logger).await?;
+
+        // The procedure's own timer applies, rather than the 10 second default.
+        let result = future::timeout(
+            Duration::from_secs(1),
+            RequestProvider::<TestProcedure>::request(&stack, (), &logger),
+        )
+        .await?;
+        assert!(matches!(result, Err(RequestError::Timeout)));
+        assert_eq!(requests.recv().await?, vec![0, TestProcedure::CODE]);
+        assert!(stack.pending_requests.lock().await.is_empty());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn request_waits_for_pending_request_slot() -> Result<()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let config = StackConfig {
+            max_pending_requests: 1,
+            ..StackConfig::default()
+        };
+        let (stack, peer, _listener, requests) = connect_to_peer(38466, config, &logger).await?;
+        let send_request = || {
+            let stack = stack.clone();
+            let logger = logger.clone();
+            task::spawn(async move {
+                RequestProvider::<TestProcedure>::request(&stack, (), &logger)
+                    .await
+                    .map(|_| ())
+            })
+        };
+        let response = vec![1, TestProcedure::CODE];
+
+        // The second request is held back while the first one is pending...
+        let first = send_request();
+        requests.recv().await?;
+        let second = send_request();
+        task::sleep(Duration::from_millis(100)).await;
+        assert!(requests.is_empty());
+
+        // ...and is sent once the first one gets its response.
+        peer.send_message(response.clone(), None, OutboundStream::Id(0), &logger)
+            .await?;
+        assert!(first.await.is_ok());
+        requests.recv().await?;
+        peer.send_message(response, None, OutboundStream::Id(0), &logger)
+            .await?;
+        assert!(second.await.is_ok());
+        Ok(())
     }
 }
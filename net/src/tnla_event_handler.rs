@@ -2,19 +2,38 @@
 
 use async_net::SocketAddr;
 use async_trait::async_trait;
-use sctp::Message;
+use sctp::{Message, StreamId};
 use slog::Logger;
 
 #[async_trait]
 pub trait TnlaEventHandler: 'static + Send + Sync + Clone {
     async fn handle_event(&self, event: TnlaEvent, tnla_id: u32, logger: &Logger);
 
-    // TODO indicate whether it is UE or non UE associated?
-    async fn handle_message(&self, message: Message, tnla_id: u32, logger: &Logger);
+    // Non UE-associated signalling arrives on stream 0.
+    async fn handle_message(
+        &self,
+        message: Message,
+        tnla_id: u32,
+        stream_id: StreamId,
+        logger: &Logger,
+    );
 }
 
 #[derive(Debug)]
 pub enum TnlaEvent {
     Established(SocketAddr),
     Terminated,
+    // A path to one of the addresses of a multihomed peer has become usable.
+    PathUp(SocketAddr),
+    // A path to one of the addresses of a multihomed peer has failed or been removed.  The
+    // association carries on over its other paths.
+    PathDown(SocketAddr),
+    // The peer restarted and has lost its state.  The TNLA is still up, but any procedures in
+    // progress on it have been lost.
+    Restarted,
+    // The peer has begun a graceful shutdown.  No more messages will arrive, and Terminated follows
+    // once our outstanding messages have been delivered.
+    PeerShutdown,
+    // A message sent on the given stream could not be delivered.
+    SendFailed(StreamId),
 }
@@ -1,29 +1,78 @@
 //! transport_provider - trait encapsulating the transport services needed by the RAN protocol stacks
 
-use crate::{tnla_event_handler::TnlaEventHandler, ShutdownHandle};
+use crate::{tnla_event_handler::TnlaEventHandler, SendQueueStats, ShutdownHandle};
 use anyhow::Result;
+use asn1_per::TransactionKey;
 use async_net::SocketAddr;
 use async_trait::async_trait;
-use sctp::Message;
+use sctp::{Message, SctpConfig, StreamId};
 use slog::Logger;
+use std::net::IpAddr;
 
 pub type AssocId = u32;
 pub struct Binding {
     pub assoc_id: AssocId,
     pub remote_ip: String,
-    // stream will go here
+    pub stream_id: StreamId,
+}
+
+/// Non UE-associated signalling goes on stream 0.  See TS 38.412, TS 38.472 and TS 37.482, section 7.
+pub const NON_UE_STREAM_ID: StreamId = 0;
+
+/// Returns the stream that a UE's signalling goes on, given the number of streams available.  A UE is
+/// pinned to one stream, so that its signalling stays in order without holding up other UEs.
+pub fn ue_stream_id(ue_key: u32, num_streams: u16) -> StreamId {
+    if num_streams > 1 {
+        1 + (ue_key % (num_streams as u32 - 1)) as StreamId
+    } else {
+        NON_UE_STREAM_ID
+    }
+}
+
+/// The stream to send a message on.  A UE's signalling is given by its key, and only mapped to a stream by
+/// ue_stream_id() once the association is known, so that it goes on the same stream however it is routed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutboundStream {
+    Id(StreamId),
+    Ue(u32),
+}
+
+impl OutboundStream {
+    /// Returns the stream ID on an association with the given number of outbound streams.  A stream ID that is
+    /// beyond the streams of the association is mapped onto one of its UE streams.
+    pub fn stream_id(&self, num_streams: u16) -> StreamId {
+        match *self {
+            OutboundStream::Id(stream_id) if stream_id < num_streams => stream_id,
+            OutboundStream::Id(stream_id) => ue_stream_id(stream_id as u32, num_streams),
+            OutboundStream::Ue(ue_key) => ue_stream_id(ue_key, num_streams),
+        }
+    }
+}
+
+/// Returns the stream to send a PDU on, given its transaction key.
+pub fn stream_for_pdu(transaction_key: Option<TransactionKey>) -> OutboundStream {
+    match transaction_key {
+        Some(TransactionKey::Ue(ue_key)) => OutboundStream::Ue(ue_key),
+        _ => OutboundStream::Id(NON_UE_STREAM_ID),
+    }
 }
 
 /// The TransportProvider trait abstracts the transport, for example, to allow a non-SCTP test transport to be used.
 #[async_trait]
-pub trait TransportProvider: Send + Sync + 'static {
+pub trait TransportProvider: Clone + Send + Sync + 'static {
+    // A transport that is not SCTP ignores the SCTP configuration.
+    fn new_with_config(config: SctpConfig) -> Self;
+
+    // The stream is resolved against the streams of the association that the message goes on.
     async fn send_message(
         &self,
         message: Message,
         assoc_id: Option<u32>,
+        stream: OutboundStream,
         logger: &Logger,
     ) -> Result<()>;
 
+    // The addresses passed to serve() and connect() may be comma separated lists, for SCTP multihoming.
     async fn serve<H>(
         self,
         listen_addr: String,
@@ -45,11 +94,57 @@ pub trait TransportProvider: Send + Sync + 'static {
     where
         H: TnlaEventHandler;
 
-    // Pick a new UE binding.
+    // Pick a new UE binding.  The association is a stable choice for the seed, weighted by the TNLA weight factors.
     async fn new_ue_binding(&self, seed: u32) -> Result<Binding>;
     async fn new_ue_binding_from_assoc(&self, assoc_id: &AssocId) -> Result<Binding>;
     async fn new_ue_binding_from_ip(&self, ip_addr: &str) -> Result<Binding>;
 
+    // The binding of a UE - the association that it was bound to by bind_ue(), if that is still up, or else
+    // the new_ue_binding() for its key.
+    async fn ue_binding(&self, ue_key: u32) -> Result<Binding>;
+
+    // The binding of a UE to the association that it was bound to by bind_ue(), if that is still up.  Unlike
+    // ue_binding(), this never picks an association for a UE that is not bound.
+    async fn explicit_ue_binding(&self, ue_key: u32) -> Result<Binding>;
+
+    // Bind a UE to an association, for as long as the association stays up.
+    async fn bind_ue(&self, ue_key: u32, assoc_id: AssocId) -> Result<()>;
+    async fn is_ue_bound(&self, ue_key: u32) -> bool;
+    async fn unbind_ue(&self, ue_key: u32);
+
+    // Set the weight factor of the TNLAs to a remote IP address, as given by the NGAP TNL Address Weight Factor.
+    // A TNLA of weight 0 is only chosen for a UE if there is no other TNLA.
+    async fn set_tnla_weight(&self, remote_ip: IpAddr, weight: u8);
+
     // Return the set of TNLA remote address to which we are currently connected
     async fn remote_tnla_addresses(&self) -> Vec<(AssocId, SocketAddr)>;
+
+    // Metrics of the send queue of each TNLA.  Empty for a transport that does not queue messages.
+    async fn send_queue_stats(&self) -> Vec<(AssocId, SendQueueStats)>;
+
+    async fn graceful_shutdown(self);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ue_signalling_goes_on_its_binding_stream() {
+        for num_streams in [1, 2, 10, 16, StreamId::MAX] {
+            for ue_key in [0, 1, 20, 65534, 65535, 65536, u32::MAX] {
+                let stream_id =
+                    stream_for_pdu(Some(TransactionKey::Ue(ue_key))).stream_id(num_streams);
+                assert_eq!(stream_id, ue_stream_id(ue_key, num_streams));
+                assert!(stream_id < num_streams);
+            }
+        }
+    }
+
+    #[test]
+    fn stream_beyond_association_is_mapped_to_ue_stream() {
+        assert_eq!(OutboundStream::Id(3).stream_id(10), 3);
+        assert_eq!(OutboundStream::Id(12).stream_id(10), ue_stream_id(12, 10));
+        assert_eq!(stream_for_pdu(None).stream_id(10), NON_UE_STREAM_ID);
+    }
 }
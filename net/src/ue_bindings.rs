@@ -0,0 +1,134 @@
+//! ue_bindings - selection of the TNLA that carries a UE's signalling
+
+use crate::transport_provider::AssocId;
+use async_std::sync::Arc;
+use dashmap::DashMap;
+use std::net::IpAddr;
+
+/// The weight factor of a TNLA whose peer has not given it one.
+const DEFAULT_TNLA_WEIGHT: u8 = 1;
+
+// A UE is either bound to a TNLA explicitly - for example, because the peer sent its signalling on that TNLA -
+// or else is given a TNLA by weighted rendezvous hashing.  The hashing gives each UE a stable choice that
+// does not depend on the order in which TNLAs came up, and when a TNLA goes down, only the UEs that
+// were using it move elsewhere.  Explicit bindings last until unbound or until their TNLA goes down.
+#[derive(Clone, Default)]
+pub(crate) struct UeBindings {
+    bindings: Arc<DashMap<u32, AssocId>>,
+    // Weight factors by remote IP address, so that they survive the TNLA being reestablished.
+    weights: Arc<DashMap<IpAddr, u8>>,
+}
+
+impl UeBindings {
+    /// Returns the TNLA for a UE, given the TNLAs that are up and their remote IP addresses.
+    pub fn assoc_for(&self, ue_key: u32, tnlas: &[(AssocId, IpAddr)]) -> Option<AssocId> {
+        self.bound_assoc(ue_key, tnlas)
+            .or_else(|| self.select(ue_key, tnlas))
+    }
+
+    /// Returns the TNLA that a UE is explicitly bound to, if it is one of the TNLAs that are up.
+    pub fn bound_assoc(&self, ue_key: u32, tnlas: &[(AssocId, IpAddr)]) -> Option<AssocId> {
+        self.bindings
+            .get(&ue_key)
+            .map(|x| *x)
+            .filter(|assoc_id| tnlas.iter().any(|(x, _)| x == assoc_id))
+    }
+
+    /// Picks a TNLA by weighted rendezvous hashing of the seed.  A TNLA of weight 0 is only picked if
+    /// all of them have weight 0.
+    pub fn select(&self, seed: u32, tnlas: &[(AssocId, IpAddr)]) -> Option<AssocId> {
+        tnlas
+            .iter()
+            .map(|(assoc_id, ip)| {
+                let weight = self.weights.get(ip).map_or(DEFAULT_TNLA_WEIGHT, |x| *x);
+                (weight > 0, score(seed, *assoc_id, weight.max(1)), *assoc_id)
+            })
+            .max_by(|a, b| (a.0, a.1).partial_cmp(&(b.0, b.1)).unwrap())
+            .map(|(_, _, assoc_id)| assoc_id)
+    }
+
+    pub fn bind(&self, ue_key: u32, assoc_id: AssocId) {
+        self.bindings.insert(ue_key, assoc_id);
+    }
+
+    pub fn is_bound(&self, ue_key: u32) -> bool {
+        self.bindings.contains_key(&ue_key)
+    }
+
+    pub fn unbind(&self, ue_key: u32) {
+        self.bindings.remove(&ue_key);
+    }
+
+    pub fn set_weight(&self, remote_ip: IpAddr, weight: u8) {
+        self.weights.insert(remote_ip, weight);
+    }
+
+    // Called when a TNLA goes down.  Its UEs are no longer bound, so they fall back to hashing onto the remaining
+    // TNLAs, where the stack allows it.
+    pub fn remove_assoc(&self, assoc_id: AssocId) {
+        self.bindings.retain(|_, x| *x != assoc_id);
+    }
+}
+
+// The score of a TNLA for a seed is -w / ln(h), where h is a hash of the two mapped onto (0, 1).  The highest
+// score wins, which picks each TNLA with probability proportional to its weight.
+fn score(seed: u32, assoc_id: AssocId, weight: u8) -> f64 {
+    let h = splitmix64(((seed as u64) << 32) | assoc_id as u64);
+    let h = ((h >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+    -(weight as f64) / h.ln()
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tnlas(ids: &[AssocId]) -> Vec<(AssocId, IpAddr)> {
+        ids.iter()
+            .map(|id| (*id, IpAddr::from([127, 0, 0, *id as u8])))
+            .collect()
+    }
+
+    #[test]
+    fn only_ues_on_failed_tnla_move() {
+        let bindings = UeBindings::default();
+        let all = tnlas(&[3, 5, 8]);
+        let mut reversed = all.clone();
+        reversed.reverse();
+        let survivors = tnlas(&[3, 8]);
+        for ue_key in 0..1000 {
+            let before = bindings.assoc_for(ue_key, &all).unwrap();
+            assert_eq!(bindings.assoc_for(ue_key, &reversed), Some(before));
+            let after = bindings.assoc_for(ue_key, &survivors).unwrap();
+            if before != 5 {
+                assert_eq!(after, before);
+            }
+        }
+    }
+
+    #[test]
+    fn weights_and_explicit_bindings() {
+        let bindings = UeBindings::default();
+        let all = tnlas(&[1, 2]);
+        bindings.set_weight(all[0].1, 0);
+        bindings.set_weight(all[1].1, 10);
+        assert!((0..100).all(|ue_key| bindings.assoc_for(ue_key, &all) == Some(2)));
+
+        // An explicit binding holds until its TNLA goes away.
+        bindings.bind(7, 1);
+        assert_eq!(bindings.assoc_for(7, &all), Some(1));
+        bindings.remove_assoc(1);
+        assert!(!bindings.is_bound(7));
+        assert_eq!(bindings.bound_assoc(7, &all), None);
+        assert_eq!(bindings.assoc_for(7, &all), Some(2));
+
+        // With nothing else available, a TNLA of weight 0 is used.
+        assert_eq!(bindings.assoc_for(7, &all[..1]), Some(1));
+    }
+}
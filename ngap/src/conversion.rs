@@ -1,4 +1,9 @@
-use crate::{ies::Snssai, Sd, Sst};
+use crate::{
+    ies::Snssai, BroadcastPlmnItem, BroadcastPlmnList, PagingDrx, PlmnIdentity, Sd,
+    SliceSupportItem, SliceSupportList, Sst, SupportedTaItem, SupportedTaList, Tac,
+};
+use anyhow::{anyhow, Result};
+use asn1_per::NonEmpty;
 
 impl From<xxap::Snssai> for Snssai {
     fn from(x: xxap::Snssai) -> Self {
@@ -14,3 +19,66 @@ impl From<Snssai> for xxap::Snssai {
         xxap::Snssai(x.sst.0[0], x.sd.map(|x| x.0))
     }
 }
+
+impl From<xxap::PagingDrx> for PagingDrx {
+    fn from(x: xxap::PagingDrx) -> Self {
+        match x {
+            xxap::PagingDrx::V32 => PagingDrx::V32,
+            xxap::PagingDrx::V64 => PagingDrx::V64,
+            xxap::PagingDrx::V128 => PagingDrx::V128,
+            xxap::PagingDrx::V256 => PagingDrx::V256,
+        }
+    }
+}
+
+impl TryFrom<&xxap::RanConfig> for SupportedTaList {
+    type Error = anyhow::Error;
+    fn try_from(config: &xxap::RanConfig) -> Result<Self> {
+        let tas = config
+            .supported_tas
+            .iter()
+            .map(SupportedTaItem::try_from)
+            .collect::<Result<_>>()?;
+        Ok(SupportedTaList(non_empty(tas, "supported TAs")?))
+    }
+}
+
+impl TryFrom<&xxap::SupportedTa> for SupportedTaItem {
+    type Error = anyhow::Error;
+    fn try_from(ta: &xxap::SupportedTa) -> Result<Self> {
+        let plmns = ta
+            .broadcast_plmns
+            .iter()
+            .map(BroadcastPlmnItem::try_from)
+            .collect::<Result<_>>()?;
+        Ok(SupportedTaItem {
+            tac: Tac(ta.tac),
+            broadcast_plmn_list: BroadcastPlmnList(non_empty(plmns, "broadcast PLMNs")?),
+            configured_tac_indication: None,
+            rat_information: None,
+        })
+    }
+}
+
+impl TryFrom<&xxap::BroadcastPlmn> for BroadcastPlmnItem {
+    type Error = anyhow::Error;
+    fn try_from(plmn: &xxap::BroadcastPlmn) -> Result<Self> {
+        let slices = plmn
+            .slices
+            .iter()
+            .map(|x| SliceSupportItem {
+                snssai: (*x).into(),
+            })
+            .collect();
+        Ok(BroadcastPlmnItem {
+            plmn_identity: PlmnIdentity(plmn.plmn),
+            tai_slice_support_list: SliceSupportList(non_empty(slices, "slices")?),
+            npn_support: None,
+            extended_tai_slice_support_list: None,
+        })
+    }
+}
+
+fn non_empty<T>(items: Vec<T>, what: &str) -> Result<NonEmpty<T>> {
+    NonEmpty::from_vec(items).ok_or_else(|| anyhow!("No {} configured", what))
+}
@@ -13,6 +13,7 @@ pub use ngap_gnb::*;
 mod display;
 pub use display::*;
 mod conversion;
+mod transaction_key;
 
 #[cfg(test)]
 mod test;
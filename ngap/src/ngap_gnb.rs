@@ -33,8 +33,12 @@ impl<T> Application for NgapGnb<T> where
         + IndicationHandler<DownlinkNasTransportProcedure>
         + RequestProvider<InitialContextSetupProcedure>
         + IndicationHandler<AmfStatusIndicationProcedure>
+        + RequestProvider<AmfConfigurationUpdateProcedure>
         + RequestProvider<PduSessionResourceSetupProcedure>
         + RequestProvider<PduSessionResourceReleaseProcedure>
+        + RequestProvider<PduSessionResourceModifyProcedure>
+        + RequestProvider<UeContextReleaseProcedure>
+        + IndicationHandler<PagingProcedure>
 {
 }
 
@@ -47,8 +51,12 @@ where
         + IndicationHandler<DownlinkNasTransportProcedure>
         + RequestProvider<InitialContextSetupProcedure>
         + IndicationHandler<AmfStatusIndicationProcedure>
+        + RequestProvider<AmfConfigurationUpdateProcedure>
         + RequestProvider<PduSessionResourceSetupProcedure>
-        + RequestProvider<PduSessionResourceReleaseProcedure>,
+        + RequestProvider<PduSessionResourceReleaseProcedure>
+        + RequestProvider<PduSessionResourceModifyProcedure>
+        + RequestProvider<UeContextReleaseProcedure>
+        + IndicationHandler<PagingProcedure>,
 {
     type TopPdu = NgapPdu;
     async fn route_request(&self, p: NgapPdu, logger: &Logger) -> Option<ResponseAction<NgapPdu>> {
@@ -70,6 +78,9 @@ where
             InitiatingMessage::InitialContextSetupRequest(req) => {
                 InitialContextSetupProcedure::call_provider(&self.0, req, logger).await
             }
+            InitiatingMessage::AmfConfigurationUpdate(req) => {
+                AmfConfigurationUpdateProcedure::call_provider(&self.0, req, logger).await
+            }
             InitiatingMessage::AmfStatusIndication(req) => {
                 AmfStatusIndicationProcedure::call_provider(&self.0, req, logger).await;
                 None
@@ -80,6 +91,16 @@ where
             InitiatingMessage::PduSessionResourceReleaseCommand(req) => {
                 PduSessionResourceReleaseProcedure::call_provider(&self.0, req, logger).await
             }
+            InitiatingMessage::PduSessionResourceModifyRequest(req) => {
+                PduSessionResourceModifyProcedure::call_provider(&self.0, req, logger).await
+            }
+            InitiatingMessage::UeContextReleaseCommand(req) => {
+                UeContextReleaseProcedure::call_provider(&self.0, req, logger).await
+            }
+            InitiatingMessage::Paging(req) => {
+                PagingProcedure::call_provider(&self.0, req, logger).await;
+                None
+            }
             _ => todo!(),
         }
     }
@@ -0,0 +1,110 @@
+//! transaction_key - identifies the transaction that an NGAP PDU belongs to, so that a response can be
+//! matched with its request.
+//!
+//! For a UE-associated procedure this is the RanUeNgapId.  NGAP has no transaction ID, so non
+//! UE-associated PDUs have no key and are matched on procedure code alone.
+
+use crate::{InitiatingMessage, NgapPdu, SuccessfulOutcome, UnsuccessfulOutcome};
+use asn1_per::{TransactionKey, TransactionKey::*, TransactionKeyed};
+
+impl TransactionKeyed for NgapPdu {
+    fn transaction_key(&self) -> Option<TransactionKey> {
+        match self {
+            NgapPdu::InitiatingMessage(m) => m.transaction_key(),
+            NgapPdu::SuccessfulOutcome(m) => m.transaction_key(),
+            NgapPdu::UnsuccessfulOutcome(m) => m.transaction_key(),
+        }
+    }
+}
+
+impl TransactionKeyed for InitiatingMessage {
+    fn transaction_key(&self) -> Option<TransactionKey> {
+        match self {
+            InitiatingMessage::AmfcpRelocationIndication(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::CellTrafficTrace(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::ConnectionEstablishmentIndication(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::DeactivateTrace(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::DownlinkNasTransport(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::DownlinkRanEarlyStatusTransfer(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::DownlinkRanStatusTransfer(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::DownlinkUeAssociatedNrPPaTransport(x) => {
+                Some(Ue(x.ran_ue_ngap_id.0))
+            }
+            InitiatingMessage::ErrorIndication(x) => x.ran_ue_ngap_id.map(|id| Ue(id.0)),
+            InitiatingMessage::HandoverCancel(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::HandoverNotify(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::HandoverRequired(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::HandoverSuccess(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::InitialContextSetupRequest(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::InitialUeMessage(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::LocationReport(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::LocationReportingControl(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::LocationReportingFailureIndication(x) => {
+                Some(Ue(x.ran_ue_ngap_id.0))
+            }
+            InitiatingMessage::NasNonDeliveryIndication(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::PathSwitchRequest(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::PduSessionResourceModifyRequest(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::PduSessionResourceModifyIndication(x) => {
+                Some(Ue(x.ran_ue_ngap_id.0))
+            }
+            InitiatingMessage::PduSessionResourceNotify(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::PduSessionResourceReleaseCommand(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::PduSessionResourceSetupRequest(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::RancpRelocationIndication(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::RerouteNasRequest(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::RrcInactiveTransitionReport(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::SecondaryRatDataUsageReport(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::TraceFailureIndication(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::TraceStart(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::UeContextModificationRequest(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::UeContextReleaseRequest(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::UeContextResumeRequest(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::UeContextSuspendRequest(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::UeRadioCapabilityCheckRequest(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::UeRadioCapabilityInfoIndication(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::UeTnlaBindingReleaseRequest(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::UplinkNasTransport(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::UplinkRanEarlyStatusTransfer(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::UplinkRanStatusTransfer(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            InitiatingMessage::UplinkUeAssociatedNrPPaTransport(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            _ => None,
+        }
+    }
+}
+
+impl TransactionKeyed for SuccessfulOutcome {
+    fn transaction_key(&self) -> Option<TransactionKey> {
+        match self {
+            SuccessfulOutcome::HandoverCancelAcknowledge(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            SuccessfulOutcome::HandoverCommand(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            SuccessfulOutcome::HandoverRequestAcknowledge(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            SuccessfulOutcome::InitialContextSetupResponse(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            SuccessfulOutcome::PathSwitchRequestAcknowledge(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            SuccessfulOutcome::PduSessionResourceModifyResponse(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            SuccessfulOutcome::PduSessionResourceModifyConfirm(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            SuccessfulOutcome::PduSessionResourceReleaseResponse(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            SuccessfulOutcome::PduSessionResourceSetupResponse(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            SuccessfulOutcome::UeContextModificationResponse(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            SuccessfulOutcome::UeContextReleaseComplete(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            SuccessfulOutcome::UeContextResumeResponse(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            SuccessfulOutcome::UeContextSuspendResponse(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            SuccessfulOutcome::UeRadioCapabilityCheckResponse(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            _ => None,
+        }
+    }
+}
+
+impl TransactionKeyed for UnsuccessfulOutcome {
+    fn transaction_key(&self) -> Option<TransactionKey> {
+        match self {
+            UnsuccessfulOutcome::HandoverPreparationFailure(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            UnsuccessfulOutcome::InitialContextSetupFailure(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            UnsuccessfulOutcome::PathSwitchRequestFailure(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            UnsuccessfulOutcome::UeContextModificationFailure(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            UnsuccessfulOutcome::UeContextResumeFailure(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            UnsuccessfulOutcome::UeContextSuspendFailure(x) => Some(Ue(x.ran_ue_ngap_id.0)),
+            _ => None,
+        }
+    }
+}
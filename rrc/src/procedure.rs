@@ -6,6 +6,13 @@ use slog::Logger;
 
 pub struct RrcSetupProcedure {}
 
+// RRC transactions are matched by the RRC transaction identifier rather than by the Stack.
+impl TransactionKeyed for UlDcchMessage {
+    fn transaction_key(&self) -> Option<TransactionKey> {
+        None
+    }
+}
+
 #[async_trait]
 impl Procedure for RrcSetupProcedure {
     type TopPdu = UlDcchMessage;
@@ -1,10 +1,15 @@
+mod notification;
 mod sctp_association;
 mod sctp_bindings;
 mod sctp_listener;
 mod sock_opt;
 mod try_io;
 
-pub use sctp_association::SctpAssociation;
+pub use notification::{AssocChangeState, Notification, PeerAddrState};
+pub use sctp_association::{
+    MessageTooBig, Received, SctpAssociation, SctpConfig, SctpProtocolParameters,
+};
 pub use sctp_listener::new_listen;
 
 pub type Message = Vec<u8>;
+pub type StreamId = u16;
@@ -0,0 +1,102 @@
+//! notification - events on an association that SCTP tells us about.  See RFC6458, 6.1.
+
+use super::sctp_bindings::*;
+use super::StreamId;
+use os_socketaddr::OsSocketAddr;
+use std::mem;
+use std::net::SocketAddr;
+
+#[derive(Debug)]
+pub enum Notification {
+    // The association came up, went down or restarted.
+    AssocChange(AssocChangeState),
+    // A peer address changed state.  This is how we find out about path failures on a multihomed
+    // association.
+    PeerAddrChange(SocketAddr, PeerAddrState),
+    // The peer has sent a SHUTDOWN, so no more messages will arrive on the association.
+    Shutdown,
+    // SCTP could not deliver a message that we sent on the given stream.
+    SendFailed { stream_id: StreamId, error: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssocChangeState {
+    CommUp,
+    CommLost,
+    // The peer restarted, losing its state.  The association has been reset.
+    Restart,
+    ShutdownComplete,
+    CantStartAssoc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerAddrState {
+    Available,
+    Unreachable,
+    Removed,
+    Added,
+    MadePrimary,
+    Confirmed,
+    PotentiallyFailed,
+}
+
+// Parse a notification that we have subscribed to.  Returns None for any other notification.
+#[allow(non_upper_case_globals)]
+pub(crate) fn parse(data: &[u8]) -> Option<Notification> {
+    let sn_type = u16::from_ne_bytes(data.get(0..2)?.try_into().ok()?);
+    match sn_type as sctp_sn_type {
+        sctp_sn_type_SCTP_ASSOC_CHANGE => {
+            let sac = read::<sctp_assoc_change>(data)?;
+            let state = match sac.sac_state as sctp_sac_state {
+                sctp_sac_state_SCTP_COMM_UP => AssocChangeState::CommUp,
+                sctp_sac_state_SCTP_COMM_LOST => AssocChangeState::CommLost,
+                sctp_sac_state_SCTP_RESTART => AssocChangeState::Restart,
+                sctp_sac_state_SCTP_SHUTDOWN_COMP => AssocChangeState::ShutdownComplete,
+                sctp_sac_state_SCTP_CANT_STR_ASSOC => AssocChangeState::CantStartAssoc,
+                _ => return None,
+            };
+            Some(Notification::AssocChange(state))
+        }
+        sctp_sn_type_SCTP_SHUTDOWN_EVENT => {
+            read::<sctp_shutdown_event>(data)?;
+            Some(Notification::Shutdown)
+        }
+        sctp_sn_type_SCTP_SEND_FAILED_EVENT => {
+            let ssf = read::<sctp_send_failed_event>(data)?;
+            Some(Notification::SendFailed {
+                stream_id: ssf.ssfe_info.snd_sid,
+                error: ssf.ssf_error,
+            })
+        }
+        sctp_sn_type_SCTP_PEER_ADDR_CHANGE => {
+            let spc = read::<sctp_paddr_change>(data)?;
+            let addr = unsafe {
+                OsSocketAddr::copy_from_raw(
+                    std::ptr::addr_of!(spc.spc_aaddr) as _,
+                    mem::size_of::<sockaddr_storage>() as _,
+                )
+            }
+            .into_addr()?;
+            let state = match spc.spc_state {
+                sctp_spc_state_SCTP_ADDR_AVAILABLE => PeerAddrState::Available,
+                sctp_spc_state_SCTP_ADDR_UNREACHABLE => PeerAddrState::Unreachable,
+                sctp_spc_state_SCTP_ADDR_REMOVED => PeerAddrState::Removed,
+                sctp_spc_state_SCTP_ADDR_ADDED => PeerAddrState::Added,
+                sctp_spc_state_SCTP_ADDR_MADE_PRIM => PeerAddrState::MadePrimary,
+                sctp_spc_state_SCTP_ADDR_CONFIRMED => PeerAddrState::Confirmed,
+                sctp_spc_state_SCTP_ADDR_POTENTIALLY_FAILED => PeerAddrState::PotentiallyFailed,
+                _ => return None,
+            };
+            Some(Notification::PeerAddrChange(addr, state))
+        }
+        _ => None,
+    }
+}
+
+// Read a notification struct out of the (not necessarily aligned) received data.
+fn read<T: Copy>(data: &[u8]) -> Option<T> {
+    if data.len() < mem::size_of::<T>() {
+        return None;
+    }
+    Some(unsafe { std::ptr::read_unaligned(data.as_ptr() as *const T) })
+}
@@ -1,72 +1,246 @@
 //! sctp_association - async SCTP association
 
+use super::notification::{self, Notification};
 use super::sctp_bindings::*;
 use super::sock_opt;
 use super::try_io::try_io;
-use super::Message;
+use super::{Message, StreamId};
 use anyhow::bail;
 use anyhow::{anyhow, Result};
 use async_io::Async;
 use async_io::Timer;
-use async_stream::try_stream;
+use async_stream::stream;
 use futures_core::stream::Stream;
 use futures_lite::future::FutureExt;
 use io::Error;
 use libc::bind;
-use libc::{connect, getpeername, read, socket, socklen_t, AF_INET, IPPROTO_SCTP, SOCK_STREAM};
+use libc::{
+    getpeername, read, setsockopt, socket, socklen_t, AF_INET, AF_INET6, IPPROTO_SCTP, SOCK_STREAM,
+};
 use os_socketaddr::OsSocketAddr;
+use serde::Deserialize;
 use slog::{warn, Logger};
+use std::fmt;
 use std::net::SocketAddr;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::time::Duration;
 use std::{io, mem};
 
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 65536;
+const DEFAULT_NUM_STREAMS: u16 = 16;
+const DEFAULT_HEARTBEAT_INTERVAL_MS: u32 = 1000;
+const DEFAULT_SEND_QUEUE_SIZE: usize = 256;
+
+/// Configuration of an SCTP association.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SctpConfig {
+    // Received messages bigger than this are discarded.
+    pub max_message_size: usize,
+
+    // The number of outbound streams we ask for, and the number of inbound streams we allow, when
+    // setting up an association.  The peer may negotiate the number of outbound streams down.
+    pub num_outbound_streams: u16,
+    pub max_inbound_streams: u16,
+
+    // The number of messages that may be queued for sending on an association.  Once the queue is full,
+    // senders wait.  The queue belongs to the user of the association, such as net's transport provider.
+    pub send_queue_size: usize,
+
+    pub protocol_parameters: SctpProtocolParameters,
+}
+
+impl Default for SctpConfig {
+    fn default() -> Self {
+        SctpConfig {
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            num_outbound_streams: DEFAULT_NUM_STREAMS,
+            max_inbound_streams: DEFAULT_NUM_STREAMS,
+            send_queue_size: DEFAULT_SEND_QUEUE_SIZE,
+            protocol_parameters: SctpProtocolParameters::default(),
+        }
+    }
+}
+
+/// SCTP protocol parameters - see RFC4960, 15.  A parameter that is None is left at the kernel's
+/// default (see /proc/sys/net/sctp).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SctpProtocolParameters {
+    pub rto_initial_ms: Option<u32>,
+    pub rto_min_ms: Option<u32>,
+    pub rto_max_ms: Option<u32>,
+    pub heartbeat_interval_ms: Option<u32>,
+    pub max_init_retransmits: Option<u16>,
+    pub path_max_retrans: Option<u16>,
+}
+
+impl Default for SctpProtocolParameters {
+    fn default() -> Self {
+        SctpProtocolParameters {
+            rto_initial_ms: None,
+            rto_min_ms: None,
+            rto_max_ms: None,
+            // Heartbeat often, so that we rapidly detect peer failures.
+            heartbeat_interval_ms: Some(DEFAULT_HEARTBEAT_INTERVAL_MS),
+            max_init_retransmits: None,
+            path_max_retrans: None,
+        }
+    }
+}
+
 /// An SCTP assocation.
 // Cannot be Cloned since it is the owner of the fd.  Instead use Arc.
 #[derive(Debug)]
 pub struct SctpAssociation {
     pub fd: i32,
+    // The socket, registered once with the async reactor so that a receive and any number of sends can
+    // wait on it at the same time.
+    socket: Async<Socket>,
     ppid: u32,
     pub remote_address: SocketAddr,
+    max_message_size: usize,
+    // Number of outbound streams, as negotiated with the peer.
+    pub num_outbound_streams: u16,
+}
+
+/// Error for a received message that was bigger than the maximum message size, and so was discarded.
+#[derive(Debug)]
+pub struct MessageTooBig {
+    pub max_message_size: usize,
+}
+
+impl fmt::Display for MessageTooBig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Discarded received message bigger than maximum size of {} bytes",
+            self.max_message_size
+        )
+    }
+}
+
+impl std::error::Error for MessageTooBig {}
+
+/// Something received on an association.
+#[derive(Debug)]
+pub enum Received {
+    Message(Message, StreamId),
+    Notification(Notification),
+}
+
+// The socket domain needed for a set of addresses.  An IPv6 socket can also use IPv4 addresses, so
+// anything involving IPv6 gets an IPv6 socket, allowing a dual stack association.
+pub(crate) fn socket_domain<'a>(addrs: impl IntoIterator<Item = &'a SocketAddr>) -> i32 {
+    if addrs.into_iter().any(|x| x.is_ipv6()) {
+        AF_INET6
+    } else {
+        AF_INET
+    }
+}
+
+// Socket options that have to be set before the association is set up - on the socket that connects,
+// or the listening socket, from which accepted associations inherit them.
+pub(crate) fn set_endpoint_sock_opts(fd: i32, config: &SctpConfig) -> Result<()> {
+    let params = &config.protocol_parameters;
+    sock_opt::set_init_msg(
+        fd,
+        config.num_outbound_streams,
+        config.max_inbound_streams,
+        params.max_init_retransmits.unwrap_or(0),
+    )?;
+    sock_opt::set_rto_info(
+        fd,
+        params.rto_initial_ms.unwrap_or(0),
+        params.rto_min_ms.unwrap_or(0),
+        params.rto_max_ms.unwrap_or(0),
+    )?;
+    sock_opt::set_peer_addr_params(
+        fd,
+        params.heartbeat_interval_ms.unwrap_or(0),
+        params.path_max_retrans.unwrap_or(0),
+    )
+}
+
+// Owner of a socket fd, which it closes when dropped.
+#[derive(Debug)]
+struct Socket(i32);
+
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
 }
 
-impl Drop for SctpAssociation {
+impl Drop for Socket {
     fn drop(&mut self) {
-        unsafe { libc::close(self.fd) };
+        unsafe { libc::close(self.0) };
     }
 }
 
 impl SctpAssociation {
-    // Establish an association as a client
+    // Establish an association as a client.  Giving more than one remote or bind address makes
+    // the association multihomed.  The first remote address is the primary path.
     pub async fn establish(
-        remote_address: SocketAddr,
-        bind_addr: SocketAddr,
+        remote_addresses: &[SocketAddr],
+        bind_addrs: &[SocketAddr],
         ppid: u32,
+        config: &SctpConfig,
         logger: &Logger,
     ) -> Result<SctpAssociation> {
+        let (Some(remote_address), Some(bind_addr)) =
+            (remote_addresses.first(), bind_addrs.first())
+        else {
+            bail!("No remote or bind address")
+        };
+
         // Get a socket and immediately wrap it in an SctpAssociation to ensure it gets closed
         // properly in the drop function if something fails later in this function.
-        let fd = try_io!(socket(AF_INET, SOCK_STREAM, IPPROTO_SCTP), "socket")?;
-        let assoc = SctpAssociation {
+        let fd = try_io!(
+            socket(
+                socket_domain(remote_addresses.iter().chain(bind_addrs)),
+                SOCK_STREAM,
+                IPPROTO_SCTP
+            ),
+            "socket"
+        )?;
+        let mut assoc = SctpAssociation {
             fd,
+            socket: Async::new(Socket(fd))?,
             ppid,
-            remote_address,
+            remote_address: *remote_address,
+            max_message_size: config.max_message_size,
+            num_outbound_streams: 1,
         };
+        set_endpoint_sock_opts(fd, config)?;
 
         // Bind.  This is useful when there are multiple local addresses available to ensure that the remote
-        // end of the connection sees the IP address we want it to.
-        let addr: OsSocketAddr = bind_addr.try_into()?;
+        // end of the connection sees the IP address we want it to.  Any further addresses are added with
+        // sctp_bindx().
+        let addr: OsSocketAddr = (*bind_addr).into();
         try_io!(bind(fd, addr.as_ptr(), addr.len()), "bind")?;
+        if bind_addrs.len() > 1 {
+            sock_opt::bindx_add(fd, &bind_addrs[1..])?;
+        }
 
-        // Connect
-        // See https://cr.yp.to/docs/connect.html.
-        let async_fd = Async::new(fd)?;
-        let addr: OsSocketAddr = assoc.remote_address.into();
-        let rc = unsafe { connect(fd, addr.as_ptr(), addr.len()) };
+        // Connect, using sctp_connectx() so that the peer's other addresses are known from the outset.
+        // See RFC6458, 9.9, and https://cr.yp.to/docs/connect.html.
+        let packed = sock_opt::pack_addrs(remote_addresses);
+        let rc = unsafe {
+            setsockopt(
+                fd,
+                SOL_SCTP as _,
+                SCTP_SOCKOPT_CONNECTX as _,
+                packed.as_ptr() as _,
+                packed.len() as _,
+            )
+        };
         let errno = errno::errno();
         if (rc < 0) && (errno.0 != libc::EINPROGRESS) && (errno.0 != libc::EWOULDBLOCK) {
-            return Err(anyhow!("connect() {:?}", errno));
+            return Err(anyhow!("connectx() {:?}", errno));
         }
-        async_fd
+        assoc
+            .socket
             .writable()
             .or(async {
                 Timer::after(Duration::from_secs(5)).await;
@@ -90,47 +264,91 @@ impl SctpAssociation {
         fd: i32,
         ppid: u32,
         remote_address: SocketAddr,
+        config: &SctpConfig,
         logger: &Logger,
     ) -> Result<SctpAssociation> {
-        let assoc = SctpAssociation {
+        let mut assoc = SctpAssociation {
             fd,
+            socket: Async::new(Socket(fd))?,
             ppid,
             remote_address,
+            max_message_size: config.max_message_size,
+            num_outbound_streams: 1,
         };
         assoc.set_sock_opts(logger)?;
         Ok(assoc)
     }
 
-    fn set_sock_opts(&self, logger: &Logger) -> Result<()> {
+    fn set_sock_opts(&mut self, logger: &Logger) -> Result<()> {
         let fd = self.fd;
 
-        sock_opt::enable_sctp_heartbeat(fd, 1000).unwrap_or_else(|e| {
-            warn!(logger, "Carrying on without heartbeat - {}", e);
-        });
         // It's not clear if this socket option definitely achieves anything - RFC6458 is
         // very vague.
         // sock_opt::enable_sock_opt(fd, SCTP_NODELAY as _).unwrap_or_else(|e| {
         //     warn!(logger, "Carrying on without NODELAY - {}", e);
         // });
         sock_opt::enable_sock_opt(fd, SCTP_RECVRCVINFO as _)?;
+        for (sn_type, name) in [
+            (sctp_sn_type_SCTP_ASSOC_CHANGE, "association change"),
+            (sctp_sn_type_SCTP_PEER_ADDR_CHANGE, "path"),
+            (sctp_sn_type_SCTP_SHUTDOWN_EVENT, "shutdown"),
+            (sctp_sn_type_SCTP_SEND_FAILED_EVENT, "send failure"),
+        ] {
+            sock_opt::enable_sctp_event(fd, sn_type).unwrap_or_else(|e| {
+                warn!(logger, "Carrying on without {} notifications - {}", name, e);
+            });
+        }
+
+        self.num_outbound_streams = sock_opt::get_num_outbound_streams(fd).unwrap_or_else(|e| {
+            warn!(logger, "Carrying on with single outbound stream - {}", e);
+            1
+        });
         Ok(())
     }
 
-    pub fn recv_msg_stream(&self) -> impl Stream<Item = Result<Message>> {
-        let fd = self.fd;
-        try_stream! {
+    // Yields each message along with the ID of the SCTP stream it arrived on, and the notifications we
+    // have subscribed to.  A message that is too big is discarded, and the stream yields a MessageTooBig
+    // error.  Any other error ends the stream.
+    pub fn recv_msg_stream(&self) -> impl Stream<Item = Result<Received>> + '_ {
+        let mut reader = MessageReader::new(self.max_message_size);
+        stream! {
             loop {
-                Async::new(fd)?.readable().await?;
-                let message = recv(fd)?;
-                yield message;
+                let result = match recv(&self.socket, &mut reader).await {
+                    Ok((data, flags, _)) if flags & MSG_NOTIFICATION as libc::c_int != 0 => {
+                        match notification::parse(&data) {
+                            Some(notification) => Ok(Received::Notification(notification)),
+                            None => continue,
+                        }
+                    }
+                    Ok((message, _, stream_id)) => Ok(Received::Message(message, stream_id)),
+                    Err(e) => Err(e),
+                };
+                let fatal = matches!(&result, Err(e) if !e.is::<MessageTooBig>());
+                yield result;
+                if fatal {
+                    break;
+                }
             }
         }
     }
 
-    pub async fn send_msg(&self, mut message: Message) -> Result<()> {
-        // Wait for the socket to become writable
-        //Async::new(self.fd)?.writable().await?;
+    // The stream ID must be less than the number of outbound streams.  If the socket's send buffer is
+    // full, this waits for the peer to acknowledge enough of what we have already sent.
+    pub async fn send_msg(&self, mut message: Message, stream_id: StreamId) -> Result<()> {
+        loop {
+            match self.try_send(&mut message, stream_id) {
+                Ok(bytes_sent) if bytes_sent == message.len() => return Ok(()),
+                // SCTP sends a message whole or not at all, so this is not expected.
+                Ok(bytes_sent) => {
+                    bail!("Partial send {} bytes of {}", bytes_sent, message.len())
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => self.socket.writable().await?,
+                Err(e) => bail!("{} during SCTP sendmsg", e),
+            }
+        }
+    }
 
+    fn try_send(&self, message: &mut Message, stream_id: StreamId) -> io::Result<usize> {
         #[repr(C)]
         #[derive(Debug)]
         // A libc::cmsghdr glued onto a sctp_c_bindings::sctp_sndinfo.
@@ -149,7 +367,7 @@ impl SctpAssociation {
             cmsg_len: mem::size_of::<Sndinfo>(),
             cmsg_level: IPPROTO_SCTP,
             cmsg_type: sctp_cmsg_type_SCTP_SNDINFO as _,
-            snd_sid: 0,
+            snd_sid: stream_id,
             snd_flags: 0,
             snd_ppid: self.ppid.to_be(),
             snd_context: 0,
@@ -162,37 +380,117 @@ impl SctpAssociation {
         };
         let msghdr = make_msghdr(&mut sndinfo, msg_iov);
 
-        let bytes_sent = try_io!(
-            libc::sendmsg(self.fd, &msghdr, libc::MSG_DONTWAIT),
-            "sendmsg"
-        )?;
-        if bytes_sent == message.len() as isize {
-            Ok(())
+        let bytes_sent = unsafe { libc::sendmsg(self.fd, &msghdr, libc::MSG_DONTWAIT) };
+        if bytes_sent < 0 {
+            Err(Error::last_os_error())
+        } else {
+            Ok(bytes_sent as usize)
+        }
+    }
+}
+
+// A message bigger than the receive buffer, or one that the kernel hands over using partial delivery,
+// takes more than one recvmsg() to read.  MSG_EOR is set on the last part.  See RFC6458, 8.1.17.
+// Notifications are read in the same way, and have MSG_NOTIFICATION set in the returned flags.
+async fn recv(
+    socket: &Async<Socket>,
+    reader: &mut MessageReader,
+) -> Result<(Message, libc::c_int, StreamId)> {
+    loop {
+        socket.readable().await?;
+        if let Some(received) = reader.read_part(|buf| recv_part(socket.get_ref().0, buf))? {
+            return Ok(received);
+        }
+    }
+}
+
+// Puts a message back together from the parts read by successive recvmsg() calls.
+struct MessageReader {
+    max_message_size: usize,
+    message: Message,
+    too_big: bool,
+}
+
+impl MessageReader {
+    fn new(max_message_size: usize) -> Self {
+        MessageReader {
+            max_message_size,
+            message: Vec::new(),
+            too_big: false,
+        }
+    }
+
+    // Reads the next part of a message onto the end of what we have so far, returning the message once its
+    // last part is in.  Once the message is known to be too big, the rest of it is read - into the same space
+    // - and discarded, after which a MessageTooBig error is returned and the next message starts afresh.
+    fn read_part(
+        &mut self,
+        read: impl FnOnce(&mut [u8]) -> Result<(usize, libc::c_int, StreamId)>,
+    ) -> Result<Option<(Message, libc::c_int, StreamId)>> {
+        let offset = self.message.len();
+        self.message.resize(offset + RECV_BUFFER_SIZE, 0);
+        let (bytes_received, flags, stream_id) = read(&mut self.message[offset..])?;
+        self.message.truncate(offset + bytes_received);
+
+        if self.too_big || self.message.len() > self.max_message_size {
+            self.too_big = true;
+            self.message.clear();
+        }
+
+        if flags & libc::MSG_EOR == 0 {
+            return Ok(None);
+        }
+        let message = mem::take(&mut self.message);
+        if mem::take(&mut self.too_big) {
+            Err(MessageTooBig {
+                max_message_size: self.max_message_size,
+            }
+            .into())
         } else {
-            // TODO Back pressure partial send
-            bail!("Partial send {} bytes of {}", bytes_sent, message.len());
+            Ok(Some((message, flags, stream_id)))
         }
     }
 }
 
-fn recv(fd: i32) -> Result<Message> {
-    let mut message: Message = vec![0; 1500];
+fn recv_part(fd: i32, buf: &mut [u8]) -> Result<(usize, libc::c_int, StreamId)> {
+    #[repr(C)]
+    #[derive(Debug, Default)]
+    // A libc::cmsghdr glued onto a sctp_c_bindings::sctp_rcvinfo.
+    struct Rcvinfo {
+        pub cmsg_len: libc::size_t,
+        pub cmsg_level: ::std::os::raw::c_int,
+        pub cmsg_type: ::std::os::raw::c_int,
+        pub rcvinfo: sctp_rcvinfo,
+    }
+
     let mut iov = libc::iovec {
-        iov_base: message.as_mut_ptr() as _,
-        iov_len: message.len(),
+        iov_base: buf.as_mut_ptr() as _,
+        iov_len: buf.len(),
     };
     let msg_iov = &mut iov;
 
-    let mut msghdr = make_msghdr(&mut sctp_rcvinfo::default(), msg_iov);
+    let mut rcvinfo = Rcvinfo::default();
+    let mut msghdr = make_msghdr(&mut rcvinfo, msg_iov);
     let bytes_received = try_io!(libc::recvmsg(fd, &mut msghdr, 0), "recvmsg")?;
     if bytes_received > 0 {
-        message.resize(bytes_received as _, 0);
-        Ok(message)
+        // SCTP_RECVRCVINFO is enabled, so the stream ID comes in an SCTP_RCVINFO cmsg.
+        let stream_id = if msghdr.msg_controllen > 0
+            && rcvinfo.cmsg_level == IPPROTO_SCTP
+            && rcvinfo.cmsg_type == sctp_cmsg_type_SCTP_RCVINFO as _
+        {
+            rcvinfo.rcvinfo.rcv_sid
+        } else {
+            0
+        };
+        Ok((bytes_received as _, msghdr.msg_flags, stream_id))
     } else {
         Err(anyhow!("Connection terminated"))
     }
 }
 
+// The amount of a message read by one recvmsg().  Most messages fit in one read.
+const RECV_BUFFER_SIZE: usize = 1500;
+
 fn make_msghdr<T>(msg_control: &mut T, msg_iov: &mut libc::iovec) -> libc::msghdr {
     libc::msghdr {
         msg_name: std::ptr::null_mut(),
@@ -204,3 +502,69 @@ fn make_msghdr<T>(msg_control: &mut T, msg_iov: &mut libc::iovec) -> libc::msghd
         msg_flags: 0,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    // Hands over queued messages in the way the kernel does, a buffer's worth at a time.
+    struct Wire(VecDeque<Message>);
+
+    impl Wire {
+        fn read(&mut self, buf: &mut [u8]) -> Result<(usize, libc::c_int, StreamId)> {
+            let message = self.0.front_mut().ok_or(anyhow!("Nothing to read"))?;
+            let len = message.len().min(buf.len());
+            buf[..len].copy_from_slice(&message[..len]);
+            message.drain(..len);
+            let flags = if message.is_empty() {
+                self.0.pop_front();
+                libc::MSG_EOR
+            } else {
+                0
+            };
+            Ok((len, flags, 3))
+        }
+    }
+
+    fn read_message(
+        reader: &mut MessageReader,
+        wire: &mut Wire,
+    ) -> Result<(Message, libc::c_int, StreamId)> {
+        loop {
+            if let Some(received) = reader.read_part(|buf| wire.read(buf))? {
+                return Ok(received);
+            }
+        }
+    }
+
+    #[test]
+    fn message_is_reassembled_from_parts() -> Result<()> {
+        let message: Message = (0..4 * RECV_BUFFER_SIZE + 10).map(|x| x as u8).collect();
+        let mut wire = Wire(VecDeque::from([message.clone()]));
+        let mut reader = MessageReader::new(DEFAULT_MAX_MESSAGE_SIZE);
+        let (received, flags, stream_id) = read_message(&mut reader, &mut wire)?;
+        assert_eq!(received, message);
+        assert_ne!(flags & libc::MSG_EOR, 0);
+        assert_eq!(stream_id, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn too_big_message_is_discarded() -> Result<()> {
+        let max_message_size = 2 * RECV_BUFFER_SIZE;
+        let too_big = vec![1; max_message_size + 1];
+        let next = vec![2; max_message_size];
+        let mut wire = Wire(VecDeque::from([too_big, next.clone()]));
+        let mut reader = MessageReader::new(max_message_size);
+
+        let error = read_message(&mut reader, &mut wire).unwrap_err();
+        assert!(error.is::<MessageTooBig>());
+
+        // The whole of the big message was read, and the next message is received intact.
+        let (received, _, _) = read_message(&mut reader, &mut wire)?;
+        assert_eq!(received, next);
+        assert!(wire.0.is_empty());
+        Ok(())
+    }
+}
@@ -12,13 +12,40 @@ pub type __s64 = ::std::os::raw::c_longlong;
 pub type __u64 = ::std::os::raw::c_ulonglong;
 pub type sctp_assoc_t = __s32;
 pub type sctp_cmsg_type = ::std::os::raw::c_uint;
+pub const SCTP_RTOINFO: u32 = 0;
+pub const SCTP_INITMSG: u32 = 2;
 //pub const SCTP_NODELAY: u32 = 3;
 pub const SCTP_PEER_ADDR_PARAMS: u32 = 9;
+pub const SCTP_STATUS: u32 = 14;
+pub const SCTP_SOCKOPT_BINDX_ADD: u32 = 100;
+pub const SCTP_SOCKOPT_CONNECTX: u32 = 110;
+pub const SCTP_EVENT: u32 = 127;
 pub const SCTP_RECVRCVINFO: u32 = 32;
 pub const SOL_SCTP: u32 = 132;
 pub const sctp_cmsg_type_SCTP_SNDINFO: sctp_cmsg_type = 2;
+pub const sctp_cmsg_type_SCTP_RCVINFO: sctp_cmsg_type = 3;
 pub const sctp_spp_flags_SPP_HB_ENABLE: sctp_spp_flags = 1;
 pub type sctp_spp_flags = ::std::os::raw::c_uint;
+pub const MSG_NOTIFICATION: u32 = 32768;
+pub const sctp_sn_type_SCTP_ASSOC_CHANGE: sctp_sn_type = 32769;
+pub const sctp_sn_type_SCTP_PEER_ADDR_CHANGE: sctp_sn_type = 32770;
+pub const sctp_sn_type_SCTP_SHUTDOWN_EVENT: sctp_sn_type = 32773;
+pub const sctp_sn_type_SCTP_SEND_FAILED_EVENT: sctp_sn_type = 32781;
+pub type sctp_sn_type = ::std::os::raw::c_uint;
+pub const sctp_spc_state_SCTP_ADDR_AVAILABLE: sctp_spc_state = 0;
+pub const sctp_spc_state_SCTP_ADDR_UNREACHABLE: sctp_spc_state = 1;
+pub const sctp_spc_state_SCTP_ADDR_REMOVED: sctp_spc_state = 2;
+pub const sctp_spc_state_SCTP_ADDR_ADDED: sctp_spc_state = 3;
+pub const sctp_spc_state_SCTP_ADDR_MADE_PRIM: sctp_spc_state = 4;
+pub const sctp_spc_state_SCTP_ADDR_CONFIRMED: sctp_spc_state = 5;
+pub const sctp_spc_state_SCTP_ADDR_POTENTIALLY_FAILED: sctp_spc_state = 6;
+pub type sctp_spc_state = ::std::os::raw::c_int;
+pub const sctp_sac_state_SCTP_COMM_UP: sctp_sac_state = 0;
+pub const sctp_sac_state_SCTP_COMM_LOST: sctp_sac_state = 1;
+pub const sctp_sac_state_SCTP_RESTART: sctp_sac_state = 2;
+pub const sctp_sac_state_SCTP_SHUTDOWN_COMP: sctp_sac_state = 3;
+pub const sctp_sac_state_SCTP_CANT_STR_ASSOC: sctp_sac_state = 4;
+pub type sctp_sac_state = ::std::os::raw::c_uint;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Default)]
@@ -33,6 +60,113 @@ pub struct sctp_rcvinfo {
     pub rcv_assoc_id: sctp_assoc_t,
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct sctp_initmsg {
+    pub sinit_num_ostreams: __u16,
+    pub sinit_max_instreams: __u16,
+    pub sinit_max_attempts: __u16,
+    pub sinit_max_init_timeo: __u16,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct sctp_rtoinfo {
+    pub srto_assoc_id: sctp_assoc_t,
+    pub srto_initial: __u32,
+    pub srto_max: __u32,
+    pub srto_min: __u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct sctp_event {
+    pub se_assoc_id: sctp_assoc_t,
+    pub se_type: __u16,
+    pub se_on: __u8,
+}
+
+#[repr(C, packed(4))]
+#[derive(Debug, Copy, Clone)]
+pub struct sctp_paddr_change {
+    pub spc_type: __u16,
+    pub spc_flags: __u16,
+    pub spc_length: __u32,
+    pub spc_aaddr: sockaddr_storage,
+    pub spc_state: ::std::os::raw::c_int,
+    pub spc_error: ::std::os::raw::c_int,
+    pub spc_assoc_id: sctp_assoc_t,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct sctp_assoc_change {
+    pub sac_type: __u16,
+    pub sac_flags: __u16,
+    pub sac_length: __u32,
+    pub sac_state: __u16,
+    pub sac_error: __u16,
+    pub sac_outbound_streams: __u16,
+    pub sac_inbound_streams: __u16,
+    pub sac_assoc_id: sctp_assoc_t,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct sctp_shutdown_event {
+    pub sse_type: __u16,
+    pub sse_flags: __u16,
+    pub sse_length: __u32,
+    pub sse_assoc_id: sctp_assoc_t,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct sctp_sndinfo {
+    pub snd_sid: __u16,
+    pub snd_flags: __u16,
+    pub snd_ppid: __u32,
+    pub snd_context: __u32,
+    pub snd_assoc_id: sctp_assoc_t,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct sctp_send_failed_event {
+    pub ssf_type: __u16,
+    pub ssf_flags: __u16,
+    pub ssf_length: __u32,
+    pub ssf_error: __u32,
+    pub ssfe_info: sctp_sndinfo,
+    pub ssf_assoc_id: sctp_assoc_t,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct sctp_status {
+    pub sstat_assoc_id: sctp_assoc_t,
+    pub sstat_state: __s32,
+    pub sstat_rwnd: __u32,
+    pub sstat_unackdata: __u16,
+    pub sstat_penddata: __u16,
+    pub sstat_instrms: __u16,
+    pub sstat_outstrms: __u16,
+    pub sstat_fragmentation_point: __u32,
+    pub sstat_primary: sctp_paddrinfo,
+}
+
+#[repr(C, packed(4))]
+#[derive(Debug, Copy, Clone)]
+pub struct sctp_paddrinfo {
+    pub spinfo_assoc_id: sctp_assoc_t,
+    pub spinfo_address: sockaddr_storage,
+    pub spinfo_state: __s32,
+    pub spinfo_cwnd: __u32,
+    pub spinfo_srtt: __u32,
+    pub spinfo_rto: __u32,
+    pub spinfo_mtu: __u32,
+}
+
 #[repr(C, packed(2))] // bindgen seems to have got this wrong - was packed(4)
 #[derive(Debug, Copy, Clone)]
 pub struct sctp_paddrparams {
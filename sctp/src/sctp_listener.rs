@@ -1,12 +1,14 @@
 //! sctp_listener - async listener for SCTP connections that produces SCTP associations
 
+use super::sctp_association::{set_endpoint_sock_opts, socket_domain};
+use super::sock_opt;
 use super::try_io::try_io;
-use super::SctpAssociation;
-use anyhow::{anyhow, Result};
+use super::{SctpAssociation, SctpConfig};
+use anyhow::{anyhow, bail, Result};
 use async_io::Async;
 use async_stream::try_stream;
 use futures_core::stream::Stream;
-use libc::{accept, bind, listen, socket, AF_INET, IPPROTO_SCTP, SOCK_STREAM};
+use libc::{accept, bind, listen, socket, IPPROTO_SCTP, SOCK_STREAM};
 use os_socketaddr::OsSocketAddr;
 use slog::Logger;
 use std::io::Error;
@@ -14,18 +16,32 @@ use std::net::SocketAddr;
 
 struct FdGuard(i32);
 
+// Listen on one or more local addresses.  An association accepted by a listener bound to several
+// addresses is multihomed.  Listening on an IPv6 address such as [::] also accepts IPv4 peers.
 pub fn new_listen(
-    addr: SocketAddr,
+    addrs: &[SocketAddr],
     ppid: u32,
     backlog: i32,
+    config: SctpConfig,
     logger: Logger,
 ) -> Result<impl Stream<Item = Result<SctpAssociation>>> {
-    let addr: OsSocketAddr = addr.try_into()?;
+    let Some(addr) = addrs.first() else {
+        bail!("No listen address")
+    };
+    let domain = socket_domain(addrs);
+    let addr: OsSocketAddr = (*addr).into();
     let fd = FdGuard(try_io!(
-        socket(AF_INET, SOCK_STREAM, IPPROTO_SCTP),
+        socket(domain, SOCK_STREAM, IPPROTO_SCTP),
         "socket"
     )?);
     try_io!(bind(fd.0, addr.as_ptr(), addr.len()), "bind")?;
+    if addrs.len() > 1 {
+        sock_opt::bindx_add(fd.0, &addrs[1..])?;
+    }
+
+    // Accepted associations inherit the number of streams and protocol parameters from the listening
+    // socket.
+    set_endpoint_sock_opts(fd.0, &config)?;
     try_io!(listen(fd.0, backlog), "listen")?;
     Ok(try_stream! {
         loop {
@@ -34,7 +50,7 @@ pub fn new_listen(
             let mut len = addr.capacity();
             let assoc_fd = try_io!(accept(fd.0, addr.as_mut_ptr(), &mut len), "accept")?;
             let addr = addr.into_addr().ok_or(anyhow!("Not IPv4 or IPv6"))?;
-            let assoc = SctpAssociation::from_accepted(assoc_fd, ppid, addr, &logger)?;
+            let assoc = SctpAssociation::from_accepted(assoc_fd, ppid, addr, &config, &logger)?;
             yield assoc;
         }
     })
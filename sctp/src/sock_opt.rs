@@ -4,15 +4,25 @@ use super::sctp_bindings::*;
 use super::try_io::try_io;
 use anyhow::{anyhow, Result};
 use io::Error;
-use libc::setsockopt;
+use libc::{getsockopt, setsockopt};
+use os_socketaddr::OsSocketAddr;
+use std::net::SocketAddr;
 use std::{io, mem};
 
-pub fn enable_sctp_heartbeat(fd: i32, interval_ms: u32) -> Result<()> {
-    // SCTP_PEER_ADDR_PARAMS - heartbeat so that we rapidly detect peer failures.
+// SCTP_PEER_ADDR_PARAMS - heartbeat so that we rapidly detect peer failures, and the number of
+// retransmissions before a path is considered failed.  Zero means no change.
+pub fn set_peer_addr_params(
+    fd: i32,
+    heartbeat_interval_ms: u32,
+    path_max_retrans: u16,
+) -> Result<()> {
     let mut sctp_paddrparams = unsafe { mem::zeroed::<sctp_paddrparams>() };
     sctp_paddrparams.spp_address.ss_family = libc::AF_INET as _;
-    sctp_paddrparams.spp_hbinterval = interval_ms;
-    sctp_paddrparams.spp_flags = sctp_spp_flags_SPP_HB_ENABLE;
+    sctp_paddrparams.spp_pathmaxrxt = path_max_retrans;
+    if heartbeat_interval_ms != 0 {
+        sctp_paddrparams.spp_hbinterval = heartbeat_interval_ms;
+        sctp_paddrparams.spp_flags = sctp_spp_flags_SPP_HB_ENABLE;
+    }
 
     try_io!(
         setsockopt(
@@ -27,6 +37,28 @@ pub fn enable_sctp_heartbeat(fd: i32, interval_ms: u32) -> Result<()> {
     Ok(())
 }
 
+pub fn set_rto_info(fd: i32, initial_ms: u32, min_ms: u32, max_ms: u32) -> Result<()> {
+    // SCTP_RTOINFO - retransmission timeout.  Zero means no change.
+    let sctp_rtoinfo = sctp_rtoinfo {
+        srto_initial: initial_ms,
+        srto_min: min_ms,
+        srto_max: max_ms,
+        ..Default::default()
+    };
+
+    try_io!(
+        setsockopt(
+            fd,
+            SOL_SCTP as _,
+            SCTP_RTOINFO as _,
+            &sctp_rtoinfo as *const _ as _,
+            mem::size_of::<sctp_rtoinfo>() as _,
+        ),
+        "setsockopt"
+    )?;
+    Ok(())
+}
+
 pub fn enable_sock_opt(fd: i32, name: libc::c_int) -> Result<()> {
     let enabled = &1 as *const _ as _;
     let enabled_len = mem::size_of::<libc::c_int>() as _;
@@ -36,3 +68,94 @@ pub fn enable_sock_opt(fd: i32, name: libc::c_int) -> Result<()> {
     )?;
     Ok(())
 }
+
+pub fn set_init_msg(
+    fd: i32,
+    num_ostreams: u16,
+    max_instreams: u16,
+    max_attempts: u16,
+) -> Result<()> {
+    // SCTP_INITMSG - the number of streams that we ask for when the association is set up, and the
+    // number of times to send INIT before giving up.  Zero max_attempts means no change.
+    let sctp_initmsg = sctp_initmsg {
+        sinit_num_ostreams: num_ostreams,
+        sinit_max_instreams: max_instreams,
+        sinit_max_attempts: max_attempts,
+        ..Default::default()
+    };
+
+    try_io!(
+        setsockopt(
+            fd,
+            SOL_SCTP as _,
+            SCTP_INITMSG as _,
+            &sctp_initmsg as *const _ as _,
+            mem::size_of::<sctp_initmsg>() as _,
+        ),
+        "setsockopt"
+    )?;
+    Ok(())
+}
+
+pub fn get_num_outbound_streams(fd: i32) -> Result<u16> {
+    // SCTP_STATUS - the number of outbound streams is the result of negotiation with the peer.
+    let mut sctp_status = unsafe { mem::zeroed::<sctp_status>() };
+    let mut len = mem::size_of::<sctp_status>() as libc::socklen_t;
+
+    try_io!(
+        getsockopt(
+            fd,
+            SOL_SCTP as _,
+            SCTP_STATUS as _,
+            &mut sctp_status as *mut _ as _,
+            &mut len,
+        ),
+        "getsockopt"
+    )?;
+    Ok(sctp_status.sstat_outstrms)
+}
+
+pub fn enable_sctp_event(fd: i32, sn_type: sctp_sn_type) -> Result<()> {
+    // SCTP_EVENT - subscribe to a type of notification.
+    let sctp_event = sctp_event {
+        se_type: sn_type as _,
+        se_on: 1,
+        ..Default::default()
+    };
+
+    try_io!(
+        setsockopt(
+            fd,
+            SOL_SCTP as _,
+            SCTP_EVENT as _,
+            &sctp_event as *const _ as _,
+            mem::size_of::<sctp_event>() as _,
+        ),
+        "setsockopt"
+    )?;
+    Ok(())
+}
+
+// sctp_bindx(SCTP_BINDX_ADD_ADDR).  See RFC6458, 9.1.
+pub fn bindx_add(fd: i32, addrs: &[SocketAddr]) -> Result<()> {
+    let packed = pack_addrs(addrs);
+    try_io!(
+        setsockopt(
+            fd,
+            SOL_SCTP as _,
+            SCTP_SOCKOPT_BINDX_ADD as _,
+            packed.as_ptr() as _,
+            packed.len() as _,
+        ),
+        "bindx"
+    )?;
+    Ok(())
+}
+
+// The sctp_bindx() and sctp_connectx() calls take a list of addresses packed one after another.
+pub fn pack_addrs(addrs: &[SocketAddr]) -> Vec<u8> {
+    addrs
+        .iter()
+        .flat_map(|addr| OsSocketAddr::from(*addr).as_ref().to_vec())
+        .collect()
+}
@@ -1,3 +1,6 @@
-// The canonical form of Snssai.
-#[derive(Copy, Clone, Debug)]
+use serde::Deserialize;
+
+// The canonical form of Snssai.  In a config file, it is written as for from_str().
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
 pub struct Snssai(pub u8, pub Option<[u8; 3]>);
@@ -1,7 +1,8 @@
-use crate::TransportLayerAddress;
-use anyhow::bail;
-use async_net::IpAddr;
+use crate::{Snssai, TransportLayerAddress};
+use anyhow::{anyhow, bail};
+use async_net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use bitvec::prelude::*;
+use std::str::FromStr;
 
 impl From<IpAddr> for TransportLayerAddress {
     fn from(ip: IpAddr) -> Self {
@@ -12,6 +13,53 @@ impl From<IpAddr> for TransportLayerAddress {
     }
 }
 
+impl TransportLayerAddress {
+    /// A 160 bit address for a dual stack endpoint - the IPv4 address followed by the IPv6 address.
+    /// See TS 38.414, 5.1.
+    pub fn from_ipv4_and_ipv6(ipv4: Ipv4Addr, ipv6: Ipv6Addr) -> Self {
+        let mut bits = BitVec::<_, Msb0>::from_slice(&ipv4.octets());
+        bits.extend_from_raw_slice(&ipv6.octets());
+        TransportLayerAddress(bits)
+    }
+
+    /// The IPv4 address, if this is a 32 bit or 160 bit address.
+    pub fn ipv4(&self) -> Option<Ipv4Addr> {
+        match self.0.len() {
+            32 | 160 => {
+                let arr: [u8; 4] = self.0.as_raw_slice()[0..4].try_into().unwrap();
+                Some(arr.into())
+            }
+            _ => None,
+        }
+    }
+
+    /// The IPv6 address, if this is a 128 bit or 160 bit address.
+    pub fn ipv6(&self) -> Option<Ipv6Addr> {
+        let start = match self.0.len() {
+            128 => 0,
+            160 => 4,
+            _ => return None,
+        };
+        let arr: [u8; 16] = self.0.as_raw_slice()[start..start + 16].try_into().unwrap();
+        Some(arr.into())
+    }
+
+    /// The address to use to reach this endpoint from a local address of the given family.
+    pub fn ip_addr_for(&self, local: &IpAddr) -> anyhow::Result<IpAddr> {
+        match local {
+            IpAddr::V4(_) => self.ipv4().map(IpAddr::V4),
+            IpAddr::V6(_) => self.ipv6().map(IpAddr::V6),
+        }
+        .ok_or_else(|| {
+            anyhow!(
+                "Transport layer address {} has no address in the family of {}",
+                self.to_string(),
+                local
+            )
+        })
+    }
+}
+
 impl TryFrom<&str> for TransportLayerAddress {
     type Error = anyhow::Error;
     fn try_from(addr: &str) -> Result<Self, anyhow::Error> {
@@ -26,19 +74,13 @@ impl TryFrom<&String> for TransportLayerAddress {
     }
 }
 
+// A dual stack address converts to its IPv4 address.  Use ip_addr_for() to pick the family.
 impl TryFrom<TransportLayerAddress> for IpAddr {
     type Error = anyhow::Error;
     fn try_from(addr: TransportLayerAddress) -> Result<Self, anyhow::Error> {
-        let v = addr.0.into_vec();
-        match v.len() {
-            4 => {
-                let arr: [u8; 4] = v.try_into().unwrap();
-                Ok(IpAddr::V4(arr.into()))
-            }
-            16 => {
-                let arr: [u8; 16] = v.try_into().unwrap();
-                Ok(IpAddr::V6(arr.into()))
-            }
+        match addr.0.len() {
+            32 | 160 => Ok(IpAddr::V4(addr.ipv4().unwrap())),
+            128 => Ok(IpAddr::V6(addr.ipv6().unwrap())),
             x => bail!("Bad length {}", x),
         }
     }
@@ -47,6 +89,9 @@ impl TryFrom<TransportLayerAddress> for IpAddr {
 impl TryFrom<TransportLayerAddress> for String {
     type Error = anyhow::Error;
     fn try_from(addr: TransportLayerAddress) -> Result<Self, anyhow::Error> {
+        if let (Some(ipv4), Some(ipv6)) = (addr.ipv4(), addr.ipv6()) {
+            return Ok(format!("{}/{}", ipv4, ipv6));
+        }
         let ip_addr: IpAddr = addr.try_into()?;
         Ok(ip_addr.to_string())
     }
@@ -57,3 +102,62 @@ impl ToString for TransportLayerAddress {
         self.clone().try_into().unwrap_or("invalid".to_string())
     }
 }
+
+// An SST, or an SST and SD separated by a colon, with the SD in hex - for example "1" or "1:010203".
+impl FromStr for Snssai {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, anyhow::Error> {
+        let (sst, sd) = match s.split_once(':') {
+            Some((sst, sd)) => (sst, Some(sd)),
+            None => (s, None),
+        };
+        let Ok(sst) = sst.parse::<u8>() else {
+            bail!("SST of slice {} must be a number from 0 to 255", s)
+        };
+        let sd = match sd.map(|x| u32::from_str_radix(x, 16)) {
+            None => None,
+            Some(Ok(sd)) if sd < (1 << 24) => {
+                let [_, a, b, c] = sd.to_be_bytes();
+                Some([a, b, c])
+            }
+            Some(_) => bail!("SD of slice {} must be at most six hex digits", s),
+        };
+        Ok(Snssai(sst, sd))
+    }
+}
+
+impl TryFrom<String> for Snssai {
+    type Error = anyhow::Error;
+    fn try_from(s: String) -> Result<Self, anyhow::Error> {
+        s.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dual_stack_address() {
+        let ipv4: Ipv4Addr = "192.168.1.2".parse().unwrap();
+        let ipv6: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let addr = TransportLayerAddress::from_ipv4_and_ipv6(ipv4, ipv6);
+        assert_eq!(addr.0.len(), 160);
+        assert_eq!(addr.ipv4(), Some(ipv4));
+        assert_eq!(addr.ipv6(), Some(ipv6));
+        assert_eq!(
+            addr.ip_addr_for(&"::".parse().unwrap()).unwrap(),
+            IpAddr::V6(ipv6)
+        );
+        assert_eq!(IpAddr::try_from(addr).unwrap(), IpAddr::V4(ipv4));
+    }
+
+    #[test]
+    fn ipv6_address() {
+        let addr: TransportLayerAddress = "2001:db8::1".try_into().unwrap();
+        assert_eq!(addr.0.len(), 128);
+        assert_eq!(addr.ipv4(), None);
+        assert_eq!(addr.to_string(), "2001:db8::1");
+        assert!(addr.ip_addr_for(&"127.0.0.1".parse().unwrap()).is_err());
+    }
+}
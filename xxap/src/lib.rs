@@ -1,7 +1,9 @@
 mod common;
 mod conversion;
 mod ies;
+mod ran_config;
 
 // Export everything except Criticality
 pub use common::*;
 pub use ies::{GtpTeid, GtpTunnel, PduSessionId, TransportLayerAddress};
+pub use ran_config::{plmn_from_mcc_mnc, BroadcastPlmn, PagingDrx, RanConfig, SupportedTa};
@@ -0,0 +1,323 @@
+//! ran_config - the radio access network configuration of a gNB, shared by its CU-CP and CU-UP
+
+use crate::Snssai;
+use anyhow::{bail, ensure, Result};
+use bitvec::prelude::*;
+use serde::{de::Error, Deserialize, Deserializer};
+
+// The default PLMN, MCC 208 / MNC 93, is the one that free5GC expects.
+const DEFAULT_PLMN: [u8; 3] = [0x2, 0xf8, 0x39];
+
+/// What the gNB tells the core and its peers about the network it serves - its identity, the tracking areas
+/// it supports, and the PLMNs and slices that are available in each.  This goes into NG Setup and RAN
+/// Configuration Update, and into the CU-UP's E1 Setup.
+///
+/// In a config file, a PLMN is written as a table of its MCC and MNC, a TAC as a number, and a slice as a string
+/// such as "1:000001" (see Snssai::from_str()).  Settings that are left out are as for for_plmn().
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RanConfigFile")]
+pub struct RanConfig {
+    // The PLMN of the gNB's global ID.
+    pub plmn: [u8; 3],
+
+    // The gNB ID, of which the low gnb_id_length bits are used.  The length is from 22 to 32 bits, and
+    // decides how many bits of the NR Cell Identity are left to identify the cells of the gNB.  See TS 38.413,
+    // 9.3.1.6.
+    pub gnb_id: u32,
+    pub gnb_id_length: u8,
+
+    pub supported_tas: Vec<SupportedTa>,
+
+    // The paging DRX cycle that applies to UEs that have not negotiated their own.
+    pub default_paging_drx: PagingDrx,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SupportedTa {
+    #[serde(deserialize_with = "deserialize_tac")]
+    pub tac: [u8; 3],
+    pub broadcast_plmns: Vec<BroadcastPlmn>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BroadcastPlmn {
+    #[serde(deserialize_with = "deserialize_plmn")]
+    pub plmn: [u8; 3],
+    pub slices: Vec<Snssai>,
+}
+
+// The paging DRX cycle, in radio frames.  In a config file, it is written as the number of frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u16")]
+pub enum PagingDrx {
+    V32,
+    V64,
+    V128,
+    V256,
+}
+
+impl RanConfig {
+    /// A gNB with a single tracking area, TAC 1, that broadcasts the given PLMN with the slices that free5GC
+    /// is set up for by default.
+    pub fn for_plmn(plmn: [u8; 3]) -> Self {
+        RanConfig {
+            plmn,
+            gnb_id: 0x3fffff,
+            gnb_id_length: 22,
+            supported_tas: vec![SupportedTa {
+                tac: [0, 0, 1],
+                broadcast_plmns: vec![BroadcastPlmn {
+                    plmn,
+                    slices: vec![
+                        Snssai(1, None),
+                        Snssai(1, Some([0, 0, 0])),
+                        Snssai(1, Some([0, 0, 1])),
+                    ],
+                }],
+            }],
+            default_paging_drx: PagingDrx::V128,
+        }
+    }
+
+    /// Checks that the configuration can be signalled - for example, that every tracking area broadcasts at
+    /// least one PLMN, with at least one slice.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            (22..=32).contains(&self.gnb_id_length),
+            "gNB ID length must be from 22 to 32 bits"
+        );
+        ensure!(
+            self.gnb_id_length == 32 || self.gnb_id < (1 << self.gnb_id_length),
+            "gNB ID {:#x} does not fit in {} bits",
+            self.gnb_id,
+            self.gnb_id_length
+        );
+        ensure!(
+            !self.supported_tas.is_empty(),
+            "No supported tracking areas"
+        );
+        for ta in self.supported_tas.iter() {
+            ensure!(
+                !ta.broadcast_plmns.is_empty(),
+                "No broadcast PLMNs for TAC {:02x?}",
+                ta.tac
+            );
+            for plmn in ta.broadcast_plmns.iter() {
+                ensure!(
+                    !plmn.slices.is_empty(),
+                    "No slices for PLMN {:02x?} in TAC {:02x?}",
+                    plmn.plmn,
+                    ta.tac
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The gNB ID as a bit string of length gnb_id_length.
+    pub fn gnb_id_bits(&self) -> BitVec<u8, Msb0> {
+        let length = (self.gnb_id_length as usize).min(32);
+        self.gnb_id.to_be_bytes().view_bits::<Msb0>()[32 - length..].to_bitvec()
+    }
+
+    /// Each PLMN that is broadcast in any of the tracking areas, once, with all the slices it supports in them.
+    pub fn plmn_slices(&self) -> Vec<([u8; 3], Vec<Snssai>)> {
+        let mut plmn_slices: Vec<([u8; 3], Vec<Snssai>)> = vec![];
+        for broadcast_plmn in self
+            .supported_tas
+            .iter()
+            .flat_map(|x| x.broadcast_plmns.iter())
+        {
+            let index = match plmn_slices.iter().position(|x| x.0 == broadcast_plmn.plmn) {
+                Some(index) => index,
+                None => {
+                    plmn_slices.push((broadcast_plmn.plmn, vec![]));
+                    plmn_slices.len() - 1
+                }
+            };
+            let slices = &mut plmn_slices[index].1;
+            for slice in broadcast_plmn.slices.iter() {
+                if !slices.contains(slice) {
+                    slices.push(*slice);
+                }
+            }
+        }
+        plmn_slices
+    }
+}
+
+impl Default for RanConfig {
+    fn default() -> Self {
+        Self::for_plmn(DEFAULT_PLMN)
+    }
+}
+
+/// Converts an MCC of three digits and an MNC of two or three digits to the encoding of a PLMN identity.
+/// See TS 38.413, 9.3.3.5.
+pub fn plmn_from_mcc_mnc(mcc: &str, mnc: &str) -> Result<[u8; 3]> {
+    ensure!(mcc.len() == 3, "MCC must be three decimal digits");
+
+    let mut plmn_digits = [0u8; 6];
+    let mut plmn = [0u8; 3];
+    for (n, c) in mcc.chars().enumerate() {
+        let Some(digit) = c.to_digit(10) else {
+            bail!("MCC must be three decimal digits");
+        };
+        plmn_digits[n] = digit as u8;
+    }
+    let offset = match mnc.len() {
+        2 => {
+            plmn_digits[3] = 0x0f;
+            4
+        }
+        3 => 3,
+        _ => bail!("MNC must be two or three digits"),
+    };
+    for (n, c) in mnc.chars().enumerate() {
+        let Some(digit) = c.to_digit(10) else {
+            bail!("MNC must be two or three digits")
+        };
+        plmn_digits[n + offset] = digit as u8;
+    }
+    for (n, digit) in plmn_digits.iter().enumerate() {
+        let index = n / 2;
+        plmn[index] = if (n % 2) == 0 {
+            *digit
+        } else {
+            plmn[index] | (digit << 4)
+        };
+    }
+    Ok(plmn)
+}
+
+// The form of a RanConfig in a config file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RanConfigFile {
+    plmn: Option<MccMnc>,
+    gnb_id: Option<u32>,
+    gnb_id_length: Option<u8>,
+    supported_tas: Option<Vec<SupportedTa>>,
+    default_paging_drx: Option<PagingDrx>,
+}
+
+impl TryFrom<RanConfigFile> for RanConfig {
+    type Error = anyhow::Error;
+    fn try_from(file: RanConfigFile) -> Result<Self> {
+        let plmn = match file.plmn {
+            Some(x) => plmn_from_mcc_mnc(&x.mcc, &x.mnc)?,
+            None => DEFAULT_PLMN,
+        };
+        let defaults = RanConfig::for_plmn(plmn);
+        let config = RanConfig {
+            plmn,
+            gnb_id: file.gnb_id.unwrap_or(defaults.gnb_id),
+            gnb_id_length: file.gnb_id_length.unwrap_or(defaults.gnb_id_length),
+            supported_tas: file.supported_tas.unwrap_or(defaults.supported_tas),
+            default_paging_drx: file
+                .default_paging_drx
+                .unwrap_or(defaults.default_paging_drx),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MccMnc {
+    mcc: String,
+    mnc: String,
+}
+
+fn deserialize_plmn<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 3], D::Error> {
+    let MccMnc { mcc, mnc } = MccMnc::deserialize(deserializer)?;
+    plmn_from_mcc_mnc(&mcc, &mnc).map_err(D::Error::custom)
+}
+
+fn deserialize_tac<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 3], D::Error> {
+    match u32::deserialize(deserializer)?.to_be_bytes() {
+        [0, a, b, c] => Ok([a, b, c]),
+        _ => Err(D::Error::custom("TAC must be at most three bytes")),
+    }
+}
+
+impl TryFrom<u16> for PagingDrx {
+    type Error = anyhow::Error;
+    fn try_from(frames: u16) -> Result<Self> {
+        Ok(match frames {
+            32 => PagingDrx::V32,
+            64 => PagingDrx::V64,
+            128 => PagingDrx::V128,
+            256 => PagingDrx::V256,
+            _ => bail!("Paging DRX must be 32, 64, 128 or 256"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gnb_id_bits() {
+        let mut config = RanConfig {
+            gnb_id: 0x123,
+            gnb_id_length: 24,
+            ..RanConfig::default()
+        };
+        assert!(config.validate().is_ok());
+        assert_eq!(config.gnb_id_bits().len(), 24);
+        assert_eq!(config.gnb_id_bits().as_raw_slice(), &[0x00, 0x01, 0x23]);
+
+        config.gnb_id = 0x1000000;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn plmn_slices_merged_across_tas() {
+        let mut config = RanConfig::default();
+        let mut second_ta = config.supported_tas[0].clone();
+        second_ta.tac = [0, 0, 2];
+        second_ta.broadcast_plmns[0].slices = vec![Snssai(1, None), Snssai(2, None)];
+        config.supported_tas.push(second_ta);
+
+        let plmn_slices = config.plmn_slices();
+        assert_eq!(plmn_slices.len(), 1);
+        assert_eq!(plmn_slices[0].1.len(), 4);
+    }
+
+    #[test]
+    fn config_file() {
+        let config: RanConfig = toml::from_str(
+            r#"
+            plmn = { mcc = "001", mnc = "01" }
+            default_paging_drx = 64
+
+            [[supported_tas]]
+            tac = 0x000102
+            broadcast_plmns = [
+                { plmn = { mcc = "001", mnc = "01" }, slices = ["1", "2:0000ff"] }
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.plmn, [0x00, 0xf1, 0x10]);
+        assert_eq!(config.gnb_id_length, 22);
+        assert_eq!(config.default_paging_drx, PagingDrx::V64);
+        assert_eq!(config.supported_tas[0].tac, [0, 1, 2]);
+        assert_eq!(
+            config.supported_tas[0].broadcast_plmns[0].slices,
+            vec![Snssai(1, None), Snssai(2, Some([0, 0, 0xff]))]
+        );
+
+        // The tracking areas default to one that broadcasts the gNB's PLMN.
+        let config: RanConfig = toml::from_str(r#"plmn = { mcc = "001", mnc = "01" }"#).unwrap();
+        assert_eq!(config.supported_tas[0].broadcast_plmns[0].plmn, config.plmn);
+
+        assert!(toml::from_str::<RanConfig>("default_paging_drx = 100").is_err());
+        assert!(toml::from_str::<RanConfig>("supported_tas = []").is_err());
+    }
+}